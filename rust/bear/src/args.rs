@@ -13,6 +13,7 @@ use clap::{arg, command, ArgAction, ArgMatches, Command};
 /// Common constants used in the module.
 const MODE_INTERCEPT_SUBCOMMAND: &str = "intercept";
 const MODE_SEMANTIC_SUBCOMMAND: &str = "semantic";
+const MODE_SERVE_SUBCOMMAND: &str = "serve";
 const DEFAULT_OUTPUT_FILE: &str = "compile_commands.json";
 const DEFAULT_EVENT_FILE: &str = "events.json";
 
@@ -36,6 +37,9 @@ pub enum Mode {
         input: BuildEvents,
         output: BuildSemantic,
     },
+    Serve {
+        input: BuildEvents,
+    },
     All {
         input: BuildCommand,
         output: BuildSemantic,
@@ -52,6 +56,7 @@ pub struct BuildCommand {
 pub struct BuildSemantic {
     pub file_name: String,
     pub append: bool,
+    pub dry_run: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -95,6 +100,18 @@ impl TryFrom<ArgMatches> for Arguments {
                 let arguments = Arguments { config, mode };
                 Ok(arguments)
             }
+            Some((MODE_SERVE_SUBCOMMAND, serve_matches)) => {
+                let input = serve_matches
+                    .get_one::<String>("input")
+                    .map(String::to_string)
+                    .expect("input is defaulted");
+
+                let mode = Mode::Serve {
+                    input: BuildEvents { file_name: input },
+                };
+                let arguments = Arguments { config, mode };
+                Ok(arguments)
+            }
             None => {
                 let input = BuildCommand::try_from(&matches)?;
                 let output = BuildSemantic::try_from(&matches)?;
@@ -129,7 +146,12 @@ impl TryFrom<&ArgMatches> for BuildSemantic {
             .map(String::to_string)
             .expect("output is defaulted");
         let append = *matches.get_one::<bool>("append").unwrap_or(&false);
-        Ok(BuildSemantic { file_name, append })
+        let dry_run = *matches.get_one::<bool>("dry-run").unwrap_or(&false);
+        Ok(BuildSemantic {
+            file_name,
+            append,
+            dry_run,
+        })
     }
 }
 
@@ -177,9 +199,19 @@ pub fn cli() -> Command {
                         .hide_default_value(false),
                     arg!(-a --append "Append result to an existing output file")
                         .action(ArgAction::SetTrue),
+                    arg!(--"dry-run" "Print the changes to the output file instead of writing it")
+                        .action(ArgAction::SetTrue),
                 ])
                 .arg_required_else_help(false),
         )
+        .subcommand(
+            Command::new(MODE_SERVE_SUBCOMMAND)
+                .about("answer the queries of the compilation database entries")
+                .args(&[arg!(-i --input <FILE> "Path of the event file")
+                    .default_value(DEFAULT_EVENT_FILE)
+                    .hide_default_value(false)])
+                .arg_required_else_help(false),
+        )
         .args(&[
             arg!(<COMMAND> "Build command")
                 .action(ArgAction::Append)
@@ -191,6 +223,8 @@ pub fn cli() -> Command {
                 .default_value(DEFAULT_OUTPUT_FILE)
                 .hide_default_value(false),
             arg!(-a --append "Append result to an existing output file").action(ArgAction::SetTrue),
+            arg!(--"dry-run" "Print the changes to the output file instead of writing it")
+                .action(ArgAction::SetTrue),
         ])
 }
 
@@ -282,7 +316,28 @@ mod test {
                     },
                     output: BuildSemantic {
                         file_name: "result.json".to_string(),
-                        append: true
+                        append: true,
+                        dry_run: false
+                    },
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_serve_call() {
+        let execution = vec!["bear", "serve", "-i", "custom.json"];
+
+        let matches = cli().get_matches_from(execution);
+        let arguments = Arguments::try_from(matches).unwrap();
+
+        assert_eq!(
+            arguments,
+            Arguments {
+                config: None,
+                mode: Mode::Serve {
+                    input: BuildEvents {
+                        file_name: "custom.json".to_string()
                     },
                 },
             }
@@ -306,13 +361,27 @@ mod test {
                     },
                     output: BuildSemantic {
                         file_name: "compile_commands.json".to_string(),
-                        append: false
+                        append: false,
+                        dry_run: false
                     },
                 },
             }
         );
     }
 
+    #[test]
+    fn test_semantic_dry_run() {
+        let execution = vec!["bear", "semantic", "--dry-run"];
+
+        let matches = cli().get_matches_from(execution);
+        let arguments = Arguments::try_from(matches).unwrap();
+
+        match arguments.mode {
+            Mode::Semantic { output, .. } => assert!(output.dry_run),
+            _ => panic!("unexpected mode"),
+        }
+    }
+
     #[test]
     fn test_all_call() {
         let execution = vec![
@@ -340,7 +409,8 @@ mod test {
                     },
                     output: BuildSemantic {
                         file_name: "result.json".to_string(),
-                        append: true
+                        append: true,
+                        dry_run: false
                     },
                 },
             }
@@ -364,7 +434,8 @@ mod test {
                     },
                     output: BuildSemantic {
                         file_name: "compile_commands.json".to_string(),
-                        append: false
+                        append: false,
+                        dry_run: false
                     },
                 },
             }
@@ -2,10 +2,11 @@
 use std::process::ExitCode;
 
 use bear::input::EventFileReader;
-use bear::output::OutputWriter;
+use bear::output::server::QueryServer;
+use bear::output::{clang, OutputWriter};
 use bear::recognition::Recognition;
 use bear::transformation::Transformation;
-use bear::{args, config};
+use bear::{args, config, shutdown};
 use log;
 
 /// Driver function of the application.
@@ -51,6 +52,12 @@ enum Application {
         semantic_transform: Transformation,
         output_writer: OutputWriter,
     },
+    /// The serve mode answers the queries of the entries, which are recognized from
+    /// the build events. (The events are read again at the reload request.)
+    Serve {
+        input: args::BuildEvents,
+        config: config::Main,
+    },
     /// The all model is combining the intercept and semantic modes.
     All {
         input: args::BuildCommand,
@@ -80,7 +87,7 @@ impl Application {
             args::Mode::Semantic { input, output } => {
                 let event_source = EventFileReader::try_from(input)?;
                 let semantic_recognition = Recognition::try_from(&config)?;
-                let semantic_transform = Transformation::from(&config.output);
+                let semantic_transform = Transformation::try_from(&config.output)?;
                 let output_writer = OutputWriter::configure(&output, &config.output)?;
                 let result = Application::Semantic {
                     event_source,
@@ -90,6 +97,12 @@ impl Application {
                 };
                 Ok(result)
             }
+            args::Mode::Serve { input } => {
+                // Check the configuration before the server starts.
+                Recognition::try_from(&config)?;
+                Transformation::try_from(&config.output)?;
+                Ok(Application::Serve { input, config })
+            }
             args::Mode::All { input, output } => {
                 let intercept_config = config.intercept;
                 let output_config = config.output;
@@ -121,16 +134,65 @@ impl Application {
                 semantic_transform,
                 output_writer,
             } => {
+                // Stop processing new events on interrupt, but write the results so far.
+                if let Err(error) = shutdown::register() {
+                    log::warn!("{}", error);
+                }
                 // Set up the pipeline of compilation database entries.
-                let entries = event_source
-                    .generate()
+                let mut entries = shutdown::until_requested(event_source.generate())
                     .flat_map(|execution| semantic_recognition.apply(execution))
-                    .flat_map(|semantic| semantic_transform.apply(semantic));
+                    .flat_map(|semantic| semantic_transform.apply(semantic))
+                    .inspect(|semantic| semantic_recognition.check_round_trip(semantic))
+                    .peekable();
+                // Fail on the empty output, before the existing output file is overwritten.
+                if entries.peek().is_none() && !output_writer.is_appending() {
+                    if let Err(error) = semantic_recognition.verify_not_empty() {
+                        log::error!("{}", error);
+                        return ExitCode::FAILURE;
+                    }
+                }
                 // Consume the entries and write them to the output file.
-                // The exit code is based on the result of the output writer.
-                match output_writer.run(entries) {
+                // The exit code is based on the result of the output writer,
+                // and the recognition errors (in strict mode).
+                let result = output_writer
+                    .run(entries)
+                    .and_then(|_| semantic_recognition.verify());
+                match result {
+                    Ok(_) => ExitCode::SUCCESS,
+                    Err(error) => {
+                        log::error!("{}", error);
+                        ExitCode::FAILURE
+                    }
+                }
+            }
+            Application::Serve { input, config } => {
+                let loader = move || -> anyhow::Result<Vec<clang::Entry>> {
+                    let event_source = EventFileReader::try_from(args::BuildEvents {
+                        file_name: input.file_name.clone(),
+                    })?;
+                    let semantic_recognition = Recognition::try_from(&config)?;
+                    let semantic_transform = Transformation::try_from(&config.output)?;
+                    let entries = event_source
+                        .generate()
+                        .flat_map(|execution| semantic_recognition.apply(execution))
+                        .flat_map(|semantic| semantic_transform.apply(semantic))
+                        .flat_map(|semantic| {
+                            bear::output::into_entries(semantic).unwrap_or_default()
+                        })
+                        .collect();
+                    Ok(entries)
+                };
+                let result = QueryServer::new(Box::new(loader)).and_then(|server| {
+                    // The clients are connecting to the printed address.
+                    println!("{}", server.address());
+                    server.serve()
+                });
+                match result {
                     Ok(_) => ExitCode::SUCCESS,
-                    Err(_) => ExitCode::FAILURE,
+                    Err(error) => {
+                        log::error!("{}", error);
+                        ExitCode::FAILURE
+                    }
                 }
             }
             Application::All {
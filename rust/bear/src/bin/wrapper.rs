@@ -18,9 +18,16 @@
 extern crate core;
 
 use anyhow::{Context, Result};
-use bear::intercept::reporter::{Reporter, TcpReporter};
-use bear::intercept::{Event, Execution, ProcessId, KEY_DESTINATION};
+use bear::intercept::reporter;
+use bear::intercept::{
+    CapturedEnvironment, Event, Execution, ProcessId, DEFAULT_ENVIRONMENT_LIMIT, KEY_DESTINATION,
+    KEY_DESTINATION_VARIABLES, KEY_ENVIRONMENT_FILTER, KEY_ENVIRONMENT_LIMIT, KEY_FALLBACK,
+    KEY_REPORTER_RETRY,
+};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicI32, Ordering};
 
 /// Implementation of the wrapper process.
 ///
@@ -34,7 +41,7 @@ fn main() -> Result<()> {
     let executable = file_name_from_arguments()?;
     log::info!("Executable as called: {:?}", executable);
     // Read the PATH variable and find the next executable with the same name
-    let real_executable = next_in_path(&executable)?;
+    let real_executable = find_executable(&executable)?;
     log::info!("Executable to call: {:?}", real_executable);
 
     // Reporting failures shall not fail the execution.
@@ -43,14 +50,91 @@ fn main() -> Result<()> {
         Err(e) => log::error!("Execution reporting failed: {}", e),
     }
 
+    // Forward the signals to the child process, to not leave it running alone.
+    if let Err(e) = forward_signals() {
+        log::warn!("Signal forwarding is not available: {}", e);
+    }
     // Execute the real executable with the same arguments
-    // TODO: handle signals and forward them to the child process.
-    let status = std::process::Command::new(real_executable)
-        .args(std::env::args().skip(1))
-        .status()?;
+    let mut command = std::process::Command::new(real_executable);
+    command.args(std::env::args().skip(1));
+    let status = run(command)?;
     log::info!("Execution finished with status: {:?}", status);
     // Return the child process status code
-    std::process::exit(status.code().unwrap_or(1));
+    std::process::exit(exit_code(status));
+}
+
+/// The process id of the running child process. (Zero, when there is none.)
+static CHILD: AtomicI32 = AtomicI32::new(0);
+/// The signal received before the child process was started. (Zero, when there was none.)
+static PENDING: AtomicI32 = AtomicI32::new(0);
+
+/// Install the signal handlers, which are forwarding the signals to the child process.
+#[cfg(unix)]
+fn forward_signals() -> Result<()> {
+    extern "C" fn handler(signal: libc::c_int) {
+        match CHILD.load(Ordering::SeqCst) {
+            0 => PENDING.store(signal, Ordering::SeqCst),
+            // SAFETY: the kill call is async-signal-safe.
+            pid => unsafe {
+                libc::kill(pid, signal);
+            },
+        }
+    }
+
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        // SAFETY: the handler is only using atomics and the kill call.
+        let previous = unsafe {
+            libc::signal(
+                signal,
+                handler as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            )
+        };
+        if previous == libc::SIG_ERR {
+            anyhow::bail!(
+                "Failed to install signal handler: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Install the signal handlers, which are forwarding the signals to the child process.
+#[cfg(not(unix))]
+fn forward_signals() -> Result<()> {
+    Ok(())
+}
+
+/// Run the command and wait for its termination.
+///
+/// The child process id is published for the signal handlers while it's running.
+/// The signal which was received before the child was started, is forwarded as
+/// soon as the child process id is known.
+fn run(mut command: std::process::Command) -> Result<ExitStatus> {
+    let mut child = command.spawn().with_context(|| "Failed to execute")?;
+    CHILD.store(child.id() as i32, Ordering::SeqCst);
+    #[cfg(unix)]
+    match PENDING.swap(0, Ordering::SeqCst) {
+        0 => {}
+        // SAFETY: the child process is not yet waited for, the process id is valid.
+        signal => unsafe {
+            libc::kill(child.id() as i32, signal);
+        },
+    }
+    let status = child.wait();
+    CHILD.store(0, Ordering::SeqCst);
+    status.with_context(|| "Failed to wait for the child process")
+}
+
+/// Returns the exit code of the child process.
+///
+/// When the child was terminated by a signal, it's `128 + signal`, like the shells do.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
 }
 
 /// Get the file name of the executable from the arguments.
@@ -70,32 +154,201 @@ fn file_name_from_arguments() -> Result<PathBuf> {
         })
 }
 
-/// Find the next executable in the PATH variable.
+/// Find the real executable to call.
 ///
-/// The function reads the PATH variable and tries to find the next executable
-/// with the same name as the given executable. It returns the path to the
-/// executable.
-fn next_in_path(target: &Path) -> Result<PathBuf> {
-    let path = std::env::var("PATH")?;
+/// The executable is searched in the PATH variable first. When it's not found,
+/// the fallback map from the environment is used. (If the build supervisor
+/// provided one.)
+fn find_executable(target: &Path) -> Result<PathBuf> {
+    let path = std::env::var("PATH").with_context(|| "$PATH is missing from the environment")?;
     log::debug!("PATH: {}", path);
     // The `current_exe` is a canonical path to the current executable.
     let current_exe = std::env::current_exe()?;
 
-    path.split(':')
-        .map(|dir| Path::new(dir).join(target))
-        // FIXME: check if it is executable
-        .filter(|path| path.is_file())
-        .filter(|path| {
-            // We need to compare it with the real path of the candidate executable to avoid
-            // calling the same executable again.
-            let real_path = match path.canonicalize() {
-                Ok(path) => path,
-                Err(_) => return false,
-            };
-            real_path != current_exe
+    next_in_path(target, &path, &current_exe).or_else(|error| {
+        let fallbacks = std::env::var(KEY_FALLBACK).unwrap_or_default();
+        match fallback_executable(target, &fallbacks) {
+            Some(executable) => {
+                log::warn!("{}. Using fallback: {:?}", error, executable);
+                Ok(executable)
+            }
+            None => Err(error),
+        }
+    })
+}
+
+/// Find the next executable in the PATH variable.
+///
+/// The function takes the PATH variable and tries to find the next executable
+/// with the same name as the given executable. It returns the path to the
+/// executable.
+fn next_in_path(target: &Path, path: &str, current_exe: &Path) -> Result<PathBuf> {
+    Platform::host().next_in_path(target, path, current_exe)
+}
+
+/// The platform specific conventions of the executable lookup.
+struct Platform {
+    /// The extensions of the executables, which are appended to the name. (From `PATHEXT`.)
+    extensions: Vec<String>,
+    /// The file names are compared case insensitive.
+    case_insensitive: bool,
+    /// The separator of the directories in the `PATH`. (With `;` the directories can be
+    /// quoted too, like on Windows.)
+    separator: char,
+}
+
+impl Platform {
+    #[cfg(windows)]
+    fn host() -> Self {
+        let extensions =
+            std::env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+        Platform {
+            extensions: extensions
+                .split(';')
+                .filter(|extension| !extension.is_empty())
+                .map(String::from)
+                .collect(),
+            case_insensitive: true,
+            separator: ';',
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn host() -> Self {
+        Platform {
+            extensions: vec![],
+            case_insensitive: false,
+            separator: ':',
+        }
+    }
+
+    fn next_in_path(&self, target: &Path, path: &str, current_exe: &Path) -> Result<PathBuf> {
+        let directories = self.split_paths(path);
+
+        directories
+            .iter()
+            .flat_map(|dir| self.candidates(dir, target))
+            .filter(|path| self.is_executable(path))
+            .find(|path| {
+                // We need to compare it with the real path of the candidate executable to avoid
+                // calling the same executable again.
+                match path.canonicalize() {
+                    Ok(real_path) => !self.is_same(&real_path, current_exe),
+                    Err(_) => false,
+                }
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Cannot find the real executable {:?} in PATH. Searched directories: {}",
+                    target,
+                    directories
+                        .iter()
+                        .map(|dir| dir.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+
+    /// Returns the directories of the `PATH` variable.
+    ///
+    /// The separator is platform specific, and so the quoting. (The Windows directories
+    /// can be quoted, to include the separator in those.)
+    fn split_paths(&self, path: &str) -> Vec<PathBuf> {
+        let quoting = self.separator == ';';
+        let mut result = vec![];
+        let mut current = String::new();
+        let mut quoted = false;
+        for c in path.chars() {
+            match c {
+                '"' if quoting => quoted = !quoted,
+                c if c == self.separator && !quoted => {
+                    result.push(PathBuf::from(std::mem::take(&mut current)))
+                }
+                c => current.push(c),
+            }
+        }
+        result.push(PathBuf::from(current));
+        result
+    }
+
+    /// Returns the candidate file names in the directory.
+    ///
+    /// When the target has no executable extension, those are appended in the order of
+    /// the `PATHEXT` variable. (Where there are no executable extensions, it's only the
+    /// target itself.)
+    fn candidates(&self, dir: &Path, target: &Path) -> Vec<PathBuf> {
+        if self.extensions.is_empty() || self.has_executable_extension(target) {
+            return vec![dir.join(target)];
+        }
+        self.extensions
+            .iter()
+            .map(|extension| {
+                let mut name = target.as_os_str().to_owned();
+                name.push(extension);
+                dir.join(name)
+            })
+            .collect()
+    }
+
+    /// Returns true, if the file can be executed.
+    ///
+    /// Where the executable extensions are defined, the extension decides. Otherwise, at
+    /// least one of the execute permission bits has to be set.
+    fn is_executable(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            has_execute_permission(path)
+        } else {
+            path.is_file() && self.has_executable_extension(path)
+        }
+    }
+
+    fn has_executable_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                self.extensions.iter().any(|known| {
+                    known
+                        .trim_start_matches('.')
+                        .eq_ignore_ascii_case(extension)
+                })
+            })
+    }
+
+    fn is_same(&self, path: &Path, other: &Path) -> bool {
+        if self.case_insensitive {
+            path.to_string_lossy().to_lowercase() == other.to_string_lossy().to_lowercase()
+        } else {
+            path == other
+        }
+    }
+}
+
+#[cfg(unix)]
+fn has_execute_permission(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn has_execute_permission(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Find the executable in the fallback map.
+///
+/// The fallback map is a list of `name=/absolute/path` entries, separated the
+/// same way as the PATH entries. Only absolute paths to existing files are accepted.
+fn fallback_executable(target: &Path, fallbacks: &str) -> Option<PathBuf> {
+    std::env::split_paths(fallbacks)
+        .filter_map(|entry| {
+            let (name, executable) = entry.to_str()?.split_once('=')?;
+            (Path::new(name) == target).then(|| PathBuf::from(executable))
         })
-        .nth(0)
-        .ok_or_else(|| anyhow::anyhow!("Cannot find the real executable"))
+        .next()
+        .filter(|executable| executable.is_absolute() && executable.is_file())
 }
 
 fn report(execution: Execution) -> Result<()> {
@@ -105,24 +358,639 @@ fn report(execution: Execution) -> Result<()> {
     };
 
     // Get the reporter address from the environment
-    std::env::var(KEY_DESTINATION)
-        .with_context(|| format!("${} is missing from the environment", KEY_DESTINATION))
+    destination(|key| std::env::var(key).ok())
         // Create a new reporter
-        .and_then(|reporter_address| TcpReporter::new(reporter_address))
-        .with_context(|| "Cannot create TCP execution reporter")
+        .and_then(|destination| reporter::for_destination_with_retry(destination, retry()))
+        .with_context(|| "Cannot create execution reporter")
         // Report the execution
         .and_then(|reporter| reporter.report(event))
         .with_context(|| "Sending execution failed")
 }
 
+/// Get the reporter address with the given environment lookup function.
+///
+/// The `KEY_DESTINATION` is checked first, then the alternative variables in the order
+/// those were listed in the `KEY_DESTINATION_VARIABLES`.
+fn destination(lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let alternatives = lookup(KEY_DESTINATION_VARIABLES).unwrap_or_default();
+    let alternatives: Vec<String> = std::env::split_paths(&alternatives)
+        .filter_map(|name| name.to_str().map(String::from))
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    std::iter::once(KEY_DESTINATION.to_string())
+        .chain(alternatives)
+        .find_map(|key| lookup(&key))
+        .ok_or_else(|| anyhow::anyhow!("${} is missing from the environment", KEY_DESTINATION))
+}
+
 fn into_execution(path_buf: &Path) -> Result<Execution> {
     std::env::current_dir()
         .with_context(|| "Cannot get current directory")
-        .map(|working_dir| Execution {
-            executable: path_buf.to_path_buf(),
-            // FIXME: substitute the executable name on the first position
-            arguments: std::env::args().collect(),
-            working_dir,
-            environment: std::env::vars().collect(),
+        .map(|working_dir| {
+            let arguments = with_executable(std::env::args().collect(), path_buf);
+            let environment = filter_environment(
+                std::env::vars().collect(),
+                &environment_filter(),
+                &referenced_variables(&arguments),
+            );
+            Execution {
+                executable: path_buf.to_path_buf(),
+                arguments,
+                working_dir,
+                environment: limit_environment(environment, environment_limit()),
+            }
         })
 }
+
+/// Substitute the first argument with the real executable.
+///
+/// The wrapper was called via the soft link, and the first argument is the name
+/// of the link. The rest of the arguments are kept as they are.
+fn with_executable(mut arguments: Vec<String>, executable: &Path) -> Vec<String> {
+    let program = executable.to_string_lossy().to_string();
+    match arguments.first_mut() {
+        Some(first) => *first = program,
+        None => arguments.push(program),
+    }
+    arguments
+}
+
+/// Get the reporter connection retries from the environment.
+fn retry() -> reporter::Retry {
+    match std::env::var(KEY_REPORTER_RETRY) {
+        Ok(value) => parse_retry(&value).unwrap_or_else(|| {
+            log::warn!("${} is not valid: {:?}", KEY_REPORTER_RETRY, value);
+            reporter::Retry::default()
+        }),
+        Err(_) => reporter::Retry::default(),
+    }
+}
+
+/// Parse the retries, which are given as `attempts,delay` (the delay in milliseconds).
+fn parse_retry(value: &str) -> Option<reporter::Retry> {
+    let (attempts, delay) = value.split_once(',')?;
+    Some(reporter::Retry {
+        attempts: attempts.trim().parse().ok()?,
+        base_delay: std::time::Duration::from_millis(delay.trim().parse().ok()?),
+    })
+}
+
+/// Get the captured environment size limit from the environment.
+fn environment_limit() -> usize {
+    match std::env::var(KEY_ENVIRONMENT_LIMIT) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            log::warn!("${} is not a number: {:?}", KEY_ENVIRONMENT_LIMIT, value);
+            DEFAULT_ENVIRONMENT_LIMIT
+        }),
+        Err(_) => DEFAULT_ENVIRONMENT_LIMIT,
+    }
+}
+
+/// Get the captured environment filter from the environment.
+fn environment_filter() -> CapturedEnvironment {
+    match std::env::var(KEY_ENVIRONMENT_FILTER) {
+        Ok(value) => serde_json::from_str(&value).unwrap_or_else(|error| {
+            log::warn!("${} is not valid: {}", KEY_ENVIRONMENT_FILTER, error);
+            CapturedEnvironment::default()
+        }),
+        Err(_) => CapturedEnvironment::default(),
+    }
+}
+
+/// Keep the variables of the environment, which are selected by the filter.
+///
+/// The variables of the interception (with the `INTERCEPT_` prefix) and the `PATH`
+/// are always kept. (The recognition of the own wrapper is relying on those.) The
+/// referenced variables are kept, unless those are denied.
+fn filter_environment(
+    environment: HashMap<String, String>,
+    filter: &CapturedEnvironment,
+    referenced: &HashSet<String>,
+) -> HashMap<String, String> {
+    let matching = |patterns: &[String], name: &str| {
+        patterns
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    };
+    environment
+        .into_iter()
+        .filter(|(name, _)| {
+            is_essential(name)
+                || ((matching(&filter.allow, name) || referenced.contains(name))
+                    && !matching(&filter.deny, name))
+        })
+        .collect()
+}
+
+/// The variables of the interception and the `PATH`, which are never dropped.
+fn is_essential(name: &str) -> bool {
+    name == KEY_DESTINATION || name.starts_with("INTERCEPT_") || name == "PATH"
+}
+
+/// Collect the variable names, which the arguments are referring to.
+///
+/// The references are like `$NAME` or `${NAME}`, the same as the recognition expands.
+fn referenced_variables(arguments: &[String]) -> HashSet<String> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = HashSet::new();
+    for argument in arguments {
+        let mut rest = argument.as_str();
+        while let Some(position) = rest.find('$') {
+            rest = &rest[position + 1..];
+            let (candidate, braced) = match rest.strip_prefix('{') {
+                Some(braced) => (braced, true),
+                None => (rest, false),
+            };
+            let length = candidate.find(|c| !is_name(c)).unwrap_or(candidate.len());
+            let name = &candidate[..length];
+            let closed = !braced || candidate[length..].starts_with('}');
+            if closed && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                result.insert(name.to_string());
+            }
+        }
+    }
+    result
+}
+
+/// Truncate the environment to the given size.
+///
+/// The size of the environment is the sum of the variable names and values in bytes.
+/// When it's over the limit, the largest variables are dropped first, until the rest
+/// fits into the limit. The variables of the interception and the `PATH` are kept,
+/// even if the rest does not fit then.
+fn limit_environment(
+    mut environment: HashMap<String, String>,
+    limit: usize,
+) -> HashMap<String, String> {
+    let size_of = |(key, value): (&String, &String)| key.len() + value.len();
+
+    let mut size: usize = environment.iter().map(size_of).sum();
+    if size <= limit {
+        return environment;
+    }
+    let mut candidates: Vec<(String, usize)> = environment
+        .iter()
+        .filter(|(key, _)| !is_essential(key))
+        .map(|entry| (entry.0.clone(), size_of(entry)))
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut dropped = vec![];
+    for (key, variable_size) in candidates {
+        if size <= limit {
+            break;
+        }
+        environment.remove(&key);
+        size -= variable_size;
+        dropped.push(key);
+    }
+    log::warn!(
+        "Environment is over the limit of {} bytes, dropped variables: {}",
+        limit,
+        dropped.join(", ")
+    );
+    environment
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_in_path_lists_searched_directories() {
+        let current_exe = std::env::current_exe().unwrap();
+        let result = next_in_path(
+            Path::new("not-existing-compiler"),
+            "/not/existing/bin:/other/not/existing/bin",
+            &current_exe,
+        );
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("\"not-existing-compiler\""));
+        assert!(message.contains("/not/existing/bin, /other/not/existing/bin"));
+    }
+
+    #[test]
+    fn test_next_in_path_skips_current_executable() {
+        let current_exe = std::env::current_exe().unwrap().canonicalize().unwrap();
+        let directory = current_exe.parent().unwrap().to_str().unwrap().to_string();
+        let file_name = current_exe.file_name().unwrap();
+
+        let result = next_in_path(Path::new(file_name), &directory, &current_exe);
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_next_in_path_with_drive_letters() {
+        let current_exe = std::env::current_exe().unwrap();
+        let result = next_in_path(
+            Path::new("cl.exe"),
+            r"C:\not\existing\bin;D:\other\bin",
+            &current_exe,
+        );
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains(r"C:\not\existing\bin, D:\other\bin"));
+    }
+
+    #[test]
+    fn test_next_in_path_with_both_path_forms() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-path-test-{}", rand::random::<u64>()));
+        for name in [
+            "wrappers/cc",
+            "bin/cc",
+            "wrappers/cl.EXE",
+            "bin/cl.EXE",
+            "bin/cl.BAT",
+        ] {
+            std::fs::create_dir_all(directory.join(name).parent().unwrap()).unwrap();
+            std::fs::write(directory.join(name), "").unwrap();
+            make_executable(&directory.join(name));
+        }
+        let wrappers = directory.join("wrappers").canonicalize().unwrap();
+        let bin = directory.join("bin").canonicalize().unwrap();
+
+        let unix = Platform {
+            extensions: vec![],
+            case_insensitive: false,
+            separator: ':',
+        };
+        let path = format!("{}:{}", wrappers.display(), bin.display());
+        assert_eq!(
+            bin.join("cc"),
+            unix.next_in_path(Path::new("cc"), &path, &wrappers.join("cc"))
+                .unwrap()
+        );
+
+        let windows = Platform {
+            extensions: vec![
+                String::from(".COM"),
+                String::from(".EXE"),
+                String::from(".BAT"),
+            ],
+            case_insensitive: true,
+            separator: ';',
+        };
+        let path = format!("{};\"{}\"", wrappers.display(), bin.display());
+        // The current executable is given with different case, like Windows could report it.
+        let current_exe = PathBuf::from(wrappers.join("cl.EXE").to_string_lossy().to_uppercase());
+        assert_eq!(
+            bin.join("cl.EXE"),
+            windows
+                .next_in_path(Path::new("cl"), &path, &current_exe)
+                .unwrap()
+        );
+        assert_eq!(
+            bin.join("cl.BAT"),
+            windows
+                .next_in_path(Path::new("cl.BAT"), &path, &current_exe)
+                .unwrap()
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_path_is_split_by_the_platform_rules() {
+        let unix = Platform {
+            extensions: vec![],
+            case_insensitive: false,
+            separator: ':',
+        };
+        let windows = Platform {
+            extensions: vec![String::from(".EXE")],
+            case_insensitive: true,
+            separator: ';',
+        };
+
+        assert_eq!(vec_of_paths(&["/a", "/b"]), unix.split_paths("/a:/b"));
+        assert_eq!(
+            vec_of_paths(&["C", "\\a;C", "\\b"]),
+            unix.split_paths("C:\\a;C:\\b")
+        );
+        assert_eq!(
+            vec_of_paths(&["C:\\a", "C:\\b"]),
+            windows.split_paths("C:\\a;C:\\b")
+        );
+        assert_eq!(vec_of_paths(&["/a:/b"]), windows.split_paths("/a:/b"));
+        // The quoted directory can have the separator in it.
+        assert_eq!(
+            vec_of_paths(&["C:\\a;b", "C:\\c"]),
+            windows.split_paths("\"C:\\a;b\";C:\\c")
+        );
+    }
+
+    fn vec_of_paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_next_in_path_skips_not_executable_files() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-path-test-{}", rand::random::<u64>()));
+        for name in ["data/gcc", "bin/gcc"] {
+            std::fs::create_dir_all(directory.join(name).parent().unwrap()).unwrap();
+            std::fs::write(directory.join(name), "").unwrap();
+        }
+        make_executable(&directory.join("bin/gcc"));
+        let current_exe = std::env::current_exe().unwrap();
+        let path = format!(
+            "{}:{}",
+            directory.join("data").display(),
+            directory.join("bin").display()
+        );
+
+        let result = next_in_path(Path::new("gcc"), &path, &current_exe);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(directory.join("bin/gcc"), result.unwrap());
+    }
+
+    #[test]
+    fn test_fallback_executable() {
+        let existing = std::env::current_exe().unwrap();
+        let fallbacks = std::env::join_paths([
+            String::from("cc=/not/existing/cc"),
+            format!("c++={}", existing.display()),
+        ])
+        .unwrap();
+        let fallbacks = fallbacks.to_str().unwrap();
+
+        assert_eq!(
+            Some(existing),
+            fallback_executable(Path::new("c++"), fallbacks)
+        );
+        assert_eq!(None, fallback_executable(Path::new("cc"), fallbacks));
+        assert_eq!(None, fallback_executable(Path::new("ld"), fallbacks));
+        assert_eq!(None, fallback_executable(Path::new("cc"), ""));
+    }
+
+    #[test]
+    fn test_first_argument_is_the_real_executable() {
+        let arguments = vec![
+            String::from("/tmp/bear/wrappers/cc"),
+            String::from("-c"),
+            String::from("main.c"),
+        ];
+
+        assert_eq!(
+            vec![
+                String::from("/usr/bin/gcc-12"),
+                String::from("-c"),
+                String::from("main.c")
+            ],
+            with_executable(arguments, Path::new("/usr/bin/gcc-12"))
+        );
+        assert_eq!(
+            vec![String::from("/usr/bin/gcc-12")],
+            with_executable(vec![], Path::new("/usr/bin/gcc-12"))
+        );
+    }
+
+    #[test]
+    fn test_destination_from_alternative_variable() {
+        let names = std::env::join_paths(["SANDBOX_REMOVED", "SANDBOX_KEPT"]).unwrap();
+        let environment = HashMap::from([
+            (
+                KEY_DESTINATION_VARIABLES.to_string(),
+                names.to_str().unwrap().to_string(),
+            ),
+            (
+                String::from("SANDBOX_KEPT"),
+                String::from("127.0.0.1:12345"),
+            ),
+        ]);
+        let lookup = |key: &str| environment.get(key).cloned();
+
+        assert_eq!("127.0.0.1:12345", destination(lookup).unwrap());
+
+        // The primary variable takes precedence.
+        let mut environment = environment.clone();
+        environment.insert(KEY_DESTINATION.to_string(), String::from("127.0.0.1:1"));
+        let lookup = |key: &str| environment.get(key).cloned();
+
+        assert_eq!("127.0.0.1:1", destination(lookup).unwrap());
+
+        assert!(destination(|_| None).is_err());
+    }
+
+    #[test]
+    fn test_oversized_environment_is_truncated() {
+        let environment = HashMap::from([
+            (String::from("PATH"), String::from("/usr/bin:/bin")),
+            (String::from("HOME"), String::from("/home/user")),
+            (String::from("JAVA_OPTS"), "x".repeat(4096)),
+        ]);
+
+        let messages = capture_warnings(|| {
+            let result = limit_environment(environment.clone(), 1024);
+
+            let mut expected = environment.clone();
+            expected.remove("JAVA_OPTS");
+            assert_eq!(expected, result);
+        });
+        assert!(messages
+            .iter()
+            .any(|message| message.contains("dropped variables: JAVA_OPTS")));
+
+        // The environment within the limit is not changed.
+        assert_eq!(environment, limit_environment(environment.clone(), 8192));
+    }
+
+    #[test]
+    fn test_oversized_environment_keeps_the_path() {
+        let environment = HashMap::from([
+            (String::from("PATH"), "/opt/bin:".repeat(512)),
+            (
+                String::from(KEY_DESTINATION),
+                String::from("127.0.0.1:8080"),
+            ),
+            (String::from("INTERCEPT_VERBOSE"), String::from("1")),
+            (String::from("HOME"), String::from("/home/user")),
+            (String::from("JAVA_OPTS"), "x".repeat(1024)),
+        ]);
+
+        let result = limit_environment(environment.clone(), 1024);
+
+        let mut expected = environment.clone();
+        expected.remove("HOME");
+        expected.remove("JAVA_OPTS");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_retry_is_parsed() {
+        assert_eq!(
+            Some(reporter::Retry {
+                attempts: 5,
+                base_delay: std::time::Duration::from_millis(40),
+            }),
+            parse_retry("5, 40")
+        );
+        assert_eq!(None, parse_retry("5"));
+        assert_eq!(None, parse_retry("five,40"));
+    }
+
+    #[test]
+    fn test_environment_is_filtered() {
+        let environment = HashMap::from([
+            (String::from("PATH"), String::from("/usr/bin:/bin")),
+            (String::from(KEY_DESTINATION), String::from("127.0.0.1:1")),
+            (String::from("CFLAGS"), String::from("-O2")),
+            (String::from("CCC_CC"), String::from("clang")),
+            (String::from("GITHUB_TOKEN"), String::from("secret")),
+            (
+                String::from("AWS_SECRET_ACCESS_KEY"),
+                String::from("secret"),
+            ),
+        ]);
+        let selected = |names: &[&str]| -> HashMap<String, String> {
+            names
+                .iter()
+                .map(|name| (name.to_string(), environment[*name].clone()))
+                .collect()
+        };
+
+        // The unknown variables are dropped by default.
+        assert_eq!(
+            selected(&["PATH", KEY_DESTINATION, "CFLAGS", "CCC_CC"]),
+            filter_environment(
+                environment.clone(),
+                &CapturedEnvironment::default(),
+                &HashSet::new()
+            )
+        );
+
+        // The interception variables are kept, even when those are denied.
+        let filter = CapturedEnvironment {
+            allow: vec![String::from("GITHUB_*"), String::from("CFLAGS")],
+            deny: vec![String::from("CFLAGS"), String::from("PATH")],
+        };
+        assert_eq!(
+            selected(&["PATH", KEY_DESTINATION, "GITHUB_TOKEN"]),
+            filter_environment(environment.clone(), &filter, &HashSet::new())
+        );
+
+        // The referenced variables are kept, unless those are denied.
+        let environment = HashMap::from([
+            (String::from("PATH"), String::from("/usr/bin:/bin")),
+            (String::from("SDKROOT"), String::from("/opt/sdk")),
+            (String::from("GITHUB_TOKEN"), String::from("secret")),
+        ]);
+        let arguments = vec![
+            String::from("cc"),
+            String::from("--sysroot=${SDKROOT}"),
+            String::from("-DT=$GITHUB_TOKEN"),
+            String::from("-c"),
+        ];
+        let filter = CapturedEnvironment {
+            allow: vec![],
+            deny: vec![String::from("GITHUB_*")],
+        };
+        assert_eq!(
+            HashMap::from([
+                (String::from("PATH"), String::from("/usr/bin:/bin")),
+                (String::from("SDKROOT"), String::from("/opt/sdk")),
+            ]),
+            filter_environment(environment, &filter, &referenced_variables(&arguments))
+        );
+    }
+
+    #[test]
+    fn test_referenced_variables() {
+        let arguments = vec![
+            String::from("cc"),
+            String::from("-I${SDKROOT}/include"),
+            String::from("$HOME/a"),
+            String::from("${BROKEN"),
+            String::from("$1"),
+            String::from("$"),
+        ];
+
+        assert_eq!(
+            HashSet::from([String::from("SDKROOT"), String::from("HOME")]),
+            referenced_variables(&arguments)
+        );
+    }
+
+    /// The child process id is global, the tests running child processes are serialized.
+    static RUNNING: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_is_forwarded_to_the_child() {
+        let _running = RUNNING.lock().unwrap();
+        forward_signals().unwrap();
+
+        let interrupt = std::thread::spawn(|| {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            unsafe { libc::raise(libc::SIGTERM) };
+        });
+        let mut command = std::process::Command::new("sleep");
+        command.arg("30");
+        let started = std::time::Instant::now();
+        let status = run(command).unwrap();
+        interrupt.join().unwrap();
+
+        assert_eq!(128 + libc::SIGTERM, exit_code(status));
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_code_of_the_child() {
+        let _running = RUNNING.lock().unwrap();
+        let status = |script: &str| {
+            let mut command = std::process::Command::new("sh");
+            command.args(["-c", script]);
+            exit_code(run(command).unwrap())
+        };
+
+        assert_eq!(0, status("true"));
+        assert_eq!(3, status("exit 3"));
+        assert_eq!(128 + libc::SIGKILL, status("kill -KILL $$"));
+    }
+
+    /// Run the function and returns the warnings it was logging.
+    fn capture_warnings(f: impl FnOnce()) -> Vec<String> {
+        use std::sync::Mutex;
+
+        static MESSAGES: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+        struct Capture;
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Warn
+            }
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    MESSAGES.lock().unwrap().push(record.args().to_string());
+                }
+            }
+            fn flush(&self) {}
+        }
+
+        let _ = log::set_logger(&Capture);
+        log::set_max_level(log::LevelFilter::Warn);
+        f();
+        MESSAGES.lock().unwrap().clone()
+    }
+
+    fn make_executable(path: &Path) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+    }
+}
@@ -84,11 +84,39 @@ use directories::{BaseDirs, ProjectDirs};
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 
+use crate::intercept::CapturedEnvironment;
+use crate::semantic::interpreters::INTERPRETER_NAMES;
+
 const SUPPORTED_SCHEMA_VERSION: &str = "4.0";
 const PRELOAD_LIBRARY_PATH: &str = env!("PRELOAD_LIBRARY_PATH");
 const WRAPPER_EXECUTABLE_PATH: &str = env!("WRAPPER_EXECUTABLE_PATH");
 
 /// Represents the application configuration.
+///
+/// The strict mode makes the application to fail, when any of the executions
+/// failed to be recognized. (The results are still written into the output.)
+///
+/// The self check mode makes the application to recognize the written entries again,
+/// and compare them to the written ones. It catches the flag transformations, which
+/// are changing the meaning of the entries. It's disabled by default, because of the
+/// cost. The mismatches are recognition errors. (Which are failing in strict mode.)
+///
+/// The preferred interpreter is the tiebreaker, when multiple interpreters are recognizing
+/// the same execution as compiler call. (By default, the first one wins.) The value is the
+/// name of the interpreter, one of `fortran`, `gcc`, `msvc`, `nvcc` or `generic`.
+///
+/// The recognition environment is the list of the environment variable names, which the
+/// recognition is allowed to read. (Like the `CCC_CC` for the `scan-build` shims.) The other
+/// variables of the executions are ignored, to make the recognition deterministic. When
+/// it's not given, all variables are visible to the recognition.
+///
+/// The empty output defines what happens, when no compiler calls were recognized. The
+/// `error` fails the run with a diagnostic of the executions (how many were ignored or
+/// not recognized), the `write` writes the empty compilation database. When it's not
+/// given, it's an error in strict mode, otherwise the empty database is written. It's
+/// never an error, when appending to an existing compilation database. (The existing
+/// entries are kept, like for an up to date build.) The check is done before the output
+/// is written, so the failed run does not overwrite the existing file.
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct Main {
     #[serde(deserialize_with = "validate_schema_version")]
@@ -97,6 +125,16 @@ pub struct Main {
     pub intercept: Intercept,
     #[serde(default)]
     pub output: Output,
+    #[serde(default = "default_disabled")]
+    pub strict: bool,
+    #[serde(default = "default_disabled")]
+    pub self_check: bool,
+    #[serde(default)]
+    pub preferred_interpreter: Option<String>,
+    #[serde(default)]
+    pub recognition_environment: Option<Vec<String>>,
+    #[serde(default)]
+    pub empty_output: Option<EmptyOutput>,
 }
 
 impl Main {
@@ -173,7 +211,7 @@ impl Main {
     fn from_reader<R, T>(rdr: R) -> serde_yml::Result<T>
     where
         R: std::io::Read,
-        T: serde::de::DeserializeOwned,
+        T: serde::de::DeserializeOwned + 'static,
     {
         serde_yml::from_reader(rdr)
     }
@@ -183,6 +221,11 @@ impl Default for Main {
     fn default() -> Self {
         Main {
             schema: String::from(SUPPORTED_SCHEMA_VERSION),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
             intercept: Intercept::default(),
             output: Output::default(),
         }
@@ -194,20 +237,50 @@ impl Validate for Main {
     fn validate(self) -> Result<Self> {
         let intercept = self.intercept.validate()?;
         let output = self.output.validate()?;
+        if let Some(name) = &self.preferred_interpreter {
+            if !INTERPRETER_NAMES.contains(&name.as_str()) {
+                anyhow::bail!(
+                    "The preferred interpreter is unknown: {:?} (expected one of {:?})",
+                    name,
+                    INTERPRETER_NAMES
+                );
+            }
+        }
 
         Ok(Main {
             schema: self.schema,
             intercept,
             output,
+            strict: self.strict,
+            self_check: self.self_check,
+            preferred_interpreter: self.preferred_interpreter,
+            recognition_environment: self.recognition_environment,
+            empty_output: self.empty_output,
         })
     }
 }
 
+/// Represents what happens, when no compiler calls were recognized.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum EmptyOutput {
+    #[serde(rename = "error")]
+    Error,
+    #[serde(rename = "write")]
+    Write,
+}
+
 /// Intercept configuration is either a wrapper or a preload mode.
 ///
 /// In wrapper mode, the compiler is wrapped with a script that intercepts the compiler calls.
 /// The configuration for that is capturing the directory where the wrapper scripts are stored
-/// and the list of executables to wrap.
+/// and the list of executables to wrap. The `launchers` are programs which are calling the
+/// compiler given as their first argument. (Like `ccache gcc -c foo.c`.) These are extending
+/// the list of the known launchers (`ccache`, `sccache`, `distcc` and `icecc`). The
+/// `destination_variables` are alternative environment variable names of the reporter
+/// address, which the wrapper consults (in the given order) when the original variable
+/// was removed from the environment. (Like by a sandbox, which keeps only some variables.)
+/// The `environment` selects the variables, which the wrapper captures for the executions.
+/// (See the `intercept::CapturedEnvironment` for the details.)
 ///
 /// In preload mode, the compiler is intercepted by a shared library that is preloaded before
 /// the compiler is executed. The configuration for that is the path to the shared library.
@@ -221,6 +294,12 @@ pub enum Intercept {
         #[serde(default = "default_wrapper_directory")]
         directory: PathBuf,
         executables: Vec<PathBuf>,
+        #[serde(default)]
+        launchers: Vec<PathBuf>,
+        #[serde(default)]
+        destination_variables: Vec<String>,
+        #[serde(default)]
+        environment: CapturedEnvironment,
     },
     #[serde(rename = "preload")]
     Preload {
@@ -244,6 +323,9 @@ impl Default for Intercept {
             path: default_wrapper_executable(),
             directory: default_wrapper_directory(),
             executables: vec![], // FIXME: better default value
+            launchers: vec![],
+            destination_variables: vec![],
+            environment: CapturedEnvironment::default(),
         }
     }
 }
@@ -256,6 +338,9 @@ impl Validate for Intercept {
                 path,
                 directory,
                 executables,
+                launchers,
+                destination_variables,
+                environment,
             } => {
                 if is_empty_path(&path) {
                     anyhow::bail!("The wrapper path cannot be empty.");
@@ -266,10 +351,16 @@ impl Validate for Intercept {
                 if executables.is_empty() {
                     anyhow::bail!("The list of executables to wrap cannot be empty.");
                 }
+                if destination_variables.iter().any(|name| name.is_empty()) {
+                    anyhow::bail!("The name of a destination variable cannot be empty.");
+                }
                 Ok(Intercept::Wrapper {
                     path,
                     directory,
                     executables,
+                    launchers,
+                    destination_variables,
+                    environment,
                 })
             }
             Intercept::Preload { path } => {
@@ -289,18 +380,13 @@ impl Validate for Intercept {
 /// - Clang: Output the compiler calls in the clang project defined "JSON compilation database"
 /// format. (The format is used by clang tooling and other tools based on that library.)
 /// - Semantic: Output the compiler calls in the semantic format. (The format is not defined yet.)
+///
+/// The options of the clang format are documented at the `ClangOutput`.
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(tag = "specification")]
 pub enum Output {
     #[serde(rename = "clang")]
-    Clang {
-        #[serde(default)]
-        compilers: Vec<Compiler>,
-        #[serde(default)]
-        filter: Filter,
-        #[serde(default)]
-        format: Format,
-    },
+    Clang(Box<ClangOutput>),
     #[serde(rename = "bear")]
     Semantic {},
 }
@@ -308,10 +394,243 @@ pub enum Output {
 /// The default output is the clang format.
 impl Default for Output {
     fn default() -> Self {
-        Output::Clang {
+        Output::Clang(Box::default())
+    }
+}
+
+/// Clang output configuration is used to customize the JSON compilation database.
+///
+/// The `flags_to_remove` are removed from all compiler calls of the clang format. A flag
+/// ending with `*` removes all flags starting with the given prefix. (Like `-g*` removes the
+/// debug information flags, which are not relevant for the tools.) By default, the
+/// flags which are formatting the diagnostic messages for terminals, and some GCC
+/// specific flags (which the clang based tools are not accepting) are removed.
+///
+/// The `dependency_manifest` is an optional file name. When given, the dependency files
+/// (requested with `-MD`, `-MMD` or `-MF` flags) of the compiled sources are listed there.
+///
+/// The `allowed_compilers` is an allowlist of the compilers. When it's not empty, only the
+/// compiler calls of these are written into the output, the others are dropped. (A compiler
+/// given without directory matches by the file name.)
+///
+/// The `hoist_leading_flags` enables the recognition of compiler calls, where flags are
+/// preceding the compiler. (Like `-I/common clang -c foo.c`, which some flag prepending
+/// shims are producing.) The leading flags are moved after the compiler. It's disabled
+/// by default, to avoid false positive recognitions.
+///
+/// The `output_directories` are used to infer the output of the compiler calls, which are
+/// not naming it with the `-o` flag. (See the `OutputDirectory` for the details.)
+///
+/// The `compile_flags_files` enables to write a `compile_flags.txt` file into each directory
+/// which contains compiled sources. The file lists the flags, which are common to all
+/// compilations in that directory. (Clangd uses it for the files which are not in the
+/// compilation database.) The relative include paths are rebased to the directory of the
+/// file, and the `-c` flag is not written. The existing files are kept (these might be
+/// written by hand), unless the `overwrite_compile_flags_files` is enabled. It's disabled
+/// by default.
+///
+/// The `response_file_depth` is the maximum depth of the response file (`@file` argument)
+/// expansion, before the recognition. (Response files can refer to other response files.)
+/// The self referring response files are not expanded. Zero disables the expansion.
+///
+/// The `json_lines_output` is an optional file name. When given, the entries are also written
+/// there, as newline delimited JSON objects. (One entry per line, without enclosing array.)
+///
+/// The `compiler_databases` are writing the entries of the given compilers into separate
+/// compilation databases. (See the `CompilerDatabases` for the details.)
+///
+/// The `append` enables to merge the entries into the existing compilation database (like
+/// the `--append` command line flag). The existing entries of the recompiled sources (with
+/// the same directory and file) are replaced, the others are kept. The entries are sorted
+/// by the directory and file. A malformed existing file is an error, to not lose entries.
+///
+/// The `max_entries_in_memory` is an optional limit of the entries kept in memory while
+/// writing the output. Over the limit the entries are sorted and spilled to temporary
+/// files next to the output, which are merged at the end. The output is then sorted by
+/// the file, the directory and the output. (The duplicate filter keeps only the keys of the entries in
+/// memory, use the hash comparison to keep those small too.) The options which need all
+/// entries in memory (the `append`, the `UnionFlags` duplicate policy, the
+/// `synthetic_outputs`, the `compiler_databases` and the `json_lines_output`) can't be
+/// used with it. Neither the dry run, which shall not write the temporary files.
+///
+/// The `include` and `exclude` are glob patterns of the source files to write into the
+/// output. These are matched against the absolute path of the source file. (A relative
+/// pattern, like `third_party/**`, matches from any directory of the path.) The `*`
+/// matches within a directory name, the `**` matches across directories too. The
+/// excludes are taking precedence over the includes, and the empty include list
+/// includes all source files. The filtering is done at the output, the recognition
+/// logs are showing the excluded compiler calls too.
+///
+/// The `keep_ambiguous_headers` keeps the compilations of header files, which are given
+/// with the `-c` or `-S` flag, but without the `-x` flag. (Like `gcc -c util.h`, which might
+/// create a precompiled header, or compile the header as a translation unit.) These are
+/// dropped by default. With the `-x` flag the header is compiled in the given language.
+///
+/// The `output_suffixes_to_strip` are regular expressions of the output file name suffixes,
+/// which are removed from the outputs. (Like `\.tmp` or `\.TMP-[0-9A-Za-z]+`, for build
+/// wrappers which are compiling into a temporary file and rename it after.) The patterns
+/// are matching at the end of the output, the first matching one is removed.
+///
+/// The `deduplicate` keeps only the last entry of the same compilation, which is the
+/// same directory, file and output. (The last one typically reflects the final flags.)
+/// It's enabled by default, disabling it keeps the entries of every compiler invocation.
+/// (The `filter.duplicates` is still applied then.)
+///
+/// The `expand_environment` enables to expand the environment variable references (like
+/// `$SYSROOT` or `${SYSROOT}`) in the paths of the compiler calls, with the environment of
+/// the execution. (Like `-I$SYSROOT/include`, which was not expanded by a shell.) The
+/// undefined variables are left as those were. It's disabled by default, because most
+/// builds are passing the expanded paths.
+///
+/// The `append_limits` are protecting the append mode from huge existing compilation
+/// databases, or slow filesystems. (See the `AppendLimits` for the details.)
+///
+/// The `synthetic_outputs` enables to replace the output of the entries, when the outputs
+/// of different sources are colliding (or the output is missing). Like multiple `foo.c`
+/// in different directories, which are compiled into `foo.o` in the same directory. The
+/// synthetic output has the hash of the source directory in its name, to keep the
+/// outputs unique and stable between runs. The arguments are not changed. It's disabled
+/// by default.
+///
+/// The `collapse_include_paths` enables to drop the `-I` flags of the directories, which
+/// are given with `-isystem` too. (The compiler is doing the same, the directory is searched
+/// as a system directory, and the warnings are suppressed in it.) It's disabled by default.
+///
+/// The `compiler_patterns` are recognizing or excluding the compilers by glob or regular
+/// expression patterns. (See the `CompilerPatterns` for the details.)
+///
+/// The `post_write_hook` is an optional command to run after the compilation database
+/// was written. (See the `PostWriteHook` for the details.)
+///
+/// The `streaming` enables to write the entries into the output as the compiler calls are
+/// recognized, instead of collecting them until the end. (The memory is not growing with
+/// the build, and the interrupted run leaves the entries so far in the output.) The killed
+/// run (like by `SIGKILL`) leaves the array unterminated, the output is not a valid JSON then.
+/// The options which need all entries (like `deduplicate`, the `union_flags` duplicates
+/// policy, `append`, `synthetic_outputs`, `max_entries_in_memory`, the keyed format, the
+/// `compiler_databases` and the `json_lines_output`) can't be used with it. (The
+/// `deduplicate` has to be disabled explicitly.) It's disabled by default.
+///
+/// The `sort` enables to write the entries sorted by the file, the directory and the
+/// output. (The order of the executions is not deterministic, the sorted output is the
+/// same between runs of the same build.) The sort is stable, the entries with the same
+/// key are keeping their order. It's enabled by default, disabling it keeps the order
+/// of the executions. (The spilled and the streamed outputs are not sorted by it.)
+///
+/// The `keep_existing_order` enables to write the entries in the order of the existing
+/// compilation database, to keep the changes of the regenerated database minimal. The
+/// entries are matched by the file and the output, the new entries are written after
+/// the existing ones (sorted, when the `sort` is enabled). It's disabled by default.
+///
+/// The `pin_resource_dir` enables to add the `-resource-dir` flag to the entries of the
+/// clang compilers. The resource directory is queried from the compiler (once for each
+/// compiler, with the environment of its first call), with the `-print-resource-dir`
+/// flag. (The relocatable toolchains are deriving it from the compiler path, which might
+/// not be stable.) The query is killed, when it's not finished in 10 seconds. It's
+/// disabled by default, because it runs the compilers.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ClangOutput {
+    #[serde(default)]
+    pub compilers: Vec<Compiler>,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub source_mappings: Vec<SourceMapping>,
+    #[serde(default)]
+    pub compiler_wrappers: Vec<CompilerWrapper>,
+    #[serde(default = "default_flags_to_remove")]
+    pub flags_to_remove: Vec<String>,
+    #[serde(default)]
+    pub dependency_manifest: Option<PathBuf>,
+    #[serde(default)]
+    pub allowed_compilers: Vec<PathBuf>,
+    #[serde(default = "default_disabled")]
+    pub hoist_leading_flags: bool,
+    #[serde(default)]
+    pub output_directories: Vec<OutputDirectory>,
+    #[serde(default = "default_disabled")]
+    pub compile_flags_files: bool,
+    #[serde(default = "default_disabled")]
+    pub overwrite_compile_flags_files: bool,
+    #[serde(default = "default_response_file_depth")]
+    pub response_file_depth: usize,
+    #[serde(default)]
+    pub json_lines_output: Option<PathBuf>,
+    #[serde(default)]
+    pub compiler_databases: CompilerDatabases,
+    #[serde(default = "default_disabled")]
+    pub append: bool,
+    #[serde(default)]
+    pub max_entries_in_memory: Option<usize>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_disabled")]
+    pub keep_ambiguous_headers: bool,
+    #[serde(default)]
+    pub output_suffixes_to_strip: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub deduplicate: bool,
+    #[serde(default = "default_disabled")]
+    pub expand_environment: bool,
+    #[serde(default)]
+    pub append_limits: AppendLimits,
+    #[serde(default = "default_disabled")]
+    pub synthetic_outputs: bool,
+    #[serde(default = "default_disabled")]
+    pub collapse_include_paths: bool,
+    #[serde(default)]
+    pub compiler_patterns: CompilerPatterns,
+    #[serde(default)]
+    pub post_write_hook: Option<PostWriteHook>,
+    #[serde(default = "default_disabled")]
+    pub streaming: bool,
+    #[serde(default = "default_enabled")]
+    pub sort: bool,
+    #[serde(default = "default_disabled")]
+    pub keep_existing_order: bool,
+    #[serde(default = "default_disabled")]
+    pub pin_resource_dir: bool,
+}
+
+impl Default for ClangOutput {
+    fn default() -> Self {
+        ClangOutput {
             compilers: vec![],
             filter: Filter::default(),
             format: Format::default(),
+            source_mappings: vec![],
+            compiler_wrappers: vec![],
+            flags_to_remove: default_flags_to_remove(),
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: default_response_file_depth(),
+            json_lines_output: None,
+            compiler_databases: CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
         }
     }
 }
@@ -320,28 +639,137 @@ impl Validate for Output {
     /// Validate the configuration of the output writer.
     fn validate(self) -> Result<Self> {
         match self {
-            Output::Clang {
-                compilers,
-                filter,
-                format,
-            } => {
-                let compilers = compilers.validate()?;
-                let filter = filter.validate()?;
-                Ok(Output::Clang {
-                    compilers,
-                    filter,
-                    format,
-                })
-            }
+            Output::Clang(clang) => Ok(Output::Clang(Box::new(clang.validate()?))),
             Output::Semantic {} => Ok(Output::Semantic {}),
         }
     }
 }
 
+impl Validate for ClangOutput {
+    /// Validate the configuration of the clang output writer.
+    fn validate(self) -> Result<Self> {
+        let ClangOutput {
+            compilers,
+            filter,
+            format,
+            source_mappings,
+            compiler_wrappers,
+            flags_to_remove,
+            dependency_manifest,
+            allowed_compilers,
+            hoist_leading_flags,
+            output_directories,
+            compile_flags_files,
+            overwrite_compile_flags_files,
+            response_file_depth,
+            json_lines_output,
+            compiler_databases,
+            append,
+            max_entries_in_memory,
+            include,
+            exclude,
+            keep_ambiguous_headers,
+            output_suffixes_to_strip,
+            deduplicate,
+            expand_environment,
+            append_limits,
+            synthetic_outputs,
+            collapse_include_paths,
+            compiler_patterns,
+            post_write_hook,
+            streaming,
+            sort,
+            keep_existing_order,
+            pin_resource_dir,
+        } = self;
+        if max_entries_in_memory == Some(0) {
+            anyhow::bail!("The maximum number of entries in memory can't be zero.");
+        }
+        if max_entries_in_memory.is_some()
+            && (append
+                || filter.duplicates.policy == DuplicatePolicy::UnionFlags
+                || synthetic_outputs
+                || !compiler_databases.databases.is_empty()
+                || json_lines_output.is_some())
+        {
+            anyhow::bail!(
+                        "The maximum number of entries in memory can't be used with options which need all entries."
+                    );
+        }
+        let compilers = compilers.validate()?;
+        let filter = filter.validate()?;
+        let source_mappings = source_mappings.validate()?;
+        let compiler_wrappers = compiler_wrappers.validate()?;
+        let compiler_patterns = compiler_patterns.validate()?;
+        if post_write_hook
+            .as_ref()
+            .is_some_and(|hook| hook.command.is_empty())
+        {
+            anyhow::bail!("The command of the post write hook can't be empty.");
+        }
+        if streaming
+            && (append
+                || deduplicate
+                || filter.duplicates.policy == DuplicatePolicy::UnionFlags
+                || synthetic_outputs
+                || max_entries_in_memory.is_some()
+                || format.keyed_by_file
+                || !compiler_databases.databases.is_empty()
+                || json_lines_output.is_some())
+        {
+            anyhow::bail!(
+                "The streaming output can't be used with options which need all entries."
+            );
+        }
+        Ok(ClangOutput {
+            compilers,
+            filter,
+            format,
+            source_mappings,
+            compiler_wrappers,
+            flags_to_remove,
+            dependency_manifest,
+            allowed_compilers,
+            hoist_leading_flags,
+            output_directories,
+            compile_flags_files,
+            overwrite_compile_flags_files,
+            response_file_depth,
+            json_lines_output,
+            compiler_databases,
+            append,
+            max_entries_in_memory,
+            include,
+            exclude,
+            keep_ambiguous_headers,
+            output_suffixes_to_strip,
+            deduplicate,
+            expand_environment,
+            append_limits,
+            synthetic_outputs,
+            collapse_include_paths,
+            compiler_patterns,
+            post_write_hook,
+            streaming,
+            sort,
+            keep_existing_order,
+            pin_resource_dir,
+        })
+    }
+}
+
 /// Represents instructions to transform the compiler calls.
 ///
 /// Allow to transform the compiler calls by adding or removing arguments.
 /// It also can instruct to filter out the compiler call from the output.
+///
+/// The `path` given without directory matches the compiler by the file name. A relative
+/// `path` is resolved against the working directory of the compiler call.
+///
+/// The `input_flags` are the flags which are giving the source files to the compiler
+/// (like `--input=foo.c`), when those are not (only) positional arguments. The flags
+/// with `=` suffix are taking the value joined to them. When it's given, the compiler
+/// is recognized by the path (besides the known compilers).
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Compiler {
     pub path: PathBuf,
@@ -349,6 +777,8 @@ pub struct Compiler {
     pub ignore: Ignore,
     #[serde(default)]
     pub arguments: Arguments,
+    #[serde(default)]
+    pub input_flags: Vec<String>,
 }
 
 impl Validate for Vec<Compiler> {
@@ -370,6 +800,12 @@ impl Validate for Compiler {
     /// Validate the configuration of the compiler.
     fn validate(self) -> Result<Self> {
         match self.ignore {
+            Ignore::Always if !self.input_flags.is_empty() => {
+                anyhow::bail!(
+                    "The input flags must be empty in always ignore mode. {:?}",
+                    self.path
+                );
+            }
             Ignore::Always if self.arguments != Arguments::default() => {
                 anyhow::bail!(
                     "All arguments must be empty in always ignore mode. {:?}",
@@ -429,6 +865,181 @@ pub struct Arguments {
     pub remove: Vec<String>,
 }
 
+/// Represents a rule to rewrite the source file path of the compiler calls.
+///
+/// Some tools are compiling a copy of the original source file. (Like `distcc`
+/// in pump mode, which is compiling the preprocessed file from a temporary
+/// directory.) The mapping allows to point the entries back to the original
+/// source file, based on the naming convention of the copy.
+///
+/// The `from` field is a regular expression, which is matched against the
+/// source file path. The `to` field is the replacement, which can refer to
+/// the capture groups of the regular expression (like `$1` or `${name}`).
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SourceMapping {
+    pub from: String,
+    pub to: String,
+}
+
+impl Validate for Vec<SourceMapping> {
+    /// Validate the regular expressions of the source mappings.
+    fn validate(self) -> Result<Self> {
+        for mapping in &self {
+            if let Err(error) = regex::Regex::new(&mapping.from) {
+                anyhow::bail!(
+                    "Invalid source mapping pattern {:?}: {}",
+                    mapping.from,
+                    error
+                );
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Represents a rule to infer the output of the compiler calls in out-of-tree builds.
+///
+/// Build systems (like CMake) are placing the objects under the build directory, which
+/// mirrors the source tree. When the compiler call does not name the output with the `-o`
+/// flag, the output is the source path relative to the `source_root`, placed under the
+/// `object_root`, with the `.o` extension. Relative roots are resolved against the
+/// working directory of the compiler call.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct OutputDirectory {
+    pub source_root: PathBuf,
+    pub object_root: PathBuf,
+}
+
+/// Represents the limits of reading the existing compilation database in append mode.
+///
+/// The `max_file_size` is the size of the existing file in bytes, and the `read_timeout`
+/// is the time to read it in seconds. Beyond the limits, the existing file is treated as
+/// empty (with a warning), and it's replaced by the new entries. The dry run is reading
+/// the existing file within the same limits. There are no limits by default.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct AppendLimits {
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    #[serde(default)]
+    pub read_timeout: Option<u64>,
+}
+
+/// Represents the per compiler partitioning of the compilation database.
+///
+/// Mixed toolchain builds (like host and cross compilers in the same build) need
+/// separate compilation databases for the tools. Each entry is written into the
+/// database of the first matching compiler. (A compiler given without directory
+/// matches by the file name.) The database is the `compile_commands.json` file in
+/// the given directory. The `combined` database is the regular output, with all
+/// entries. It's enabled by default.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CompilerDatabases {
+    #[serde(default)]
+    pub databases: Vec<CompilerDatabase>,
+    #[serde(default = "default_enabled")]
+    pub combined: bool,
+}
+
+impl Default for CompilerDatabases {
+    fn default() -> Self {
+        CompilerDatabases {
+            databases: vec![],
+            combined: true,
+        }
+    }
+}
+
+/// Represents the compilation database of a compiler.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CompilerDatabase {
+    pub compiler: PathBuf,
+    pub directory: PathBuf,
+}
+
+/// Represents the compilers to recognize or to exclude by patterns.
+///
+/// The compilers with unusual names (like the cross compilers `aarch64-linux-gnu-gcc-13`)
+/// can be recognized without listing all of them. The patterns are matched against the
+/// full path and the file name of the executable. The `exclude` patterns are taking
+/// precedence over the `include` patterns, and over the known compilers too.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompilerPatterns {
+    #[serde(default)]
+    pub include: Vec<CompilerPattern>,
+    #[serde(default)]
+    pub exclude: Vec<CompilerPattern>,
+}
+
+/// Represents a pattern of the compiler executable.
+///
+/// The `glob` is a glob pattern (like `*-gcc-*`), the `regex` is a regular expression
+/// (like `^clang-[0-9]+$`).
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum CompilerPattern {
+    #[serde(rename = "glob")]
+    Glob(String),
+    #[serde(rename = "regex")]
+    Regex(String),
+}
+
+impl Validate for CompilerPatterns {
+    /// Validate the regular expressions of the compiler patterns.
+    fn validate(self) -> Result<Self> {
+        for pattern in self.include.iter().chain(self.exclude.iter()) {
+            if let CompilerPattern::Regex(regex) = pattern {
+                if let Err(error) = regex::Regex::new(regex) {
+                    anyhow::bail!("Invalid compiler pattern {:?}: {}", regex, error);
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Represents the command to run after the compilation database was written.
+///
+/// Like a formatter, or a notifier of the other tools. The `command` is the program and
+/// its arguments, the path of the compilation database is passed as the last argument.
+/// The command is not run, when the write failed. The exit status of the command is
+/// logged, and with the `fail_on_error` the failing command fails the run too. (It's
+/// disabled by default.)
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PostWriteHook {
+    pub command: Vec<String>,
+    #[serde(default = "default_disabled")]
+    pub fail_on_error: bool,
+}
+
+/// Represents a wrapper program of the compiler.
+///
+/// There are build systems which are calling the compiler via a script. (Like
+/// `python3 cc_wrapper.py gcc -c foo.c`.) The `executable` is the wrapper program,
+/// and the `compiler` field is a regular expression, which identifies the real
+/// compiler among the arguments. The first matching argument and the rest of
+/// the arguments are recognized as the compiler call.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CompilerWrapper {
+    pub executable: PathBuf,
+    pub compiler: String,
+}
+
+impl Validate for Vec<CompilerWrapper> {
+    /// Validate the regular expressions of the compiler wrappers.
+    fn validate(self) -> Result<Self> {
+        for wrapper in &self {
+            if let Err(error) = regex::Regex::new(&wrapper.compiler) {
+                anyhow::bail!(
+                    "Invalid compiler pattern {:?} for wrapper {:?}: {}",
+                    wrapper.compiler,
+                    wrapper.executable,
+                    error
+                );
+            }
+        }
+        Ok(self)
+    }
+}
+
 /// Filter configuration is used to filter the compiler calls.
 ///
 /// Allow to filter the compiler calls by compiler, source files and duplicates.
@@ -436,12 +1047,20 @@ pub struct Arguments {
 /// - Compilers: Specify on the compiler path and arguments.
 /// - Source: Specify the source file location.
 /// - Duplicates: Specify the fields of the JSON compilation database record to detect duplicates.
+/// - Keep null outputs: The compiler calls writing the output to the null device (`/dev/null`
+///   or `NUL`) are dropped by default. (Like the configure scripts' probe compilations.)
+/// - Keep assembly outputs: The compiler calls generating assembly (with the `-S` flag) are
+///   dropped by default. (The same source is usually compiled with the `-c` flag too.)
 #[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct Filter {
     #[serde(default)]
     pub source: SourceFilter,
     #[serde(default)]
     pub duplicates: DuplicateFilter,
+    #[serde(default = "default_disabled")]
+    pub keep_null_outputs: bool,
+    #[serde(default = "default_disabled")]
+    pub keep_assembly_outputs: bool,
 }
 
 impl Validate for Filter {
@@ -450,6 +1069,8 @@ impl Validate for Filter {
         self.duplicates.validate().map(|duplicates| Filter {
             source: self.source,
             duplicates,
+            keep_null_outputs: self.keep_null_outputs,
+            keep_assembly_outputs: self.keep_assembly_outputs,
         })
     }
 }
@@ -461,6 +1082,9 @@ impl Validate for Filter {
 /// - Include only existing files: can be true or false.
 /// - Paths to include: Only include the compiler calls that compiles source files from this path.
 /// - Paths to exclude: Exclude the compiler calls that compiles source files from this path.
+/// - Changed files: Only include the compiler calls that compiles one of these files. (Like the
+///   output of `git diff --name-only`.) Relative paths are matching the end of the source
+///   file path. When it's not given, the compiler calls are not filtered by it.
 #[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct SourceFilter {
     #[serde(default = "default_disabled")]
@@ -469,26 +1093,54 @@ pub struct SourceFilter {
     pub paths_to_include: Vec<PathBuf>,
     #[serde(default)]
     pub paths_to_exclude: Vec<PathBuf>,
+    #[serde(default)]
+    pub changed_files: Option<Vec<PathBuf>>,
 }
 
 /// Duplicate filter configuration is used to filter the duplicate compiler calls.
 ///
 /// - By fields: Specify the fields of the JSON compilation database record to detect duplicates.
+/// - Policy: Specify what to do with the compilations of the same source file.
+/// - Compare by: Specify how the seen entries are remembered. (See `DuplicateComparison`.)
+/// - Dedup key: Specify the duplicate detection fields with a predefined key. (See
+///   `DuplicateKey`.) It's an alternative to the `by_fields`, only one of them can be given.
+///   When none of them is given, the file and the arguments fields are detecting duplicates.
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct DuplicateFilter {
+    #[serde(default)]
     pub by_fields: Vec<OutputFields>,
+    #[serde(default)]
+    pub policy: DuplicatePolicy,
+    #[serde(default)]
+    pub compare_by: DuplicateComparison,
+    #[serde(default)]
+    pub dedup_key: Option<DuplicateKey>,
 }
 
 impl Validate for DuplicateFilter {
     /// Deduplicate the fields of the fields vector.
+    ///
+    /// When the dedup key is given, the fields are defined by the key. (The key is
+    /// replaced by the fields.) When none of them is given, the default fields are used.
     fn validate(self) -> Result<Self> {
+        let by_fields = match (&self.dedup_key, self.by_fields.is_empty()) {
+            (Some(key), true) => key.fields(),
+            (Some(_), false) => {
+                anyhow::bail!("The duplicate filter can't have both by_fields and dedup_key.")
+            }
+            (None, true) => DuplicateFilter::default().by_fields,
+            (None, false) => self.by_fields,
+        };
+        let mut seen = HashSet::new();
         let result = Self {
-            by_fields: (&self.by_fields)
-                .into_iter()
-                .map(|field| field.clone())
-                .collect::<HashSet<_>>()
+            // Keep the order of the fields, to keep the validation repeatable.
+            by_fields: by_fields
                 .into_iter()
+                .filter(|field| seen.insert(field.clone()))
                 .collect(),
+            policy: self.policy,
+            compare_by: self.compare_by,
+            dedup_key: None,
         };
         Ok(result)
     }
@@ -498,10 +1150,138 @@ impl Default for DuplicateFilter {
     fn default() -> Self {
         DuplicateFilter {
             by_fields: vec![OutputFields::File, OutputFields::Arguments],
+            policy: DuplicatePolicy::default(),
+            compare_by: DuplicateComparison::default(),
+            dedup_key: None,
         }
     }
 }
 
+/// Represents the predefined keys of the duplicate detection.
+///
+/// The meaning of the possible values are:
+/// - File: The entries of the same source file are duplicates.
+/// - FileOutput: The entries of the same source and output file are duplicates.
+/// - FullCommand: The entries with the same directory, source, output and arguments
+///   are duplicates.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DuplicateKey {
+    #[serde(rename = "file")]
+    File,
+    #[serde(rename = "file_output")]
+    FileOutput,
+    #[serde(rename = "full_command")]
+    FullCommand,
+}
+
+impl DuplicateKey {
+    /// Returns the fields of the JSON compilation database record, which are the key.
+    pub fn fields(&self) -> Vec<OutputFields> {
+        match self {
+            DuplicateKey::File => vec![OutputFields::File],
+            DuplicateKey::FileOutput => vec![OutputFields::File, OutputFields::Output],
+            DuplicateKey::FullCommand => vec![
+                OutputFields::Directory,
+                OutputFields::File,
+                OutputFields::Output,
+                OutputFields::Arguments,
+            ],
+        }
+    }
+}
+
+/// Represents the handling of multiple compilations of the same source file.
+///
+/// The meaning of the possible values are:
+/// - KeepFirst: Keep the first entry of the duplicates. (Default)
+/// - UnionFlags: Merge all compilations of the same source file into one entry,
+///   which contains the union of the flags. (Order is preserved, duplicates are
+///   removed.)
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum DuplicatePolicy {
+    #[default]
+    #[serde(rename = "keep_first")]
+    KeepFirst,
+    #[serde(rename = "union_flags")]
+    UnionFlags,
+}
+
+/// Represents how the paths of the compiler call are written into the entries.
+///
+/// The meaning of the possible values are:
+/// - Relative: Keep the paths as those were given to the compiler. (Default)
+/// - Absolute: Join the relative paths to the working directory. The `.` and `..`
+///   components are resolved without accessing the filesystem, the paths are not
+///   required to exist.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum PathFormat {
+    #[default]
+    #[serde(rename = "relative")]
+    Relative,
+    #[serde(rename = "absolute")]
+    Absolute,
+}
+
+/// Represents how the arguments are quoted in the `command` string.
+///
+/// The `quote` is the quote character, `single` (the default) or `double`. The `always`
+/// option quotes all arguments, otherwise only the ones with special characters (like
+/// spaces) are quoted. The default is the POSIX shell quoting, the double quotes are for
+/// the consumers which are splitting the command by the Windows rules.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CommandQuoting {
+    #[serde(default)]
+    pub quote: Quote,
+    #[serde(default = "default_disabled")]
+    pub always: bool,
+}
+
+/// Represents the quote character of the `command` string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum Quote {
+    #[default]
+    #[serde(rename = "single")]
+    Single,
+    #[serde(rename = "double")]
+    Double,
+}
+
+/// Represents the path separator style of the entries.
+///
+/// The meaning of the possible values are:
+/// - Forward: The paths are separated by `/`.
+/// - Backward: The paths are separated by `\`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PathSeparator {
+    #[serde(rename = "forward")]
+    Forward,
+    #[serde(rename = "backward")]
+    Backward,
+}
+
+/// Represents how the duplicate filter remembers the entries it has seen.
+///
+/// The meaning of the possible values are:
+/// - Exact: Keep a copy of the selected fields. It has no false positives, but needs
+///   memory proportional to the size of the entries.
+/// - Hash64: Keep a 64-bit hash of the selected fields. (Default)
+/// - Hash128: Keep a 128-bit hash of the selected fields. It's for builds with millions
+///   of entries, where the collision chance of the 64-bit hash is not negligible anymore.
+///
+/// The hash function is the SipHash-1-3 of the standard library. (The 128-bit hash is
+/// the concatenation of two differently seeded 64-bit hashes.) The hash values are only
+/// used within a single run, and are not persisted.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum DuplicateComparison {
+    #[serde(rename = "exact")]
+    Exact,
+    #[default]
+    #[serde(rename = "hash64")]
+    Hash64,
+    #[serde(rename = "hash128")]
+    Hash128,
+}
+
 /// Represent the fields of the JSON compilation database record.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum OutputFields {
@@ -516,23 +1296,119 @@ pub enum OutputFields {
 }
 
 /// Format configuration of the JSON compilation database.
+///
+/// The `command_as_array` option writes the compiler call as `arguments` array
+/// (the default), otherwise as a single shell quoted `command` string. The array
+/// form is preferred, because it has no ambiguity with the spaces in the paths.
+///
+/// The `command_quoting` option decides how the arguments are quoted in the `command`
+/// string. (See the `CommandQuoting` for the details.)
+///
+/// The `sort_flags` option sorts the flags into a canonical order, which gives stable
+/// diffs between builds. The order sensitive flags (like search paths and macro
+/// definitions) are keeping their relative order. (See the `output::canonical` module
+/// for the list of the order sensitive flags.)
+///
+/// The `keyed_by_file` option writes the entries into a JSON object, where the keys
+/// are the source files. (This is not the standard format of the JSON compilation
+/// database, but allows faster lookups by the source file.) The values are following
+/// the `command_as_array` and `command_quoting` options too.
+///
+/// The `original_compiler` option writes the first argument of the compiler call, as it
+/// was captured, into the `original_compiler` field of the entries. (This is not part of
+/// the standard format either. The `arguments` are starting with the normalized compiler
+/// path, this field shows how the compiler was invoked.)
+///
+/// The `language` option writes the recognized language of the source into the `language`
+/// field of the entries. (Like `c`, `c++` or `cuda`. This is not part of the standard
+/// format, but allows tools to configure the entries by the language.)
+///
+/// The `path_format` option decides how the source and the output are written into the
+/// `arguments` of the entries. (The `file` and `output` fields are always absolute, and
+/// the `directory` field is the working directory of the compiler call.)
+///
+/// The `file_relative_to_directory` option writes the `file` field relative to the
+/// `directory` field, when the source is under the working directory. The sources out
+/// of it are kept absolute. (The specification allows both, some tools are expecting
+/// the relative form.) It's applied at writing the compilation database files only.
+///
+/// The `path_separator` option validates the paths of the entries against the given
+/// separator style. The paths (the `file`, `directory` and `output` fields, and the
+/// values of the path flags, like `-I`) which are still containing the other separator
+/// are logged. (The paths are not changed, it checks the result of the normalization.)
+///
+/// The `strip_dependency_flags` option removes the dependency file generation flags
+/// (like `-MD`, `-MF foo.d` or `-MT target`) from the `arguments` of the entries. (The
+/// tools would write stray dependency files, or fail on the phony targets.) The flags
+/// are removed with their values, in the separate and the joined forms too.
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Format {
     #[serde(default = "default_enabled")]
-    command_as_array: bool,
+    pub command_as_array: bool,
+    #[serde(default)]
+    pub command_quoting: CommandQuoting,
     #[serde(default = "default_disabled")]
     drop_output_field: bool,
+    #[serde(default = "default_disabled")]
+    pub sort_flags: bool,
+    #[serde(default = "default_disabled")]
+    pub keyed_by_file: bool,
+    #[serde(default = "default_disabled")]
+    pub original_compiler: bool,
+    #[serde(default = "default_disabled")]
+    pub language: bool,
+    #[serde(default)]
+    pub path_format: PathFormat,
+    #[serde(default = "default_disabled")]
+    pub file_relative_to_directory: bool,
+    #[serde(default)]
+    pub path_separator: Option<PathSeparator>,
+    #[serde(default = "default_disabled")]
+    pub strip_dependency_flags: bool,
 }
 
 impl Default for Format {
     fn default() -> Self {
         Format {
             command_as_array: true,
+            command_quoting: CommandQuoting::default(),
             drop_output_field: false,
+            sort_flags: false,
+            keyed_by_file: false,
+            original_compiler: false,
+            language: false,
+            path_format: PathFormat::default(),
+            file_relative_to_directory: false,
+            path_separator: None,
+            strip_dependency_flags: false,
         }
     }
 }
 
+/// The default maximum depth of the response file expansion.
+fn default_response_file_depth() -> usize {
+    16
+}
+
+/// The default flags to remove from the compiler calls.
+///
+/// These are formatting the diagnostic messages for terminals, which makes
+/// the tools (reading the compilation database) to print escape codes. And
+/// the GCC specific flags, which are not known by the clang based tools (and
+/// are not changing the meaning of the compilation).
+fn default_flags_to_remove() -> Vec<String> {
+    vec![
+        String::from("-fcolor-diagnostics"),
+        String::from("-fno-color-diagnostics"),
+        String::from("-fdiagnostics-color*"),
+        String::from("-fno-diagnostics-color"),
+        String::from("-fansi-escape-codes"),
+        String::from("-fno-canonical-system-headers"),
+        String::from("-fno-diagnostics-show-caret"),
+        String::from("-fno-var-tracking-assignments"),
+    ]
+}
+
 fn default_disabled() -> bool {
     false
 }
@@ -605,6 +1481,16 @@ mod test {
           executables:
             - /usr/bin/cc
             - /usr/bin/c++
+          launchers:
+            - buildcache
+          destination_variables:
+            - SANDBOX_ADDRESS
+          environment:
+            allow:
+              - PATH
+              - MY_SDK_*
+            deny:
+              - MY_SDK_TOKEN
         output:
           specification: clang
           compilers:
@@ -649,13 +1535,20 @@ mod test {
                 path: default_wrapper_executable(),
                 directory: PathBuf::from("/tmp"),
                 executables: vec_of_pathbuf!["/usr/bin/cc", "/usr/bin/c++"],
+                launchers: vec_of_pathbuf!["buildcache"],
+                destination_variables: vec_of_strings!["SANDBOX_ADDRESS"],
+                environment: CapturedEnvironment {
+                    allow: vec_of_strings!["PATH", "MY_SDK_*"],
+                    deny: vec_of_strings!["MY_SDK_TOKEN"],
+                },
             },
-            output: Output::Clang {
+            output: Output::Clang(Box::new(ClangOutput {
                 compilers: vec![
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/cc"),
                         ignore: Ignore::Always,
                         arguments: Arguments::default(),
+                        input_flags: vec![],
                     },
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/c++"),
@@ -664,6 +1557,7 @@ mod test {
                             match_: vec_of_strings!["-###"],
                             ..Default::default()
                         },
+                        input_flags: vec![],
                     },
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/clang"),
@@ -673,6 +1567,7 @@ mod test {
                             remove: vec_of_strings!["-Wall"],
                             ..Default::default()
                         },
+                        input_flags: vec![],
                     },
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/clang++"),
@@ -681,6 +1576,7 @@ mod test {
                             remove: vec_of_strings!["-Wall"],
                             ..Default::default()
                         },
+                        input_flags: vec![],
                     },
                 ],
                 filter: Filter {
@@ -688,17 +1584,66 @@ mod test {
                         include_only_existing_files: true,
                         paths_to_include: vec_of_pathbuf!["sources"],
                         paths_to_exclude: vec_of_pathbuf!["tests"],
+                        changed_files: None,
                     },
                     duplicates: DuplicateFilter {
                         by_fields: vec![OutputFields::File, OutputFields::Directory],
+                        policy: DuplicatePolicy::KeepFirst,
+                        compare_by: DuplicateComparison::Hash64,
+                        dedup_key: None,
                     },
+                    keep_null_outputs: false,
+                    keep_assembly_outputs: false,
                 },
                 format: Format {
                     command_as_array: true,
+                    command_quoting: CommandQuoting::default(),
                     drop_output_field: false,
+                    sort_flags: false,
+                    keyed_by_file: false,
+                    original_compiler: false,
+                    language: false,
+                    path_format: PathFormat::Relative,
+                    file_relative_to_directory: false,
+                    path_separator: None,
+                    strip_dependency_flags: false,
                 },
-            },
+                source_mappings: vec![],
+                compiler_wrappers: vec![],
+                flags_to_remove: default_flags_to_remove(),
+                dependency_manifest: None,
+                allowed_compilers: vec![],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: default_response_file_depth(),
+                json_lines_output: None,
+                compiler_databases: CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
             schema: String::from("4.0"),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
         };
 
         assert_eq!(expected, result);
@@ -734,25 +1679,77 @@ mod test {
                 path: default_wrapper_executable(),
                 directory: default_wrapper_directory(),
                 executables: vec_of_pathbuf!["/usr/bin/cc", "/usr/bin/c++"],
+                launchers: vec![],
+                destination_variables: vec![],
+                environment: CapturedEnvironment::default(),
             },
-            output: Output::Clang {
+            output: Output::Clang(Box::new(ClangOutput {
                 compilers: vec![],
                 filter: Filter {
                     source: SourceFilter {
                         include_only_existing_files: true,
                         paths_to_include: vec_of_pathbuf![],
                         paths_to_exclude: vec_of_pathbuf![],
+                        changed_files: None,
                     },
                     duplicates: DuplicateFilter {
                         by_fields: vec![OutputFields::File, OutputFields::Directory],
+                        policy: DuplicatePolicy::KeepFirst,
+                        compare_by: DuplicateComparison::Hash64,
+                        dedup_key: None,
                     },
+                    keep_null_outputs: false,
+                    keep_assembly_outputs: false,
                 },
                 format: Format {
                     command_as_array: true,
+                    command_quoting: CommandQuoting::default(),
                     drop_output_field: false,
+                    sort_flags: false,
+                    keyed_by_file: false,
+                    original_compiler: false,
+                    language: false,
+                    path_format: PathFormat::Relative,
+                    file_relative_to_directory: false,
+                    path_separator: None,
+                    strip_dependency_flags: false,
                 },
-            },
+                source_mappings: vec![],
+                compiler_wrappers: vec![],
+                flags_to_remove: default_flags_to_remove(),
+                dependency_manifest: None,
+                allowed_compilers: vec![],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: default_response_file_depth(),
+                json_lines_output: None,
+                compiler_databases: CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
             schema: String::from("4.0"),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
         };
 
         assert_eq!(expected, result);
@@ -778,6 +1775,11 @@ mod test {
             },
             output: Output::Semantic {},
             schema: String::from("4.0"),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
         };
 
         assert_eq!(expected, result);
@@ -816,27 +1818,31 @@ mod test {
             intercept: Intercept::Preload {
                 path: default_preload_library(),
             },
-            output: Output::Clang {
+            output: Output::Clang(Box::new(ClangOutput {
                 compilers: vec![
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/cc"),
                         ignore: Ignore::Never,
                         arguments: Arguments::default(),
+                        input_flags: vec![],
                     },
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/c++"),
                         ignore: Ignore::Never,
                         arguments: Arguments::default(),
+                        input_flags: vec![],
                     },
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/clang"),
                         ignore: Ignore::Always,
                         arguments: Arguments::default(),
+                        input_flags: vec![],
                     },
                     Compiler {
                         path: PathBuf::from("/usr/local/bin/clang++"),
                         ignore: Ignore::Always,
                         arguments: Arguments::default(),
+                        input_flags: vec![],
                     },
                 ],
                 filter: Filter {
@@ -844,17 +1850,66 @@ mod test {
                         include_only_existing_files: false,
                         paths_to_include: vec_of_pathbuf![],
                         paths_to_exclude: vec_of_pathbuf![],
+                        changed_files: None,
                     },
                     duplicates: DuplicateFilter {
                         by_fields: vec![OutputFields::File],
+                        policy: DuplicatePolicy::KeepFirst,
+                        compare_by: DuplicateComparison::Hash64,
+                        dedup_key: None,
                     },
+                    keep_null_outputs: false,
+                    keep_assembly_outputs: false,
                 },
                 format: Format {
                     command_as_array: true,
+                    command_quoting: CommandQuoting::default(),
                     drop_output_field: false,
+                    sort_flags: false,
+                    keyed_by_file: false,
+                    original_compiler: false,
+                    language: false,
+                    path_format: PathFormat::Relative,
+                    file_relative_to_directory: false,
+                    path_separator: None,
+                    strip_dependency_flags: false,
                 },
-            },
+                source_mappings: vec![],
+                compiler_wrappers: vec![],
+                flags_to_remove: default_flags_to_remove(),
+                dependency_manifest: None,
+                allowed_compilers: vec![],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: default_response_file_depth(),
+                json_lines_output: None,
+                compiler_databases: CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
             schema: String::from("4.0"),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
         };
 
         assert_eq!(expected, result);
@@ -866,12 +1921,46 @@ mod test {
 
         let expected = Main {
             intercept: Intercept::default(),
-            output: Output::Clang {
+            output: Output::Clang(Box::new(ClangOutput {
                 compilers: vec![],
                 filter: Filter::default(),
                 format: Format::default(),
-            },
+                source_mappings: vec![],
+                compiler_wrappers: vec![],
+                flags_to_remove: default_flags_to_remove(),
+                dependency_manifest: None,
+                allowed_compilers: vec![],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: default_response_file_depth(),
+                json_lines_output: None,
+                compiler_databases: CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
             schema: String::from(SUPPORTED_SCHEMA_VERSION),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
         };
 
         assert_eq!(expected, result);
@@ -918,4 +2007,422 @@ mod test {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_source_mappings_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        intercept:
+          mode: preload
+        output:
+          specification: clang
+          source_mappings:
+            - from: '^/tmp/distcc_pump_[^/]+(?P<path>/.*)\.i$'
+              to: '${path}.c'
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        let expected = Main {
+            intercept: Intercept::Preload {
+                path: default_preload_library(),
+            },
+            output: Output::Clang(Box::new(ClangOutput {
+                compilers: vec![],
+                filter: Filter::default(),
+                format: Format::default(),
+                source_mappings: vec![SourceMapping {
+                    from: String::from(r"^/tmp/distcc_pump_[^/]+(?P<path>/.*)\.i$"),
+                    to: String::from("${path}.c"),
+                }],
+                compiler_wrappers: vec![],
+                flags_to_remove: default_flags_to_remove(),
+                dependency_manifest: None,
+                allowed_compilers: vec![],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: default_response_file_depth(),
+                json_lines_output: None,
+                compiler_databases: CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
+            schema: String::from("4.0"),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
+        };
+
+        assert_eq!(expected, result);
+        assert!(result.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_source_mapping() {
+        let mappings = vec![SourceMapping {
+            from: String::from("(unclosed"),
+            to: String::from("$1"),
+        }];
+
+        assert!(mappings.validate().is_err());
+    }
+
+    #[test]
+    fn test_compiler_wrappers_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        intercept:
+          mode: preload
+        output:
+          specification: clang
+          compiler_wrappers:
+            - executable: /usr/bin/python3
+              compiler: '(gcc|clang)$'
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        let expected = Main {
+            intercept: Intercept::Preload {
+                path: default_preload_library(),
+            },
+            output: Output::Clang(Box::new(ClangOutput {
+                compilers: vec![],
+                filter: Filter::default(),
+                format: Format::default(),
+                source_mappings: vec![],
+                compiler_wrappers: vec![CompilerWrapper {
+                    executable: PathBuf::from("/usr/bin/python3"),
+                    compiler: String::from("(gcc|clang)$"),
+                }],
+                flags_to_remove: default_flags_to_remove(),
+                dependency_manifest: None,
+                allowed_compilers: vec![],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: default_response_file_depth(),
+                json_lines_output: None,
+                compiler_databases: CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
+            schema: String::from("4.0"),
+            strict: false,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
+        };
+
+        assert_eq!(expected, result);
+        assert!(result.validate().is_ok());
+    }
+
+    #[test]
+    fn test_strict_mode_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+        strict: true
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        let expected = Main {
+            intercept: Intercept::default(),
+            output: Output::default(),
+            schema: String::from("4.0"),
+            strict: true,
+            self_check: false,
+            preferred_interpreter: None,
+            recognition_environment: None,
+            empty_output: None,
+        };
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_flags_to_remove_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          flags_to_remove:
+            - -fdiagnostics-color*
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        let expected = Output::Clang(Box::new(ClangOutput {
+            compilers: vec![],
+            filter: Filter::default(),
+            format: Format::default(),
+            source_mappings: vec![],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec![String::from("-fdiagnostics-color*")],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: default_response_file_depth(),
+            json_lines_output: None,
+            compiler_databases: CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+
+        assert_eq!(expected, result.output);
+    }
+
+    #[test]
+    fn test_allowed_compilers_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          allowed_compilers:
+            - /usr/bin/cc
+            - clang
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        match result.output {
+            Output::Clang(clang) => assert_eq!(
+                vec_of_pathbuf!["/usr/bin/cc", "clang"],
+                clang.allowed_compilers
+            ),
+            _ => panic!("unexpected output"),
+        }
+    }
+
+    #[test]
+    fn test_dedup_key_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          filter:
+            duplicates:
+              dedup_key: file_output
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+        let result = result.validate().unwrap();
+
+        match result.output {
+            Output::Clang(clang) => {
+                let fields = clang.filter.duplicates.by_fields;
+                assert_eq!(2, fields.len());
+                assert!(fields.contains(&OutputFields::File));
+                assert!(fields.contains(&OutputFields::Output));
+            }
+            _ => panic!("unexpected output"),
+        }
+
+        let both = DuplicateFilter {
+            by_fields: vec![OutputFields::File],
+            dedup_key: Some(DuplicateKey::File),
+            ..DuplicateFilter::default()
+        };
+        assert!(both.validate().is_err());
+
+        // The validation is repeatable.
+        let key = DuplicateFilter {
+            by_fields: vec![],
+            dedup_key: Some(DuplicateKey::FileOutput),
+            ..DuplicateFilter::default()
+        };
+        let validated = key.validate().unwrap();
+        assert_eq!(validated.clone(), validated.validate().unwrap());
+
+        // The default fields are used, when none is given.
+        let none = DuplicateFilter {
+            by_fields: vec![],
+            dedup_key: None,
+            ..DuplicateFilter::default()
+        };
+        let mut fields = none.validate().unwrap().by_fields;
+        fields.sort_by_key(|field| format!("{:?}", field));
+        assert_eq!(vec![OutputFields::Arguments, OutputFields::File], fields);
+    }
+
+    #[test]
+    fn test_compiler_patterns_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          compiler_patterns:
+            include:
+              - glob: '*-gcc-*'
+              - regex: '^clang-[0-9]+$'
+            exclude:
+              - glob: '*-gcc-ar-*'
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+        let result = result.validate().unwrap();
+
+        let expected = CompilerPatterns {
+            include: vec![
+                CompilerPattern::Glob(String::from("*-gcc-*")),
+                CompilerPattern::Regex(String::from("^clang-[0-9]+$")),
+            ],
+            exclude: vec![CompilerPattern::Glob(String::from("*-gcc-ar-*"))],
+        };
+        match result.output {
+            Output::Clang(clang) => assert_eq!(expected, clang.compiler_patterns),
+            _ => panic!("unexpected output"),
+        }
+
+        let invalid = CompilerPatterns {
+            include: vec![CompilerPattern::Regex(String::from("(gcc"))],
+            exclude: vec![],
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_streaming_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          streaming: true
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        // The deduplication is enabled by default, it needs all entries.
+        assert!(result.validate().is_err());
+
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          streaming: true
+          deduplicate: false
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        assert!(result.validate().is_ok());
+    }
+
+    #[test]
+    fn test_max_entries_in_memory_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        output:
+          specification: clang
+          max_entries_in_memory: 1000
+          json_lines_output: compile_commands.jsonl
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        assert!(result.validate().is_err());
+    }
+
+    #[test]
+    fn test_max_entries_in_memory_with_union_flags() {
+        let mut output = ClangOutput {
+            max_entries_in_memory: Some(1000),
+            ..Default::default()
+        };
+        output.filter.duplicates.policy = DuplicatePolicy::UnionFlags;
+        assert!(output.validate().is_err());
+
+        let output = ClangOutput {
+            max_entries_in_memory: Some(1000),
+            ..Default::default()
+        };
+        assert!(output.validate().is_ok());
+    }
+
+    #[test]
+    fn test_preferred_interpreter_config() {
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        preferred_interpreter: nvcc
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        assert!(result.validate().is_ok());
+
+        let content: &[u8] = br#"
+        schema: 4.0
+
+        preferred_interpreter: clang
+        "#;
+
+        let result: Main = Main::from_reader(content).unwrap();
+
+        assert!(result.validate().is_err());
+    }
 }
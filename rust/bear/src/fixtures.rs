@@ -11,4 +11,38 @@ pub mod fixtures {
     macro_rules! vec_of_pathbuf {
         ($($x:expr),*) => (vec![$(PathBuf::from($x)),*]);
     }
+
+    /// Run the function and returns the warnings it was logging.
+    pub fn capture_warnings(f: impl FnOnce()) -> Vec<String> {
+        use std::sync::Mutex;
+
+        static MESSAGES: Mutex<Vec<(std::thread::ThreadId, String)>> = Mutex::new(vec![]);
+
+        struct Capture;
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Warn
+            }
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    let message = (std::thread::current().id(), record.args().to_string());
+                    MESSAGES.lock().unwrap().push(message);
+                }
+            }
+            fn flush(&self) {}
+        }
+
+        // The logger can be set only once, other tests might have set it already.
+        let _ = log::set_logger(&Capture);
+        log::set_max_level(log::LevelFilter::Warn);
+        f();
+        let current = std::thread::current().id();
+        MESSAGES
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(thread, _)| *thread == current)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
 }
@@ -8,14 +8,23 @@ use std::io::BufReader;
 use std::path::PathBuf;
 
 use super::args;
-use super::intercept::Execution;
+use super::intercept::correlation::{Correlator, Strategy};
+use super::intercept::{collector, Execution};
 
 /// Responsible for reading the build events from the intercept mode.
 ///
 /// The file syntax is defined by the `events` module, and the parsing logic is implemented there.
 /// Here we only handle the file opening and the error handling.
+///
+/// The events written by the `FileReporter` (one envelope per line) are read too.
 pub struct EventFileReader {
-    reader: BufReader<File>,
+    source: Source,
+}
+
+/// The formats of the build events file.
+enum Source {
+    Events(BufReader<File>),
+    Reported(Vec<Execution>),
 }
 
 impl TryFrom<args::BuildEvents> for EventFileReader {
@@ -26,13 +35,37 @@ impl TryFrom<args::BuildEvents> for EventFileReader {
     /// If the file cannot be opened, the error will be logged and escalated.
     fn try_from(value: args::BuildEvents) -> Result<Self, Self::Error> {
         let file_name = PathBuf::from(value.file_name);
+        if collector::is_events_file(&file_name) {
+            // The process ids are reused during the build, the executions are
+            // correlated by the process id and the time of the report.
+            let mut correlator = Correlator::new(Strategy::ProcessIdAndTime);
+            let executions = collector::read_events_file(&file_name)?
+                .into_iter()
+                .map(|envelope| {
+                    let id = correlator.correlate(&envelope);
+                    if id.sequence > 0 {
+                        log::debug!(
+                            "Process id {} was reported more than once, correlated as {:?}",
+                            id.pid,
+                            id
+                        );
+                    }
+                    envelope.event.execution
+                })
+                .collect();
+            return Ok(EventFileReader {
+                source: Source::Reported(executions),
+            });
+        }
         let file = OpenOptions::new()
             .read(true)
             .open(file_name.as_path())
             .with_context(|| format!("Failed to open input file: {:?}", file_name))?;
         let reader = BufReader::new(file);
 
-        Ok(EventFileReader { reader })
+        Ok(EventFileReader {
+            source: Source::Events(reader),
+        })
     }
 }
 
@@ -41,9 +74,13 @@ impl EventFileReader {
     ///
     /// Returns an iterator over the build events. Any error during the reading
     /// of the file will be logged and the failed entries will be skipped.
-    pub fn generate(self) -> impl Iterator<Item = Execution> {
+    pub fn generate(self) -> Box<dyn Iterator<Item = Execution>> {
+        let reader = match self.source {
+            Source::Events(reader) => reader,
+            Source::Reported(executions) => return Box::new(executions.into_iter()),
+        };
         // Process the file line by line.
-        from_reader(self.reader)
+        let executions = from_reader(reader)
             // Log the errors and skip the failed entries.
             .flat_map(|candidate| match candidate {
                 Ok(execution) => Some(execution),
@@ -51,7 +88,8 @@ impl EventFileReader {
                     log::warn!("Failed to read entry from input: {}", error);
                     None
                 }
-            })
+            });
+        Box::new(executions)
     }
 }
 
@@ -241,6 +279,53 @@ mod test {
         assert!(result.next().is_none());
     }
 
+    #[test]
+    fn test_reading_reported_events() {
+        use crate::intercept::{Envelope, Event, ProcessId, ReporterId};
+
+        let directory =
+            std::env::temp_dir().join(format!("bear-input-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("events.jsonl");
+        let execution = |executable: &str| Execution {
+            executable: PathBuf::from(executable),
+            arguments: vec_of_strings![executable, "-c", "main.c"],
+            working_dir: PathBuf::from("/home/user/project"),
+            environment: HashMap::new(),
+        };
+        let line = |execution: Execution| {
+            let envelope = Envelope::new(
+                &ReporterId(1),
+                Event {
+                    pid: ProcessId(42),
+                    execution,
+                },
+            );
+            serde_json::to_string(&envelope).unwrap()
+        };
+        // The malformed lines are skipped, not only at the end.
+        let content = [
+            line(execution("/usr/bin/cc")),
+            String::from("{\"rid\":1,\"timest"),
+            line(execution("/usr/bin/c++")),
+            String::from("{\"rid\":1,"),
+        ]
+        .join("\n");
+        std::fs::write(&path, content).unwrap();
+
+        let sut = EventFileReader::try_from(args::BuildEvents {
+            file_name: path.to_string_lossy().to_string(),
+        })
+        .unwrap();
+        let result: Vec<Execution> = sut.generate().collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            vec![execution("/usr/bin/cc"), execution("/usr/bin/c++")],
+            result
+        );
+    }
+
     fn into_single_line(content: &str) -> String {
         content.chars().filter(|c| *c != '\n').collect()
     }
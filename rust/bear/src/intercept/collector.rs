@@ -1,11 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
 
 use crossbeam::channel::Sender;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use anyhow::Context;
+
 use super::Envelope;
 
 /// Represents the local sink of supervised process events.
@@ -56,17 +61,21 @@ impl EventCollectorOnTcp {
 
         Ok(result)
     }
+}
 
-    fn send(
-        &self,
-        mut socket: TcpStream,
-        destination: Sender<Envelope>,
-    ) -> Result<(), anyhow::Error> {
-        let envelope = Envelope::read_from(&mut socket)?;
-        destination.send(envelope)?;
-
-        Ok(())
+/// Reads the events of the connection, and sends them to the destination channel.
+///
+/// The persistent reporters are sending multiple events, until they close the connection.
+fn send(mut socket: TcpStream, destination: Sender<Envelope>) -> Result<(), anyhow::Error> {
+    loop {
+        match Envelope::read_from(&mut socket) {
+            Ok(envelope) => destination.send(envelope)?,
+            Err(error) if is_end_of_stream(&error) => break,
+            Err(error) => return Err(error),
+        }
     }
+
+    Ok(())
 }
 
 impl EventCollector for EventCollectorOnTcp {
@@ -74,11 +83,11 @@ impl EventCollector for EventCollectorOnTcp {
         self.address.to_string()
     }
 
-    /// Single-threaded implementation of the collector.
-    ///
     /// The collector listens on the TCP port and accepts incoming connections.
-    /// When a connection is accepted, the collector reads the events from the
-    /// connection and sends them to the destination channel.
+    /// When a connection is accepted, the events of the connection are read on
+    /// a separate thread, and sent to the destination channel. (The persistent
+    /// reporters are keeping their connection open, these are not blocking the
+    /// other reporters.)
     fn collect(&self, destination: Sender<Envelope>) -> Result<(), anyhow::Error> {
         for stream in self.listener.incoming() {
             // This has to be the first thing to do, in order to implement the stop method!
@@ -88,8 +97,12 @@ impl EventCollector for EventCollectorOnTcp {
 
             match stream {
                 Ok(connection) => {
-                    // ... (process the connection in a separate thread or task)
-                    self.send(connection, destination.clone())?;
+                    let destination = destination.clone();
+                    std::thread::spawn(move || {
+                        if let Err(error) = send(connection, destination) {
+                            log::warn!("Failed to read the events of a reporter: {}", error);
+                        }
+                    });
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     // No new connection available, continue checking for shutdown
@@ -115,3 +128,45 @@ impl EventCollector for EventCollectorOnTcp {
         Ok(())
     }
 }
+
+fn is_end_of_stream(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|error| error.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+/// Reads the events, which were written by the `FileReporter`.
+///
+/// The file has one envelope per line. The malformed lines are skipped with a
+/// warning. (Like the partial line of a reporter, which was killed while it was
+/// writing it.)
+pub fn read_events_file(path: &Path) -> Result<Vec<Envelope>, anyhow::Error> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+
+    let mut result = vec![];
+    for (index, line) in content.lines().enumerate() {
+        match serde_json::from_str::<Envelope>(line) {
+            Ok(envelope) => result.push(envelope),
+            Err(error) => {
+                log::warn!(
+                    "Malformed event is skipped at line {} of {:?}: {}",
+                    index + 1,
+                    path,
+                    error
+                );
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns true, when the file was written by the `FileReporter`.
+///
+/// (The first line of it is an envelope.)
+pub fn is_events_file(path: &Path) -> bool {
+    let mut line = String::new();
+    File::open(path)
+        .and_then(|file| BufReader::new(file).read_line(&mut line))
+        .is_ok_and(|_| serde_json::from_str::<Envelope>(&line).is_ok())
+}
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Correlates the events by the process id.
+//!
+//! The process ids are not unique during a build. The OS reuses them, and the
+//! `exec` calls after a `fork` are reporting sequential executions with the same
+//! process id. When the process id is used to correlate the events, it's combined
+//! with the timestamp of the event. And the events which are still colliding (even
+//! the timestamp is the same) are getting a sequence number.
+
+use std::collections::HashMap;
+
+use super::Envelope;
+
+/// The strategy to correlate the events with the same process id.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// The events with the same process id are the same process.
+    ProcessId,
+    /// The events with the same process id are distinct, when those were reported
+    /// at different times or more than once.
+    ProcessIdAndTime,
+}
+
+/// Identifies a process execution for the correlation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CorrelationId {
+    pub pid: u32,
+    pub timestamp: u64,
+    pub sequence: u32,
+}
+
+/// Assigns the correlation ids to the events.
+pub struct Correlator {
+    strategy: Strategy,
+    seen: HashMap<(u32, u64), u32>,
+}
+
+impl Correlator {
+    pub fn new(strategy: Strategy) -> Self {
+        Correlator {
+            strategy,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Returns the correlation id of the event.
+    ///
+    /// The events are expected in the order those were received.
+    pub fn correlate(&mut self, envelope: &Envelope) -> CorrelationId {
+        let pid = envelope.event.pid.0;
+        match self.strategy {
+            Strategy::ProcessId => CorrelationId {
+                pid,
+                timestamp: 0,
+                sequence: 0,
+            },
+            Strategy::ProcessIdAndTime => {
+                let timestamp = envelope.timestamp;
+                let count = self.seen.entry((pid, timestamp)).or_insert(0);
+                let sequence = *count;
+                *count += 1;
+                CorrelationId {
+                    pid,
+                    timestamp,
+                    sequence,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::super::{Event, Execution, ProcessId, ReporterId};
+    use super::*;
+
+    fn envelope(pid: u32, timestamp: u64, program: &str) -> Envelope {
+        Envelope {
+            rid: ReporterId::new(),
+            timestamp,
+            event: Event {
+                pid: ProcessId(pid),
+                execution: Execution {
+                    executable: PathBuf::from(program),
+                    arguments: vec![program.to_string()],
+                    working_dir: PathBuf::from("/home/user"),
+                    environment: HashMap::new(),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_same_pid_gets_distinct_correlation_ids() {
+        // Like `sh -c 'exec cc ...'`, which reports twice with the same process id.
+        let events = [
+            envelope(42, 1000, "/bin/sh"),
+            envelope(42, 1000, "/usr/bin/cc"),
+            envelope(42, 1007, "/usr/bin/as"),
+            envelope(43, 1000, "/usr/bin/cc"),
+        ];
+
+        let mut sut = Correlator::new(Strategy::ProcessIdAndTime);
+        let ids: Vec<CorrelationId> = events.iter().map(|event| sut.correlate(event)).collect();
+        for (index, id) in ids.iter().enumerate() {
+            assert!(!ids[..index].contains(id), "{:?} is not unique", id);
+        }
+        assert_eq!(1, ids[1].sequence);
+
+        // The process id only strategy keeps them correlated.
+        let mut sut = Correlator::new(Strategy::ProcessId);
+        assert_eq!(sut.correlate(&events[0]), sut.correlate(&events[1]));
+    }
+}
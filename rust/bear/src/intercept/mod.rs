@@ -8,7 +8,10 @@ use chrono::Utc;
 use rand::random;
 use serde::{Deserialize, Serialize};
 
+use crate::config;
+
 pub mod collector;
+pub mod correlation;
 pub mod reporter;
 
 /// Reporter id is a unique identifier for a reporter.
@@ -107,4 +110,177 @@ impl Envelope {
 }
 
 /// Declare the environment variable name for the reporter address.
+///
+/// The address is a TCP address (`ip:port`), a Unix domain socket path
+/// (with `unix:` scheme, or as an absolute path), or a file path (with the
+/// `file://` scheme, the events are appended to it).
 pub const KEY_DESTINATION: &str = "INTERCEPT_REPORTER_ADDRESS";
+
+/// Declare the environment variable name for the alternative reporter address variables.
+///
+/// The value is an ordered list of environment variable names (separated the same way
+/// as the `PATH` entries), which are consulted when the `KEY_DESTINATION` is missing.
+/// The first one present is used as reporter address.
+pub const KEY_DESTINATION_VARIABLES: &str = "INTERCEPT_REPORTER_ADDRESS_VARIABLES";
+
+/// Declare the environment variable name for the wrapper fallback executables.
+///
+/// The value is a list of `name=/absolute/path` entries (separated the same way
+/// as the `PATH` entries), which are used when the wrapper can't find the real
+/// executable in the `PATH`.
+pub const KEY_FALLBACK: &str = "INTERCEPT_WRAPPER_FALLBACK";
+
+/// Declare the environment variable name for the reporter connection retries.
+///
+/// The value is the number of retries and the base delay in milliseconds, separated
+/// by a comma (like `3,25`). When it's missing, the `reporter::Retry` default is used.
+pub const KEY_REPORTER_RETRY: &str = "INTERCEPT_REPORTER_RETRY";
+
+/// Declare the environment variable name for the captured environment size limit.
+///
+/// The value is the maximum number of bytes (of the variable names and values)
+/// the wrapper captures from its environment. When the environment is larger,
+/// the largest variables are dropped from the reported execution.
+pub const KEY_ENVIRONMENT_LIMIT: &str = "INTERCEPT_ENVIRONMENT_LIMIT";
+
+/// Declare the environment variable name for the captured environment filter.
+///
+/// The value is the JSON serialized `CapturedEnvironment`, which selects the
+/// variables the wrapper captures from its environment. When it's missing, the
+/// default variables are captured.
+pub const KEY_ENVIRONMENT_FILTER: &str = "INTERCEPT_ENVIRONMENT_FILTER";
+
+/// Represents the environment variables, which the wrapper captures for the executions.
+///
+/// The variables are given by name, or by a prefix with a trailing `*` (like `CCC_*`).
+/// Only the variables in the `allow` list are captured, unless those are in the `deny`
+/// list too. By default, the variables which are relevant to the compilers are allowed,
+/// everything else is dropped. (The other variables might contain secrets, like tokens.)
+/// The variables of the interception and the `PATH` are always captured, and so are
+/// the variables which the arguments are referring to (like `$SDKROOT`), unless those
+/// are denied. (The recognition is expanding these references.)
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CapturedEnvironment {
+    #[serde(default = "default_captured_variables")]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl Default for CapturedEnvironment {
+    fn default() -> Self {
+        CapturedEnvironment {
+            allow: default_captured_variables(),
+            deny: vec![],
+        }
+    }
+}
+
+/// The default environment variables, which the wrapper captures.
+///
+/// These are the variables which are changing the compiler calls, like the
+/// flags, the search paths and the compilers of the static analyzer shims.
+fn default_captured_variables() -> Vec<String> {
+    [
+        "PATH",
+        "CC",
+        "CXX",
+        "CPP",
+        "CFLAGS",
+        "CXXFLAGS",
+        "CPPFLAGS",
+        "LDFLAGS",
+        "SYSROOT",
+        "CPATH",
+        "C_INCLUDE_PATH",
+        "CPLUS_INCLUDE_PATH",
+        "OBJC_INCLUDE_PATH",
+        "CCC_*",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// The captured environment size limit, when it's not given in the environment.
+pub const DEFAULT_ENVIRONMENT_LIMIT: usize = 1024 * 1024;
+
+/// Returns the environment variables, which are passing the configuration to the wrapper.
+///
+/// The supervisor sets these for the build (besides the `KEY_DESTINATION`), the wrapper
+/// has no access to the configuration file. The preload mode is not configured this way.
+pub fn wrapper_environment(config: &config::Intercept) -> anyhow::Result<HashMap<String, String>> {
+    let mut environment = HashMap::new();
+    if let config::Intercept::Wrapper {
+        destination_variables,
+        environment: filter,
+        ..
+    } = config
+    {
+        environment.insert(
+            KEY_ENVIRONMENT_FILTER.to_string(),
+            serde_json::to_string(filter)?,
+        );
+        if !destination_variables.is_empty() {
+            let names = std::env::join_paths(destination_variables)?;
+            environment.insert(
+                KEY_DESTINATION_VARIABLES.to_string(),
+                names.to_string_lossy().to_string(),
+            );
+        }
+    }
+    Ok(environment)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{vec_of_pathbuf, vec_of_strings};
+
+    fn wrapper_config(
+        destination_variables: Vec<String>,
+        environment: CapturedEnvironment,
+    ) -> config::Intercept {
+        config::Intercept::Wrapper {
+            path: PathBuf::from("/usr/libexec/bear/wrapper"),
+            directory: PathBuf::from("/tmp"),
+            executables: vec_of_pathbuf!["/usr/bin/cc"],
+            launchers: vec![],
+            destination_variables,
+            environment,
+        }
+    }
+
+    #[test]
+    fn test_wrapper_environment_lists_destination_variables() {
+        let config = wrapper_config(
+            vec_of_strings!["SANDBOX_ADDRESS", "OTHER_ADDRESS"],
+            CapturedEnvironment::default(),
+        );
+
+        let result = wrapper_environment(&config).unwrap();
+
+        let names: Vec<PathBuf> =
+            std::env::split_paths(&result[KEY_DESTINATION_VARIABLES]).collect();
+        assert_eq!(vec_of_pathbuf!["SANDBOX_ADDRESS", "OTHER_ADDRESS"], names);
+        let config = wrapper_config(vec![], CapturedEnvironment::default());
+        assert!(!wrapper_environment(&config)
+            .unwrap()
+            .contains_key(KEY_DESTINATION_VARIABLES));
+    }
+
+    #[test]
+    fn test_wrapper_environment_passes_the_filter() {
+        let filter = CapturedEnvironment {
+            allow: vec_of_strings!["MY_SDK_*"],
+            deny: vec_of_strings!["MY_SDK_TOKEN"],
+        };
+        let config = wrapper_config(vec![], filter.clone());
+
+        let result = wrapper_environment(&config).unwrap();
+
+        let passed: CapturedEnvironment =
+            serde_json::from_str(&result[KEY_ENVIRONMENT_FILTER]).unwrap();
+        assert_eq!(filter, passed);
+    }
+}
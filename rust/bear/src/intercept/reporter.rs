@@ -1,6 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::cell::{Cell, RefCell};
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
 
 use super::{Envelope, Event, ReporterId};
 
@@ -9,11 +19,66 @@ use super::{Envelope, Event, ReporterId};
 /// This allows the reporters to send events to a remote collector.
 pub trait Reporter {
     fn report(&self, event: Event) -> Result<(), anyhow::Error>;
+
+    /// Sends the events which were buffered by the reporter.
+    ///
+    /// The reporters without buffering are sending the events immediately,
+    /// for those this is a no-op.
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+/// The retries of the connection to the collector.
+///
+/// The collector might not accept the connection at the moment (like on a busy
+/// machine). The connection is retried with exponential backoff: the first retry
+/// waits for the base delay, and each next one doubles it. The default is a few
+/// retries within two hundred milliseconds, to not stall the build for long when
+/// the collector is down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Retry {
+    pub attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            attempts: 3,
+            base_delay: Duration::from_millis(25),
+        }
+    }
+}
+
+impl Retry {
+    /// Calls the connect function, until it succeeds or the retries are exhausted.
+    ///
+    /// Returns the error of the last attempt.
+    fn connect<T>(&self, connect: impl Fn() -> std::io::Result<T>) -> std::io::Result<T> {
+        let mut delay = self.base_delay;
+        let mut attempt = 0;
+        loop {
+            match connect() {
+                Ok(result) => return Ok(result),
+                Err(error) if attempt < self.attempts => {
+                    log::debug!("Connection to the collector failed, retrying: {}", error);
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
 }
 
 pub struct TcpReporter {
     destination: String,
     reporter_id: ReporterId,
+    persistent: bool,
+    retry: Retry,
+    connection: RefCell<Option<TcpStream>>,
 }
 
 impl TcpReporter {
@@ -26,21 +91,728 @@ impl TcpReporter {
         let result = TcpReporter {
             destination,
             reporter_id,
+            persistent: false,
+            retry: Retry::default(),
+            connection: RefCell::new(None),
         };
         Ok(result)
     }
+
+    /// Sets the retries of the connection to the collector.
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn connect(&self) -> Result<TcpStream, anyhow::Error> {
+        self.retry
+            .connect(|| TcpStream::connect(self.destination.as_str()))
+            .with_context(|| format!("Failed to connect to {}", self.destination))
+    }
+
+    /// Creates a new TCP reporter instance, which keeps the connection open.
+    ///
+    /// This is for the long-lived reporters, to not pay the connection setup
+    /// for each event.
+    pub fn persistent(destination: String) -> Result<Self, anyhow::Error> {
+        let mut result = Self::new(destination)?;
+        result.persistent = true;
+        Ok(result)
+    }
+
+    /// Sends the envelope over the kept connection.
+    ///
+    /// When the connection was dropped, it makes a single attempt to reconnect.
+    /// (The connection closed by the collector is detected before the write,
+    /// because the write into it might succeed, while the event is lost.)
+    fn send_persistent(&self, envelope: &Envelope) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.borrow_mut();
+        if connection.as_ref().is_some_and(is_closed) {
+            log::debug!("Connection to the collector is closed by the collector");
+            *connection = None;
+        }
+        if let Some(socket) = connection.as_mut() {
+            match envelope.write_into(socket) {
+                Ok(_) => return Ok(()),
+                Err(error) => log::debug!("Connection to the collector is lost: {}", error),
+            }
+        }
+        *connection = None;
+        let mut socket = self.connect()?;
+        envelope.write_into(&mut socket)?;
+        *connection = Some(socket);
+
+        Ok(())
+    }
+}
+
+/// Returns true, if the peer has closed the connection (or the connection is broken).
+///
+/// The collector is not sending anything, so any readable state means the end of it.
+fn is_closed(socket: &TcpStream) -> bool {
+    if socket.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let mut buffer = [0; 1];
+    let closed = match socket.peek(&mut buffer) {
+        Err(error) => error.kind() != std::io::ErrorKind::WouldBlock,
+        Ok(_) => true,
+    };
+    closed || socket.set_nonblocking(false).is_err()
 }
 
 impl Reporter for TcpReporter {
     /// Sends an event to the remote collector.
     ///
     /// The event is wrapped in an envelope and sent to the remote collector.
-    /// The TCP connection is opened and closed for each event.
+    /// The TCP connection is opened and closed for each event, unless the
+    /// reporter was created as persistent.
+    fn report(&self, event: Event) -> Result<(), anyhow::Error> {
+        let envelope = Envelope::new(&self.reporter_id, event);
+        if self.persistent {
+            return self.send_persistent(&envelope);
+        }
+        let mut socket = self.connect()?;
+        envelope.write_into(&mut socket)?;
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        if let Some(socket) = self.connection.borrow_mut().as_mut() {
+            socket.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Buffers the events, and sends them in batches with the given reporter.
+///
+/// The buffer is flushed when it reaches the capacity, or at the first event
+/// after the timeout since the last flush. The remaining events are flushed
+/// when the reporter is dropped. This is meant to be used by the long-lived
+/// reporters, the short-lived processes (like the wrapper) are reporting a
+/// single event anyway.
+///
+/// The events which failed to be sent are kept in the buffer, and retried at
+/// the next flush.
+pub struct BufferedReporter<R: Reporter> {
+    reporter: R,
+    capacity: usize,
+    timeout: Duration,
+    buffer: RefCell<Vec<Event>>,
+    last_flush: Cell<Instant>,
+}
+
+impl<R: Reporter> BufferedReporter<R> {
+    pub fn new(reporter: R, capacity: usize, timeout: Duration) -> Self {
+        BufferedReporter {
+            reporter,
+            capacity,
+            timeout,
+            buffer: RefCell::new(Vec::with_capacity(capacity)),
+            last_flush: Cell::new(Instant::now()),
+        }
+    }
+}
+
+impl<R: Reporter> Reporter for BufferedReporter<R> {
+    fn report(&self, event: Event) -> Result<(), anyhow::Error> {
+        let length = {
+            let mut buffer = self.buffer.borrow_mut();
+            buffer.push(event);
+            buffer.len()
+        };
+        if length >= self.capacity || self.last_flush.get().elapsed() >= self.timeout {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        self.last_flush.set(Instant::now());
+        let mut events = std::mem::take(&mut *self.buffer.borrow_mut()).into_iter();
+        while let Some(event) = events.next() {
+            if let Err(error) = self.reporter.report(event.clone()) {
+                // Keep the failed and the remaining events for the next flush.
+                let mut buffer = self.buffer.borrow_mut();
+                let newer = std::mem::take(&mut *buffer);
+                buffer.push(event);
+                buffer.extend(events);
+                buffer.extend(newer);
+                return Err(error);
+            }
+        }
+        self.reporter.flush()
+    }
+}
+
+impl<R: Reporter> Drop for BufferedReporter<R> {
+    fn drop(&mut self) {
+        if let Err(error) = self.flush() {
+            log::warn!(
+                "{} event(s) failed to be reported: {}",
+                self.buffer.borrow().len(),
+                error
+            );
+        }
+    }
+}
+
+/// Sends the events to the collector over a Unix domain socket.
+///
+/// The events are serialized the same way as the `TcpReporter` does, only the
+/// transport is different. (The socket file permissions are controlling the
+/// access to the collector, unlike the TCP ports.)
+#[cfg(unix)]
+pub struct UnixReporter {
+    destination: PathBuf,
+    reporter_id: ReporterId,
+    retry: Retry,
+}
+
+#[cfg(unix)]
+impl UnixReporter {
+    /// Creates a new Unix domain socket reporter instance.
+    ///
+    /// It does not connect to the socket yet. Stores the socket path
+    /// and creates a unique reporter id.
+    pub fn new(destination: PathBuf) -> Result<Self, anyhow::Error> {
+        let reporter_id = ReporterId::new();
+        let result = UnixReporter {
+            destination,
+            reporter_id,
+            retry: Retry::default(),
+        };
+        Ok(result)
+    }
+
+    /// Sets the retries of the connection to the collector.
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[cfg(unix)]
+impl Reporter for UnixReporter {
+    /// Sends an event to the remote collector.
+    ///
+    /// The connection is opened and closed for each event.
     fn report(&self, event: Event) -> Result<(), anyhow::Error> {
         let envelope = Envelope::new(&self.reporter_id, event);
-        let mut socket = TcpStream::connect(self.destination.clone())?;
+        let mut socket = self
+            .retry
+            .connect(|| UnixStream::connect(&self.destination))
+            .with_context(|| format!("Failed to connect to {:?}", self.destination))?;
         envelope.write_into(&mut socket)?;
 
         Ok(())
     }
 }
+
+/// Appends the events to a file, one JSON object per line.
+///
+/// This is for the environments, where the sockets can't be used. (Like some
+/// build sandboxes.) The file is opened in append mode and locked for each
+/// event, the concurrent reporters are not interleaving their lines. The
+/// lines are the serialized envelopes, the collector reads them with the
+/// `read_events_file` function.
+pub struct FileReporter {
+    destination: PathBuf,
+    reporter_id: ReporterId,
+}
+
+impl FileReporter {
+    /// Creates a new file reporter instance.
+    ///
+    /// It does not open the file yet. Stores the file path and creates
+    /// a unique reporter id.
+    pub fn new(destination: PathBuf) -> Self {
+        FileReporter {
+            destination,
+            reporter_id: ReporterId::new(),
+        }
+    }
+}
+
+impl Reporter for FileReporter {
+    /// Appends the event to the file.
+    ///
+    /// The line is written with a single write, while holding an exclusive
+    /// lock on the file. (The lock is released when the file is closed.)
+    fn report(&self, event: Event) -> Result<(), anyhow::Error> {
+        let envelope = Envelope::new(&self.reporter_id, event);
+        let mut line = serde_json::to_string(&envelope)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.destination)
+            .with_context(|| format!("Failed to open {:?}", self.destination))?;
+        lock_exclusive(&file).with_context(|| format!("Failed to lock {:?}", self.destination))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write {:?}", self.destination))?;
+
+        Ok(())
+    }
+}
+
+/// Takes an advisory exclusive lock on the file, waits until it's available.
+#[cfg(unix)]
+fn lock_exclusive(file: &std::fs::File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) };
+        if result == 0 {
+            return Ok(());
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// The appends are not locked on the other platforms.
+#[cfg(not(unix))]
+fn lock_exclusive(_: &std::fs::File) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Returns the file path, if the destination is a file. (It has the `file://` scheme.)
+pub fn file_path(destination: &str) -> Option<&Path> {
+    destination.strip_prefix("file://").map(Path::new)
+}
+
+/// Returns the socket path, if the destination is a Unix domain socket.
+///
+/// The destination is a socket path, when it has the `unix:` scheme or it's an
+/// absolute path. Otherwise, it's considered as a TCP address.
+pub fn unix_socket_path(destination: &str) -> Option<&Path> {
+    match destination.strip_prefix("unix:") {
+        Some(path) => Some(Path::new(path)),
+        None if destination.starts_with('/') => Some(Path::new(destination)),
+        None => None,
+    }
+}
+
+/// Creates the reporter for the destination, which is a file, a TCP address or a socket path.
+pub fn for_destination(destination: String) -> Result<Box<dyn Reporter>, anyhow::Error> {
+    for_destination_with_retry(destination, Retry::default())
+}
+
+/// Creates the reporter for the destination, with the given connection retries.
+///
+/// The retries are not used by the file reporter.
+pub fn for_destination_with_retry(
+    destination: String,
+    retry: Retry,
+) -> Result<Box<dyn Reporter>, anyhow::Error> {
+    if let Some(path) = file_path(&destination) {
+        return Ok(Box::new(FileReporter::new(path.to_path_buf())));
+    }
+    match unix_socket_path(&destination) {
+        #[cfg(unix)]
+        Some(path) => Ok(Box::new(
+            UnixReporter::new(path.to_path_buf())?.with_retry(retry),
+        )),
+        #[cfg(not(unix))]
+        Some(path) => anyhow::bail!("Unix domain sockets are not supported: {:?}", path),
+        None => Ok(Box::new(TcpReporter::new(destination)?.with_retry(retry))),
+    }
+}
+
+/// Stores the events in a SQLite database, to make the build queryable with SQL.
+///
+/// The events are written by the `sqlite3` command line tool, which has to be
+/// available in the `PATH` (or be given explicitly). The tool is started at the
+/// first event (it keeps the database open), and the table is created then. Each
+/// event is inserted as a row, the tool is stopped when the reporter is dropped:
+///
+/// ```sql
+/// CREATE TABLE events (
+///     rid INTEGER, pid INTEGER, timestamp INTEGER,
+///     executable TEXT, arguments TEXT, working_dir TEXT
+/// );
+/// ```
+///
+/// The arguments are stored as a JSON array.
+pub struct SqliteReporter {
+    database: PathBuf,
+    program: PathBuf,
+    reporter_id: ReporterId,
+    connection: RefCell<Option<Child>>,
+}
+
+/// The schema of the events database.
+const SQLITE_SCHEMA: &str = "\
+    CREATE TABLE IF NOT EXISTS events (\
+        rid INTEGER, pid INTEGER, timestamp INTEGER, \
+        executable TEXT, arguments TEXT, working_dir TEXT);\n\
+    CREATE INDEX IF NOT EXISTS events_executable ON events (executable);\n";
+
+impl SqliteReporter {
+    /// Creates a new SQLite reporter instance, which is using `sqlite3` from the `PATH`.
+    pub fn new(database: PathBuf) -> Self {
+        Self::with_program(database, PathBuf::from("sqlite3"))
+    }
+
+    /// Creates a new SQLite reporter instance, which is using the given program.
+    pub fn with_program(database: PathBuf, program: PathBuf) -> Self {
+        SqliteReporter {
+            database,
+            program,
+            reporter_id: ReporterId::new(),
+            connection: RefCell::new(None),
+        }
+    }
+
+    /// Starts the `sqlite3` program on the database, and creates the schema.
+    fn open(&self) -> Result<Child, anyhow::Error> {
+        let mut child = Command::new(&self.program)
+            .arg(&self.database)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to execute {:?}", self.program))?;
+        if let Some(stdin) = child.stdin.as_mut() {
+            stdin.write_all(SQLITE_SCHEMA.as_bytes())?;
+        }
+        Ok(child)
+    }
+
+    fn statements(envelope: &Envelope) -> Result<String, anyhow::Error> {
+        let execution = &envelope.event.execution;
+        let arguments = serde_json::to_string(&execution.arguments)?;
+        Ok(format!(
+            "INSERT INTO events VALUES ({}, {}, {}, {}, {}, {});\n",
+            // SQLite integers are signed 64 bits, the reporter id is stored as such.
+            envelope.rid.0 as i64,
+            envelope.event.pid.0,
+            envelope.timestamp,
+            quote(&execution.executable.to_string_lossy()),
+            quote(&arguments),
+            quote(&execution.working_dir.to_string_lossy()),
+        ))
+    }
+}
+
+impl Reporter for SqliteReporter {
+    /// Inserts the event into the database.
+    ///
+    /// The database is kept open between the events. (SQLite is handling the
+    /// locking, when multiple processes are writing it.)
+    fn report(&self, event: Event) -> Result<(), anyhow::Error> {
+        let envelope = Envelope::new(&self.reporter_id, event);
+        let statements = Self::statements(&envelope)?;
+
+        let mut connection = self.connection.borrow_mut();
+        if connection.is_none() {
+            *connection = Some(self.open()?);
+        }
+        connection
+            .as_mut()
+            .and_then(|child| child.stdin.as_mut())
+            .ok_or_else(|| anyhow::anyhow!("The {:?} is not open", self.database))?
+            .write_all(statements.as_bytes())
+            .with_context(|| format!("Failed to insert event into {:?}", self.database))
+    }
+}
+
+impl Drop for SqliteReporter {
+    /// Stops the `sqlite3` program, after it executed the statements.
+    fn drop(&mut self) {
+        if let Some(mut child) = self.connection.get_mut().take() {
+            drop(child.stdin.take());
+            match child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => log::warn!(
+                    "Failed to insert events into {:?}: {:?}",
+                    self.database,
+                    status
+                ),
+                Err(error) => log::warn!("Failed to wait for {:?}: {}", self.program, error),
+            }
+        }
+    }
+}
+
+/// Quote the value as an SQL string literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Execution, ProcessId};
+    use super::*;
+    use crate::vec_of_strings;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    /// Records the reported events, or fails to report, when it's asked to.
+    struct Recording {
+        events: Rc<RefCell<Vec<Event>>>,
+        failing: Rc<Cell<bool>>,
+    }
+
+    impl Reporter for Recording {
+        fn report(&self, event: Event) -> Result<(), anyhow::Error> {
+            if self.failing.get() {
+                anyhow::bail!("collector is not available");
+            }
+            self.events.borrow_mut().push(event);
+            Ok(())
+        }
+    }
+
+    fn event(pid: u32) -> Event {
+        Event {
+            pid: ProcessId(pid),
+            execution: Execution {
+                executable: PathBuf::from("/usr/bin/cc"),
+                arguments: vec_of_strings!["cc", "-c", "main.c"],
+                working_dir: PathBuf::from("/home/user"),
+                environment: HashMap::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_buffered_reporter_sends_in_batches() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let failing = Rc::new(Cell::new(false));
+        let recording = Recording {
+            events: events.clone(),
+            failing: failing.clone(),
+        };
+        let sut = BufferedReporter::new(recording, 2, Duration::from_secs(3600));
+
+        sut.report(event(1)).unwrap();
+        assert!(events.borrow().is_empty());
+        sut.report(event(2)).unwrap();
+        assert_eq!(vec![event(1), event(2)], *events.borrow());
+
+        // The failed events are kept, and sent when the reporter is dropped.
+        failing.set(true);
+        sut.report(event(3)).unwrap();
+        assert!(sut.report(event(4)).is_err());
+        failing.set(false);
+        sut.report(event(5)).unwrap();
+        drop(sut);
+        assert_eq!(
+            vec![event(1), event(2), event(3), event(4), event(5)],
+            *events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_persistent_tcp_reporter_keeps_the_connection() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let sut = BufferedReporter::new(
+            TcpReporter::persistent(address).unwrap(),
+            10,
+            Duration::from_secs(3600),
+        );
+
+        sut.report(event(1)).unwrap();
+        sut.report(event(2)).unwrap();
+        sut.flush().unwrap();
+
+        let (mut connection, _) = listener.accept().unwrap();
+        let first = Envelope::read_from(&mut connection).unwrap();
+        let second = Envelope::read_from(&mut connection).unwrap();
+
+        assert_eq!(event(1), first.event);
+        assert_eq!(event(2), second.event);
+        assert_eq!(first.rid, second.rid);
+    }
+
+    #[test]
+    fn test_persistent_tcp_reporter_reconnects_when_closed() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let sut = TcpReporter::persistent(address).unwrap();
+
+        sut.report(event(1)).unwrap();
+        let (mut connection, _) = listener.accept().unwrap();
+        assert_eq!(
+            event(1),
+            Envelope::read_from(&mut connection).unwrap().event
+        );
+        // The collector closes the connection, the next event goes on a new one.
+        drop(connection);
+        std::thread::sleep(Duration::from_millis(20));
+        sut.report(event(2)).unwrap();
+
+        let (mut connection, _) = listener.accept().unwrap();
+        assert_eq!(
+            event(2),
+            Envelope::read_from(&mut connection).unwrap().event
+        );
+    }
+
+    #[test]
+    fn test_tcp_reporter_retries_the_connection() {
+        // Reserve a port, and start listening on it only after the first attempts.
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        let collector = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            let listener = std::net::TcpListener::bind(address).unwrap();
+            let (mut connection, _) = listener.accept().unwrap();
+            Envelope::read_from(&mut connection).unwrap()
+        });
+
+        let retry = Retry {
+            attempts: 8,
+            base_delay: Duration::from_millis(10),
+        };
+        let sut = TcpReporter::new(address.to_string())
+            .unwrap()
+            .with_retry(retry);
+        sut.report(event(1)).unwrap();
+
+        assert_eq!(event(1), collector.join().unwrap().event);
+
+        // Without retries, the down collector fails the report immediately.
+        let sut = TcpReporter::new(address.to_string())
+            .unwrap()
+            .with_retry(Retry {
+                attempts: 0,
+                ..retry
+            });
+        assert!(sut.report(event(2)).is_err());
+    }
+
+    #[test]
+    fn test_unix_socket_path_detection() {
+        assert_eq!(
+            Some(Path::new("/tmp/bear.sock")),
+            unix_socket_path("unix:/tmp/bear.sock")
+        );
+        assert_eq!(
+            Some(Path::new("/tmp/bear.sock")),
+            unix_socket_path("/tmp/bear.sock")
+        );
+        assert_eq!(None, unix_socket_path("127.0.0.1:4567"));
+    }
+
+    #[test]
+    fn test_file_reporter_appends_events_concurrently() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-reporter-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("events.jsonl");
+        let destination = format!("file://{}", path.display());
+        assert_eq!(Some(path.as_path()), file_path(&destination));
+
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let destination = destination.clone();
+                std::thread::spawn(move || {
+                    let sut = for_destination(destination).unwrap();
+                    for index in 0..50 {
+                        sut.report(event(thread * 100 + index)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // A killed reporter leaves a partial line at the end.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"rid\":1,\"timest").unwrap();
+
+        let result = super::super::collector::read_events_file(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let mut pids: Vec<u32> = result
+            .unwrap()
+            .into_iter()
+            .map(|envelope| envelope.event.pid.0)
+            .collect();
+        pids.sort();
+        let expected: Vec<u32> = (0..8)
+            .flat_map(|thread| (0..50).map(move |index| thread * 100 + index))
+            .collect();
+        assert_eq!(expected, pids);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_reporter_sends_events() {
+        use std::os::unix::net::UnixListener;
+
+        let socket =
+            std::env::temp_dir().join(format!("bear-unix-test-{}.sock", rand::random::<u64>()));
+        let listener = UnixListener::bind(&socket).unwrap();
+        let sut = for_destination(format!("unix:{}", socket.display())).unwrap();
+
+        let event = Event {
+            pid: ProcessId(42),
+            execution: Execution {
+                executable: PathBuf::from("/usr/bin/cc"),
+                arguments: vec_of_strings!["cc", "-c", "main.c"],
+                working_dir: PathBuf::from("/home/user"),
+                environment: HashMap::from([("PATH".to_string(), "/usr/bin".to_string())]),
+            },
+        };
+        sut.report(event.clone()).unwrap();
+
+        let (mut connection, _) = listener.accept().unwrap();
+        let envelope = Envelope::read_from(&mut connection).unwrap();
+        std::fs::remove_file(&socket).unwrap();
+
+        assert_eq!(event, envelope.event);
+    }
+
+    #[test]
+    #[ignore = "needs the sqlite3 program"]
+    fn test_sqlite_reporter_stores_events() {
+        let database =
+            std::env::temp_dir().join(format!("bear-sqlite-test-{}.db", rand::random::<u64>()));
+        let sut = SqliteReporter::new(database.clone());
+
+        for (pid, executable) in [(1, "/usr/bin/cc"), (2, "/usr/bin/ld"), (3, "/usr/bin/cc")] {
+            let event = Event {
+                pid: ProcessId(pid),
+                execution: Execution {
+                    executable: PathBuf::from(executable),
+                    arguments: vec_of_strings!["cc", "-c", "it's.c"],
+                    working_dir: PathBuf::from("/home/user"),
+                    environment: HashMap::new(),
+                },
+            };
+            sut.report(event).unwrap();
+        }
+        drop(sut);
+
+        let output = Command::new("sqlite3")
+            .arg(&database)
+            .arg("SELECT executable, COUNT(*) FROM events GROUP BY executable ORDER BY executable;")
+            .output()
+            .unwrap();
+        let arguments = Command::new("sqlite3")
+            .arg(&database)
+            .arg("SELECT arguments FROM events WHERE pid = 1;")
+            .output()
+            .unwrap();
+        std::fs::remove_file(&database).unwrap();
+
+        assert_eq!(
+            "/usr/bin/cc|2\n/usr/bin/ld|1\n",
+            String::from_utf8_lossy(&output.stdout)
+        );
+        assert_eq!(
+            "[\"cc\",\"-c\",\"it's.c\"]\n",
+            String::from_utf8_lossy(&arguments.stdout)
+        );
+    }
+}
@@ -8,4 +8,5 @@ pub mod intercept;
 pub mod output;
 pub mod recognition;
 pub mod semantic;
+pub mod shutdown;
 pub mod transformation;
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements the canonical ordering of the compiler flags.
+//!
+//! When the compilation database is kept under version control, the order of the
+//! flags (which might change between builds) makes noise in the diffs. Sorting the
+//! flags gives a stable output, but it must not change the meaning of the compiler
+//! call. Therefore the flags are partitioned into order sensitive and order
+//! insensitive groups, and only the latter are sorted.
+//!
+//! The order sensitive flags are:
+//! - search paths (like `-I`, `-isystem`, `-iquote`, `-idirafter`, `-F`, `-L`),
+//!   because the first match wins at the lookup,
+//! - macro definitions and undefinitions (`-D` and `-U`), because they are
+//!   processed in sequence,
+//! - forced includes (`-include`, `-imacros`),
+//! - language selection (`-x`) and the flags passed to other tools (like `-Xclang`
+//!   or `-Wl,`),
+//! - flags which are present multiple times in the same family (like `-O0` and
+//!   `-O2`, `-fexceptions` and `-fno-exceptions`, `-m32` and `-m64`, `-g0` and `-g`,
+//!   `-fpic` and `-fPIC`, or `-ansi` and `-std=c11`), because the last one wins.
+//!
+//! The order sensitive flags are keeping their relative order, and are emitted
+//! after the sorted order insensitive flags.
+
+use std::collections::HashMap;
+
+use super::merge::into_units;
+use super::semantic;
+
+/// Prefixes of the flags, which are always order sensitive.
+const ORDER_SENSITIVE: [&str; 15] = [
+    "-I",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-iprefix",
+    "-iwithprefix",
+    "-F",
+    "-L",
+    "-D",
+    "-U",
+    "-include",
+    "-imacros",
+    "-x",
+    "-X",
+    "-Wl,",
+];
+
+/// Sort the flags of all compile passes.
+pub(super) fn sort_flags_of_call(call: semantic::CompilerCall) -> semantic::CompilerCall {
+    let passes = call
+        .passes
+        .into_iter()
+        .map(|pass| match pass {
+            semantic::CompilerPass::Compile {
+                source,
+                output,
+                flags,
+                output_implied,
+            } => semantic::CompilerPass::Compile {
+                source,
+                output,
+                flags: sort_flags(&flags),
+                output_implied,
+            },
+            semantic::CompilerPass::Preprocess => semantic::CompilerPass::Preprocess,
+        })
+        .collect();
+    semantic::CompilerCall {
+        compiler: call.compiler,
+        working_dir: call.working_dir,
+        passes,
+        original_compiler: call.original_compiler,
+        environment: call.environment,
+    }
+}
+
+/// Sort the order insensitive flags, and keep the order sensitive ones in order.
+fn sort_flags(flags: &[String]) -> Vec<String> {
+    let units = into_units(flags);
+
+    let mut families: HashMap<String, usize> = HashMap::new();
+    for unit in &units {
+        *families.entry(family(&unit[0])).or_default() += 1;
+    }
+    let is_order_sensitive = |unit: &Vec<String>| {
+        let flag = unit[0].as_str();
+        ORDER_SENSITIVE
+            .iter()
+            .any(|prefix| flag.starts_with(prefix))
+            || !flag.starts_with('-')
+            || families.get(&family(flag)).copied().unwrap_or_default() > 1
+    };
+
+    let (sensitive, mut insensitive): (Vec<_>, Vec<_>) =
+        units.into_iter().partition(is_order_sensitive);
+    insensitive.sort();
+
+    insensitive.into_iter().chain(sensitive).flatten().collect()
+}
+
+/// Returns the family of the flag, where the members are overriding each other.
+///
+/// The negated forms (like `-fno-rtti`) are in the same family as the positive ones,
+/// and flags with values (like `-std=c11`) are in the same family regardless the value.
+/// The flags selecting the same property with different names (like the debug levels,
+/// the data models, the position independent code models and the language standards)
+/// are in the same family too.
+fn family(flag: &str) -> String {
+    if flag.starts_with("-O") {
+        return String::from("-O");
+    }
+    if flag == "-ansi" || flag.starts_with("-std=") {
+        return String::from("-std");
+    }
+    if let Some(level) = flag.strip_prefix("-g") {
+        let level = level.strip_prefix("gdb").unwrap_or(level);
+        if level.chars().all(|c| c.is_ascii_digit()) {
+            return String::from("-g");
+        }
+    }
+    if ["-m16", "-m32", "-m64", "-mx32"].contains(&flag) {
+        return String::from("-m32");
+    }
+    if let Some(model) = flag
+        .strip_prefix("-fno-")
+        .or_else(|| flag.strip_prefix("-f"))
+    {
+        if ["pic", "pie"].contains(&model.to_ascii_lowercase().as_str()) {
+            return String::from("-fpic");
+        }
+    }
+    let name = flag.split('=').next().unwrap_or(flag);
+    for prefix in ["-f", "-W", "-m"] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            return format!("{}{}", prefix, rest.strip_prefix("no-").unwrap_or(rest));
+        }
+    }
+    name.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_order_insensitive_flags_are_sorted() {
+        let input = vec_of_strings![
+            "-Wall",
+            "-I",
+            "include/b",
+            "-c",
+            "-Iinclude/a",
+            "-DNAME=1",
+            "-UNAME",
+            "-g",
+            "-fPIC"
+        ];
+
+        let expected = vec_of_strings![
+            "-Wall",
+            "-c",
+            "-fPIC",
+            "-g",
+            "-I",
+            "include/b",
+            "-Iinclude/a",
+            "-DNAME=1",
+            "-UNAME"
+        ];
+        assert_eq!(expected, sort_flags(&input));
+    }
+
+    #[test]
+    fn test_overriding_flags_are_kept_in_order() {
+        let input = vec_of_strings!["-O2", "-fno-exceptions", "-c", "-fexceptions", "-O0"];
+
+        let expected = vec_of_strings!["-c", "-O2", "-fno-exceptions", "-fexceptions", "-O0"];
+        assert_eq!(expected, sort_flags(&input));
+    }
+
+    #[test]
+    fn test_conflicting_pairs_are_kept_in_order() {
+        let conflicting = [
+            vec_of_strings!["-m64", "-m32"],
+            vec_of_strings!["-g0", "-g"],
+            vec_of_strings!["-ggdb3", "-g1"],
+            vec_of_strings!["-fpic", "-fPIC"],
+            vec_of_strings!["-fPIE", "-fno-pie"],
+            vec_of_strings!["-std=c11", "-ansi"],
+            vec_of_strings!["-Os", "-O3"],
+        ];
+        for pair in conflicting {
+            let mut input = vec_of_strings!["-Wextra", "-c"];
+            input.splice(1..1, pair.clone());
+
+            let mut expected = vec_of_strings!["-Wextra", "-c"];
+            expected.extend(pair);
+            assert_eq!(expected, sort_flags(&input));
+        }
+    }
+}
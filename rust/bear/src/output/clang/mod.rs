@@ -36,6 +36,35 @@ pub struct Entry {
     /// The name of the output created by this compilation step. This field is optional.
     /// It can be used to distinguish different processing modes of the same input file.
     pub output: Option<std::path::PathBuf>,
+    /// The first argument of the compiler call, as it was captured. This field is not
+    /// part of the format, it's written only when it was requested.
+    pub original_compiler: Option<String>,
+    /// The language of the source file, as it was recognized. (Like `c` or `c++`.) This
+    /// field is not part of the format, it's written only when it was requested.
+    pub language: Option<String>,
+}
+
+/// Represents how the arguments are quoted in the `command` field.
+///
+/// The default is the POSIX shell quoting: the arguments are single quoted, when
+/// those are having special characters (like spaces).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quoting {
+    pub style: QuoteStyle,
+    /// Quote all arguments, not only the ones which are having special characters.
+    pub always: bool,
+}
+
+/// Represents the quote character of the arguments in the `command` field.
+///
+/// Within single quotes, the single quote is written as `'\''`. (The POSIX shell
+/// rules.) Within double quotes, the double quote is escaped with a backslash, and
+/// the backslashes are escaped only before a double quote. (The Windows rules.)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    #[default]
+    Single,
+    Double,
 }
 
 pub fn write(
@@ -50,6 +79,165 @@ pub fn write(
     seq.end()
 }
 
+/// Write the entries with the `command` field, instead of the `arguments` field.
+///
+/// The command is a single string, where the arguments are shell quoted.
+pub fn write_with_command(
+    writer: impl std::io::Write,
+    entries: impl Iterator<Item = Entry>,
+) -> Result<(), Error> {
+    write_with_quoted_command(writer, entries, Quoting::default())
+}
+
+/// Write the entries with the `command` field, where the arguments are quoted
+/// by the given policy.
+pub fn write_with_quoted_command(
+    writer: impl std::io::Write,
+    entries: impl Iterator<Item = Entry>,
+    quoting: Quoting,
+) -> Result<(), Error> {
+    let mut ser = serde_json::Serializer::new(writer);
+    let mut seq = ser.serialize_seq(None)?;
+    for entry in entries {
+        seq.serialize_element(&type_ser::CommandEntry(&entry, quoting))?;
+    }
+    seq.end()
+}
+
+/// Write the entries as a JSON object, where the keys are the source files.
+///
+/// This is not the standard JSON compilation database format, but it allows
+/// to look up the entries by the source file. The value under the key is the
+/// entry without the `file` field. When multiple entries share the same file,
+/// the value is an array of those.
+pub fn write_keyed_by_file(
+    writer: impl std::io::Write,
+    entries: impl Iterator<Item = Entry>,
+) -> Result<(), Error> {
+    write_keyed(writer, entries, None)
+}
+
+/// Write the entries as a JSON object, where the keys are the source files, and
+/// the values are having the `command` field, where the arguments are quoted by
+/// the given policy.
+pub fn write_keyed_by_file_with_quoted_command(
+    writer: impl std::io::Write,
+    entries: impl Iterator<Item = Entry>,
+    quoting: Quoting,
+) -> Result<(), Error> {
+    write_keyed(writer, entries, Some(quoting))
+}
+
+fn write_keyed(
+    writer: impl std::io::Write,
+    entries: impl Iterator<Item = Entry>,
+    with_command: Option<Quoting>,
+) -> Result<(), Error> {
+    // Group the entries by file, and keep the order of the first appearance.
+    let mut groups: Vec<(std::path::PathBuf, Vec<Entry>)> = vec![];
+    let mut positions: std::collections::HashMap<std::path::PathBuf, usize> =
+        std::collections::HashMap::new();
+    for entry in entries {
+        match positions.get(&entry.file) {
+            Some(position) => groups[*position].1.push(entry),
+            None => {
+                positions.insert(entry.file.clone(), groups.len());
+                groups.push((entry.file.clone(), vec![entry]));
+            }
+        }
+    }
+
+    use serde::ser::SerializeMap;
+
+    let mut ser = serde_json::Serializer::new(writer);
+    let mut map = ser.serialize_map(Some(groups.len()))?;
+    for (file, entries) in &groups {
+        let values: Vec<type_ser::KeyedEntry> = entries
+            .iter()
+            .map(|entry| type_ser::KeyedEntry(entry, with_command))
+            .collect();
+        match values.as_slice() {
+            [value] => map.serialize_entry(file, value)?,
+            _ => map.serialize_entry(file, &values)?,
+        }
+    }
+    SerializeMap::end(map)
+}
+
+/// Write the entries as newline delimited JSON objects. (Known as JSON Lines format.)
+///
+/// This is not the standard JSON compilation database format, but it's easier to
+/// process line by line. There is no enclosing array, each entry is on its own line.
+pub fn write_lines(
+    mut writer: impl std::io::Write,
+    entries: impl Iterator<Item = Entry>,
+) -> Result<(), Error> {
+    for entry in entries {
+        serde_json::to_writer(&mut writer, &entry)?;
+        writer.write_all(b"\n").map_err(Error::io)?;
+    }
+    Ok(())
+}
+
+/// Writes the entries of the compilation database one by one.
+///
+/// The entries are written as those are appended, and the writer is flushed after
+/// each of them. The array is closed by the `finish` method, the finished output
+/// is a valid compilation database even when no entries were appended. (Without
+/// calling it, the output is left unterminated.)
+pub struct IncrementalWriter<W: std::io::Write> {
+    writer: W,
+    with_command: Option<Quoting>,
+    count: usize,
+}
+
+impl<W: std::io::Write> IncrementalWriter<W> {
+    /// Creates a writer of the entries with the `arguments` field.
+    pub fn new(writer: W) -> Self {
+        IncrementalWriter {
+            writer,
+            with_command: None,
+            count: 0,
+        }
+    }
+
+    /// Creates a writer of the entries with the `command` field.
+    pub fn with_command(writer: W) -> Self {
+        Self::with_quoted_command(writer, Quoting::default())
+    }
+
+    /// Creates a writer of the entries with the `command` field, where the arguments
+    /// are quoted by the given policy.
+    pub fn with_quoted_command(writer: W, quoting: Quoting) -> Self {
+        IncrementalWriter {
+            with_command: Some(quoting),
+            ..Self::new(writer)
+        }
+    }
+
+    /// Write the entry as the next element of the array.
+    pub fn append_entry(&mut self, entry: &Entry) -> Result<(), Error> {
+        let separator: &[u8] = if self.count == 0 { b"[" } else { b"," };
+        self.writer.write_all(separator).map_err(Error::io)?;
+        match self.with_command {
+            Some(quoting) => {
+                serde_json::to_writer(&mut self.writer, &type_ser::CommandEntry(entry, quoting))?
+            }
+            None => serde_json::to_writer(&mut self.writer, entry)?,
+        }
+        self.count += 1;
+        self.writer.flush().map_err(Error::io)
+    }
+
+    /// Close the array, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let terminator: &[u8] = if self.count == 0 { b"[]" } else { b"]" };
+        self.writer.write_all(terminator).map_err(Error::io)?;
+        self.writer.flush().map_err(Error::io)?;
+        Ok(self.writer)
+    }
+}
+
 pub fn read(reader: impl std::io::Read) -> impl Iterator<Item = Result<Entry, Error>> {
     iterator::iter_json_array(reader)
 }
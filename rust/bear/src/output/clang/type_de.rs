@@ -19,8 +19,18 @@ impl<'de> Deserialize<'de> for Entry {
             Command,
             Arguments,
             Output,
+            OriginalCompiler,
+            Language,
         }
-        const FIELDS: &[&str] = &["directory", "file", "command", "arguments", "output"];
+        const FIELDS: &[&str] = &[
+            "directory",
+            "file",
+            "command",
+            "arguments",
+            "output",
+            "original_compiler",
+            "language",
+        ];
 
         impl<'de> Deserialize<'de> for Field {
             fn deserialize<D>(deserializer: D) -> Result<Field, D::Error>
@@ -33,8 +43,9 @@ impl<'de> Deserialize<'de> for Entry {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                        formatter
-                            .write_str("`directory`, `file`, `command`, `arguments`, or `output`")
+                        formatter.write_str(
+                            "`directory`, `file`, `command`, `arguments`, `output`, `original_compiler`, or `language`",
+                        )
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -47,6 +58,8 @@ impl<'de> Deserialize<'de> for Entry {
                             "command" => Ok(Field::Command),
                             "arguments" => Ok(Field::Arguments),
                             "output" => Ok(Field::Output),
+                            "original_compiler" => Ok(Field::OriginalCompiler),
+                            "language" => Ok(Field::Language),
                             _ => Err(de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -74,6 +87,8 @@ impl<'de> Deserialize<'de> for Entry {
                 let mut command: Option<String> = None;
                 let mut arguments: Option<Vec<String>> = None;
                 let mut output: Option<path::PathBuf> = None;
+                let mut original_compiler: Option<String> = None;
+                let mut language: Option<String> = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -107,6 +122,18 @@ impl<'de> Deserialize<'de> for Entry {
                             }
                             output = Some(map.next_value()?);
                         }
+                        Field::OriginalCompiler => {
+                            if original_compiler.is_some() {
+                                return Err(de::Error::duplicate_field("original_compiler"));
+                            }
+                            original_compiler = Some(map.next_value()?);
+                        }
+                        Field::Language => {
+                            if language.is_some() {
+                                return Err(de::Error::duplicate_field("language"));
+                            }
+                            language = Some(map.next_value()?);
+                        }
                     }
                 }
                 let directory = directory.ok_or_else(|| de::Error::missing_field("directory"))?;
@@ -136,6 +163,8 @@ impl<'de> Deserialize<'de> for Entry {
                     file,
                     arguments,
                     output,
+                    original_compiler,
+                    language,
                 })
             }
         }
@@ -3,21 +3,145 @@
 
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 
-use super::Entry;
+use super::{Entry, QuoteStyle, Quoting};
 
 impl Serialize for Entry {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let size = if self.output.is_some() { 4 } else { 3 };
+        let size = 3 + optional_fields(self);
         let mut state = serializer.serialize_struct("Entry", size)?;
         state.serialize_field("directory", &self.directory)?;
         state.serialize_field("file", &self.file)?;
         state.serialize_field("arguments", &self.arguments)?;
-        if self.output.is_some() {
-            state.serialize_field("output", &self.output)?;
+        serialize_optional_fields(&mut state, self)?;
+        state.end()
+    }
+}
+
+/// The entry without the `file` field, which is the key of the entry.
+///
+/// With quoting, it has the `command` field, instead of the `arguments` field.
+pub(super) struct KeyedEntry<'a>(pub(super) &'a Entry, pub(super) Option<Quoting>);
+
+impl Serialize for KeyedEntry<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let entry = self.0;
+        let size = 2 + optional_fields(entry);
+        let mut state = serializer.serialize_struct("Entry", size)?;
+        state.serialize_field("directory", &entry.directory)?;
+        match self.1 {
+            Some(quoting) => state.serialize_field("command", &join(&entry.arguments, quoting))?,
+            None => state.serialize_field("arguments", &entry.arguments)?,
         }
+        serialize_optional_fields(&mut state, entry)?;
         state.end()
     }
 }
+
+/// The entry with the `command` field, instead of the `arguments` field.
+///
+/// The command is the arguments joined with shell quoting.
+pub(super) struct CommandEntry<'a>(pub(super) &'a Entry, pub(super) Quoting);
+
+impl Serialize for CommandEntry<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let entry = self.0;
+        let size = 3 + optional_fields(entry);
+        let mut state = serializer.serialize_struct("Entry", size)?;
+        state.serialize_field("directory", &entry.directory)?;
+        state.serialize_field("file", &entry.file)?;
+        state.serialize_field("command", &join(&entry.arguments, self.1))?;
+        serialize_optional_fields(&mut state, entry)?;
+        state.end()
+    }
+}
+
+/// Join the arguments into a command string, quoted by the policy.
+fn join(arguments: &[String], quoting: Quoting) -> String {
+    let quoted: Vec<String> = arguments
+        .iter()
+        .map(|argument| match quoting {
+            Quoting {
+                style: QuoteStyle::Single,
+                always: false,
+            } => shell_words::quote(argument).to_string(),
+            Quoting {
+                style: QuoteStyle::Single,
+                always: true,
+            } => format!("'{}'", argument.replace('\'', r"'\''")),
+            Quoting {
+                style: QuoteStyle::Double,
+                always,
+            } if always || needs_quoting(argument) => quote_for_windows(argument),
+            _ => argument.clone(),
+        })
+        .collect();
+    quoted.join(" ")
+}
+
+/// Quote the argument with double quotes, by the Windows (`CommandLineToArgvW`) rules.
+///
+/// The backslashes are literal, unless those are followed by a double quote. Before
+/// a double quote (and before the closing quote), the backslashes are doubled.
+fn quote_for_windows(argument: &str) -> String {
+    let mut result = String::from("\"");
+    let mut backslashes = 0;
+    for c in argument.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                result.push_str(&"\\".repeat(backslashes * 2 + 1));
+                result.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                result.push_str(&"\\".repeat(backslashes));
+                result.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    result.push_str(&"\\".repeat(backslashes * 2));
+    result.push('"');
+    result
+}
+
+/// Returns true, if the argument is empty, or it has characters with special meaning.
+fn needs_quoting(argument: &str) -> bool {
+    argument.is_empty()
+        || argument
+            .chars()
+            .any(|c| !(c.is_ascii_alphanumeric() || ",._+:@%/-=".contains(c)))
+}
+
+/// Returns the number of the optional fields, which are present.
+fn optional_fields(entry: &Entry) -> usize {
+    usize::from(entry.output.is_some())
+        + usize::from(entry.original_compiler.is_some())
+        + usize::from(entry.language.is_some())
+}
+
+/// Serialize the optional fields, when those are present.
+fn serialize_optional_fields<S: SerializeStruct>(
+    state: &mut S,
+    entry: &Entry,
+) -> Result<(), S::Error> {
+    if entry.output.is_some() {
+        state.serialize_field("output", &entry.output)?;
+    }
+    if entry.original_compiler.is_some() {
+        state.serialize_field("original_compiler", &entry.original_compiler)?;
+    }
+    if entry.language.is_some() {
+        state.serialize_field("language", &entry.language)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Collects the common flags of the compilations per source directory.
+//!
+//! Clangd reads the `compile_flags.txt` file (next to the source or in its parent
+//! directories) for the files which are not in the compilation database. The file
+//! contains one flag per line, which are used for all files in that directory.
+//! Therefore only those flags are written, which are present in all compilations
+//! of the sources in that directory.
+//!
+//! Clangd resolves the relative paths of the file from the directory of it. The
+//! relative include paths are rebased from the working directory of the compiler
+//! call to the directory of the file.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::dependency::without_dependency_flags;
+use super::merge::into_units;
+use super::{into_abspath, semantic};
+
+/// The name of the file, which clangd is looking for.
+const FILE_NAME: &str = "compile_flags.txt";
+
+/// The flags which are followed by a path as a separate argument.
+const FLAGS_WITH_PATH: [&str; 6] = [
+    "-I",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-include",
+    "-imacros",
+];
+
+/// The flags which might be joined with their path. (Like `-Iinclude`.)
+const PREFIXES_WITH_PATH: [&str; 4] = ["-isystem", "-iquote", "-idirafter", "-I"];
+
+/// Represents the common flags of the compilations per directory.
+///
+/// The flags are kept as units (a flag with its values), to not break them apart
+/// at the intersection.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct CompileFlags {
+    directories: BTreeMap<PathBuf, Vec<Vec<String>>>,
+}
+
+impl CompileFlags {
+    /// Record the flags of the compiler call.
+    pub(super) fn add(&mut self, call: &semantic::CompilerCall) {
+        for pass in &call.passes {
+            if let semantic::CompilerPass::Compile { source, flags, .. } = pass {
+                let directory = match into_abspath(source.clone(), &call.working_dir) {
+                    Ok(source) => source.parent().map(PathBuf::from),
+                    Err(error) => {
+                        log::warn!("Failed to record compile flags: {}", error);
+                        None
+                    }
+                };
+                if let Some(directory) = directory {
+                    let flags = rebased(&without_dependency_flags(flags), call, &directory);
+                    let units: Vec<Vec<String>> = into_units(&flags)
+                        .into_iter()
+                        .filter(|unit| unit != &["-c"])
+                        .collect();
+                    self.directories
+                        .entry(directory)
+                        .and_modify(|common| common.retain(|unit| units.contains(unit)))
+                        .or_insert(units);
+                }
+            }
+        }
+    }
+
+    /// Write the `compile_flags.txt` file into each recorded directory.
+    ///
+    /// The directories without common flags are skipped. The existing files are
+    /// kept, unless those shall be overwritten.
+    pub(super) fn write(&self, overwrite: bool) -> Result<()> {
+        for (directory, units) in &self.directories {
+            if units.is_empty() {
+                continue;
+            }
+            let file_name = directory.join(FILE_NAME);
+            if !overwrite && file_name.exists() {
+                log::info!("The existing file is kept: {:?}", file_name);
+                continue;
+            }
+            let file = File::create(&file_name)
+                .with_context(|| format!("Failed to create file: {:?}", file_name))?;
+            let mut writer = BufWriter::new(file);
+            for flag in units.iter().flatten() {
+                writeln!(writer, "{}", flag)
+                    .with_context(|| format!("Failed to write file: {:?}", file_name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rebase the relative paths of the include flags to the given directory.
+fn rebased(flags: &[String], call: &semantic::CompilerCall, directory: &Path) -> Vec<String> {
+    let rebase = |value: &str| -> String {
+        match into_abspath(PathBuf::from(value), &call.working_dir) {
+            Ok(path) if !Path::new(value).is_absolute() => {
+                relative_to(&path, directory).to_string_lossy().into_owned()
+            }
+            _ => value.to_string(),
+        }
+    };
+    let mut result = Vec::with_capacity(flags.len());
+    let mut expect_path = false;
+    for flag in flags {
+        if expect_path {
+            result.push(rebase(flag));
+            expect_path = false;
+        } else if FLAGS_WITH_PATH.contains(&flag.as_str()) {
+            result.push(flag.clone());
+            expect_path = true;
+        } else {
+            let joined = PREFIXES_WITH_PATH.iter().find_map(|prefix| {
+                flag.strip_prefix(prefix)
+                    .filter(|path| !path.is_empty())
+                    .map(|path| format!("{}{}", prefix, rebase(path)))
+            });
+            result.push(joined.unwrap_or_else(|| flag.clone()));
+        }
+    }
+    result
+}
+
+/// Returns the path relative to the base directory. (Both are absolute and normalized.)
+///
+/// The path is kept absolute, when these are not sharing a root. (Like different drives.)
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+    if common == 0 {
+        return path.to_path_buf();
+    }
+    let mut result: PathBuf = base
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    result.extend(path.components().skip(common));
+    if result.as_os_str().is_empty() {
+        result.push(".");
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_compile_flags_are_the_common_flags_of_the_directory() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-compile-flags-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(directory.join("src")).unwrap();
+        let call = |source: &str, flags: Vec<String>| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: directory.clone(),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags,
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let mut sut = CompileFlags::default();
+        sut.add(&call(
+            "src/foo.c",
+            vec_of_strings!["-c", "-I", "include", "-DFOO", "-Wall", "-MD"],
+        ));
+        sut.add(&call(
+            "src/bar.c",
+            vec_of_strings!["-c", "-I", "include", "-I", "other", "-Wall", "-O2"],
+        ));
+        sut.add(&call(
+            "main.c",
+            vec_of_strings!["-c", "-Isrc/include", "-g"],
+        ));
+        sut.add(&call("lib/foo.c", vec_of_strings!["-c", "-O2"]));
+        sut.add(&call("lib/bar.c", vec_of_strings!["-c", "-O0"]));
+        sut.write(true).unwrap();
+
+        let content = |file: PathBuf| std::fs::read_to_string(file).unwrap();
+        assert_eq!(
+            "-I\n../include\n-Wall\n",
+            content(directory.join("src").join(FILE_NAME))
+        );
+        assert_eq!("-Isrc/include\n-g\n", content(directory.join(FILE_NAME)));
+        assert!(!directory.join("lib").join(FILE_NAME).exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_existing_compile_flags_are_kept() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-compile-flags-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join(FILE_NAME), "-DEXISTING\n").unwrap();
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: directory.clone(),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("main.c"),
+                output: None,
+                flags: vec_of_strings!["-c", "-g"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let mut sut = CompileFlags::default();
+        sut.add(&call);
+        sut.write(false).unwrap();
+        let kept = std::fs::read_to_string(directory.join(FILE_NAME)).unwrap();
+        sut.write(true).unwrap();
+        let overwritten = std::fs::read_to_string(directory.join(FILE_NAME)).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("-DEXISTING\n", kept);
+        assert_eq!("-g\n", overwritten);
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements the deduplication of the entries of the same compilation.
+//!
+//! The entries are of the same compilation, when these have the same directory,
+//! file and output. (Like redundant make rules, which are compiling the same
+//! source again.) The last occurrence is kept, because that one typically
+//! reflects the final flags of the build.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::path::PathBuf;
+
+use super::clang::Entry;
+
+/// The fields which are identifying the compilation.
+type Key = (PathBuf, PathBuf, Option<PathBuf>);
+
+fn key(entry: &Entry) -> Key {
+    (
+        entry.directory.clone(),
+        entry.file.clone(),
+        entry.output.clone(),
+    )
+}
+
+/// Returns the last occurrence of each compilation, in the order of the last occurrences.
+pub(super) fn keep_last(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut last: HashMap<Key, usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        last.insert(key(entry), index);
+    }
+    entries
+        .into_iter()
+        .enumerate()
+        .filter(|(index, entry)| last.get(&key(entry)) == Some(index))
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+/// Keeps the last occurrence of each compilation, from entries sorted by directory and file.
+///
+/// Only the entries of the same source are kept in memory. (The sort must be stable,
+/// to keep the original order of the same compilations.)
+pub(super) struct KeepLastOfSorted<I: Iterator<Item = Entry>> {
+    entries: Peekable<I>,
+    group: std::vec::IntoIter<Entry>,
+}
+
+impl<I: Iterator<Item = Entry>> KeepLastOfSorted<I> {
+    pub(super) fn new(entries: I) -> Self {
+        KeepLastOfSorted {
+            entries: entries.peekable(),
+            group: vec![].into_iter(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Entry>> Iterator for KeepLastOfSorted<I> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.group.next() {
+                return Some(entry);
+            }
+            let first = self.entries.next()?;
+            let mut group = vec![];
+            while let Some(entry) = self
+                .entries
+                .next_if(|entry| entry.directory == first.directory && entry.file == first.file)
+            {
+                group.push(entry);
+            }
+            group.insert(0, first);
+            self.group = keep_last(group).into_iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_last_occurrence_is_kept() {
+        let entry = |file: &str, output: Option<&str>, flag: &str| Entry {
+            file: PathBuf::from(file),
+            arguments: vec_of_strings!["cc", "-c", flag, file],
+            directory: PathBuf::from("/home/user"),
+            output: output.map(PathBuf::from),
+            original_compiler: None,
+            language: None,
+        };
+        let input = vec![
+            entry("/home/user/a.c", Some("/home/user/a.o"), "-O0"),
+            entry("/home/user/b.c", None, "-O0"),
+            entry("/home/user/a.c", Some("/home/user/a.o"), "-O0"),
+            entry("/home/user/a.c", Some("/home/user/a.o"), "-O2"),
+            entry("/home/user/a.c", Some("/home/user/a.pic.o"), "-fPIC"),
+        ];
+
+        let expected = vec![
+            entry("/home/user/b.c", None, "-O0"),
+            entry("/home/user/a.c", Some("/home/user/a.o"), "-O2"),
+            entry("/home/user/a.c", Some("/home/user/a.pic.o"), "-fPIC"),
+        ];
+        assert_eq!(expected, keep_last(input.clone()));
+
+        // The sorted entries are giving the same result, in sorted order.
+        let mut sorted = input;
+        sorted.sort_by(|lhs, rhs| lhs.file.cmp(&rhs.file));
+        let mut expected = expected;
+        expected.sort_by(|lhs, rhs| lhs.file.cmp(&rhs.file));
+        let result: Vec<Entry> = KeepLastOfSorted::new(sorted.into_iter()).collect();
+        assert_eq!(expected, result);
+    }
+}
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Collects the dependency files of the compiler calls.
+//!
+//! The compiler writes a dependency file (in make syntax) when it was asked with the
+//! `-MD`, `-MMD` or `-MF` flags. These flags are removed from the compilation database
+//! entries on request (by the `strip_dependency_flags` format option), and the association
+//! between the source and the dependency file can be written into a separate manifest file. (The content of the dependency files
+//! is not parsed, only their location is recorded.)
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::{into_abspath, semantic};
+
+/// The flags which are controlling the dependency file generation, and are not taking a value.
+///
+/// (The `-M` and `-MM` are making the compiler to stop after the preprocessing, but
+/// those are removed too, when these are given with other compiler passes.)
+const FLAGS: [&str; 6] = ["-M", "-MM", "-MD", "-MMD", "-MG", "-MP"];
+/// The flags which are controlling the dependency file generation, and are taking a value.
+const FLAGS_WITH_VALUE: [&str; 3] = ["-MF", "-MT", "-MQ"];
+
+/// Represents the dependency files of the compiled sources.
+///
+/// Both the source and the dependency file names are absolute paths.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Manifest {
+    files: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Manifest {
+    /// Record the dependency files of the compiler call.
+    pub(super) fn add(&mut self, call: &semantic::CompilerCall) {
+        for pass in &call.passes {
+            if let semantic::CompilerPass::Compile {
+                source,
+                output,
+                flags,
+                ..
+            } = pass
+            {
+                if let Some(dependency) = dependency_file(flags, source, output.as_deref()) {
+                    let source = into_abspath(source.clone(), &call.working_dir);
+                    let dependency = into_abspath(dependency, &call.working_dir);
+                    match source
+                        .and_then(|source| dependency.map(|dependency| (source, dependency)))
+                    {
+                        Ok((source, dependency)) => {
+                            self.files.insert(source, dependency);
+                        }
+                        Err(error) => {
+                            log::warn!("Failed to record dependency file: {}", error);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write the manifest into the given file.
+    ///
+    /// The manifest is a JSON object, where the keys are the sources and the values are
+    /// the dependency files.
+    pub(super) fn write(&self, file_name: &Path) -> Result<()> {
+        let file = File::create(file_name)
+            .with_context(|| format!("Failed to create file: {:?}", file_name))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &self.files)
+            .with_context(|| format!("Failed to write file: {:?}", file_name))
+    }
+}
+
+/// Returns the dependency file of a compilation, if it was requested.
+///
+/// The `-MF` flag names the file explicitly. Otherwise, the `-MD` and `-MMD` flags are
+/// writing it next to the output (or the source, when there is no output) with the `.d`
+/// extension.
+fn dependency_file(flags: &[String], source: &Path, output: Option<&Path>) -> Option<PathBuf> {
+    let mut explicit = None;
+    let mut requested = false;
+    let mut arguments = flags.iter();
+    while let Some(flag) = arguments.next() {
+        if flag == "-MF" {
+            explicit = arguments.next().map(PathBuf::from);
+        } else if let Some(value) = flag.strip_prefix("-MF") {
+            explicit = Some(PathBuf::from(value));
+        } else if flag == "-MD" || flag == "-MMD" {
+            requested = true;
+        }
+    }
+    explicit.or_else(|| {
+        requested.then(|| match output {
+            Some(output) => output.with_extension("d"),
+            None => PathBuf::from(source.file_name().unwrap_or_default()).with_extension("d"),
+        })
+    })
+}
+
+/// Remove the flags which are controlling the dependency file generation.
+///
+/// The flags with value are removed with their value, in both the separate
+/// form (like `-MF foo.d`) and the joined form (like `-MFfoo.d` or `-MF=foo.d`).
+pub(super) fn without_dependency_flags(flags: &[String]) -> Vec<String> {
+    let mut result = vec![];
+    let mut arguments = flags.iter();
+    while let Some(flag) = arguments.next() {
+        if FLAGS.contains(&flag.as_str()) {
+            continue;
+        }
+        if FLAGS_WITH_VALUE.contains(&flag.as_str()) {
+            arguments.next();
+            continue;
+        }
+        if FLAGS_WITH_VALUE
+            .iter()
+            .any(|prefix| flag.starts_with(prefix))
+        {
+            continue;
+        }
+        result.push(flag.clone());
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_manifest_from_dependency_flags() {
+        let call =
+            |source: &str, output: Option<&str>, flags: Vec<String>| semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from(source),
+                    output: output.map(PathBuf::from),
+                    flags,
+                    output_implied: false,
+                }],
+                original_compiler: None,
+                environment: None,
+            };
+
+        let mut sut = Manifest::default();
+        sut.add(&call(
+            "src/foo.c",
+            Some("build/foo.o"),
+            vec_of_strings!["-c", "-MD", "-MF", "build/foo.d"],
+        ));
+        sut.add(&call(
+            "bar.c",
+            None,
+            vec_of_strings!["-c", "-MFdeps/bar.dep"],
+        ));
+        sut.add(&call(
+            "baz.c",
+            Some("out/baz.o"),
+            vec_of_strings!["-c", "-MMD"],
+        ));
+        sut.add(&call("qux.c", Some("qux.o"), vec_of_strings!["-c"]));
+
+        let expected = Manifest {
+            files: BTreeMap::from([
+                (
+                    PathBuf::from("/home/user/project/src/foo.c"),
+                    PathBuf::from("/home/user/project/build/foo.d"),
+                ),
+                (
+                    PathBuf::from("/home/user/project/bar.c"),
+                    PathBuf::from("/home/user/project/deps/bar.dep"),
+                ),
+                (
+                    PathBuf::from("/home/user/project/baz.c"),
+                    PathBuf::from("/home/user/project/out/baz.d"),
+                ),
+            ]),
+        };
+        assert_eq!(expected, sut);
+    }
+
+    #[test]
+    fn test_dependency_flags_are_removed() {
+        let input =
+            vec_of_strings!["-c", "-MD", "-MF", "foo.d", "-MTfoo.o", "-MP", "-DMF=1", "-Wall"];
+
+        assert_eq!(
+            vec_of_strings!["-c", "-DMF=1", "-Wall"],
+            without_dependency_flags(&input)
+        );
+    }
+
+    #[test]
+    fn test_dependency_flags_are_removed_with_their_values() {
+        let input = vec_of_strings!["-MF", "foo.d", "-MF=bar.d", "-MQ", "$(objdir)/foo.o", "-M"];
+        assert_eq!(Vec::<String>::new(), without_dependency_flags(&input));
+
+        // The flags starting with `-M`, but not controlling the dependencies, are kept.
+        let input = vec_of_strings!["-MJ", "cmd.json", "-MMD", "-march=native", "-c"];
+        assert_eq!(
+            vec_of_strings!["-MJ", "cmd.json", "-march=native", "-c"],
+            without_dependency_flags(&input)
+        );
+    }
+}
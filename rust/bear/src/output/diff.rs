@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements the comparison of two compilation databases.
+//!
+//! It's used by the dry-run mode, which reports the changes the run would make
+//! on the existing compilation database, without writing it.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::clang::Entry;
+
+/// Represents the changes between two compilation databases.
+///
+/// The entries are identified by their directory, file and output fields. An entry
+/// is modified when the arguments of the entry are different.
+#[derive(Debug, Default, PartialEq)]
+pub struct Diff {
+    pub added: Vec<Entry>,
+    pub removed: Vec<Entry>,
+    pub modified: Vec<Entry>,
+}
+
+type Key = (PathBuf, PathBuf, Option<PathBuf>);
+
+impl Diff {
+    /// Compare the current entries to the existing ones.
+    pub fn new(existing: Vec<Entry>, current: Vec<Entry>) -> Self {
+        let into_map = |entries: Vec<Entry>| -> BTreeMap<Key, Entry> {
+            entries
+                .into_iter()
+                .map(|entry| {
+                    let key = (
+                        entry.directory.clone(),
+                        entry.file.clone(),
+                        entry.output.clone(),
+                    );
+                    (key, entry)
+                })
+                .collect()
+        };
+        let mut existing = into_map(existing);
+        let mut diff = Diff::default();
+        for (key, entry) in into_map(current) {
+            match existing.remove(&key) {
+                None => diff.added.push(entry),
+                Some(previous) if previous.arguments != entry.arguments => {
+                    diff.modified.push(entry)
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = existing.into_values().collect();
+        diff
+    }
+
+    /// Returns true, if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Print the changes in a human readable form.
+    ///
+    /// Each changed entry is a line, with a `+` (added), `-` (removed) or `~` (modified)
+    /// marker and the source file name. The last line is the summary.
+    pub fn print(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for (marker, entries) in [
+            ("+", &self.added),
+            ("-", &self.removed),
+            ("~", &self.modified),
+        ] {
+            for entry in entries {
+                writeln!(writer, "{} {}", marker, entry.file.display())?;
+            }
+        }
+        writeln!(
+            writer,
+            "{} added, {} removed, {} modified",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_printed_diff() {
+        let entry = |file: &str, arguments: Vec<String>| Entry {
+            file: PathBuf::from(file),
+            arguments,
+            directory: PathBuf::from("/home/user/project"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+        let existing = vec![
+            entry(
+                "/home/user/project/same.c",
+                vec_of_strings!["cc", "-c", "same.c"],
+            ),
+            entry(
+                "/home/user/project/changed.c",
+                vec_of_strings!["cc", "-c", "changed.c"],
+            ),
+            entry(
+                "/home/user/project/deleted.c",
+                vec_of_strings!["cc", "-c", "deleted.c"],
+            ),
+        ];
+        let current = vec![
+            entry(
+                "/home/user/project/same.c",
+                vec_of_strings!["cc", "-c", "same.c"],
+            ),
+            entry(
+                "/home/user/project/changed.c",
+                vec_of_strings!["cc", "-c", "-O2", "changed.c"],
+            ),
+            entry(
+                "/home/user/project/new.c",
+                vec_of_strings!["cc", "-c", "new.c"],
+            ),
+        ];
+
+        let sut = Diff::new(existing, current);
+        let mut output: Vec<u8> = vec![];
+        sut.print(&mut output).unwrap();
+
+        let expected = "\
++ /home/user/project/new.c
+- /home/user/project/deleted.c
+~ /home/user/project/changed.c
+1 added, 1 removed, 1 modified
+";
+        assert!(!sut.is_empty());
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn test_same_databases_have_no_diff() {
+        let entries = vec![Entry {
+            file: PathBuf::from("/home/user/project/same.c"),
+            arguments: vec_of_strings!["cc", "-c", "same.c"],
+            directory: PathBuf::from("/home/user/project"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        }];
+
+        assert!(Diff::new(entries.clone(), entries).is_empty());
+    }
+}
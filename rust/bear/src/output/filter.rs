@@ -1,11 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::hash::Hash;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use regex::Regex;
 
 use super::clang::Entry;
 use super::config;
-use builder::create_hash;
 use builder::EntryPredicateBuilder as Builder;
+use builder::{create_hash, create_hash128, create_key};
 
 /// A predicate that can be used to filter compilation database entries.
 ///
@@ -27,15 +30,117 @@ impl TryFrom<&config::Filter> for EntryPredicate {
             Builder::filter_by_source_paths(&config.source.paths_to_exclude);
         let source_paths_to_include =
             Builder::filter_by_source_paths(&config.source.paths_to_include);
-        let source_checks = source_exist_check & !source_paths_to_exclude & source_paths_to_include;
+        let source_changed = Builder::filter_by_changed_files(&config.source.changed_files);
+        let source_checks = source_exist_check
+            & !source_paths_to_exclude
+            & source_paths_to_include
+            & source_changed;
+        // - Check if the output is not the null device
+        let null_output_check = Builder::filter_by_null_output(!config.keep_null_outputs);
+        // - Check if the compilation is not generating assembly
+        let assembly_output_check =
+            Builder::filter_by_assembly_output(!config.keep_assembly_outputs);
         // - Check if the entry is not a duplicate based on the fields of the configuration
-        let hash_function = create_hash(&config.duplicates.by_fields);
-        let duplicates = Builder::filter_duplicate_entries(hash_function);
+        let fields = &config.duplicates.by_fields;
+        let duplicates = match config.duplicates.compare_by {
+            config::DuplicateComparison::Exact => {
+                Builder::filter_duplicate_entries(create_key(fields))
+            }
+            config::DuplicateComparison::Hash64 => {
+                Builder::filter_duplicate_entries(create_hash(fields))
+            }
+            config::DuplicateComparison::Hash128 => {
+                Builder::filter_duplicate_entries(create_hash128(fields))
+            }
+        };
 
-        Ok((source_checks & duplicates).build())
+        Ok((source_checks & null_output_check & assembly_output_check & duplicates).build())
     }
 }
 
+/// Create a filter from the source file glob patterns.
+///
+/// The excludes are taking precedence over the includes, and the empty include
+/// list is including all entries.
+pub fn by_source_globs(include: &[Regex], exclude: &[Regex]) -> EntryPredicate {
+    let source_globs_to_exclude = Builder::filter_by_source_globs(exclude);
+    let source_globs_to_include = Builder::filter_by_source_globs(include);
+    (!source_globs_to_exclude & source_globs_to_include).build()
+}
+
+/// Translate the glob patterns into regular expressions.
+///
+/// The `**` matches any characters, the `*` and the `?` are not matching
+/// the path separator. The character classes are written like `[abc]`, and
+/// negated like `[!abc]`. A relative pattern is matching
+/// from any directory, an absolute pattern only from the root.
+pub fn compile_globs(patterns: &[String]) -> anyhow::Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            glob_to_regex(pattern)
+                .and_then(|regex| Ok(Regex::new(&regex)?))
+                .with_context(|| format!("Invalid glob pattern: {}", pattern))
+        })
+        .collect()
+}
+
+fn glob_to_regex(pattern: &str) -> anyhow::Result<String> {
+    let mut result = String::from(if pattern.starts_with('/') {
+        "^"
+    } else {
+        "(?:^|/)"
+    });
+    let mut chars = pattern.chars().peekable();
+    while let Some(current) = chars.next() {
+        match current {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    result.push_str("(?:.*/)?");
+                } else {
+                    result.push_str(".*");
+                }
+            }
+            '*' => result.push_str("[^/]*"),
+            '?' => result.push_str("[^/]"),
+            '[' => {
+                result.push('[');
+                let negated = chars.peek() == Some(&'!');
+                if negated {
+                    chars.next();
+                    result.push('^');
+                }
+                let mut closed = false;
+                for class in chars.by_ref() {
+                    if class == ']' {
+                        closed = true;
+                        break;
+                    }
+                    match class {
+                        '\\' | '[' | '^' | '&' | '~' => {
+                            result.push('\\');
+                            result.push(class);
+                        }
+                        _ => result.push(class),
+                    }
+                }
+                if !closed {
+                    anyhow::bail!("Unterminated character class.");
+                }
+                if negated {
+                    result.push('/');
+                }
+                result.push(']');
+            }
+            _ => result.push_str(&regex::escape(&current.to_string())),
+        }
+    }
+    result.push('$');
+    Ok(result)
+}
+
 mod builder {
     use super::*;
     use std::collections::HashSet;
@@ -83,6 +188,40 @@ mod builder {
             }
         }
 
+        /// Create a predicate that filters out entries
+        /// that source file is not matching any of the given patterns.
+        pub(super) fn filter_by_source_globs(patterns: &[Regex]) -> Self {
+            if patterns.is_empty() {
+                Self::new()
+            } else {
+                let owned_patterns: Vec<Regex> = patterns.to_vec();
+                Self::from(move |entry| {
+                    let file = entry.file.to_string_lossy();
+                    owned_patterns.iter().any(|pattern| pattern.is_match(&file))
+                })
+            }
+        }
+
+        /// Create a predicate that filters out entries
+        /// that source file is not in the given changed files.
+        pub(super) fn filter_by_changed_files(files: &Option<Vec<PathBuf>>) -> Self {
+            match files {
+                Some(files) => {
+                    let owned_files: Vec<PathBuf> = files.clone();
+                    Self::from(move |entry| {
+                        owned_files.iter().any(|file| {
+                            if file.is_absolute() {
+                                &entry.file == file
+                            } else {
+                                entry.file.ends_with(file)
+                            }
+                        })
+                    })
+                }
+                None => Self::new(),
+            }
+        }
+
         /// Create a predicate that filters out entries
         /// that source file does not exist.
         pub(super) fn filter_by_source_existence(only_existing: bool) -> Self {
@@ -94,9 +233,29 @@ mod builder {
         }
 
         /// Create a predicate that filters out entries
-        /// that are already in the compilation database based on their hash.
-        pub(super) fn filter_duplicate_entries(
-            hash_function: impl Fn(&Entry) -> u64 + 'static,
+        /// that output is the null device.
+        pub(super) fn filter_by_null_output(drop_null_outputs: bool) -> Self {
+            if drop_null_outputs {
+                Self::from(|entry| !entry.output.as_deref().is_some_and(is_null_device))
+            } else {
+                Self::new()
+            }
+        }
+
+        /// Create a predicate that filters out entries
+        /// that are generating assembly.
+        pub(super) fn filter_by_assembly_output(drop_assembly_outputs: bool) -> Self {
+            if drop_assembly_outputs {
+                Self::from(|entry| !entry.arguments.iter().any(|argument| argument == "-S"))
+            } else {
+                Self::new()
+            }
+        }
+
+        /// Create a predicate that filters out entries
+        /// that are already in the compilation database based on their key (or hash).
+        pub(super) fn filter_duplicate_entries<K: Hash + Eq + 'static>(
+            hash_function: impl Fn(&Entry) -> K + 'static,
         ) -> Self {
             let mut have_seen = HashSet::new();
 
@@ -156,18 +315,69 @@ mod builder {
         let owned_fields: Vec<config::OutputFields> = fields.iter().cloned().collect();
         move |entry: &Entry| {
             let mut hasher = DefaultHasher::new();
-            for field in &owned_fields {
-                match field {
-                    config::OutputFields::Directory => entry.directory.hash(&mut hasher),
-                    config::OutputFields::File => entry.file.hash(&mut hasher),
-                    config::OutputFields::Arguments => entry.arguments.hash(&mut hasher),
-                    config::OutputFields::Output => entry.output.hash(&mut hasher),
-                }
-            }
+            hash_fields(&owned_fields, entry, &mut hasher);
             hasher.finish()
         }
     }
 
+    fn hash_fields(fields: &[config::OutputFields], entry: &Entry, hasher: &mut impl Hasher) {
+        for field in fields {
+            match field {
+                config::OutputFields::Directory => entry.directory.hash(hasher),
+                config::OutputFields::File => entry.file.hash(hasher),
+                config::OutputFields::Arguments => entry.arguments.hash(hasher),
+                config::OutputFields::Output => entry.output.hash(hasher),
+            }
+        }
+    }
+
+    /// Create a hash function that is using the given fields to calculate a 128-bit hash.
+    ///
+    /// The hash is made of two 64-bit hashes, where the second one is seeded differently.
+    pub(super) fn create_hash128(
+        fields: &[config::OutputFields],
+    ) -> impl Fn(&Entry) -> u128 + 'static {
+        let hash = create_hash(fields);
+        let seeded_fields: Vec<config::OutputFields> = fields.to_vec();
+        move |entry: &Entry| {
+            let mut hasher = DefaultHasher::new();
+            hasher.write_u64(0x9e37_79b9_7f4a_7c15);
+            hash_fields(&seeded_fields, entry, &mut hasher);
+            ((hash(entry) as u128) << 64) | hasher.finish() as u128
+        }
+    }
+
+    /// Returns true, if the path is the null device. (On Windows it's `NUL` in any directory.)
+    fn is_null_device(path: &Path) -> bool {
+        path == Path::new("/dev/null")
+            || path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.eq_ignore_ascii_case("nul"))
+    }
+
+    /// The copy of the selected fields of an entry.
+    type Key = (
+        Option<PathBuf>,
+        Option<PathBuf>,
+        Option<Vec<String>>,
+        Option<Option<PathBuf>>,
+    );
+
+    /// Create a key function that is copying the given fields of an entry.
+    pub(super) fn create_key(fields: &[config::OutputFields]) -> impl Fn(&Entry) -> Key + 'static {
+        let owned_fields: Vec<config::OutputFields> = fields.to_vec();
+        move |entry: &Entry| {
+            let selected = |field: config::OutputFields| owned_fields.contains(&field);
+            (
+                selected(config::OutputFields::Directory).then(|| entry.directory.clone()),
+                selected(config::OutputFields::File).then(|| entry.file.clone()),
+                selected(config::OutputFields::Arguments).then(|| entry.arguments.clone()),
+                selected(config::OutputFields::Output).then(|| entry.output.clone()),
+            )
+        }
+    }
+
     #[cfg(test)]
     mod test {
         use super::*;
@@ -185,12 +395,16 @@ mod builder {
                     arguments: vec_of_strings!["cc", "-c", "source.c"],
                     directory: PathBuf::from("/home/user/project"),
                     output: None,
+                    original_compiler: None,
+                    language: None,
                 },
                 Entry {
                     file: PathBuf::from("/home/user/project/test/source.c"),
                     arguments: vec_of_strings!["cc", "-c", "test.c"],
                     directory: PathBuf::from("/home/user/project"),
                     output: None,
+                    original_compiler: None,
+                    language: None,
                 },
             ];
 
@@ -204,6 +418,120 @@ mod builder {
             assert_eq!(result, expected);
         }
 
+        #[test]
+        fn test_filter_by_source_globs() {
+            let entry = |file: &str| Entry {
+                file: PathBuf::from(file),
+                arguments: vec_of_strings!["cc", "-c", file],
+                directory: PathBuf::from("/home/user/project"),
+                output: None,
+                original_compiler: None,
+                language: None,
+            };
+            let matching = |pattern: &str, file: &str| {
+                let patterns = compile_globs(&[pattern.to_string()]).unwrap();
+                (EntryPredicateBuilder::filter_by_source_globs(&patterns).build())(&entry(file))
+            };
+
+            assert!(matching("*.c", "/home/user/project/main.c"));
+            assert!(matching("src/*.c", "/home/user/project/src/main.c"));
+            assert!(!matching("src/*.c", "/home/user/project/src/lib/util.c"));
+            assert!(matching("src/**/*.c", "/home/user/project/src/main.c"));
+            assert!(matching("src/**/*.c", "/home/user/project/src/lib/util.c"));
+            assert!(matching("/home/**/main.?", "/home/user/project/main.c"));
+            assert!(!matching("/project/**", "/home/user/project/main.c"));
+            assert!(matching("main.[ch]", "/home/user/project/main.h"));
+            assert!(!matching("main.[!ch]", "/home/user/project/main.h"));
+            assert!(!matching("ain.c", "/home/user/project/main.c"));
+            assert!(!matching("main+c", "/home/user/project/main.c"));
+        }
+
+        #[test]
+        fn test_filter_by_changed_files() {
+            let changed_files = Some(vec_of_pathbuf!["src/changed.c", "/opt/lib/other.c"]);
+
+            let entry = |file: &str| Entry {
+                file: PathBuf::from(file),
+                arguments: vec_of_strings!["cc", "-c", file],
+                directory: PathBuf::from("/home/user/project"),
+                output: None,
+                original_compiler: None,
+                language: None,
+            };
+            let input: Vec<Entry> = vec![
+                entry("/home/user/project/src/changed.c"),
+                entry("/home/user/project/src/unchanged.c"),
+                entry("/home/user/project/test/changed.c"),
+                entry("/opt/lib/other.c"),
+            ];
+
+            let expected: Vec<Entry> = vec![input[0].clone(), input[3].clone()];
+
+            let sut: EntryPredicate =
+                EntryPredicateBuilder::filter_by_changed_files(&changed_files).build();
+            let result: Vec<Entry> = input.into_iter().filter(sut).collect();
+            assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn test_filter_by_null_output() {
+            let entry = |output: &str| Entry {
+                file: PathBuf::from("/home/user/project/conftest.c"),
+                arguments: vec_of_strings!["gcc", "-c", "-o", output, "conftest.c"],
+                directory: PathBuf::from("/home/user/project"),
+                output: Some(PathBuf::from(output)),
+                original_compiler: None,
+                language: None,
+            };
+            let input: Vec<Entry> = vec![
+                entry("/dev/null"),
+                entry("/home/user/project/NUL"),
+                entry("/home/user/project/conftest.o"),
+            ];
+
+            let expected: Vec<Entry> = vec![input[2].clone()];
+
+            let sut: EntryPredicate = EntryPredicateBuilder::filter_by_null_output(true).build();
+            let result: Vec<Entry> = input.iter().cloned().filter(sut).collect();
+            assert_eq!(result, expected);
+
+            let sut: EntryPredicate = EntryPredicateBuilder::filter_by_null_output(false).build();
+            let result: Vec<Entry> = input.iter().cloned().filter(sut).collect();
+            assert_eq!(result, input);
+        }
+
+        #[test]
+        fn test_filter_by_assembly_output() {
+            let input: Vec<Entry> = vec![
+                Entry {
+                    file: PathBuf::from("/home/user/project/foo.c"),
+                    arguments: vec_of_strings!["gcc", "-S", "-o", "foo.s", "foo.c"],
+                    directory: PathBuf::from("/home/user/project"),
+                    output: Some(PathBuf::from("/home/user/project/foo.s")),
+                    original_compiler: None,
+                    language: None,
+                },
+                Entry {
+                    file: PathBuf::from("/home/user/project/foo.c"),
+                    arguments: vec_of_strings!["gcc", "-c", "-o", "foo.o", "foo.c"],
+                    directory: PathBuf::from("/home/user/project"),
+                    output: Some(PathBuf::from("/home/user/project/foo.o")),
+                    original_compiler: None,
+                    language: None,
+                },
+            ];
+
+            let sut: EntryPredicate =
+                EntryPredicateBuilder::filter_by_assembly_output(true).build();
+            let result: Vec<Entry> = input.iter().cloned().filter(sut).collect();
+            assert_eq!(result, vec![input[1].clone()]);
+
+            let sut: EntryPredicate =
+                EntryPredicateBuilder::filter_by_assembly_output(false).build();
+            let result: Vec<Entry> = input.iter().cloned().filter(sut).collect();
+            assert_eq!(result, input);
+        }
+
         #[test]
         fn test_duplicate_detection_works() {
             let input: Vec<Entry> = vec![
@@ -212,18 +540,24 @@ mod builder {
                     arguments: vec_of_strings!["cc", "-c", "source.c"],
                     directory: PathBuf::from("/home/user/project"),
                     output: Some(PathBuf::from("/home/user/project/source.o")),
+                    original_compiler: None,
+                    language: None,
                 },
                 Entry {
                     file: PathBuf::from("/home/user/project/source.c"),
                     arguments: vec_of_strings!["cc", "-c", "-Wall", "source.c"],
                     directory: PathBuf::from("/home/user/project"),
                     output: Some(PathBuf::from("/home/user/project/source.o")),
+                    original_compiler: None,
+                    language: None,
                 },
                 Entry {
                     file: PathBuf::from("/home/user/project/source.c"),
                     arguments: vec_of_strings!["cc", "-c", "source.c", "-o", "test.o"],
                     directory: PathBuf::from("/home/user/project"),
                     output: Some(PathBuf::from("/home/user/project/test.o")),
+                    original_compiler: None,
+                    language: None,
                 },
             ];
 
@@ -240,5 +574,36 @@ mod builder {
             let result: Vec<Entry> = input.into_iter().filter(sut).collect();
             assert_eq!(result, expected);
         }
+
+        #[test]
+        fn test_hash_based_detection_matches_exact_detection() {
+            let input: Vec<Entry> = (0..200)
+                .map(|index| Entry {
+                    file: PathBuf::from(format!("/home/user/project/source{}.c", index % 50)),
+                    arguments: vec_of_strings!["cc", "-c", format!("-DINDEX={}", index % 5)],
+                    directory: PathBuf::from("/home/user/project"),
+                    output: None,
+                    original_compiler: None,
+                    language: None,
+                })
+                .collect();
+            let fields = [config::OutputFields::File, config::OutputFields::Arguments];
+
+            let filter =
+                |sut: EntryPredicate| -> Vec<Entry> { input.iter().cloned().filter(sut).collect() };
+            let exact = filter(
+                EntryPredicateBuilder::filter_duplicate_entries(create_key(&fields)).build(),
+            );
+            let hash64 = filter(
+                EntryPredicateBuilder::filter_duplicate_entries(create_hash(&fields)).build(),
+            );
+            let hash128 = filter(
+                EntryPredicateBuilder::filter_duplicate_entries(create_hash128(&fields)).build(),
+            );
+
+            assert_eq!(50, exact.len());
+            assert_eq!(exact, hash64);
+            assert_eq!(exact, hash128);
+        }
     }
 }
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Runs the configured command after the compilation database was written.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Result;
+
+use super::config;
+
+/// Runs the hook command with the path of the compilation database as last argument.
+///
+/// The exit status is logged. The failure of the command is an error only, when the
+/// hook was configured to fail the run.
+pub(super) fn run(hook: &config::PostWriteHook, database: &Path) -> Result<()> {
+    let (program, arguments) = match hook.command.split_first() {
+        Some(command) => command,
+        None => anyhow::bail!("The command of the post write hook is empty."),
+    };
+    let failure = match Command::new(program).args(arguments).arg(database).status() {
+        Ok(status) if status.success() => {
+            log::debug!("Post write hook {:?} finished: {}", hook.command, status);
+            return Ok(());
+        }
+        Ok(status) => format!("Post write hook {:?} failed: {}", hook.command, status),
+        Err(error) => format!("Post write hook {:?} can't be run: {}", hook.command, error),
+    };
+    if hook.fail_on_error {
+        anyhow::bail!(failure);
+    }
+    log::warn!("{}", failure);
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_failing_hook_fails_only_when_configured() {
+        let database = PathBuf::from("compile_commands.json");
+        let hook = |command: Vec<String>, fail_on_error: bool| config::PostWriteHook {
+            command,
+            fail_on_error,
+        };
+
+        let failing = vec_of_strings!["sh", "-c", "exit 3"];
+        assert!(run(&hook(failing.clone(), false), &database).is_ok());
+        assert!(run(&hook(failing, true), &database).is_err());
+        let missing = vec_of_strings!["/nonexistent/hook"];
+        assert!(run(&hook(missing.clone(), false), &database).is_ok());
+        assert!(run(&hook(missing, true), &database).is_err());
+        assert!(run(&hook(vec_of_strings!["true"], true), &database).is_ok());
+    }
+}
@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements merging compiler calls of the same source file.
+//!
+//! When a source file is compiled multiple times with different flags (like
+//! different build configurations), the merged compiler call contains the
+//! union of the flags. This gives the broadest understanding for the tools
+//! which are reading the compilation database.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::semantic;
+
+/// Flags which are taking the next argument as value, even when the value
+/// looks like a flag. (Like `-Xclang -fno-rtti`.)
+const FLAGS_WITH_FLAG_VALUE: [&str; 4] = ["-Xclang", "-Xpreprocessor", "-Xassembler", "-Xlinker"];
+/// Prefix of the flags, which are taking the next argument as value. (Like `-Xarch_arm64 -DARM`.)
+const PREFIX_WITH_FLAG_VALUE: &str = "-Xarch_";
+
+/// Merge the compile passes of the same source file into a single compiler call.
+///
+/// The source files are identified by their absolute path. The first compilation
+/// of the source defines the compiler, the working directory and the output of
+/// the merged compiler call. The flags from the later compilations are appended,
+/// when those were not seen before.
+///
+/// Conflicting macro definitions (like `-DNAME=1` and `-DNAME=2`) are kept both,
+/// but a warning is logged about them.
+pub fn union_flags_by_file(
+    calls: impl Iterator<Item = semantic::CompilerCall>,
+) -> Vec<semantic::CompilerCall> {
+    let mut result: Vec<semantic::CompilerCall> = vec![];
+    let mut index: HashMap<PathBuf, usize> = HashMap::new();
+
+    for call in calls {
+        for pass in call.passes {
+            let (source, output, flags, output_implied) = match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    flags,
+                    output_implied,
+                } => (source, output, flags, output_implied),
+                semantic::CompilerPass::Preprocess => continue,
+            };
+            let key = match super::into_abspath(source.clone(), call.working_dir.as_path()) {
+                Ok(key) => key,
+                Err(_) => source.clone(),
+            };
+            match index.get(&key) {
+                Some(position) => {
+                    if let Some(semantic::CompilerPass::Compile { flags: current, .. }) =
+                        result[*position].passes.first_mut()
+                    {
+                        union_flags(current, &flags, &key);
+                    }
+                }
+                None => {
+                    index.insert(key, result.len());
+                    result.push(semantic::CompilerCall {
+                        compiler: call.compiler.clone(),
+                        working_dir: call.working_dir.clone(),
+                        passes: vec![semantic::CompilerPass::Compile {
+                            source,
+                            output,
+                            flags,
+                            output_implied,
+                        }],
+                        original_compiler: call.original_compiler.clone(),
+                        environment: call.environment.clone(),
+                    });
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Append the flags which are not yet in the current flags.
+fn union_flags(current: &mut Vec<String>, flags: &[String], source: &Path) {
+    let mut current_units = into_units(current);
+    for unit in into_units(flags) {
+        if current_units.contains(&unit) {
+            continue;
+        }
+        if let Some(name) = macro_name(&unit) {
+            let conflicts = current_units
+                .iter()
+                .any(|existing| macro_name(existing) == Some(name));
+            if conflicts {
+                log::warn!(
+                    "Conflicting macro definitions for {:?} are merged for source {:?}",
+                    name,
+                    source
+                );
+            }
+        }
+        current.extend(unit.iter().cloned());
+        current_units.push(unit);
+    }
+}
+
+/// Group the flags into units, where a unit is a flag and its values.
+///
+/// Values are the arguments which are not starting with a dash, and following a flag.
+pub(super) fn into_units(flags: &[String]) -> Vec<Vec<String>> {
+    let mut units: Vec<Vec<String>> = vec![];
+    let mut expect_value = false;
+    for flag in flags {
+        match units.last_mut() {
+            Some(unit) if expect_value || !flag.starts_with('-') => unit.push(flag.clone()),
+            _ => units.push(vec![flag.clone()]),
+        }
+        expect_value = !expect_value
+            && (FLAGS_WITH_FLAG_VALUE.contains(&flag.as_str())
+                || flag.starts_with(PREFIX_WITH_FLAG_VALUE));
+    }
+    units
+}
+
+/// Returns the macro name, if the unit is a macro definition.
+fn macro_name(unit: &[String]) -> Option<&str> {
+    let definition = match unit {
+        [flag] if flag.len() > 2 => flag.strip_prefix("-D"),
+        [flag, value] if flag == "-D" => Some(value.as_str()),
+        _ => None,
+    }?;
+    definition.split('=').next()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_compilations_of_the_same_file_are_merged() {
+        let input = vec![
+            semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from("source.c"),
+                    output: Some(PathBuf::from("debug/source.o")),
+                    flags: vec_of_strings!["-c", "-I", "include", "-DDEBUG", "-DLEVEL=1"],
+                    output_implied: false,
+                }],
+                original_compiler: None,
+                environment: None,
+            },
+            semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from("/home/user/project/source.c"),
+                    output: Some(PathBuf::from("release/source.o")),
+                    flags: vec_of_strings![
+                        "-c",
+                        "-I",
+                        "include",
+                        "-I",
+                        "other",
+                        "-DLEVEL=2",
+                        "-O2"
+                    ],
+                    output_implied: false,
+                }],
+                original_compiler: None,
+                environment: None,
+            },
+            semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from("other.c"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                }],
+                original_compiler: None,
+                environment: None,
+            },
+        ];
+
+        let expected = vec![
+            semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from("source.c"),
+                    output: Some(PathBuf::from("debug/source.o")),
+                    flags: vec_of_strings![
+                        "-c",
+                        "-I",
+                        "include",
+                        "-DDEBUG",
+                        "-DLEVEL=1",
+                        "-I",
+                        "other",
+                        "-DLEVEL=2",
+                        "-O2"
+                    ],
+                    output_implied: false,
+                }],
+                original_compiler: None,
+                environment: None,
+            },
+            semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from("other.c"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                }],
+                original_compiler: None,
+                environment: None,
+            },
+        ];
+
+        assert_eq!(expected, union_flags_by_file(input.into_iter()));
+    }
+
+    #[test]
+    fn test_flags_are_grouped_with_their_values() {
+        let input = vec_of_strings![
+            "-c",
+            "-I",
+            "include",
+            "-Xclang",
+            "-fno-rtti",
+            "-Wall",
+            "-Xarch_arm64",
+            "-DARM"
+        ];
+
+        let expected = vec![
+            vec_of_strings!["-c"],
+            vec_of_strings!["-I", "include"],
+            vec_of_strings!["-Xclang", "-fno-rtti"],
+            vec_of_strings!["-Wall"],
+            vec_of_strings!["-Xarch_arm64", "-DARM"],
+        ];
+
+        assert_eq!(expected, into_units(&input));
+    }
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Read};
 use std::path::{Path, PathBuf};
 
 use super::{args, config, semantic};
@@ -9,8 +10,22 @@ use clang::Entry;
 use path_absolutize::Absolutize;
 use serde_json::Error;
 
+mod canonical;
 pub mod clang;
+mod compile_flags;
+mod dedup;
+mod dependency;
+pub mod diff;
 mod filter;
+mod hook;
+mod merge;
+mod partition;
+pub mod server;
+mod spill;
+mod synthetic;
+pub mod validation;
+
+pub(crate) use filter::compile_globs;
 
 /// Responsible for writing the final compilation database file.
 ///
@@ -22,20 +37,86 @@ mod filter;
 pub struct OutputWriter {
     output: PathBuf,
     append: bool,
+    dry_run: bool,
     filter: config::Filter,
     format: config::Format,
+    dependency_manifest: Option<PathBuf>,
+    compile_flags_files: bool,
+    overwrite_compile_flags_files: bool,
+    json_lines_output: Option<PathBuf>,
+    compiler_databases: config::CompilerDatabases,
+    max_entries_in_memory: Option<usize>,
+    sources_to_include: Vec<regex::Regex>,
+    sources_to_exclude: Vec<regex::Regex>,
+    deduplicate: bool,
+    append_limits: config::AppendLimits,
+    synthetic_outputs: bool,
+    post_write_hook: Option<config::PostWriteHook>,
+    streaming: bool,
+    sort: bool,
+    keep_existing_order: bool,
 }
 
 impl OutputWriter {
     /// Create a new instance of the output writer.
     pub fn configure(args: &args::BuildSemantic, config: &config::Output) -> Result<Self> {
         match config {
-            config::Output::Clang { format, filter, .. } => {
+            config::Output::Clang(clang) => {
+                let config::ClangOutput {
+                    format,
+                    filter,
+                    dependency_manifest,
+                    compile_flags_files,
+                    overwrite_compile_flags_files,
+                    json_lines_output,
+                    compiler_databases,
+                    append,
+                    max_entries_in_memory,
+                    include,
+                    exclude,
+                    deduplicate,
+                    append_limits,
+                    synthetic_outputs,
+                    post_write_hook,
+                    streaming,
+                    sort,
+                    keep_existing_order,
+                    ..
+                } = clang.as_ref();
+                if *streaming && args.append {
+                    anyhow::bail!("The streaming output can't be used with the append option.");
+                }
+                if max_entries_in_memory.is_some() && args.append {
+                    anyhow::bail!(
+                        "The maximum number of entries in memory can't be used with the append option."
+                    );
+                }
+                if max_entries_in_memory.is_some() && args.dry_run {
+                    anyhow::bail!(
+                        "The maximum number of entries in memory can't be used with the dry run option."
+                    );
+                }
                 let result = OutputWriter {
                     output: PathBuf::from(&args.file_name),
-                    append: args.append,
+                    append: args.append || *append,
+                    dry_run: args.dry_run,
                     filter: filter.clone(),
                     format: format.clone(),
+                    dependency_manifest: dependency_manifest.clone(),
+                    compile_flags_files: *compile_flags_files,
+                    overwrite_compile_flags_files: *overwrite_compile_flags_files,
+                    json_lines_output: json_lines_output.clone(),
+                    compiler_databases: compiler_databases.clone(),
+                    max_entries_in_memory: *max_entries_in_memory,
+                    sources_to_include: filter::compile_globs(include)?,
+                    sources_to_exclude: filter::compile_globs(exclude)?,
+                    deduplicate: *deduplicate,
+                    append_limits: append_limits.clone(),
+                    synthetic_outputs: *synthetic_outputs,
+                    post_write_hook: post_write_hook.clone(),
+                    streaming: *streaming,
+                    sort: *sort,
+                    keep_existing_order: *keep_existing_order,
                 };
                 Ok(result)
             }
@@ -50,19 +131,99 @@ impl OutputWriter {
         &self,
         meanings: impl Iterator<Item = semantic::CompilerCall>,
     ) -> anyhow::Result<()> {
-        let entries = meanings.flat_map(|value| {
-            into_entries(value).unwrap_or_else(|error| {
+        let mut manifest = dependency::Manifest::default();
+        let mut compile_flags = compile_flags::CompileFlags::default();
+        let sort_flags = self.format.sort_flags;
+        let meanings = meanings
+            .map(|call| {
+                if sort_flags {
+                    canonical::sort_flags_of_call(call)
+                } else {
+                    call
+                }
+            })
+            .inspect(|call| {
+                if self.dependency_manifest.is_some() {
+                    manifest.add(call)
+                }
+                if self.compile_flags_files {
+                    compile_flags.add(call)
+                }
+            });
+        let result = match self.filter.duplicates.policy {
+            config::DuplicatePolicy::UnionFlags => {
+                let merged = merge::union_flags_by_file(meanings);
+                self.write(
+                    merged
+                        .into_iter()
+                        .flat_map(|call| self.to_entries_logged(call)),
+                )
+            }
+            config::DuplicatePolicy::KeepFirst => {
+                self.write(meanings.flat_map(|call| self.to_entries_logged(call)))
+            }
+        };
+        let result = match &self.dependency_manifest {
+            Some(file_name) if !self.dry_run => result.and_then(|_| manifest.write(file_name)),
+            _ => result,
+        };
+        let result = if self.compile_flags_files && !self.dry_run {
+            result.and_then(|_| compile_flags.write(self.overwrite_compile_flags_files))
+        } else {
+            result
+        };
+        match &self.post_write_hook {
+            Some(hook) if !self.dry_run => result.and_then(|_| hook::run(hook, &self.output)),
+            _ => result,
+        }
+    }
+
+    /// Merge the compiler calls of multiple runs into one compilation database.
+    ///
+    /// The runs are recognized separately (like on the machines of a distributed build),
+    /// and these are written as if the calls were recognized in a single run. The
+    /// duplicates are detected across the runs by the configured policy. (With the keep
+    /// first policy, the earlier runs are taking precedence.)
+    pub fn merge<I>(&self, runs: impl IntoIterator<Item = I>) -> anyhow::Result<()>
+    where
+        I: IntoIterator<Item = semantic::CompilerCall>,
+    {
+        self.run(runs.into_iter().flatten())
+    }
+
+    fn to_entries_logged(&self, value: semantic::CompilerCall) -> Vec<Entry> {
+        let value = match self.format.path_format {
+            config::PathFormat::Absolute => into_absolute_paths(value),
+            config::PathFormat::Relative => Ok(value),
+        };
+        value
+            .and_then(|value| into_formatted_entries(value, &self.format))
+            .unwrap_or_else(|error| {
                 log::error!(
                     "Failed to convert semantic meaning to compilation database entries: {}",
                     error
                 );
                 vec![]
             })
-        });
+    }
+
+    fn write(&self, entries: impl Iterator<Item = Entry>) -> anyhow::Result<()> {
+        // The collisions are found over all entries, these are collected for that.
+        let entries: Box<dyn Iterator<Item = Entry>> = match self.synthetic_outputs {
+            true => Box::new(synthetic::disambiguate_outputs(entries.collect()).into_iter()),
+            false => Box::new(entries),
+        };
+        if self.dry_run {
+            return self.print_diff(entries);
+        }
+        if self.streaming {
+            return self.write_incrementally(entries);
+        }
         if self.append && self.output.exists() {
-            let from_db = Self::read_from_compilation_db(Path::new(&self.output))?;
-            let final_entries = entries.chain(from_db);
-            self.write_into_compilation_db(final_entries)
+            let existing =
+                Self::read_existing_compilation_db(Path::new(&self.output), &self.append_limits)?;
+            let final_entries = merge_with_existing(entries.collect(), existing);
+            self.write_into_compilation_db(final_entries.into_iter())
         } else {
             if self.append {
                 log::warn!("The output file does not exist, the append option is ignored.");
@@ -71,38 +232,307 @@ impl OutputWriter {
         }
     }
 
+    /// Returns true, when the entries are appended to an existing compilation database.
+    ///
+    /// (The output is not empty then, even without new entries.)
+    pub fn is_appending(&self) -> bool {
+        self.append && self.output.exists()
+    }
+
+    /// Print the changes to the existing compilation database, instead of writing it.
+    fn print_diff(&self, entries: impl Iterator<Item = Entry>) -> Result<()> {
+        let existing: Vec<Entry> = if self.output.exists() {
+            Self::read_within_limits(Path::new(&self.output), &self.append_limits)?
+                .map(|content| {
+                    clang::read(content.as_slice())
+                        .flat_map(Self::failed_entry_read_logged)
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let filter = self.create_filter()?;
+        let current: Vec<Entry> = if self.append {
+            merge_with_existing(entries.collect(), existing.clone())
+                .into_iter()
+                .filter(filter)
+                .collect()
+        } else {
+            entries.filter(filter).collect()
+        };
+        let mut current = match self.deduplicate {
+            true => dedup::keep_last(current),
+            false => current,
+        };
+        let order = match self.keep_existing_order {
+            true => Some(order_of(existing.iter().cloned())),
+            false => None,
+        };
+        self.order_entries(&mut current, order.as_ref());
+        diff::Diff::new(existing, current)
+            .print(std::io::stdout().lock())
+            .with_context(|| "Failed to print the changes of the compilation database")
+    }
+
+    /// Create the filter of the source globs and the filter configuration.
+    ///
+    /// The source globs are checked first, to not register the excluded
+    /// entries for the duplicate detection.
+    fn create_filter(&self) -> Result<filter::EntryPredicate> {
+        let mut globs = filter::by_source_globs(&self.sources_to_include, &self.sources_to_exclude);
+        let mut filter: filter::EntryPredicate = TryFrom::try_from(&self.filter)?;
+        Ok(Box::new(move |entry| globs(entry) && filter(entry)))
+    }
+
     fn write_into_compilation_db(&self, entries: impl Iterator<Item = Entry>) -> Result<()> {
+        // The existing order is read before the output is overwritten.
+        let order = self.existing_order();
         // Filter out the entries as per the configuration.
-        let filter: filter::EntryPredicate = TryFrom::try_from(&self.filter)?;
-        let filtered_entries = entries.filter(filter);
+        let filter = self.create_filter()?;
+        // Keep a copy of the entries for the JSON Lines output and the compiler databases.
+        let keep_copies =
+            self.json_lines_output.is_some() || !self.compiler_databases.databases.is_empty();
+        let mut copies: Vec<Entry> = vec![];
+        let filtered_entries = entries.filter(filter).inspect(|entry| {
+            if keep_copies {
+                copies.push(entry.clone())
+            }
+        });
+        match (self.max_entries_in_memory, self.deduplicate) {
+            (Some(limit), deduplicate) => {
+                if order.is_some() {
+                    log::warn!("The existing order is not kept, the spilled entries are sorted.");
+                }
+                let mut spill = spill::Spill::new(limit, &self.output);
+                for entry in filtered_entries {
+                    spill.push(entry)?;
+                }
+                let sorted = spill.into_sorted()?;
+                let failure = sorted.failure();
+                let check = || failure.check();
+                if deduplicate {
+                    self.write_into_combined(dedup::KeepLastOfSorted::new(sorted), check)?;
+                } else {
+                    self.write_into_combined(sorted, check)?;
+                }
+            }
+            (None, true) => {
+                let mut entries = dedup::keep_last(filtered_entries.collect());
+                self.order_entries(&mut entries, order.as_ref());
+                self.write_into_combined(entries.into_iter(), || Ok(()))?;
+            }
+            (None, false) if self.sort || order.is_some() => {
+                let mut entries: Vec<Entry> = filtered_entries.collect();
+                self.order_entries(&mut entries, order.as_ref());
+                self.write_into_combined(entries.into_iter(), || Ok(()))?;
+            }
+            (None, false) => self.write_into_combined(filtered_entries, || Ok(()))?,
+        }
+        let mut copies = match self.deduplicate {
+            true => dedup::keep_last(copies),
+            false => copies,
+        };
+        self.order_entries(&mut copies, order.as_ref());
+        for (file_name, entries) in
+            partition::by_compiler(&self.compiler_databases.databases, &copies)
+        {
+            if let Some(directory) = file_name.parent() {
+                std::fs::create_dir_all(directory)
+                    .with_context(|| format!("Failed to create directory: {:?}", directory))?;
+            }
+            self.write_into_file(&file_name, entries.into_iter())?;
+        }
+        match &self.json_lines_output {
+            Some(file_name) => Self::write_into_json_lines(file_name, copies.into_iter()),
+            None => Ok(()),
+        }
+    }
+
+    /// Read the order of the entries in the existing compilation database, when it was requested.
+    ///
+    /// The missing or unreadable file is not an error, the entries are ordered without it.
+    fn existing_order(&self) -> Option<ExistingOrder> {
+        if !self.keep_existing_order || !self.output.exists() {
+            return None;
+        }
+        match Self::read_from_compilation_db(&self.output) {
+            Ok(entries) => Some(order_of(entries)),
+            Err(error) => {
+                log::warn!(
+                    "Failed to read the order of the existing entries: {}",
+                    error
+                );
+                None
+            }
+        }
+    }
+
+    /// Order the entries by the sort and by the existing order, as those were configured.
+    ///
+    /// The entries of the existing compilation database are taking their position from
+    /// there, the new entries are following them.
+    fn order_entries(&self, entries: &mut [Entry], existing: Option<&ExistingOrder>) {
+        if self.sort {
+            sort_entries(entries);
+        }
+        if let Some(existing) = existing {
+            entries.sort_by_cached_key(|entry| {
+                existing
+                    .get(&order_key(entry))
+                    .copied()
+                    .unwrap_or(usize::MAX)
+            });
+        }
+    }
+
+    /// Write the entries into the output, unless only the compiler databases are requested.
+    ///
+    /// The check is called after the entries were consumed, and its failure keeps the
+    /// existing output. (Like the failed merge of the spilled entries.)
+    fn write_into_combined(
+        &self,
+        entries: impl Iterator<Item = Entry>,
+        check: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        if self.compiler_databases.combined {
+            self.write_into_file_checked(&self.output, entries, check)
+        } else {
+            // The entries are still needed for the compiler databases.
+            entries.for_each(drop);
+            check()
+        }
+    }
+
+    /// Write the entries into the given compilation database file atomically.
+    fn write_into_file(
+        &self,
+        file_name: &Path,
+        entries: impl Iterator<Item = Entry>,
+    ) -> Result<()> {
+        self.write_into_file_checked(file_name, entries, || Ok(()))
+    }
+
+    /// Write the entries into the given compilation database file atomically, when the
+    /// check after the write succeeds. (Otherwise the temporary file is removed.)
+    fn write_into_file_checked(
+        &self,
+        file_name: &Path,
+        entries: impl Iterator<Item = Entry>,
+        check: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        // Write into the target of the symbolic link, to keep the link.
+        let target = resolve_symlink(file_name);
         // Write the entries to a temporary file.
-        self.write_into_temporary_compilation_db(filtered_entries)
+        self.write_into_temporary_compilation_db(&target, entries)
             .and_then(|temp| {
-                // Rename the temporary file to the final output.
-                std::fs::rename(temp.as_path(), self.output.as_path()).with_context(|| {
-                    format!(
-                        "Failed to rename file from '{:?}' to '{:?}'.",
-                        temp.as_path(),
-                        self.output.as_path()
-                    )
-                })
+                if let Err(error) = check() {
+                    let _ = std::fs::remove_file(&temp);
+                    return Err(error);
+                }
+                // Move the temporary file to the final output.
+                persist(&temp, &target, |from, to| std::fs::rename(from, to))
             })
     }
 
+    /// Returns the quoting of the `command` field, as it was configured.
+    fn quoting(&self) -> clang::Quoting {
+        let config::CommandQuoting { quote, always } = &self.format.command_quoting;
+        clang::Quoting {
+            style: match quote {
+                config::Quote::Single => clang::QuoteStyle::Single,
+                config::Quote::Double => clang::QuoteStyle::Double,
+            },
+            always: *always,
+        }
+    }
+
+    /// Write the entries into the output, as those are coming.
+    ///
+    /// The output is written in place (not via a temporary file), to see the entries
+    /// of the running build. The array is closed after the last entry.
+    fn write_incrementally(&self, entries: impl Iterator<Item = Entry>) -> Result<()> {
+        let filter = self.create_filter()?;
+        // Write into the target of the symbolic link, to keep the link.
+        let target = resolve_symlink(&self.output);
+        let file = File::create(&target)
+            .with_context(|| format!("Failed to create file: {:?}", target))?;
+        let mut writer = match self.format.command_as_array {
+            true => clang::IncrementalWriter::new(BufWriter::new(file)),
+            false => {
+                clang::IncrementalWriter::with_quoted_command(BufWriter::new(file), self.quoting())
+            }
+        };
+        for entry in entries.filter(filter) {
+            let entry = match self.format.file_relative_to_directory {
+                true => into_file_relative_to_directory(entry),
+                false => entry,
+            };
+            writer
+                .append_entry(&entry)
+                .with_context(|| format!("Failed to write file: {:?}", target))?;
+        }
+        writer
+            .finish()
+            .map(drop)
+            .with_context(|| format!("Failed to write file: {:?}", target))
+    }
+
+    /// Write the entries into the given file in JSON Lines format.
+    fn write_into_json_lines(file_name: &Path, entries: impl Iterator<Item = Entry>) -> Result<()> {
+        let file = File::create(file_name)
+            .with_context(|| format!("Failed to create file: {:?}", file_name))?;
+        clang::write_lines(BufWriter::new(file), entries)
+            .with_context(|| format!("Failed to write file: {:?}", file_name))
+    }
+
     /// Write the entries to a temporary file and returns the temporary file name.
+    ///
+    /// The temporary file is created next to the target, to be on the same filesystem.
     fn write_into_temporary_compilation_db(
         &self,
+        target: &Path,
         entries: impl Iterator<Item = Entry>,
     ) -> anyhow::Result<PathBuf> {
         // FIXME: Implement entry formatting.
+        let relative = self.format.file_relative_to_directory;
+        let separator = self.format.path_separator;
+        let entries = entries
+            .inspect(move |entry| {
+                if let Some(separator) = separator {
+                    for path in validation::paths_with_other_separator(entry, separator) {
+                        log::warn!(
+                            "Path with the other separator in {:?}: {}",
+                            entry.file,
+                            path
+                        );
+                    }
+                }
+            })
+            .map(move |entry| match relative {
+                true => into_file_relative_to_directory(entry),
+                false => entry,
+            });
 
         // Generate a temporary file name.
-        let file_name = self.output.with_extension("tmp");
+        let file_name = target.with_extension("tmp");
         // Open the file for writing.
         let file = File::create(&file_name)
             .with_context(|| format!("Failed to create file: {:?}", file_name.as_path()))?;
         // Write the entries to the file.
-        clang::write(BufWriter::new(file), entries)?;
+        if self.format.keyed_by_file && !self.format.command_as_array {
+            clang::write_keyed_by_file_with_quoted_command(
+                BufWriter::new(file),
+                entries,
+                self.quoting(),
+            )?;
+        } else if self.format.keyed_by_file {
+            clang::write_keyed_by_file(BufWriter::new(file), entries)?;
+        } else if !self.format.command_as_array {
+            clang::write_with_quoted_command(BufWriter::new(file), entries, self.quoting())?;
+        } else {
+            clang::write(BufWriter::new(file), entries)?;
+        }
         // Return the temporary file name.
         Ok(file_name)
     }
@@ -118,6 +548,84 @@ impl OutputWriter {
         Ok(entries)
     }
 
+    /// Read the existing compilation database to merge the new entries into.
+    ///
+    /// The empty file is read as an empty compilation database, and so the file
+    /// beyond the limits. The malformed file is an error, to not lose the existing
+    /// entries by overwriting it.
+    fn read_existing_compilation_db(
+        source: &Path,
+        limits: &config::AppendLimits,
+    ) -> Result<Vec<Entry>> {
+        let Some(content) = Self::read_within_limits(source, limits)? else {
+            return Ok(vec![]);
+        };
+        if content.iter().all(u8::is_ascii_whitespace) {
+            return Ok(vec![]);
+        }
+        // The relative files are resolved, to match them with the new entries.
+        clang::read(content.as_slice())
+            .map(|entry| entry.map(into_file_with_directory))
+            .collect::<std::result::Result<Vec<Entry>, Error>>()
+            .with_context(|| format!("Failed to read existing compilation database: {:?}", source))
+    }
+
+    /// Read the content of the file, unless it's beyond the limits. (Then it's logged,
+    /// and returns nothing.)
+    ///
+    /// The read (with the size check) is running on a separate thread, when it has a
+    /// timeout. (The thread is left behind at the timeout, it finishes when the read
+    /// returns.)
+    fn read_within_limits(source: &Path, limits: &config::AppendLimits) -> Result<Option<Vec<u8>>> {
+        let max_file_size = limits.max_file_size;
+        let file_name = source.to_path_buf();
+        let read = move || -> Result<Option<Vec<u8>>> {
+            let open = || format!("Failed to open file: {:?}", file_name);
+            let file = File::open(&file_name).with_context(open)?;
+            if let Some(limit) = max_file_size {
+                if file.metadata().with_context(open)?.len() > limit {
+                    return Ok(None);
+                }
+            }
+            let mut content = vec![];
+            match max_file_size {
+                // Read one more byte, to notice when the file has grown since.
+                Some(limit) => (&file).take(limit + 1).read_to_end(&mut content),
+                None => BufReader::new(&file).read_to_end(&mut content),
+            }
+            .with_context(open)?;
+            if max_file_size.is_some_and(|limit| content.len() as u64 > limit) {
+                return Ok(None);
+            }
+            Ok(Some(content))
+        };
+        let content = match limits.read_timeout {
+            Some(timeout) => {
+                let (sender, receiver) = std::sync::mpsc::channel();
+                std::thread::spawn(move || sender.send(read()));
+                match receiver.recv_timeout(std::time::Duration::from_secs(timeout)) {
+                    Ok(result) => result?,
+                    Err(_) => {
+                        log::warn!(
+                            "The existing compilation database is not read in {} seconds, it's treated as empty: {:?}",
+                            timeout,
+                            source
+                        );
+                        return Ok(None);
+                    }
+                }
+            }
+            None => read()?,
+        };
+        if content.is_none() {
+            log::warn!(
+                "The existing compilation database is too large, it's treated as empty: {:?}",
+                source
+            );
+        }
+        Ok(content)
+    }
+
     fn failed_entry_read_logged(candidate: std::result::Result<Entry, Error>) -> Option<Entry> {
         match candidate {
             Ok(entry) => Some(entry),
@@ -130,12 +638,119 @@ impl OutputWriter {
     }
 }
 
+/// Returns the final target of the path, when it's a symbolic link.
+///
+/// Renaming a file onto the symbolic link would replace the link itself.
+fn resolve_symlink(path: &Path) -> PathBuf {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Merge the new entries into the existing ones.
+///
+/// The existing entries with the same directory and file as a new entry are replaced,
+/// the others are kept. The result is sorted by the directory and the file. (The sort
+/// is stable, the entries of the same source keep their order.)
+fn merge_with_existing(current: Vec<Entry>, existing: Vec<Entry>) -> Vec<Entry> {
+    let replaced: HashSet<(PathBuf, PathBuf)> = current
+        .iter()
+        .map(|entry| (entry.directory.clone(), entry.file.clone()))
+        .collect();
+    let mut result: Vec<Entry> = existing
+        .into_iter()
+        .filter(|entry| !replaced.contains(&(entry.directory.clone(), entry.file.clone())))
+        .chain(current)
+        .collect();
+    result.sort_by(|a, b| (&a.directory, &a.file).cmp(&(&b.directory, &b.file)));
+    result
+}
+
+/// The positions of the entries in the existing compilation database, by the file and output.
+type ExistingOrder = HashMap<(PathBuf, Option<PathBuf>), usize>;
+
+/// Returns the positions of the entries. (The first position is kept for the same key.)
+fn order_of(entries: impl Iterator<Item = Entry>) -> ExistingOrder {
+    let mut order = ExistingOrder::new();
+    for (position, entry) in entries.enumerate() {
+        order.entry(order_key(&entry)).or_insert(position);
+    }
+    order
+}
+
+/// Returns the file and the output of the entry, as absolute paths.
+///
+/// The existing and the new entries are matched by it, regardless how the paths
+/// were written into those.
+fn order_key(entry: &Entry) -> (PathBuf, Option<PathBuf>) {
+    let resolve =
+        |path: &PathBuf| into_abspath(path.clone(), &entry.directory).unwrap_or(path.clone());
+    (resolve(&entry.file), entry.output.as_ref().map(resolve))
+}
+
+/// Sort the entries by the file, the directory and the output.
+///
+/// The sort is stable, the entries with the same key are keeping their order.
+fn sort_entries(entries: &mut [Entry]) {
+    entries.sort_by(compare_entries);
+}
+
+/// Compare the entries by the file, the directory and the output.
+fn compare_entries(lhs: &Entry, rhs: &Entry) -> std::cmp::Ordering {
+    (&lhs.file, &lhs.directory, &lhs.output).cmp(&(&rhs.file, &rhs.directory, &rhs.output))
+}
+
+/// Move the temporary file to the target with the given rename function.
+///
+/// The rename is atomic, but it fails when the files are on different devices.
+/// In that case the content is copied to the target, and the temporary file is removed.
+fn persist(
+    temp: &Path,
+    target: &Path,
+    rename: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result<()> {
+    match rename(temp, target) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::CrossesDevices => {
+            log::debug!("Rename failed ({}), copying the file instead.", error);
+            std::fs::copy(temp, target)
+                .and_then(|_| std::fs::remove_file(temp))
+                .with_context(|| {
+                    format!("Failed to copy file from '{:?}' to '{:?}'.", temp, target)
+                })
+        }
+        Err(error) => Err(error)
+            .with_context(|| format!("Failed to rename file from '{:?}' to '{:?}'.", temp, target)),
+    }
+}
+
+/// Converts the compiler call into compilation database entries, one per compile pass.
+///
+/// The `file` field is always an absolute path, while the source in the `arguments`
+/// is kept as it was captured. (Some tools are matching the arguments against the
+/// relative paths of the build graph.)
 pub fn into_entries(value: semantic::CompilerCall) -> Result<Vec<Entry>, anyhow::Error> {
+    into_formatted_entries(value, &config::Format::default())
+}
+
+/// Converts the compiler call into compilation database entries, as the format requires.
+///
+/// The optional fields (the original compiler and the language) are set by each pass.
+fn into_formatted_entries(
+    value: semantic::CompilerCall,
+    format: &config::Format,
+) -> Result<Vec<Entry>, anyhow::Error> {
     let semantic::CompilerCall {
         compiler,
         working_dir,
         passes,
+        original_compiler,
+        ..
     } = value;
+    let original_compiler = original_compiler.filter(|_| format.original_compiler);
     let entries = passes
         .iter()
         .flat_map(|pass| -> Result<Entry, anyhow::Error> {
@@ -147,11 +762,24 @@ pub fn into_entries(value: semantic::CompilerCall) -> Result<Vec<Entry>, anyhow:
                     source,
                     output,
                     flags,
+                    output_implied,
                 } => Ok(Entry {
                     file: into_abspath(source.clone(), working_dir.as_path())?,
                     directory: working_dir.clone(),
                     output: into_abspath_opt(output.clone(), working_dir.as_path())?,
-                    arguments: into_arguments(&compiler, source, output, flags)?,
+                    arguments: into_arguments(
+                        &compiler,
+                        source,
+                        // The implied output is not given to the compiler.
+                        output.as_ref().filter(|_| !output_implied),
+                        flags,
+                        format.strip_dependency_flags,
+                    )?,
+                    original_compiler: original_compiler.clone(),
+                    language: pass
+                        .language()
+                        .filter(|_| format.language)
+                        .map(|language| language.name().to_string()),
                 }),
             }
         })
@@ -160,17 +788,67 @@ pub fn into_entries(value: semantic::CompilerCall) -> Result<Vec<Entry>, anyhow:
     Ok(entries)
 }
 
+/// Converts the relative source and output paths of the compile passes into absolute paths.
+///
+/// The paths are relative to the working directory of the compiler call.
+fn into_absolute_paths(
+    value: semantic::CompilerCall,
+) -> Result<semantic::CompilerCall, anyhow::Error> {
+    let passes = value
+        .passes
+        .into_iter()
+        .map(|pass| match pass {
+            semantic::CompilerPass::Compile {
+                source,
+                output,
+                flags,
+                output_implied,
+            } => Ok(semantic::CompilerPass::Compile {
+                source: into_abspath(source, &value.working_dir)?,
+                output: into_abspath_opt(output, &value.working_dir)?,
+                flags,
+                output_implied,
+            }),
+            semantic::CompilerPass::Preprocess => Ok(pass),
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    Ok(semantic::CompilerCall { passes, ..value })
+}
+
+/// Writes the `file` field relative to the `directory`, when the file is under it.
+fn into_file_relative_to_directory(mut entry: Entry) -> Entry {
+    if let Ok(relative) = entry.file.strip_prefix(&entry.directory) {
+        if entry.directory.is_absolute() && !relative.as_os_str().is_empty() {
+            entry.file = relative.to_path_buf();
+        }
+    }
+    entry
+}
+
+/// Resolves the relative `file` field against the `directory`.
+fn into_file_with_directory(mut entry: Entry) -> Entry {
+    if entry.file.is_relative() {
+        if let Ok(file) = into_abspath(entry.file.clone(), &entry.directory) {
+            entry.file = file;
+        }
+    }
+    entry
+}
+
 fn into_arguments(
     compiler: &PathBuf,
     source: &PathBuf,
-    output: &Option<PathBuf>,
+    output: Option<&PathBuf>,
     flags: &Vec<String>,
+    strip_dependency_flags: bool,
 ) -> Result<Vec<String>, anyhow::Error> {
     let mut arguments: Vec<String> = vec![];
     // Assemble the arguments as it would be for a single source file.
     arguments.push(into_string(&compiler)?);
-    for flag in flags {
-        arguments.push(flag.clone());
+    if strip_dependency_flags {
+        arguments.extend(dependency::without_dependency_flags(flags));
+    } else {
+        arguments.extend(flags.iter().cloned());
     }
     if let Some(file) = output {
         arguments.push(String::from("-o"));
@@ -203,7 +881,8 @@ fn into_string(path: &Path) -> Result<String> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::vec_of_strings;
+    use crate::fixtures::fixtures::capture_warnings;
+    use crate::{vec_of_pathbuf, vec_of_strings};
 
     #[test]
     fn test_non_compilations() -> Result<()> {
@@ -213,6 +892,8 @@ mod test {
             compiler: PathBuf::from("/usr/bin/cc"),
             working_dir: PathBuf::from("/home/user"),
             passes: vec![],
+            original_compiler: None,
+            environment: None,
         };
         let result: Vec<Entry> = into_entries(input)?;
         assert_eq!(empty, result);
@@ -220,6 +901,32 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_preprocess_passes_are_skipped() -> Result<()> {
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![
+                semantic::CompilerPass::Preprocess,
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("source.c"),
+                    output: Some(PathBuf::from("source.s")),
+                    flags: vec_of_strings!["-S"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let result: Vec<Entry> = into_entries(input)?;
+
+        assert_eq!(1, result.len());
+        assert_eq!(PathBuf::from("/home/user/source.c"), result[0].file);
+
+        Ok(())
+    }
+
     #[test]
     fn test_single_source_compilation() -> Result<()> {
         let input = semantic::CompilerCall {
@@ -229,7 +936,10 @@ mod test {
                 source: PathBuf::from("source.c"),
                 output: Some(PathBuf::from("source.o")),
                 flags: vec_of_strings!["-Wall"],
+                output_implied: false,
             }],
+            original_compiler: None,
+            environment: None,
         };
 
         let expected = vec![Entry {
@@ -237,6 +947,8 @@ mod test {
             file: PathBuf::from("/home/user/source.c"),
             arguments: vec_of_strings!["clang", "-Wall", "-o", "source.o", "source.c"],
             output: Some(PathBuf::from("/home/user/source.o")),
+            original_compiler: None,
+            language: None,
         }];
 
         let result: Vec<Entry> = into_entries(input)?;
@@ -257,13 +969,17 @@ mod test {
                     source: PathBuf::from("/tmp/source1.c"),
                     output: None,
                     flags: vec_of_strings!["-Wall"],
+                    output_implied: false,
                 },
                 semantic::CompilerPass::Compile {
                     source: PathBuf::from("../source2.c"),
                     output: None,
                     flags: vec_of_strings!["-Wall"],
+                    output_implied: false,
                 },
             ],
+            original_compiler: None,
+            environment: None,
         };
 
         let expected = vec![
@@ -272,12 +988,1270 @@ mod test {
                 file: PathBuf::from("/tmp/source1.c"),
                 arguments: vec_of_strings!["clang", "-Wall", "/tmp/source1.c"],
                 output: None,
+                original_compiler: None,
+                language: None,
             },
             Entry {
                 directory: PathBuf::from("/home/user"),
                 file: PathBuf::from("/home/source2.c"),
                 arguments: vec_of_strings!["clang", "-Wall", "../source2.c"],
                 output: None,
+                original_compiler: None,
+                language: None,
+            },
+        ];
+
+        let result: Vec<Entry> = into_entries(input)?;
+
+        assert_eq!(expected, result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cross_device_rename_falls_back_to_copy() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-persist-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let temp = directory.join("compile_commands.tmp");
+        let target = directory.join("compile_commands.json");
+        std::fs::write(&temp, "[]")?;
+
+        let result = persist(&temp, &target, |_, _| {
+            Err(std::io::Error::from(std::io::ErrorKind::CrossesDevices))
+        });
+        let content = std::fs::read_to_string(&target);
+        let temp_exists = temp.exists();
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(result.is_ok());
+        assert_eq!("[]", content?);
+        assert!(!temp_exists);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_output_is_kept() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-symlink-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(directory.join("build"))?;
+        let target = directory.join("build/compile_commands.json");
+        let link = directory.join("compile_commands.json");
+        std::fs::write(&target, "[]")?;
+        std::os::unix::fs::symlink(&target, &link)?;
+
+        let writer = OutputWriter::configure(
+            &args::BuildSemantic {
+                file_name: link.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            },
+            &config::Output::default(),
+        )?;
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("source.c"),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let result = writer.run(vec![call].into_iter());
+        let is_symlink = std::fs::symlink_metadata(&link)?.file_type().is_symlink();
+        let content = std::fs::read_to_string(&target);
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(result.is_ok());
+        assert!(is_symlink);
+        assert!(content?.contains("source.c"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_replaces_the_recompiled_entries() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-append-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let entry = |file: &str, flags: &[&str]| Entry {
+            file: PathBuf::from(file),
+            arguments: ["cc"]
+                .iter()
+                .chain(flags)
+                .chain(&[file])
+                .map(|argument| argument.to_string())
+                .collect(),
+            directory: PathBuf::from("/home/user"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+        let writer = OutputWriter::configure(
+            &args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: true,
+                dry_run: false,
+            },
+            &config::Output::default(),
+        )?;
+        let call = |source: &str| semantic::CompilerCall {
+            compiler: PathBuf::from("cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags: vec_of_strings!["-O2"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let read = || -> Result<Vec<Entry>> {
+            OutputWriter::read_existing_compilation_db(&file_name, &config::AppendLimits::default())
+        };
+
+        // The missing or empty file is like a fresh run.
+        writer.run(vec![call("/home/user/b.c")].into_iter())?;
+        let fresh = read()?;
+        std::fs::write(&file_name, "")?;
+        writer.run(vec![call("/home/user/b.c")].into_iter())?;
+        let empty = read()?;
+
+        let existing = vec![
+            entry("/home/user/c.c", &["-g"]),
+            entry("/home/user/b.c", &["-g"]),
+        ];
+        clang::write(File::create(&file_name)?, existing.into_iter())?;
+        writer.run(vec![call("/home/user/b.c"), call("/home/user/a.c")].into_iter())?;
+        let merged = read()?;
+
+        // The malformed file is not overwritten.
+        std::fs::write(&file_name, "[{\"directory\": ")?;
+        let malformed = writer.run(vec![call("/home/user/b.c")].into_iter());
+        let content = std::fs::read_to_string(&file_name)?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(vec![entry("/home/user/b.c", &["-O2"])], fresh);
+        assert_eq!(fresh, empty);
+        assert_eq!(
+            vec![
+                entry("/home/user/a.c", &["-O2"]),
+                entry("/home/user/b.c", &["-O2"]),
+                entry("/home/user/c.c", &["-g"]),
+            ],
+            merged
+        );
+        assert!(malformed.is_err());
+        assert_eq!("[{\"directory\": ", content);
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_skips_the_oversized_existing_file() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-append-limit-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let writer = |max_file_size: Option<u64>| {
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.append_limits.max_file_size = max_file_size;
+                clang.append_limits.read_timeout = Some(60);
+            }
+            let args = args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: true,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let call = |source: &str| semantic::CompilerCall {
+            compiler: PathBuf::from("cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let read = || -> Result<Vec<PathBuf>> {
+            Ok(OutputWriter::read_from_compilation_db(&file_name)?
+                .map(|entry| entry.file)
+                .collect())
+        };
+
+        writer(None)?.run(vec![call("a.c")].into_iter())?;
+        let size = std::fs::metadata(&file_name)?.len();
+        writer(Some(size))?.run(vec![call("b.c")].into_iter())?;
+        let within = read()?;
+        let size = std::fs::metadata(&file_name)?.len();
+        let warnings = capture_warnings(|| {
+            writer(Some(size - 1))
+                .and_then(|sut| sut.run(vec![call("c.c")].into_iter()))
+                .unwrap()
+        });
+        let beyond = read()?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            vec![
+                PathBuf::from("/home/user/a.c"),
+                PathBuf::from("/home/user/b.c")
+            ],
+            within
+        );
+        // The existing file is over the limit, it's treated as empty.
+        assert!(
+            warnings.iter().any(|warning| warning.contains("too large")),
+            "{:?}",
+            warnings
+        );
+        assert_eq!(vec![PathBuf::from("/home/user/c.c")], beyond);
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_globs_are_filtering_the_entries() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-globs-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.include = vec_of_strings!["src/**"];
+            clang.exclude = vec_of_strings!["third_party/**"];
+        }
+        let writer = OutputWriter::configure(
+            &args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            },
+            &config,
+        )?;
+        let call = |source: &str| semantic::CompilerCall {
+            compiler: PathBuf::from("cc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let calls = vec![
+            call("src/main.c"),
+            call("src/lib/util.c"),
+            call("src/third_party/zlib/inflate.c"),
+            call("third_party/zlib/deflate.c"),
+            call("test/main_test.c"),
+        ];
+        writer.run(calls.into_iter())?;
+        let result: Vec<PathBuf> = OutputWriter::read_from_compilation_db(&file_name)?
+            .map(|entry| entry.file)
+            .collect();
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            vec![
+                PathBuf::from("/home/user/project/src/lib/util.c"),
+                PathBuf::from("/home/user/project/src/main.c"),
+            ],
+            result
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_compilations_are_deduplicated() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-dedup-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let writer = |enabled: bool| {
+            let mut config = config::Output::default();
+            // The order of the executions is kept, to see which entry was kept.
+            if let config::Output::Clang(clang) = &mut config {
+                clang.deduplicate = enabled;
+                clang.sort = false;
+            }
+            let args = args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let pass = |source: &str, flag: &str| semantic::CompilerPass::Compile {
+            source: PathBuf::from(source),
+            output: Some(PathBuf::from(source).with_extension("o")),
+            flags: vec_of_strings!["-c", flag],
+            output_implied: false,
+        };
+        let calls = || {
+            vec![semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user"),
+                passes: vec![
+                    pass("a.c", "-O0"),
+                    pass("b.c", "-O0"),
+                    pass("a.c", "-O0"),
+                    pass("a.c", "-O2"),
+                ],
+                original_compiler: None,
+                environment: None,
+            }]
+        };
+        let read = || -> Result<Vec<Vec<String>>> {
+            Ok(OutputWriter::read_from_compilation_db(&file_name)?
+                .map(|entry| entry.arguments)
+                .collect())
+        };
+
+        writer(true)?.run(calls().into_iter())?;
+        let deduplicated = read()?;
+        writer(false)?.run(calls().into_iter())?;
+        let every = read()?;
+        std::fs::remove_dir_all(&directory)?;
+
+        // The last compilation of the same source and output wins.
+        assert_eq!(
+            vec![
+                vec_of_strings!["/usr/bin/cc", "-c", "-O0", "-o", "b.o", "b.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "-O2", "-o", "a.o", "a.c"],
+            ],
+            deduplicated
+        );
+        // Without it, only the identical entries are removed (by the duplicate filter).
+        assert_eq!(
+            vec![
+                vec_of_strings!["/usr/bin/cc", "-c", "-O0", "-o", "a.o", "a.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "-O0", "-o", "b.o", "b.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "-O2", "-o", "a.o", "a.c"],
+            ],
+            every
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_are_sorted_regardless_of_the_order() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-sort-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let args = args::BuildSemantic {
+            file_name: file_name.to_string_lossy().to_string(),
+            append: false,
+            dry_run: false,
+        };
+        let writer = OutputWriter::configure(&args, &config::Output::default())?;
+        let call = |working_dir: &str, source: &str, output: &str| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from(working_dir),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: Some(PathBuf::from(output)),
+                // The arguments are different, to not filter the entries as duplicates.
+                flags: vec_of_strings!["-c", format!("-I{}", working_dir)],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let calls = vec![
+            call("/home/user/b", "/home/user/src/main.c", "main.o"),
+            call("/home/user/a", "/home/user/src/util.c", "util.o"),
+            call("/home/user/c", "/home/user/src/main.c", "main.o"),
+            call("/home/user/a", "/home/user/src/main.c", "main.o"),
+        ];
+        let read = || -> Result<Vec<Entry>> {
+            Ok(OutputWriter::read_from_compilation_db(&file_name)?.collect())
+        };
+
+        writer.run(calls.clone().into_iter())?;
+        let sorted = read()?;
+        writer.run(calls.into_iter().rev())?;
+        let reversed = read()?;
+        std::fs::remove_dir_all(&directory)?;
+
+        let keys: Vec<(&str, &str, &str)> = sorted
+            .iter()
+            .map(|entry| {
+                (
+                    entry.file.to_str().unwrap(),
+                    entry.directory.to_str().unwrap(),
+                    entry.output.as_deref().and_then(Path::to_str).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "/home/user/src/main.c",
+                    "/home/user/a",
+                    "/home/user/a/main.o"
+                ),
+                (
+                    "/home/user/src/main.c",
+                    "/home/user/b",
+                    "/home/user/b/main.o"
+                ),
+                (
+                    "/home/user/src/main.c",
+                    "/home/user/c",
+                    "/home/user/c/main.o"
+                ),
+                (
+                    "/home/user/src/util.c",
+                    "/home/user/a",
+                    "/home/user/a/util.o"
+                ),
+            ],
+            keys
+        );
+        assert_eq!(sorted, reversed);
+
+        // The entries with the same key are keeping their order.
+        let entry = |flag: &str| Entry {
+            file: PathBuf::from("/home/user/src/main.c"),
+            arguments: vec_of_strings!["cc", flag, "-c", "main.c"],
+            directory: PathBuf::from("/home/user"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+        let mut entries = vec![entry("-O2"), entry("-O0"), entry("-O1")];
+        sort_entries(&mut entries);
+        assert_eq!(vec![entry("-O2"), entry("-O0"), entry("-O1")], entries);
+        Ok(())
+    }
+
+    #[test]
+    fn test_existing_order_is_kept() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-order-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let writer = |keep_order: bool| {
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.sort = false;
+                clang.keep_existing_order = keep_order;
+                // The existing entries are matched, however the paths were written.
+                clang.format.file_relative_to_directory = !keep_order;
+            }
+            let args = args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let calls = |sources: &[&str]| -> Vec<semantic::CompilerCall> {
+            sources
+                .iter()
+                .map(|source| semantic::CompilerCall {
+                    compiler: PathBuf::from("/usr/bin/cc"),
+                    working_dir: PathBuf::from("/home/user"),
+                    passes: vec![semantic::CompilerPass::Compile {
+                        source: PathBuf::from(source),
+                        output: Some(PathBuf::from(source).with_extension("o")),
+                        flags: vec_of_strings!["-c"],
+                        output_implied: false,
+                    }],
+                    original_compiler: None,
+                    environment: None,
+                })
+                .collect()
+        };
+        let read = || -> Result<Vec<PathBuf>> {
+            Ok(OutputWriter::read_from_compilation_db(&file_name)?
+                .map(|entry| entry.file)
+                .collect())
+        };
+
+        // The existing database was not sorted.
+        writer(false)?.run(calls(&["z.c", "a.c", "m.c"]).into_iter())?;
+        writer(true)?.run(calls(&["new.c", "m.c", "a.c", "z.c"]).into_iter())?;
+        let result = read()?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            vec_of_pathbuf![
+                "/home/user/z.c",
+                "/home/user/a.c",
+                "/home/user/m.c",
+                "/home/user/new.c"
+            ],
+            result
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_existing_order_matches_the_relative_paths() {
+        let entry = |file: &str, output: &str| Entry {
+            file: PathBuf::from(file),
+            arguments: vec_of_strings!["cc", "-c", file],
+            directory: PathBuf::from("/home/user"),
+            output: Some(PathBuf::from(output)),
+            original_compiler: None,
+            language: None,
+        };
+        let order = order_of(vec![entry("./src/../a.c", "objs/a.o")].into_iter());
+
+        assert_eq!(
+            Some(&0),
+            order.get(&order_key(&entry("/home/user/a.c", "/home/user/objs/a.o")))
+        );
+    }
+
+    #[test]
+    fn test_merge_of_multiple_runs() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-merge-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let writer = |policy: config::DuplicatePolicy| {
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.filter.duplicates.policy = policy;
+                clang.deduplicate = false;
+            }
+            let args = args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let call = |source: &str, flags: Vec<String>| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags,
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let runs = || {
+            vec![
+                vec![
+                    call("a.c", vec_of_strings!["-c"]),
+                    call("b.c", vec_of_strings!["-c"]),
+                ],
+                vec![
+                    call("b.c", vec_of_strings!["-c"]),
+                    call("b.c", vec_of_strings!["-c", "-DREMOTE"]),
+                    call("c.c", vec_of_strings!["-c"]),
+                ],
+            ]
+        };
+        let read = || -> Result<Vec<Vec<String>>> {
+            Ok(OutputWriter::read_from_compilation_db(&file_name)?
+                .map(|entry| entry.arguments)
+                .collect())
+        };
+
+        writer(config::DuplicatePolicy::KeepFirst)?.merge(runs())?;
+        let kept = read()?;
+        writer(config::DuplicatePolicy::UnionFlags)?.merge(runs())?;
+        let united = read()?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            vec![
+                vec_of_strings!["/usr/bin/cc", "-c", "a.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "b.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "-DREMOTE", "b.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "c.c"],
+            ],
+            kept
+        );
+        assert_eq!(
+            vec![
+                vec_of_strings!["/usr/bin/cc", "-c", "a.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "-DREMOTE", "b.c"],
+                vec_of_strings!["/usr/bin/cc", "-c", "c.c"],
+            ],
+            united
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dependency_flags_are_stripped() -> Result<()> {
+        let writer = |strip_dependency_flags: bool| {
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.format.strip_dependency_flags = strip_dependency_flags;
+            }
+            let args = args::BuildSemantic {
+                file_name: "compile_commands.json".to_string(),
+                append: false,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("/home/user/project/foo.c"),
+                output: None,
+                flags: vec_of_strings!["-c", "-MD", "-MF", "foo.d", "-MT", "foo.o", "-Wall"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let stripped = writer(true)?.to_entries_logged(call.clone());
+        assert_eq!(
+            vec_of_strings!["/usr/bin/cc", "-c", "-Wall", "/home/user/project/foo.c"],
+            stripped[0].arguments
+        );
+        // The flags are kept by default.
+        let kept = writer(false)?.to_entries_logged(call);
+        assert_eq!(
+            vec_of_strings![
+                "/usr/bin/cc",
+                "-c",
+                "-MD",
+                "-MF",
+                "foo.d",
+                "-MT",
+                "foo.o",
+                "-Wall",
+                "/home/user/project/foo.c"
+            ],
+            kept[0].arguments
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_absolute_path_format() -> Result<()> {
+        let writer = |path_format: config::PathFormat| {
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.format.path_format = path_format;
+            }
+            let args = args::BuildSemantic {
+                file_name: "compile_commands.json".to_string(),
+                append: false,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user/project/build"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("../common/./x.c"),
+                output: Some(PathBuf::from("objs/x.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = Entry {
+            file: PathBuf::from("/home/user/project/common/x.c"),
+            arguments: vec_of_strings![
+                "/usr/bin/cc",
+                "-c",
+                "-o",
+                "/home/user/project/build/objs/x.o",
+                "/home/user/project/common/x.c"
+            ],
+            directory: PathBuf::from("/home/user/project/build"),
+            output: Some(PathBuf::from("/home/user/project/build/objs/x.o")),
+            original_compiler: None,
+            language: None,
+        };
+        let absolute = writer(config::PathFormat::Absolute)?.to_entries_logged(call.clone());
+        assert_eq!(vec![expected.clone()], absolute);
+
+        // The relative format keeps the arguments as those were given.
+        let relative = writer(config::PathFormat::Relative)?.to_entries_logged(call);
+        assert_eq!(
+            vec![Entry {
+                arguments: vec_of_strings![
+                    "/usr/bin/cc",
+                    "-c",
+                    "-o",
+                    "objs/x.o",
+                    "../common/./x.c"
+                ],
+                ..expected
+            }],
+            relative
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_post_write_hook_is_run_after_the_write() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-post-write-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let output = directory.join("compile_commands.json");
+        let copy = directory.join("copy.json");
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.post_write_hook = Some(config::PostWriteHook {
+                command: vec_of_strings![
+                    "sh",
+                    "-c",
+                    format!("cp \"$1\" {}", copy.display()),
+                    "hook"
+                ],
+                fail_on_error: true,
+            });
+        }
+        let args = args::BuildSemantic {
+            file_name: output.to_string_lossy().to_string(),
+            append: false,
+            dry_run: false,
+        };
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("main.c"),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let result = OutputWriter::configure(&args, &config)?.run(vec![call].into_iter());
+        // The hook sees the written database, which is given as the argument.
+        let copied = OutputWriter::read_from_compilation_db(&copy)
+            .map(|entries| entries.map(|entry| entry.file).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(result.is_ok());
+        assert_eq!(vec![PathBuf::from("/home/user/main.c")], copied?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_output() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-streaming-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let output = directory.join("compile_commands.json");
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.streaming = true;
+            clang.deduplicate = false;
+        }
+        let args = args::BuildSemantic {
+            file_name: output.to_string_lossy().to_string(),
+            append: false,
+            dry_run: false,
+        };
+        let call = |source: &str| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let calls = vec![call("a.c"), call("b.c"), call("c.c")];
+
+        let result = OutputWriter::configure(&args, &config)?.run(calls.into_iter());
+        let written = OutputWriter::read_from_compilation_db(&output)
+            .map(|entries| entries.map(|entry| entry.file).collect::<Vec<_>>());
+        let with_append = OutputWriter::configure(
+            &args::BuildSemantic {
+                append: true,
+                ..args
+            },
+            &config,
+        );
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            vec_of_pathbuf!["/home/user/a.c", "/home/user/b.c", "/home/user/c.c"],
+            written?
+        );
+        assert!(with_append.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_source_glob_is_rejected() {
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.include = vec_of_strings!["src/[a-z"];
+        }
+        let args = args::BuildSemantic {
+            file_name: "compile_commands.json".to_string(),
+            append: false,
+            dry_run: false,
+        };
+        assert!(OutputWriter::configure(&args, &config).is_err());
+    }
+
+    #[test]
+    fn test_max_entries_in_memory_is_rejected_on_dry_run() {
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.max_entries_in_memory = Some(1000);
+        }
+        let args = |dry_run: bool| args::BuildSemantic {
+            file_name: "compile_commands.json".to_string(),
+            append: false,
+            dry_run,
+        };
+        assert!(OutputWriter::configure(&args(true), &config).is_err());
+        assert!(OutputWriter::configure(&args(false), &config).is_ok());
+    }
+
+    #[test]
+    fn test_compiler_databases_are_written() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-compiler-dbs-{}", rand::random::<u64>()));
+        let combined = directory.join("compile_commands.json");
+        let config = config::Output::Clang(Box::new(config::ClangOutput {
+            compilers: vec![],
+            filter: config::Filter::default(),
+            format: config::Format::default(),
+            source_mappings: vec![],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec![],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: 16,
+            json_lines_output: None,
+            compiler_databases: config::CompilerDatabases {
+                databases: vec![
+                    config::CompilerDatabase {
+                        compiler: PathBuf::from("gcc"),
+                        directory: directory.join("host"),
+                    },
+                    config::CompilerDatabase {
+                        compiler: PathBuf::from("arm-none-eabi-gcc"),
+                        directory: directory.join("target"),
+                    },
+                ],
+                combined: false,
+            },
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: config::AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: config::CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+        let writer = OutputWriter::configure(
+            &args::BuildSemantic {
+                file_name: combined.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            },
+            &config,
+        )?;
+        let call = |compiler: &str, source: &str| semantic::CompilerCall {
+            compiler: PathBuf::from(compiler),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from(source),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let calls = vec![
+            call("/usr/bin/gcc", "host.c"),
+            call("/opt/arm/bin/arm-none-eabi-gcc", "target.c"),
+        ];
+        let result = writer.run(calls.into_iter());
+        let read = |file_name: PathBuf| -> Result<Vec<PathBuf>> {
+            let entries = OutputWriter::read_from_compilation_db(&file_name)?;
+            Ok(entries.map(|entry| entry.file).collect())
+        };
+        let host = read(directory.join("host").join(partition::FILE_NAME));
+        let target = read(directory.join("target").join(partition::FILE_NAME));
+        let combined_exists = combined.exists();
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(result.is_ok());
+        assert_eq!(vec![PathBuf::from("/home/user/host.c")], host?);
+        assert_eq!(vec![PathBuf::from("/home/user/target.c")], target?);
+        assert!(!combined_exists);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unusual_characters_are_preserved() -> Result<()> {
+        use semantic::Interpreter;
+
+        let execution = crate::intercept::Execution {
+            executable: PathBuf::from("/usr/bin/cc"),
+            arguments: vec_of_strings!["cc", "-DMESSAGE=\"a\tb  c\"", "-c", "tab\tname.c"],
+            environment: std::collections::HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+        let call = match semantic::interpreters::Builder::new()
+            .build()
+            .recognize(&execution)
+        {
+            semantic::Recognition::Success(call) => call,
+            _ => return Err(anyhow!("execution is not recognized")),
+        };
+        let entries = into_entries(call)?;
+
+        let mut serialized: Vec<u8> = vec![];
+        clang::write(&mut serialized, entries.into_iter())?;
+        let result = clang::read(serialized.as_slice()).collect::<Result<Vec<_>, _>>()?;
+
+        let expected = vec_of_strings!["/usr/bin/cc", "-DMESSAGE=\"a\tb  c\"", "-c", "tab\tname.c"];
+        assert_eq!(1, result.len());
+        assert_eq!(expected, result[0].arguments);
+        assert_eq!(PathBuf::from("/home/user/tab\tname.c"), result[0].file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_relative_to_directory() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-relative-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.format.file_relative_to_directory = true;
+            clang.append = true;
+        }
+        let args = args::BuildSemantic {
+            file_name: file_name.to_string_lossy().to_string(),
+            append: false,
+            dry_run: false,
+        };
+        let sut = OutputWriter::configure(&args, &config)?;
+        let pass = |source: &str| semantic::CompilerPass::Compile {
+            source: PathBuf::from(source),
+            output: None,
+            flags: vec_of_strings!["-c"],
+            output_implied: false,
+        };
+        let calls = || {
+            vec![semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/cc"),
+                working_dir: PathBuf::from("/home/user/project"),
+                passes: vec![pass("src/main.c"), pass("../common/util.c")],
+                original_compiler: None,
+                environment: None,
+            }]
+        };
+        let read = || -> Result<Vec<PathBuf>> {
+            Ok(OutputWriter::read_from_compilation_db(&file_name)?
+                .map(|entry| entry.file)
+                .collect())
+        };
+
+        sut.run(calls().into_iter())?;
+        let written = read()?;
+        // The relative files of the existing entries are matching the new ones.
+        sut.run(calls().into_iter())?;
+        let mut appended = read()?;
+        appended.sort();
+        std::fs::remove_dir_all(&directory)?;
+
+        let expected = vec![
+            PathBuf::from("/home/user/common/util.c"),
+            PathBuf::from("src/main.c"),
+        ];
+        assert_eq!(expected, written);
+        assert_eq!(expected, appended);
+        Ok(())
+    }
+
+    #[test]
+    fn test_language_field_is_written() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-language-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let file_name = directory.join("compile_commands.json");
+        let writer = |language: bool| -> Result<OutputWriter> {
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.format.language = language;
+            }
+            let args = args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            };
+            OutputWriter::configure(&args, &config)
+        };
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/c++"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("main.cpp"),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        writer(true)?.run(vec![call.clone()].into_iter())?;
+        let content = std::fs::read_to_string(&file_name)?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(content.contains(r#""language":"c++""#), "{}", content);
+        // The field is not written by default.
+        let entries = writer(false)?.to_entries_logged(call);
+        assert_eq!(None, entries[0].language);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_language_field_is_set_by_the_pass() -> Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.format.language = true;
+        }
+        let args = args::BuildSemantic {
+            file_name: String::from("compile_commands.json"),
+            append: false,
+            dry_run: true,
+        };
+        let writer = OutputWriter::configure(&args, &config)?;
+        // The output of the first pass can't be written into the arguments.
+        let call = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/c++"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("first.c"),
+                    output: Some(PathBuf::from(std::ffi::OsStr::from_bytes(b"\xff.o"))),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("second.cpp"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let entries = writer.to_entries_logged(call);
+
+        assert_eq!(1, entries.len());
+        assert_eq!(
+            PathBuf::from("/home/user/project/second.cpp"),
+            entries[0].file
+        );
+        assert_eq!(Some(String::from("c++")), entries[0].language);
+        Ok(())
+    }
+
+    #[test]
+    fn test_original_compiler_field_is_written_on_request() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-original-compiler-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let write = |original_compiler: bool| -> Result<serde_json::Value> {
+            let output = directory.join(format!("{}.json", original_compiler));
+            let mut config = config::Output::default();
+            if let config::Output::Clang(clang) = &mut config {
+                clang.format.original_compiler = original_compiler;
+            }
+            let args = args::BuildSemantic {
+                file_name: output.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            };
+            let call = semantic::CompilerCall {
+                compiler: PathBuf::from("/usr/bin/gcc"),
+                working_dir: PathBuf::from("/home/user"),
+                passes: vec![semantic::CompilerPass::Compile {
+                    source: PathBuf::from("main.c"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                }],
+                original_compiler: Some(String::from("cc")),
+                environment: None,
+            };
+            OutputWriter::configure(&args, &config)?.run(vec![call].into_iter())?;
+            Ok(serde_json::from_slice(&std::fs::read(&output)?)?)
+        };
+
+        let requested = write(true);
+        let not_requested = write(false);
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            Some(&serde_json::Value::from("cc")),
+            requested?[0].get("original_compiler")
+        );
+        assert_eq!(None, not_requested?[0].get("original_compiler"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_the_file_field_is_absolute() -> Result<()> {
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("cc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("src/main.c"),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let result: Vec<Entry> = into_entries(input)?;
+
+        assert_eq!(1, result.len());
+        assert_eq!(
+            PathBuf::from("/home/user/project/src/main.c"),
+            result[0].file
+        );
+        assert_eq!(
+            vec_of_strings!["cc", "-c", "src/main.c"],
+            result[0].arguments
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_into_not_existing_directory() -> Result<()> {
+        let working_dir =
+            std::env::temp_dir().join(format!("bear-not-existing-{}", rand::random::<u64>()));
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("cc"),
+            working_dir: working_dir.clone(),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("src/../foo.c"),
+                output: Some(PathBuf::from("build/a/./b/../b/foo.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        // The paths are made absolute lexically, the directories are not accessed.
+        let result: Vec<Entry> = into_entries(input)?;
+
+        assert_eq!(1, result.len());
+        assert_eq!(working_dir.join("foo.c"), result[0].file);
+        assert_eq!(Some(working_dir.join("build/a/b/foo.o")), result[0].output);
+        assert!(!working_dir.exists());
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_compilation_with_drive_absolute_paths() -> Result<()> {
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from(r"C:\mingw\bin\gcc.exe"),
+            working_dir: PathBuf::from(r"C:\build"),
+            passes: vec![
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from(r"D:\src\foo.cpp"),
+                    output: Some(PathBuf::from("foo.o")),
+                    flags: vec_of_strings![r"-IC:\include", "-c"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from(r"src\bar.cpp"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = vec![
+            Entry {
+                directory: PathBuf::from(r"C:\build"),
+                file: PathBuf::from(r"D:\src\foo.cpp"),
+                arguments: vec_of_strings![
+                    r"C:\mingw\bin\gcc.exe",
+                    r"-IC:\include",
+                    "-c",
+                    "-o",
+                    "foo.o",
+                    r"D:\src\foo.cpp"
+                ],
+                output: Some(PathBuf::from(r"C:\build\foo.o")),
+                original_compiler: None,
+                language: None,
+            },
+            Entry {
+                directory: PathBuf::from(r"C:\build"),
+                file: PathBuf::from(r"C:\build\src\bar.cpp"),
+                arguments: vec_of_strings![r"C:\mingw\bin\gcc.exe", "-c", r"src\bar.cpp"],
+                output: None,
+                original_compiler: None,
+                language: None,
             },
         ];
 
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Partitions the entries of the compilation database by the compiler.
+//!
+//! The compiler of an entry is the first argument of it. Each entry goes into
+//! the partition of the first matching compiler, the others are not partitioned.
+
+use std::path::{Path, PathBuf};
+
+use super::clang::Entry;
+use super::config;
+
+/// The name of the compilation database file in the partition directories.
+pub(super) const FILE_NAME: &str = "compile_commands.json";
+
+/// Returns the entries of the partitions, in the order of the configured databases.
+///
+/// The partitions without entries are also returned, to not leave stale files
+/// from previous runs.
+pub(super) fn by_compiler(
+    databases: &[config::CompilerDatabase],
+    entries: &[Entry],
+) -> Vec<(PathBuf, Vec<Entry>)> {
+    let mut partitions: Vec<(PathBuf, Vec<Entry>)> = databases
+        .iter()
+        .map(|database| (database.directory.join(FILE_NAME), vec![]))
+        .collect();
+    for entry in entries {
+        let compiler = match entry.arguments.first() {
+            Some(compiler) => Path::new(compiler),
+            None => continue,
+        };
+        let position = databases
+            .iter()
+            .position(|database| is_matching(&database.compiler, compiler));
+        if let Some(position) = position {
+            partitions[position].1.push(entry.clone());
+        }
+    }
+    partitions
+}
+
+/// A compiler given without directory matches by the file name.
+fn is_matching(expected: &Path, compiler: &Path) -> bool {
+    if expected.components().count() == 1 {
+        compiler.file_name() == Some(expected.as_os_str())
+    } else {
+        compiler == expected
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_entries_are_partitioned_by_compiler() {
+        let entry = |compiler: &str, file: &str| Entry {
+            file: PathBuf::from(file),
+            arguments: vec_of_strings![compiler, "-c", file],
+            directory: PathBuf::from("/home/user"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+        let entries = vec![
+            entry("/usr/bin/gcc", "host.c"),
+            entry("/opt/arm/bin/arm-none-eabi-gcc", "target.c"),
+            entry("/usr/bin/clang", "other.c"),
+            entry("/usr/local/bin/gcc", "local.c"),
+        ];
+        let databases = vec![
+            config::CompilerDatabase {
+                compiler: PathBuf::from("/usr/bin/gcc"),
+                directory: PathBuf::from("build/host"),
+            },
+            config::CompilerDatabase {
+                compiler: PathBuf::from("arm-none-eabi-gcc"),
+                directory: PathBuf::from("build/target"),
+            },
+        ];
+
+        let expected = vec![
+            (
+                PathBuf::from("build/host/compile_commands.json"),
+                vec![entry("/usr/bin/gcc", "host.c")],
+            ),
+            (
+                PathBuf::from("build/target/compile_commands.json"),
+                vec![entry("/opt/arm/bin/arm-none-eabi-gcc", "target.c")],
+            ),
+        ];
+        assert_eq!(expected, by_compiler(&databases, &entries));
+    }
+}
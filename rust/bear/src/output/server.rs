@@ -0,0 +1,356 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements a query server of the compilation database entries.
+//!
+//! The server keeps the entries in memory, and answers the queries of the clients
+//! (like editor integrations) over a local TCP socket. The entries are loaded at
+//! the first query (not when the server starts), and these can be reloaded later,
+//! after a new build produced new events.
+//!
+//! The protocol is request/response: the client sends a request, and the server
+//! answers it with a response. The messages are framed the same way as the events
+//! of the reporters, the JSON serialized message is preceded by its length (as a
+//! 4 byte big-endian integer). The client can send multiple requests on the same
+//! connection, until it closes it. The size of the messages is limited, the longer
+//! request closes the connection.
+//!
+//! The connections are served on separate threads, an idle client is not blocking
+//! the others.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::clang::Entry;
+
+/// The maximum size of a request in bytes. (The requests are small, like a file name.)
+const MAX_REQUEST_SIZE: usize = 1 << 20;
+
+/// The maximum size of a response in bytes.
+const MAX_RESPONSE_SIZE: usize = 1 << 30;
+
+/// Represents the queries of the clients.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "request")]
+pub enum Request {
+    /// Get the entries of the source file. (The file is compared with the `file`
+    /// field of the entries, and with the absolute form of it.)
+    #[serde(rename = "command")]
+    Command { file: PathBuf },
+    /// List the source files of the entries.
+    #[serde(rename = "files")]
+    Files,
+    /// Load the entries again. (Like after a new build.)
+    #[serde(rename = "reload")]
+    Reload,
+}
+
+/// Represents the answers of the server.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "response")]
+pub enum Response {
+    /// The entries of the queried source file. (Empty, when it's not compiled.)
+    #[serde(rename = "entries")]
+    Entries { entries: Vec<Entry> },
+    /// The source files of the entries, in the order of the entries.
+    #[serde(rename = "files")]
+    Files { files: Vec<PathBuf> },
+    /// The number of the entries, after those were reloaded.
+    #[serde(rename = "reloaded")]
+    Reloaded { count: usize },
+    /// The request failed, with the reason of it.
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+/// The source of the entries. (Like the recognition of the events of a build.)
+pub type Loader = Box<dyn Fn() -> Result<Vec<Entry>> + Send>;
+
+/// Represents the query server of the compilation database entries.
+pub struct QueryServer {
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+    listener: TcpListener,
+    address: SocketAddr,
+}
+
+/// The entries of the server, which are shared between the connections.
+struct State {
+    loader: Loader,
+    entries: Option<Vec<Entry>>,
+}
+
+impl QueryServer {
+    /// Creates a new query server of the entries, which are given by the loader.
+    ///
+    /// The server listens on a random port of the loopback interface. The address
+    /// of the server can be obtained by the `address` method.
+    pub fn new(loader: Loader) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        Ok(QueryServer {
+            state: Arc::new(Mutex::new(State {
+                loader,
+                entries: None,
+            })),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            listener,
+            address,
+        })
+    }
+
+    /// Returns the address of the server, in the format of `ip:port`.
+    pub fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    /// Returns the handle to stop the server (from another thread).
+    pub fn stopper(&self) -> Stopper {
+        Stopper {
+            shutdown: self.shutdown.clone(),
+            address: self.address,
+        }
+    }
+
+    /// Answers the queries of the clients, until the server is stopped.
+    ///
+    /// Each connection is served on its own thread. (These are not joined, the
+    /// connections are left open at the stop.)
+    pub fn serve(&self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            // This has to be the first thing to do, in order to implement the stop method!
+            if self.shutdown.load(Ordering::Relaxed) {
+                break;
+            }
+            match stream {
+                Ok(connection) => {
+                    let state = self.state.clone();
+                    std::thread::spawn(move || {
+                        if let Err(error) = serve_connection(&state, connection) {
+                            log::warn!("Query connection failed: {}", error);
+                        }
+                    });
+                }
+                Err(error) => {
+                    log::error!("Query server failed: {}", error);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the response to the request.
+    pub fn answer(&self, request: Request) -> Response {
+        lock(&self.state).answer(request)
+    }
+}
+
+/// Answers the requests of the connection, until the client closes it.
+fn serve_connection(state: &Mutex<State>, mut connection: TcpStream) -> Result<()> {
+    loop {
+        let request = match read_message::<Request>(&mut connection, MAX_REQUEST_SIZE) {
+            Ok(request) => request,
+            Err(error) if is_end_of_stream(&error) => break,
+            Err(error) => return Err(error),
+        };
+        log::debug!("Query request: {:?}", request);
+        let response = lock(state).answer(request);
+        write_message(&mut connection, &response)?;
+    }
+    Ok(())
+}
+
+/// Lock the state. (The failed loader is not stopping the other connections.)
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl State {
+    /// Returns the response to the request.
+    fn answer(&mut self, request: Request) -> Response {
+        let result = match request {
+            Request::Reload => self.reload().map(|count| Response::Reloaded { count }),
+            Request::Command { file } => self.with_entries(|entries| Response::Entries {
+                entries: entries
+                    .iter()
+                    .filter(|entry| entry.file == file || entry.directory.join(&entry.file) == file)
+                    .cloned()
+                    .collect(),
+            }),
+            Request::Files => self.with_entries(|entries| Response::Files {
+                files: entries.iter().map(|entry| entry.file.clone()).collect(),
+            }),
+        };
+        result.unwrap_or_else(|error| Response::Error {
+            message: error.to_string(),
+        })
+    }
+
+    /// Calls the function with the entries. (These are loaded at the first call.)
+    fn with_entries<F>(&mut self, function: F) -> Result<Response>
+    where
+        F: FnOnce(&[Entry]) -> Response,
+    {
+        if self.entries.is_none() {
+            self.reload()?;
+        }
+        Ok(function(self.entries.as_deref().unwrap_or_default()))
+    }
+
+    /// Load the entries, and returns the number of those.
+    fn reload(&mut self) -> Result<usize> {
+        let entries = (self.loader)()?;
+        let count = entries.len();
+        self.entries = Some(entries);
+        Ok(count)
+    }
+}
+
+/// Stops the query server, by flipping the shutdown flag and connecting to it.
+///
+/// (The connection unblocks the `accept` call, to see the shutdown flag.)
+pub struct Stopper {
+    shutdown: Arc<AtomicBool>,
+    address: SocketAddr,
+}
+
+impl Stopper {
+    pub fn stop(&self) -> Result<()> {
+        self.shutdown.store(true, Ordering::Relaxed);
+        let _ = TcpStream::connect(self.address)?;
+        Ok(())
+    }
+}
+
+/// Sends the request to the query server, and returns the response of it.
+pub fn query(connection: &mut TcpStream, request: &Request) -> Result<Response> {
+    write_message(connection, request)?;
+    read_message(connection, MAX_RESPONSE_SIZE)
+}
+
+/// Read a message, which is preceded by its length. (The longer message is an error.)
+fn read_message<T: DeserializeOwned>(reader: &mut impl Read, limit: usize) -> Result<T> {
+    let mut length_bytes = [0; 4];
+    reader.read_exact(&mut length_bytes)?;
+    let length = u32::from_be_bytes(length_bytes) as usize;
+    if length > limit {
+        anyhow::bail!("The message is too long: {} bytes", length);
+    }
+
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer)?;
+    Ok(serde_json::from_slice(buffer.as_ref())?)
+}
+
+/// Write a message, which is preceded by its length.
+fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    Ok(writer.flush()?)
+}
+
+fn is_end_of_stream(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|error| error.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_command_of_file_is_queried() -> Result<()> {
+        let entry = |file: &str| Entry {
+            file: PathBuf::from(file),
+            arguments: vec_of_strings!["cc", "-c", file],
+            directory: PathBuf::from("/home/user/project"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+        let entries = vec![entry("/home/user/project/main.c"), entry("util.c")];
+        let loaded = entries.clone();
+        let sut = QueryServer::new(Box::new(move || Ok(loaded.clone())))?;
+        let address = sut.address();
+        let stopper = sut.stopper();
+
+        let client = std::thread::spawn(move || -> Result<Vec<Response>> {
+            let mut connection = TcpStream::connect(address)?;
+            let requests = [
+                Request::Command {
+                    file: PathBuf::from("/home/user/project/main.c"),
+                },
+                Request::Command {
+                    file: PathBuf::from("/home/user/project/util.c"),
+                },
+                Request::Command {
+                    file: PathBuf::from("/home/user/project/other.c"),
+                },
+            ];
+            let responses = requests
+                .iter()
+                .map(|request| query(&mut connection, request))
+                .collect();
+            drop(connection);
+            stopper.stop()?;
+            responses
+        });
+        sut.serve()?;
+        let responses = client.join().expect("client thread panicked")?;
+
+        let expected = vec![
+            Response::Entries {
+                entries: vec![entries[0].clone()],
+            },
+            Response::Entries {
+                entries: vec![entries[1].clone()],
+            },
+            Response::Entries { entries: vec![] },
+        ];
+        assert_eq!(expected, responses);
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_client_is_not_blocking_the_others() -> Result<()> {
+        let sut = QueryServer::new(Box::new(|| Ok(vec![])))?;
+        let address = sut.address();
+        let stopper = sut.stopper();
+
+        let client = std::thread::spawn(move || -> Result<Response> {
+            let idle = TcpStream::connect(&address)?;
+            let mut connection = TcpStream::connect(&address)?;
+            let response = query(&mut connection, &Request::Files);
+            stopper.stop()?;
+            drop(idle);
+            response
+        });
+        sut.serve()?;
+        let response = client.join().expect("client thread panicked")?;
+
+        assert_eq!(Response::Files { files: vec![] }, response);
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_message_is_rejected() {
+        let mut message: Vec<u8> = u32::MAX.to_be_bytes().to_vec();
+        message.extend_from_slice(b"{}");
+
+        let result = read_message::<Request>(&mut message.as_slice(), MAX_REQUEST_SIZE);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements sorting the entries with bounded memory.
+//!
+//! The entries are collected in memory until the limit is reached. Then the
+//! collected entries are sorted and written into a temporary file (a run).
+//! At the end, the runs are merged into a single sorted sequence. (This is
+//! known as external merge sort.)
+//!
+//! The entries are sorted by the file, the directory and the output. (The same
+//! as the entries which are not spilled.) The sort is stable,
+//! the entries of the same source are keeping their original order.
+//!
+//! A run which can't be read (like a removed or corrupted temporary file) stops
+//! the merge. The error of it is kept, to be checked after the entries were
+//! consumed. (The output of the incomplete merge shall not be used.)
+
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+
+use super::clang::{self, Entry};
+use super::compare_entries;
+
+/// Collects the entries, and spills them into temporary files over the limit.
+pub(super) struct Spill {
+    limit: usize,
+    prefix: PathBuf,
+    chunk: Vec<Entry>,
+    runs: Vec<PathBuf>,
+}
+
+impl Spill {
+    /// Creates a new instance, which keeps at most `limit` entries in memory.
+    ///
+    /// The temporary files are named after the given prefix. (Like the output
+    /// file name, to create them on the same filesystem.)
+    pub(super) fn new(limit: usize, prefix: &Path) -> Self {
+        Spill {
+            limit,
+            prefix: prefix.to_path_buf(),
+            chunk: Vec::with_capacity(limit),
+            runs: vec![],
+        }
+    }
+
+    pub(super) fn push(&mut self, entry: Entry) -> Result<()> {
+        self.chunk.push(entry);
+        if self.chunk.len() >= self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the sorted entries of all runs.
+    pub(super) fn into_sorted(mut self) -> Result<Merge> {
+        self.chunk.sort_by(compare_entries);
+        let mut runs = vec![];
+        for file_name in std::mem::take(&mut self.runs) {
+            let file = File::open(&file_name)
+                .with_context(|| format!("Failed to open file: {:?}", file_name))?;
+            let name = file_name.clone();
+            let entries = serde_json::Deserializer::from_reader(BufReader::new(file))
+                .into_iter::<Entry>()
+                .map(move |entry| {
+                    entry.with_context(|| format!("Failed to read spilled entries: {:?}", name))
+                });
+            runs.push(Run {
+                file_name: Some(file_name),
+                entries: Box::new(entries),
+            });
+        }
+        // The entries in memory are the latest ones, these are merged as the last run.
+        runs.push(Run {
+            file_name: None,
+            entries: Box::new(std::mem::take(&mut self.chunk).into_iter().map(Ok)),
+        });
+        Ok(Merge::new(runs))
+    }
+
+    /// Sort the entries in memory, and write them into a new run.
+    fn spill(&mut self) -> Result<()> {
+        self.chunk.sort_by(compare_entries);
+        let mut file_name = self.prefix.clone().into_os_string();
+        file_name.push(format!(".spill.{}", self.runs.len()));
+        let file_name = PathBuf::from(file_name);
+        let file = File::create(&file_name)
+            .with_context(|| format!("Failed to create file: {:?}", file_name))?;
+        // Register the run first, to remove the file even when the write fails.
+        self.runs.push(file_name.clone());
+        clang::write_lines(BufWriter::new(file), self.chunk.drain(..))
+            .with_context(|| format!("Failed to write file: {:?}", file_name))?;
+        log::debug!("Entries are spilled into {:?}", file_name);
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        for file_name in &self.runs {
+            remove_run(file_name);
+        }
+    }
+}
+
+/// A sorted sequence of entries. The temporary file (if any) is removed with it.
+struct Run {
+    file_name: Option<PathBuf>,
+    entries: Box<dyn Iterator<Item = Result<Entry>>>,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        if let Some(file_name) = &self.file_name {
+            remove_run(file_name);
+        }
+    }
+}
+
+/// The head entry of a run in the merge.
+///
+/// The ordering is reversed by the `BinaryHeap`, and the earlier runs are
+/// preceding the later ones with the same key, to keep the sort stable.
+struct Head {
+    entry: Entry,
+    run: usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_entries(&self.entry, &other.entry).then(self.run.cmp(&other.run))
+    }
+}
+
+/// The failure of the merge, which is checked after the entries were consumed.
+#[derive(Clone, Default)]
+pub(super) struct Failure(Rc<RefCell<Option<anyhow::Error>>>);
+
+impl Failure {
+    /// Returns the error, which has stopped the merge.
+    pub(super) fn check(&self) -> Result<()> {
+        match self.0.borrow_mut().take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn set(&self, error: anyhow::Error) {
+        self.0.borrow_mut().get_or_insert(error);
+    }
+}
+
+/// Merges the sorted runs into a single sorted sequence.
+pub(super) struct Merge {
+    runs: Vec<Run>,
+    heads: BinaryHeap<Reverse<Head>>,
+    failure: Failure,
+    failed: bool,
+}
+
+impl Merge {
+    fn new(runs: Vec<Run>) -> Self {
+        let mut merge = Merge {
+            runs,
+            heads: BinaryHeap::new(),
+            failure: Failure::default(),
+            failed: false,
+        };
+        for run in 0..merge.runs.len() {
+            if let Some(entry) = merge.next_of(run) {
+                merge.heads.push(Reverse(Head { entry, run }));
+            }
+        }
+        merge
+    }
+
+    /// Returns the handle to check the failure of the merge.
+    pub(super) fn failure(&self) -> Failure {
+        self.failure.clone()
+    }
+
+    /// Returns the next entry of the run. A read failure stops the merge.
+    fn next_of(&mut self, run: usize) -> Option<Entry> {
+        match self.runs[run].entries.next()? {
+            Ok(entry) => Some(entry),
+            Err(error) => {
+                self.failure.set(error);
+                self.failed = true;
+                None
+            }
+        }
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let Reverse(Head { entry, run }) = self.heads.pop()?;
+        if let Some(next) = self.next_of(run) {
+            self.heads.push(Reverse(Head { entry: next, run }));
+        }
+        Some(entry)
+    }
+}
+
+fn remove_run(file_name: &Path) {
+    if let Err(error) = std::fs::remove_file(file_name) {
+        log::debug!("Failed to remove file {:?}: {}", file_name, error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_spilled_entries_are_sorted_like_in_memory() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-spill-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        // Synthetic input with repeated sources, to check the sort is stable.
+        let entries: Vec<Entry> = (0..1000)
+            .map(|index| Entry {
+                file: PathBuf::from(format!("/home/user/src/file{}.c", (index * 7919) % 311)),
+                arguments: vec_of_strings!["cc", "-c", format!("-DINDEX={}", index)],
+                directory: PathBuf::from(format!("/home/user/build{}", index % 3)),
+                output: None,
+                original_compiler: None,
+                language: None,
+            })
+            .collect();
+
+        let mut sut = Spill::new(64, &directory.join("compile_commands.json"));
+        for entry in entries.iter().cloned() {
+            sut.push(entry)?;
+        }
+        let spilled = std::fs::read_dir(&directory)?.count();
+        let result: Vec<Entry> = sut.into_sorted()?.collect();
+        let remained = std::fs::read_dir(&directory)?.count();
+        std::fs::remove_dir_all(&directory)?;
+
+        let mut expected = entries;
+        expected.sort_by(compare_entries);
+        assert_eq!(15, spilled);
+        assert_eq!(expected, result);
+        assert_eq!(0, remained);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_run_fails_the_merge() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-spill-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let entry = |index: usize| Entry {
+            file: PathBuf::from(format!("/home/user/src/file{}.c", index)),
+            arguments: vec_of_strings!["cc", "-c", format!("file{}.c", index)],
+            directory: PathBuf::from("/home/user/build"),
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+
+        let mut sut = Spill::new(2, &directory.join("compile_commands.json"));
+        for index in 0..5 {
+            sut.push(entry(index))?;
+        }
+        std::fs::write(&sut.runs[1], "{ \"file\": ")?;
+        let merge = sut.into_sorted()?;
+        let failure = merge.failure();
+        let result: Vec<Entry> = merge.collect();
+        let checked = failure.check();
+        std::fs::remove_dir_all(&directory)?;
+
+        assert!(result.len() < 5);
+        assert!(checked.is_err());
+        Ok(())
+    }
+}
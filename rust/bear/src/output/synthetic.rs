@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements the synthetic outputs of the entries.
+//!
+//! The compilers are writing the object file into the working directory, when
+//! the output is not given. Sources with the same name from different directories
+//! are then having the same output. (Like `src/foo.c` and `test/foo.c`, which are
+//! both compiled into `foo.o`.) These colliding outputs are replaced with names,
+//! which have the hash of the source directory in it. (Like `foo.3f2a9c1e.o`.)
+//! The hash is computed from the source path, to be stable between runs.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::clang::Entry;
+
+/// Replaces the outputs, which are missing or shared by different sources.
+///
+/// The entries of the same source with the same output are not colliding.
+/// (Those are the same compilation, and the deduplication is handling them.)
+pub(super) fn disambiguate_outputs(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut sources: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
+    for entry in &entries {
+        if let Some(output) = &entry.output {
+            sources
+                .entry(output.clone())
+                .or_default()
+                .insert(entry.file.clone());
+        }
+    }
+    entries
+        .into_iter()
+        .map(|mut entry| {
+            let colliding = match &entry.output {
+                Some(output) => sources.get(output).is_some_and(|files| files.len() > 1),
+                None => true,
+            };
+            if colliding {
+                let output = synthetic_output(&entry);
+                log::debug!("Output of {:?} is replaced with {:?}", entry.file, output);
+                entry.output = Some(output);
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Returns the output name with the hash of the source directory.
+///
+/// The synthetic output is placed next to the original output. The entries
+/// without output get an object file in the working directory.
+fn synthetic_output(entry: &Entry) -> PathBuf {
+    let hash = fnv1a(entry.file.parent().unwrap_or(Path::new("")));
+    let (directory, stem, extension) = match &entry.output {
+        Some(output) => (
+            output.parent().map(Path::to_path_buf).unwrap_or_default(),
+            output.file_stem(),
+            output.extension(),
+        ),
+        None => (
+            entry.directory.clone(),
+            entry.file.file_stem(),
+            Some("o".as_ref()),
+        ),
+    };
+    let mut file_name = stem.unwrap_or_default().to_os_string();
+    file_name.push(format!(".{:08x}", hash as u32));
+    if let Some(extension) = extension {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    directory.join(file_name)
+}
+
+/// The FNV-1a hash of the path. (It does not depend on the platform or the
+/// version of the standard library, unlike the default hasher.)
+fn fnv1a(path: &Path) -> u64 {
+    path.to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    fn entry(file: &str, output: Option<&str>) -> Entry {
+        Entry {
+            file: PathBuf::from(file),
+            arguments: vec_of_strings!["cc", "-c", file],
+            directory: PathBuf::from("/home/user/build"),
+            output: output.map(PathBuf::from),
+            original_compiler: None,
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_colliding_outputs_are_replaced() {
+        let input = vec![
+            entry("/home/user/src/foo.c", Some("/home/user/build/foo.o")),
+            entry("/home/user/test/foo.c", Some("/home/user/build/foo.o")),
+            entry("/home/user/src/bar.c", Some("/home/user/build/bar.o")),
+            entry("/home/user/src/bar.c", Some("/home/user/build/bar.o")),
+            entry("/home/user/src/baz.c", None),
+        ];
+
+        let result = disambiguate_outputs(input.clone());
+
+        let outputs: Vec<PathBuf> = result
+            .iter()
+            .map(|entry| entry.output.clone().unwrap())
+            .collect();
+        // The colliding outputs are distinct, and the others are not changed.
+        assert_ne!(outputs[0], outputs[1]);
+        assert_eq!(Some(Path::new("/home/user/build")), outputs[0].parent());
+        assert_eq!(Some("o".as_ref()), outputs[1].extension());
+        assert_eq!(input[2].output, Some(outputs[2].clone()));
+        assert_eq!(outputs[2], outputs[3]);
+        assert_eq!(Some(Path::new("/home/user/build")), outputs[4].parent());
+        // The arguments are kept, and the names are stable.
+        assert_eq!(input[0].arguments, result[0].arguments);
+        assert_eq!(result, disambiguate_outputs(input));
+    }
+}
@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Implements consistency checks over the final compilation database entries.
+//!
+//! The duplicate filter and the merge policies are supposed to produce entries
+//! with unique keys. The check here is independent of those, and can detect
+//! when a policy failed to do so.
+//!
+//! The paths of the entries can be checked against the expected path separator,
+//! to detect the paths which were not normalized.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::clang::Entry;
+use super::config;
+
+/// Represents entries which are sharing the same key.
+///
+/// The entries are referred by their position in the validated entry list.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub positions: Vec<usize>,
+}
+
+/// Find the entries which are not unique by the given key fields.
+///
+/// Returns the conflicts in the order of their first appearance.
+pub fn find_conflicts(entries: &[Entry], fields: &[config::OutputFields]) -> Vec<Conflict> {
+    let mut positions: HashMap<Key, usize> = HashMap::new();
+    let mut conflicts: Vec<Conflict> = vec![];
+
+    for (position, entry) in entries.iter().enumerate() {
+        let key = Key::new(entry, fields);
+        match positions.get(&key) {
+            Some(first) => match conflicts
+                .iter_mut()
+                .find(|conflict| conflict.positions.first() == Some(first))
+            {
+                Some(conflict) => conflict.positions.push(position),
+                None => conflicts.push(Conflict {
+                    positions: vec![*first, position],
+                }),
+            },
+            None => {
+                positions.insert(key, position);
+            }
+        }
+    }
+    conflicts
+}
+
+/// The flags which are taking a path as value. (Glued or as the next argument.)
+///
+/// The longer flags are preceding the shorter ones with the same prefix.
+const PATH_FLAGS: [&str; 9] = [
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-include",
+    "-imacros",
+    "--sysroot=",
+    "-I",
+    "-L",
+    "-o",
+];
+
+/// Returns the paths of the entry, which are containing the other separator.
+///
+/// The `file`, `directory` and `output` fields are checked, and the values of the
+/// path flags in the arguments.
+pub fn paths_with_other_separator(entry: &Entry, style: config::PathSeparator) -> Vec<String> {
+    let other = match style {
+        config::PathSeparator::Forward => '\\',
+        config::PathSeparator::Backward => '/',
+    };
+    let mut paths: Vec<String> = [
+        Some(&entry.file),
+        Some(&entry.directory),
+        entry.output.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| path.to_string_lossy().to_string())
+    .collect();
+    let mut arguments = entry.arguments.iter().skip(1);
+    while let Some(argument) = arguments.next() {
+        let flag = PATH_FLAGS.iter().find(|flag| argument.starts_with(*flag));
+        match flag {
+            Some(flag) if argument == flag => paths.extend(arguments.next().cloned()),
+            Some(flag) => paths.push(argument[flag.len()..].to_string()),
+            None => {}
+        }
+    }
+    paths.retain(|path| path.contains(other));
+    paths
+}
+
+/// The key of an entry, which contains only the selected fields.
+#[derive(Hash, PartialEq, Eq)]
+struct Key<'a> {
+    directory: Option<&'a Path>,
+    file: Option<&'a Path>,
+    arguments: Option<&'a [String]>,
+    output: Option<Option<&'a Path>>,
+}
+
+impl<'a> Key<'a> {
+    fn new(entry: &'a Entry, fields: &[config::OutputFields]) -> Self {
+        let selected = |field: config::OutputFields| fields.contains(&field);
+        Key {
+            directory: selected(config::OutputFields::Directory)
+                .then_some(entry.directory.as_path()),
+            file: selected(config::OutputFields::File).then_some(entry.file.as_path()),
+            arguments: selected(config::OutputFields::Arguments)
+                .then_some(entry.arguments.as_slice()),
+            output: selected(config::OutputFields::Output).then_some(entry.output.as_deref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::filter::EntryPredicate;
+    use super::*;
+    use crate::config::Validate;
+    use crate::vec_of_strings;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_dedup_by_file_leaves_no_conflicts() {
+        let input = entries();
+
+        let config = config::Filter {
+            source: config::SourceFilter::default(),
+            duplicates: config::DuplicateFilter {
+                by_fields: vec![config::OutputFields::File],
+                policy: config::DuplicatePolicy::KeepFirst,
+                compare_by: config::DuplicateComparison::default(),
+                dedup_key: None,
+            },
+            keep_null_outputs: false,
+            keep_assembly_outputs: false,
+        };
+        let filter: EntryPredicate = TryFrom::try_from(&config).unwrap();
+        let filtered: Vec<Entry> = input.into_iter().filter(filter).collect();
+
+        assert_eq!(
+            Vec::<Conflict>::new(),
+            find_conflicts(&filtered, &[config::OutputFields::File])
+        );
+    }
+
+    #[test]
+    fn test_dedup_keys_leave_no_conflicts() {
+        let mut input = entries();
+        input.push(input[0].clone());
+
+        for (key, expected_count) in [
+            (config::DuplicateKey::File, 2),
+            (config::DuplicateKey::FileOutput, 3),
+            (config::DuplicateKey::FullCommand, 3),
+        ] {
+            let duplicates = config::DuplicateFilter {
+                by_fields: vec![],
+                policy: config::DuplicatePolicy::KeepFirst,
+                compare_by: config::DuplicateComparison::default(),
+                dedup_key: Some(key.clone()),
+            };
+            let config = config::Filter {
+                duplicates: duplicates.validate().unwrap(),
+                ..config::Filter::default()
+            };
+            let filter: EntryPredicate = TryFrom::try_from(&config).unwrap();
+            let filtered: Vec<Entry> = input.iter().cloned().filter(filter).collect();
+
+            assert_eq!(expected_count, filtered.len(), "{:?}", key);
+            assert_eq!(
+                Vec::<Conflict>::new(),
+                find_conflicts(&filtered, &key.fields())
+            );
+        }
+    }
+
+    #[test]
+    fn test_duplicates_are_reported() {
+        let input = entries();
+
+        let expected = vec![Conflict {
+            positions: vec![0, 2],
+        }];
+        assert_eq!(
+            expected,
+            find_conflicts(&input, &[config::OutputFields::File])
+        );
+        assert_eq!(
+            Vec::<Conflict>::new(),
+            find_conflicts(
+                &input,
+                &[config::OutputFields::File, config::OutputFields::Output]
+            )
+        );
+    }
+
+    #[test]
+    fn test_residual_backslash_is_detected() {
+        let entry = Entry {
+            file: PathBuf::from("C:/project/source.c"),
+            arguments: vec_of_strings![
+                "cc",
+                "-c",
+                "-IC:/sdk/include",
+                "-IC:/sdk\\shared\\include",
+                "-isystem",
+                "C:\\vendor",
+                "-DPATTERN=a\\b",
+                "C:/project/source.c"
+            ],
+            directory: PathBuf::from("C:/project"),
+            output: Some(PathBuf::from("C:/project/source.o")),
+            original_compiler: None,
+            language: None,
+        };
+
+        assert_eq!(
+            vec_of_strings!["C:/sdk\\shared\\include", "C:\\vendor"],
+            paths_with_other_separator(&entry, config::PathSeparator::Forward)
+        );
+        // The fields and the include paths with forward slashes.
+        assert_eq!(
+            5,
+            paths_with_other_separator(&entry, config::PathSeparator::Backward).len()
+        );
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                file: PathBuf::from("/home/user/project/source.c"),
+                arguments: vec_of_strings!["cc", "-c", "source.c"],
+                directory: PathBuf::from("/home/user/project"),
+                output: Some(PathBuf::from("/home/user/project/source.o")),
+                original_compiler: None,
+                language: None,
+            },
+            Entry {
+                file: PathBuf::from("/home/user/project/other.c"),
+                arguments: vec_of_strings!["cc", "-c", "other.c"],
+                directory: PathBuf::from("/home/user/project"),
+                output: None,
+                original_compiler: None,
+                language: None,
+            },
+            Entry {
+                file: PathBuf::from("/home/user/project/source.c"),
+                arguments: vec_of_strings!["cc", "-c", "-O2", "source.c", "-o", "release.o"],
+                directory: PathBuf::from("/home/user/project"),
+                output: Some(PathBuf::from("/home/user/project/release.o")),
+                original_compiler: None,
+                language: None,
+            },
+        ]
+    }
+}
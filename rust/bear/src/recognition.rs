@@ -5,11 +5,39 @@
 //! The recognition logic is implemented in the `interpreters` module.
 //! Here we only handle the errors and logging them to the console.
 
-use super::{config, intercept, semantic};
+use super::{config, intercept, output, semantic};
+use path_absolutize::Absolutize;
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 
 pub struct Recognition {
     interpreter: Box<dyn semantic::Interpreter>,
+    allowed_compilers: Vec<PathBuf>,
+    strict: bool,
+    self_check: bool,
+    environment: Option<Vec<String>>,
+    empty_output: config::EmptyOutput,
+    errors: RefCell<Vec<String>>,
+    counts: RefCell<Counts>,
+}
+
+/// The number of the executions by the recognition results.
+#[derive(Debug, Default)]
+struct Counts {
+    recognized: usize,
+    not_allowed: usize,
+    ignored: usize,
+    failed: usize,
+    unknown: usize,
+}
+
+impl Counts {
+    fn executions(&self) -> usize {
+        self.recognized + self.not_allowed + self.ignored + self.failed + self.unknown
+    }
 }
 
 impl TryFrom<&config::Main> for Recognition {
@@ -28,21 +56,103 @@ impl TryFrom<&config::Main> for Recognition {
             config::Intercept::Wrapper { executables, .. } => executables.clone(),
             _ => vec![],
         };
+        let compiler_launchers = match &config.intercept {
+            config::Intercept::Wrapper { launchers, .. } => launchers.clone(),
+            _ => vec![],
+        };
         let compilers_to_exclude = match &config.output {
-            config::Output::Clang { compilers, .. } => compilers
-                .into_iter()
+            config::Output::Clang(clang) => clang
+                .compilers
+                .iter()
                 .filter(|compiler| compiler.ignore == config::Ignore::Always)
                 .map(|compiler| compiler.path.clone())
                 .collect(),
             _ => vec![],
         };
-        let interpreter = semantic::interpreters::Builder::new()
+        let compilers_with_inputs: Vec<(PathBuf, Vec<String>)> = match &config.output {
+            config::Output::Clang(clang) => clang
+                .compilers
+                .iter()
+                .filter(|compiler| !compiler.input_flags.is_empty())
+                .map(|compiler| (compiler.path.clone(), compiler.input_flags.clone()))
+                .collect(),
+            _ => vec![],
+        };
+        let compiler_wrappers = match &config.output {
+            config::Output::Clang(clang) => clang
+                .compiler_wrappers
+                .iter()
+                .map(|wrapper| {
+                    Regex::new(&wrapper.compiler)
+                        .map(|compiler| (wrapper.executable.clone(), compiler))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => vec![],
+        };
+        let hoist_leading_flags = match &config.output {
+            config::Output::Clang(clang) => clang.hoist_leading_flags,
+            _ => false,
+        };
+        let response_file_depth = match &config.output {
+            config::Output::Clang(clang) => clang.response_file_depth,
+            _ => 0,
+        };
+        let keep_ambiguous_headers = match &config.output {
+            config::Output::Clang(clang) => clang.keep_ambiguous_headers,
+            _ => false,
+        };
+        let expand_environment = match &config.output {
+            config::Output::Clang(clang) => clang.expand_environment,
+            _ => false,
+        };
+        let allowed_compilers = match &config.output {
+            config::Output::Clang(clang) => clang.allowed_compilers.clone(),
+            _ => vec![],
+        };
+        let (patterns_to_include, patterns_to_exclude) = match &config.output {
+            config::Output::Clang(clang) => (
+                compile_compiler_patterns(&clang.compiler_patterns.include)?,
+                compile_compiler_patterns(&clang.compiler_patterns.exclude)?,
+            ),
+            _ => (vec![], vec![]),
+        };
+        let mut builder = semantic::interpreters::Builder::new();
+        if let config::Intercept::Wrapper {
+            path, directory, ..
+        } = &config.intercept
+        {
+            builder = builder.own_wrapper(path, directory);
+        }
+        for (compiler, inputs) in &compilers_with_inputs {
+            builder =
+                builder.compilers_to_recognize_with_inputs(std::slice::from_ref(compiler), inputs);
+        }
+        let interpreter = builder
             .compilers_to_recognize(compilers_to_include.as_slice())
             .compilers_to_exclude(compilers_to_exclude.as_slice())
+            .compilers_to_recognize_matching(patterns_to_include.as_slice())
+            .compilers_to_exclude_matching(patterns_to_exclude.as_slice())
+            .compiler_wrappers(compiler_wrappers.as_slice())
+            .compiler_launchers(compiler_launchers.as_slice())
+            .hoist_leading_flags(hoist_leading_flags)
+            .keep_ambiguous_headers(keep_ambiguous_headers)
+            .expand_environment(expand_environment)
+            .response_file_depth(response_file_depth)
+            .preferred_interpreter(config.preferred_interpreter.as_deref())
             .build();
 
         Ok(Recognition {
             interpreter: Box::new(interpreter),
+            allowed_compilers,
+            strict: config.strict,
+            self_check: config.self_check,
+            environment: config.recognition_environment.clone(),
+            empty_output: config.empty_output.unwrap_or(match config.strict {
+                true => config::EmptyOutput::Error,
+                false => config::EmptyOutput::Write,
+            }),
+            errors: RefCell::new(vec![]),
+            counts: RefCell::new(Counts::default()),
         })
     }
 }
@@ -51,8 +161,28 @@ impl Recognition {
     /// Simple call the semantic module to recognize the execution.
     /// Forward only the compiler calls, and log each recognition result.
     pub fn apply(&self, execution: intercept::Execution) -> Option<semantic::CompilerCall> {
+        let execution = self.restrict_environment(execution);
+        let mut counts = self.counts.borrow_mut();
         match self.interpreter.recognize(&execution) {
-            semantic::Recognition::Success(semantic) => {
+            semantic::Recognition::Success(mut semantic) => {
+                semantic.compiler = resolve_compiler(&semantic.compiler, &execution);
+                if !self.is_allowed(&semantic) {
+                    counts.not_allowed += 1;
+                    log::warn!(
+                        "compiler call dropped, compiler is not allowed, {:?} : {:?}",
+                        semantic.compiler,
+                        execution
+                    );
+                    self.errors.borrow_mut().push(format!(
+                        "compiler is not allowed : {:?}",
+                        execution.arguments
+                    ));
+                    return None;
+                }
+                counts.recognized += 1;
+                // Keep the first argument as it was captured, before any rewriting.
+                semantic.original_compiler = execution.arguments.first().cloned();
+                semantic.environment = Some(execution.environment.clone());
                 log::debug!(
                     "execution recognized as compiler call, {:?} : {:?}",
                     semantic,
@@ -61,21 +191,540 @@ impl Recognition {
                 Some(semantic)
             }
             semantic::Recognition::Ignored => {
+                counts.ignored += 1;
                 log::debug!("execution recognized, but ignored: {:?}", execution);
                 None
             }
             semantic::Recognition::Error(reason) => {
+                counts.failed += 1;
                 log::debug!(
                     "execution recognized with failure, {:?} : {:?}",
                     reason,
                     execution
                 );
+                self.errors
+                    .borrow_mut()
+                    .push(format!("{} : {:?}", reason, execution.arguments));
                 None
             }
             semantic::Recognition::Unknown => {
+                counts.unknown += 1;
                 log::debug!("execution not recognized: {:?}", execution);
                 None
             }
         }
     }
+
+    /// Recognize the execution, and report which interpreter produced the result.
+    ///
+    /// This is for debugging purposes, it does not record the recognition errors.
+    pub fn apply_with_attribution(
+        &self,
+        execution: &intercept::Execution,
+    ) -> (
+        semantic::Recognition<semantic::CompilerCall>,
+        &'static str,
+        String,
+    ) {
+        let execution = self.restrict_environment(execution.clone());
+        self.interpreter.recognize_with_attribution(&execution)
+    }
+
+    /// Remove the environment variables, which the recognition is not allowed to read.
+    fn restrict_environment(&self, mut execution: intercept::Execution) -> intercept::Execution {
+        if let Some(allowed) = &self.environment {
+            execution
+                .environment
+                .retain(|key, _| allowed.iter().any(|name| name == key));
+        }
+        execution
+    }
+
+    /// Check the compiler against the allowlist. (Empty allowlist allows all compilers.)
+    ///
+    /// The compiler of the call is the resolved one. (See `CompilerCall::is_compiler`
+    /// for the matching.)
+    fn is_allowed(&self, call: &semantic::CompilerCall) -> bool {
+        self.allowed_compilers.is_empty()
+            || self
+                .allowed_compilers
+                .iter()
+                .any(|allowed| call.is_compiler(allowed))
+    }
+
+    /// Check the compiler call is recognized the same way, as it will be written.
+    ///
+    /// The entries of the compiler call are recognized again, and the entries of the
+    /// recognized compiler calls must be the same. The mismatches are logged, and
+    /// recorded as recognition errors. (Only in self check mode.)
+    pub fn check_round_trip(&self, call: &semantic::CompilerCall) {
+        if !self.self_check {
+            return;
+        }
+        let entries = match output::into_entries(call.clone()) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries {
+            let execution = intercept::Execution {
+                executable: call.compiler.clone(),
+                arguments: entry.arguments.clone(),
+                working_dir: entry.directory.clone(),
+                environment: HashMap::new(),
+            };
+            let round_trip = match self.interpreter.recognize(&execution) {
+                semantic::Recognition::Success(recognized) => output::into_entries(recognized).ok(),
+                _ => None,
+            };
+            if round_trip.as_deref() != Some(std::slice::from_ref(&entry)) {
+                log::warn!(
+                    "entry is not recognized the same way, {:?} : {:?}",
+                    entry,
+                    round_trip
+                );
+                self.errors.borrow_mut().push(format!(
+                    "entry is not recognized the same way : {:?}",
+                    entry.arguments
+                ));
+            }
+        }
+    }
+
+    /// Check the recognition errors after all executions were processed.
+    ///
+    /// In strict mode, any recognition error makes this check fail, with the
+    /// summary of the failed executions. Otherwise, the errors are tolerated.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        let errors = self.errors.borrow();
+        if self.strict && !errors.is_empty() {
+            anyhow::bail!(
+                "{} execution(s) failed to be recognized:\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the recognized compiler calls, before the output is written.
+    ///
+    /// When no compiler calls were recognized, and the empty output is an error,
+    /// this check fails with the counts of the executions by the recognition results.
+    pub fn verify_not_empty(&self) -> anyhow::Result<()> {
+        let counts = self.counts.borrow();
+        if counts.recognized == 0 && self.empty_output == config::EmptyOutput::Error {
+            anyhow::bail!("{}", empty_output_diagnostic(&counts));
+        }
+        Ok(())
+    }
+}
+
+/// Explains the likely causes of the empty output, with the counts of the executions.
+fn empty_output_diagnostic(counts: &Counts) -> String {
+    if counts.executions() == 0 {
+        return String::from(
+            "No compiler calls were recognized, because no executions were intercepted. \
+             (Was the build already up to date, or the interception not working?)",
+        );
+    }
+    format!(
+        "No compiler calls were recognized from {} execution(s): \
+         {} not allowed compiler(s), {} ignored (excluded or not compiler), \
+         {} failed to be recognized and {} not recognized as compiler. \
+         (Are the compilers excluded, or unknown to the recognition?)",
+        counts.executions(),
+        counts.not_allowed,
+        counts.ignored,
+        counts.failed,
+        counts.unknown
+    )
+}
+
+/// Resolve the compiler to an absolute path.
+///
+/// The compiler is kept when it's already absolute. The compiler with directory (like
+/// `./bin/cc`) is resolved against the working directory, the compiler without directory
+/// is searched in the `PATH` of the execution. (The way the shell does it. The database
+/// is then usable without the `PATH` of the build.) Only the executable files are taken.
+/// When the compiler is not found, it's kept as it was.
+fn resolve_compiler(compiler: &Path, execution: &intercept::Execution) -> PathBuf {
+    if compiler.is_absolute() {
+        return compiler.to_path_buf();
+    }
+    let absolute = |path: PathBuf| {
+        path.absolutize()
+            .map(|absolute| absolute.to_path_buf())
+            .unwrap_or(path)
+    };
+    let found = match compiler.components().count() {
+        1 => execution.environment.get("PATH").and_then(|paths| {
+            std::env::split_paths(paths)
+                .map(|directory| absolute(execution.working_dir.join(directory).join(compiler)))
+                .find(|candidate| is_executable(candidate))
+        }),
+        _ => Some(absolute(execution.working_dir.join(compiler)))
+            .filter(|candidate| is_executable(candidate)),
+    };
+    found.unwrap_or_else(|| {
+        log::debug!("compiler is not resolved to absolute path: {:?}", compiler);
+        compiler.to_path_buf()
+    })
+}
+
+/// Returns true, if the file can be executed. (At least one of the execute bits is set.)
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Returns true, if the file can be executed.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Translate the compiler patterns into regular expressions.
+fn compile_compiler_patterns(patterns: &[config::CompilerPattern]) -> anyhow::Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| match pattern {
+            config::CompilerPattern::Glob(glob) => {
+                output::compile_globs(std::slice::from_ref(glob))
+                    .map(|mut regexes| regexes.remove(0))
+            }
+            config::CompilerPattern::Regex(regex) => Ok(Regex::new(regex)?),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{vec_of_pathbuf, vec_of_strings};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_strict_mode_fails_on_errors() {
+        let sut = Recognition::try_from(&config(true)).unwrap();
+
+        assert_eq!(None, sut.apply(failing_execution()));
+        assert!(sut.verify().is_err());
+    }
+
+    #[test]
+    fn test_strict_mode_passes_without_errors() {
+        let sut = Recognition::try_from(&config(true)).unwrap();
+
+        assert!(sut.apply(compiler_execution()).is_some());
+        assert!(sut.verify().is_ok());
+    }
+
+    #[test]
+    fn test_lenient_mode_tolerates_errors() {
+        let sut = Recognition::try_from(&config(false)).unwrap();
+
+        assert_eq!(None, sut.apply(failing_execution()));
+        assert!(sut.verify().is_ok());
+    }
+
+    #[test]
+    fn test_strict_mode_fails_on_empty_output() {
+        let unknown = |executable: &str| intercept::Execution {
+            executable: PathBuf::from(executable),
+            arguments: vec_of_strings![executable, "--generate", "source.c"],
+            ..compiler_execution()
+        };
+        let sut = Recognition::try_from(&config(true)).unwrap();
+
+        assert_eq!(None, sut.apply(unknown("/opt/tools/bin/generator")));
+        assert_eq!(None, sut.apply(unknown("/opt/tools/bin/packer")));
+        let error = sut.verify_not_empty().unwrap_err().to_string();
+        assert!(error.contains("from 2 execution(s)"), "{}", error);
+        assert!(error.contains("2 not recognized as compiler"), "{}", error);
+
+        // The empty output is written in lenient mode, or when it's configured.
+        let lenient = Recognition::try_from(&config(false)).unwrap();
+        assert_eq!(None, lenient.apply(unknown("/opt/tools/bin/generator")));
+        assert!(lenient.verify_not_empty().is_ok());
+        let configured = Recognition::try_from(&config::Main {
+            empty_output: Some(config::EmptyOutput::Write),
+            ..config(true)
+        })
+        .unwrap();
+        assert!(configured.verify_not_empty().is_ok());
+    }
+
+    #[test]
+    fn test_only_allowed_compilers_are_recognized() {
+        let config = config::Main {
+            output: config::Output::Clang(Box::new(config::ClangOutput {
+                compilers: vec![],
+                filter: config::Filter::default(),
+                format: config::Format::default(),
+                source_mappings: vec![],
+                compiler_wrappers: vec![],
+                flags_to_remove: vec![],
+                dependency_manifest: None,
+                allowed_compilers: vec_of_pathbuf!["/usr/bin/cc", "clang"],
+                hoist_leading_flags: false,
+                output_directories: vec![],
+                compile_flags_files: false,
+                overwrite_compile_flags_files: false,
+                response_file_depth: 16,
+                json_lines_output: None,
+                compiler_databases: config::CompilerDatabases::default(),
+                append: false,
+                max_entries_in_memory: None,
+                include: vec![],
+                exclude: vec![],
+                keep_ambiguous_headers: false,
+                output_suffixes_to_strip: vec![],
+                deduplicate: true,
+                expand_environment: false,
+                append_limits: config::AppendLimits::default(),
+                synthetic_outputs: false,
+                collapse_include_paths: false,
+                compiler_patterns: config::CompilerPatterns::default(),
+                post_write_hook: None,
+                streaming: false,
+                sort: true,
+                keep_existing_order: false,
+                pin_resource_dir: false,
+            })),
+            strict: true,
+            ..config::Main::default()
+        };
+        let sut = Recognition::try_from(&config).unwrap();
+
+        let gcc = intercept::Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "source.c"],
+            ..compiler_execution()
+        };
+        let clang = intercept::Execution {
+            executable: PathBuf::from("/opt/llvm/bin/clang"),
+            arguments: vec_of_strings!["clang", "-c", "source.c"],
+            ..compiler_execution()
+        };
+        assert!(sut.apply(compiler_execution()).is_some());
+        assert!(sut.apply(clang).is_some());
+        assert_eq!(None, sut.apply(gcc));
+        // In strict mode the dropped compiler calls are failing the run.
+        assert!(sut.verify().is_err());
+    }
+
+    #[test]
+    fn test_compilers_with_input_flags_are_recognized() {
+        let mut config = config::Main::default();
+        if let config::Output::Clang(clang) = &mut config.output {
+            clang.compilers.push(config::Compiler {
+                path: PathBuf::from("/opt/vendor/bin/vcc"),
+                ignore: config::Ignore::Never,
+                arguments: config::Arguments::default(),
+                input_flags: vec_of_strings!["--input="],
+            });
+        }
+        let sut = Recognition::try_from(&config).unwrap();
+
+        let execution = intercept::Execution {
+            executable: PathBuf::from("/opt/vendor/bin/vcc"),
+            arguments: vec_of_strings!["vcc", "-O2", "--input=foo.c"],
+            ..compiler_execution()
+        };
+        let passes = sut.apply(execution).map(|call| call.passes);
+
+        assert_eq!(
+            Some(vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings!["-O2"],
+                output_implied: false,
+            }]),
+            passes
+        );
+    }
+
+    #[test]
+    fn test_self_check_flags_broken_transformations() {
+        let config = config::Main {
+            strict: true,
+            self_check: true,
+            // The calls are checked without recognizing executions.
+            empty_output: Some(config::EmptyOutput::Write),
+            ..config::Main::default()
+        };
+        let call = |flags: Vec<String>| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("source.c"),
+                output: Some(PathBuf::from("source.o")),
+                flags,
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        // Removing a flag (like `-fcolor-diagnostics`) keeps the meaning.
+        let transformation = crate::transformation::Transformation::try_from(&config.output);
+        let transformed = transformation
+            .unwrap()
+            .apply(call(vec_of_strings!["-c", "-fcolor-diagnostics", "-Wall"]))
+            .unwrap();
+        let sut = Recognition::try_from(&config).unwrap();
+        sut.check_round_trip(&transformed);
+        assert!(sut.verify().is_ok());
+
+        // Removing the flag, but not its value (`-include other.c`) changes the meaning.
+        let sut = Recognition::try_from(&config).unwrap();
+        sut.check_round_trip(&call(vec_of_strings!["-c", "other.c"]));
+        assert!(sut.verify().is_err());
+    }
+
+    #[test]
+    fn test_original_compiler_is_recorded() {
+        let sut = Recognition::try_from(&config::Main::default()).unwrap();
+        let execution = intercept::Execution {
+            executable: PathBuf::from("/usr/bin/gcc-12"),
+            arguments: vec_of_strings!["cc", "-c", "source.c"],
+            ..compiler_execution()
+        };
+
+        let call = sut.apply(execution).unwrap();
+        assert_eq!(PathBuf::from("/usr/bin/gcc-12"), call.compiler);
+        assert_eq!(Some(String::from("cc")), call.original_compiler);
+
+        // The entries are written with the normalized compiler path.
+        let entries = output::into_entries(call).unwrap();
+        assert_eq!("/usr/bin/gcc-12", entries[0].arguments[0]);
+    }
+
+    #[test]
+    fn test_only_allowed_environment_is_visible() {
+        let execution = intercept::Execution {
+            executable: PathBuf::from("/usr/libexec/scan-build/ccc-analyzer"),
+            arguments: vec_of_strings!["ccc-analyzer", "-c", "source.c"],
+            environment: HashMap::from([
+                ("CCC_CC".to_string(), "/usr/bin/clang".to_string()),
+                ("CFLAGS".to_string(), "-O2".to_string()),
+            ]),
+            ..compiler_execution()
+        };
+        let compiler = |allowed: Option<Vec<String>>| {
+            let config = config::Main {
+                recognition_environment: allowed,
+                ..config::Main::default()
+            };
+            let sut = Recognition::try_from(&config).unwrap();
+            sut.apply(execution.clone()).map(|call| call.compiler)
+        };
+
+        // The compiler of the shim is taken from the environment, when it's visible.
+        let clang = Some(PathBuf::from("/usr/bin/clang"));
+        assert_eq!(clang, compiler(None));
+        assert_eq!(clang, compiler(Some(vec_of_strings!["CCC_CC"])));
+        // The not allowed variables are ignored, the shim uses the default compiler.
+        let ignored = compiler(Some(vec_of_strings!["CFLAGS"]));
+        assert!(ignored.is_some());
+        assert_ne!(clang, ignored);
+    }
+
+    #[test]
+    fn test_compiler_is_resolved_from_path() -> anyhow::Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("bear-recognition-test-{}", rand::random::<u64>()));
+        let bin = directory.join("bin");
+        std::fs::create_dir_all(&bin)?;
+        std::fs::create_dir_all(directory.join("build"))?;
+        std::fs::write(bin.join("gcc"), "")?;
+        let sut = Recognition::try_from(&config::Main::default()).unwrap();
+        let execution = |compiler: &str, path: &str| intercept::Execution {
+            executable: PathBuf::from(compiler),
+            arguments: vec_of_strings![compiler, "-c", "source.c"],
+            environment: HashMap::from([("PATH".to_string(), path.to_string())]),
+            working_dir: directory.join("build"),
+        };
+
+        // The files without execute permission are not taken.
+        #[cfg(unix)]
+        let not_executable = sut.apply(execution("gcc", &bin.display().to_string()));
+        make_executable(&bin.join("gcc"));
+        // The compiler without directory is not searched in the working directory.
+        std::fs::write(directory.join("build/gcc"), "")?;
+        make_executable(&directory.join("build/gcc"));
+
+        let resolved = sut.apply(execution("gcc", &format!("/nonexistent:{}", bin.display())));
+        // The relative directories of the PATH are resolved against the working directory.
+        let relative = sut.apply(execution("gcc", "../bin"));
+        // The compiler with directory is resolved against the working directory.
+        let with_directory = sut.apply(execution("../bin/gcc", "/nonexistent"));
+        // The compiler is kept as it was, when it's not found.
+        let missing = sut.apply(execution("gcc", "/nonexistent"));
+        std::fs::remove_dir_all(&directory)?;
+
+        #[cfg(unix)]
+        assert_eq!(
+            Some(PathBuf::from("gcc")),
+            not_executable.map(|call| call.compiler)
+        );
+        assert_eq!(Some(bin.join("gcc")), resolved.map(|call| call.compiler));
+        assert_eq!(Some(bin.join("gcc")), relative.map(|call| call.compiler));
+        assert_eq!(
+            Some(bin.join("gcc")),
+            with_directory.map(|call| call.compiler)
+        );
+        assert_eq!(
+            Some(PathBuf::from("gcc")),
+            missing.map(|call| call.compiler)
+        );
+        Ok(())
+    }
+
+    fn make_executable(path: &Path) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+    }
+
+    fn config(strict: bool) -> config::Main {
+        config::Main {
+            intercept: config::Intercept::Wrapper {
+                path: PathBuf::from("/usr/libexec/bear/wrapper"),
+                directory: PathBuf::from("/tmp"),
+                executables: vec_of_pathbuf!["/usr/bin/something"],
+                launchers: vec![],
+                destination_variables: vec![],
+                environment: intercept::CapturedEnvironment::default(),
+            },
+            strict,
+            ..config::Main::default()
+        }
+    }
+
+    fn failing_execution() -> intercept::Execution {
+        intercept::Execution {
+            executable: PathBuf::from("/usr/bin/something"),
+            arguments: vec_of_strings!["something", "--help"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        }
+    }
+
+    fn compiler_execution() -> intercept::Execution {
+        intercept::Execution {
+            executable: PathBuf::from("/usr/bin/cc"),
+            arguments: vec_of_strings!["cc", "-c", "source.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        }
+    }
 }
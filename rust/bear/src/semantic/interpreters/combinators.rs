@@ -5,27 +5,86 @@ use super::super::{CompilerCall, Execution, Interpreter, Recognition};
 /// Represents a set of interpreters, where any of them can recognize the semantic.
 /// The evaluation is done in the order of the interpreters. The first one which
 /// recognizes the semantic will be returned as result.
+///
+/// When there is a preferred interpreter, and an other one recognized the execution
+/// as compiler call (before the preferred one), the preferred interpreter is still
+/// consulted. If it's recognizing it as compiler call too, its result wins.
 pub(super) struct Any {
     interpreters: Vec<Box<dyn Interpreter>>,
+    preferred: Option<String>,
 }
 
 impl Any {
-    pub(super) fn new(tools: Vec<Box<dyn Interpreter>>) -> impl Interpreter {
+    pub(super) fn new(
+        tools: Vec<Box<dyn Interpreter>>,
+        preferred: Option<String>,
+    ) -> impl Interpreter {
         Any {
             interpreters: tools,
+            preferred,
         }
     }
-}
 
-impl Interpreter for Any {
-    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+    /// Select the result of the interpreters.
+    ///
+    /// The `recognize` is calling an interpreter, and the `result` is returning the
+    /// recognition of that call.
+    fn select<R>(
+        &self,
+        recognize: impl Fn(&dyn Interpreter) -> R,
+        result: impl Fn(&R) -> &Recognition<CompilerCall>,
+    ) -> Option<R> {
+        let is_preferred = |tool: &dyn Interpreter| self.preferred.as_deref() == Some(tool.name());
+
+        let mut candidate: Option<R> = None;
         for tool in &self.interpreters {
-            match tool.recognize(x) {
+            let tool = tool.as_ref();
+            if candidate.is_some() {
+                // Only the preferred interpreter can override the first compiler call.
+                if is_preferred(tool) {
+                    let current = recognize(tool);
+                    if let Recognition::Success(_) = result(&current) {
+                        log::debug!(
+                            "ambiguous recognition, the preferred {:?} wins",
+                            tool.name()
+                        );
+                        return Some(current);
+                    }
+                }
+                continue;
+            }
+            let current = recognize(tool);
+            match result(&current) {
                 Recognition::Unknown => continue,
-                result => return result,
+                Recognition::Success(_) if self.preferred.is_some() && !is_preferred(tool) => {
+                    candidate = Some(current)
+                }
+                _ => return Some(current),
             }
         }
-        Recognition::Unknown
+        candidate
+    }
+}
+
+impl Interpreter for Any {
+    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+        self.select(|tool| tool.recognize(x), |result| result)
+            .unwrap_or(Recognition::Unknown)
+    }
+
+    fn recognize_with_attribution(
+        &self,
+        x: &Execution,
+    ) -> (Recognition<CompilerCall>, &'static str, String) {
+        self.select(
+            |tool| tool.recognize_with_attribution(x),
+            |result| &result.0,
+        )
+        .unwrap_or((
+            Recognition::Unknown,
+            "any",
+            String::from("none of the interpreters recognized it"),
+        ))
     }
 }
 
@@ -45,6 +104,7 @@ mod test {
                 Box::new(MockTool::NotRecognize),
                 Box::new(MockTool::NotRecognize),
             ],
+            preferred: None,
         };
 
         let input = any_execution();
@@ -63,6 +123,7 @@ mod test {
                 Box::new(MockTool::Recognize),
                 Box::new(MockTool::NotRecognize),
             ],
+            preferred: None,
         };
 
         let input = any_execution();
@@ -81,6 +142,7 @@ mod test {
                 Box::new(MockTool::RecognizeIgnored),
                 Box::new(MockTool::Recognize),
             ],
+            preferred: None,
         };
 
         let input = any_execution();
@@ -100,6 +162,7 @@ mod test {
                 Box::new(MockTool::Recognize),
                 Box::new(MockTool::NotRecognize),
             ],
+            preferred: None,
         };
 
         let input = any_execution();
@@ -110,7 +173,35 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_any_when_ambiguous() {
+        let interpreters = || -> Vec<Box<dyn Interpreter>> {
+            vec![
+                Box::new(MockTool::NotRecognize),
+                Box::new(MockTool::RecognizeAs("clang")),
+                Box::new(MockTool::RecognizeIgnored),
+                Box::new(MockTool::RecognizeAs("clang-cl")),
+            ]
+        };
+        let recognized = |sut: &dyn Interpreter| match sut.recognize(&any_execution()) {
+            Recognition::Success(call) => call.compiler,
+            _ => PathBuf::from("not recognized"),
+        };
+
+        let sut = Any::new(interpreters(), Some(String::from("clang-cl")));
+        assert_eq!(PathBuf::from("clang-cl"), recognized(&sut));
+        let (_, name, _) = sut.recognize_with_attribution(&any_execution());
+        assert_eq!("clang-cl", name);
+
+        // Without a tiebreaker, or with one that is not matching, the first one wins.
+        let sut = Any::new(interpreters(), None);
+        assert_eq!(PathBuf::from("clang"), recognized(&sut));
+        let sut = Any::new(interpreters(), Some(String::from("nvcc")));
+        assert_eq!(PathBuf::from("clang"), recognized(&sut));
+    }
+
     enum MockTool {
+        RecognizeAs(&'static str),
         Recognize,
         RecognizeIgnored,
         RecognizeFailed,
@@ -118,8 +209,19 @@ mod test {
     }
 
     impl Interpreter for MockTool {
+        fn name(&self) -> &'static str {
+            match self {
+                MockTool::RecognizeAs(name) => name,
+                _ => "mock",
+            }
+        }
+
         fn recognize(&self, _: &Execution) -> Recognition<CompilerCall> {
             match self {
+                MockTool::RecognizeAs(name) => Recognition::Success(CompilerCall {
+                    compiler: PathBuf::from(name),
+                    ..any_compiler_call()
+                }),
                 MockTool::Recognize => Recognition::Success(any_compiler_call()),
                 MockTool::RecognizeIgnored => Recognition::Ignored,
                 MockTool::RecognizeFailed => Recognition::Error(String::from("problem")),
@@ -142,6 +244,8 @@ mod test {
             compiler: PathBuf::new(),
             working_dir: PathBuf::new(),
             passes: vec![],
+            original_compiler: None,
+            environment: None,
         }
     }
 }
@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::super::{CompilerCall, CompilerPass, Execution, Interpreter, Recognition};
+use super::matchers::source::looks_like_a_source_file;
+
+/// The flags which are taking the next argument as value.
+///
+/// The module directory flags (`-J` for GNU, `-module` for Intel) are kept
+/// in the flags, for the tools to find the compiled module files.
+const FLAGS_WITH_VALUE: [&str; 11] = [
+    "-I", "-J", "-module", "-D", "-U", "-L", "-l", "-isystem", "-include", "-MF", "-MT",
+];
+
+/// The flags which are making the compiler to stop after the compilation.
+const COMPILE_FLAGS: [&str; 2] = ["-c", "-S"];
+
+/// The flags which are making the compiler to stop after the preprocessing.
+const PREPROCESS_FLAGS: [&str; 1] = ["-E"];
+
+/// The flags which are queries to the compiler, these are not compiling anything.
+const QUERY_FLAGS: [&str; 4] = ["--version", "-V", "--help", "-dumpversion"];
+
+/// A tool to recognize the Fortran compilers (`gfortran`, `flang`, `ifort` and `ifx`),
+/// and the MPI wrappers of them (`mpifort`).
+pub(super) struct Fortran {}
+
+impl Fortran {
+    pub(super) fn new() -> Box<dyn Interpreter> {
+        Box::new(Fortran {})
+    }
+}
+
+impl Interpreter for Fortran {
+    fn name(&self) -> &'static str {
+        "fortran"
+    }
+
+    /// The recognition considers:
+    /// - the executable name (with the cross compiler prefixes and version suffixes),
+    /// - the sources (Fortran or other languages, the driver compiles those too),
+    /// - the output and the flags controlling the compiler passes.
+    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+        if !is_fortran_compiler(&x.executable) {
+            return Recognition::Unknown;
+        }
+
+        let mut flags: Vec<String> = vec![];
+        let mut sources: Vec<PathBuf> = vec![];
+        let mut output: Option<String> = None;
+        let mut stop_after: Option<&str> = None;
+        let mut preprocessing = false;
+        let mut querying = false;
+
+        let mut arguments = x.arguments.iter().skip(1);
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "-o" => {
+                    output = arguments.next().cloned();
+                }
+                name if FLAGS_WITH_VALUE.contains(&name) => {
+                    flags.push(argument.clone());
+                    flags.extend(arguments.next().cloned());
+                }
+                name if COMPILE_FLAGS.contains(&name) => {
+                    // The earliest pass to stop after wins.
+                    if name == "-S" || stop_after.is_none() {
+                        stop_after = Some(name);
+                    }
+                    flags.push(argument.clone());
+                }
+                name if PREPROCESS_FLAGS.contains(&name) => {
+                    preprocessing = true;
+                    flags.push(argument.clone());
+                }
+                name if QUERY_FLAGS.contains(&name) => {
+                    querying = true;
+                    flags.push(argument.clone());
+                }
+                _ if looks_like_a_source_file(argument) => {
+                    sources.push(PathBuf::from(argument));
+                }
+                // The other inputs (like object files) are for the linker.
+                _ if !argument.starts_with('-') => {}
+                _ => {
+                    flags.push(argument.clone());
+                }
+            }
+        }
+
+        let passes = if querying {
+            vec![]
+        } else if preprocessing {
+            match sources.is_empty() {
+                true => vec![],
+                false => vec![CompilerPass::Preprocess],
+            }
+        } else {
+            if stop_after.is_some() && sources.is_empty() {
+                log::debug!("Fortran found no source to compile (with -c or -S flag).");
+                return Recognition::Unknown;
+            }
+            // The output flag is not specific to any source, when there are multiple.
+            let output = match sources.len() {
+                1 => output,
+                _ => None,
+            };
+            sources
+                .into_iter()
+                .map(|source| {
+                    let implied = implied_output(&source, stop_after);
+                    CompilerPass::Compile {
+                        output_implied: output.is_none() && implied.is_some(),
+                        output: output.as_ref().map(PathBuf::from).or(implied),
+                        source,
+                        flags: flags.clone(),
+                    }
+                })
+                .collect()
+        };
+
+        Recognition::Success(CompilerCall {
+            compiler: x.executable.clone(),
+            working_dir: x.working_dir.clone(),
+            passes,
+            original_compiler: None,
+            environment: None,
+        })
+    }
+}
+
+/// Returns true, if the executable is one of the known Fortran compilers.
+fn is_fortran_compiler(executable: &Path) -> bool {
+    executable
+        .file_name()
+        .and_then(|name| name.to_str())
+        // Windows executables might be called with the extension.
+        .map(|name| name.strip_suffix(".exe").unwrap_or(name))
+        .is_some_and(|name| COMPILER_REGEX.is_match(name))
+}
+
+/// Returns the output file the compiler creates when the `-o` flag is not given.
+///
+/// Like the GCC, it replaces the extension of the source file name, and creates
+/// the file in the working directory.
+fn implied_output(source: &Path, stop_after: Option<&str>) -> Option<PathBuf> {
+    let extension = match stop_after {
+        Some("-c") => "o",
+        Some("-S") => "s",
+        _ => return None,
+    };
+    source
+        .file_name()
+        .map(|name| Path::new(name).with_extension(extension))
+}
+
+lazy_static! {
+    static ref COMPILER_REGEX: Regex = Regex::new(
+        r"^(([^-]*-)*gfortran|flang(-new)?|ifort|ifx|mpifort|mpif77|mpif90)(-?\d+(\.\d+){0,2})?$"
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::vec_of_strings;
+
+    use super::*;
+
+    #[test]
+    fn test_compilation_with_module_directory() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gfortran"),
+            arguments: vec_of_strings![
+                "gfortran",
+                "-O2",
+                "-J",
+                "build/mod",
+                "-I",
+                "include",
+                "-c",
+                "src/solver.f90",
+                "-o",
+                "build/solver.o"
+            ],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gfortran"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("src/solver.f90"),
+                output: Some(PathBuf::from("build/solver.o")),
+                flags: vec_of_strings!["-O2", "-J", "build/mod", "-I", "include", "-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_multiple_sources() {
+        let input = Execution {
+            executable: PathBuf::from("/opt/intel/bin/ifx"),
+            arguments: vec_of_strings!["ifx", "-module", "mod", "-c", "a.f", "b.F90", "c.for"],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/opt/intel/bin/ifx"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: ["a", "b", "c"]
+                .iter()
+                .zip(["a.f", "b.F90", "c.for"])
+                .map(|(stem, source)| CompilerPass::Compile {
+                    source: PathBuf::from(source),
+                    output: Some(PathBuf::from(format!("{}.o", stem))),
+                    flags: vec_of_strings!["-module", "mod", "-c"],
+                    output_implied: true,
+                })
+                .collect(),
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compiler_names() {
+        for name in [
+            "gfortran",
+            "gfortran-12",
+            "x86_64-linux-gnu-gfortran",
+            "flang",
+            "flang-new",
+            "flang-new-17",
+            "ifort",
+            "ifx.exe",
+            "mpifort",
+            "mpif90",
+        ] {
+            assert!(is_fortran_compiler(Path::new(name)), "{}", name);
+        }
+        for name in ["gcc", "fortran-lint", "f2c"] {
+            assert!(!is_fortran_compiler(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_queries_and_linking() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from("/usr/bin/flang"),
+            arguments,
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = Recognition::Success(CompilerCall {
+            compiler: PathBuf::from("/usr/bin/flang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![],
+            original_compiler: None,
+            environment: None,
+        });
+        assert_eq!(
+            expected,
+            SUT.recognize(&execution(vec_of_strings!["flang", "--version"]))
+        );
+        assert_eq!(
+            expected,
+            SUT.recognize(&execution(vec_of_strings!["flang", "a.o", "-o", "app"]))
+        );
+        assert_eq!(
+            Recognition::Unknown,
+            SUT.recognize(&execution(vec_of_strings!["flang", "-c"]))
+        );
+    }
+
+    const SUT: Fortran = Fortran {};
+}
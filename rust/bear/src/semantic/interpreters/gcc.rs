@@ -1,37 +1,63 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use nom::branch::alt;
-use nom::multi::many1;
-use nom::sequence::preceded;
-
 use super::super::{CompilerCall, Execution, Interpreter, Recognition};
 use internal::Argument;
 
-pub(super) struct Gcc {}
+pub(super) struct Gcc {
+    /// Keep the compilation of headers, which are given without the language.
+    keep_ambiguous_headers: bool,
+    /// Expand the environment variable references in the paths.
+    expand_environment: bool,
+}
 
 impl Gcc {
     pub(super) fn new() -> Box<dyn Interpreter> {
-        Box::new(Gcc {})
+        Self::with_options(false, false)
     }
-}
 
-impl Interpreter for Gcc {
-    fn recognize(&self, execution: &Execution) -> Recognition<CompilerCall> {
-        let mut parser = preceded(
-            internal::compiler,
-            many1(alt((internal::flag, internal::source))),
-        );
+    pub(super) fn with_options(
+        keep_ambiguous_headers: bool,
+        expand_environment: bool,
+    ) -> Box<dyn Interpreter> {
+        Box::new(Gcc {
+            keep_ambiguous_headers,
+            expand_environment,
+        })
+    }
 
-        match parser(execution.arguments.as_slice()) {
+    fn recognize_arguments(
+        &self,
+        execution: &Execution,
+        arguments: &[String],
+    ) -> Recognition<CompilerCall> {
+        match internal::parse(arguments) {
             Ok(result) => {
                 // TODO: append flags from environment
                 let flags = result.1;
-                let passes = Argument::passes(flags.as_slice());
+                if Argument::is_dry_run(flags.as_slice()) {
+                    log::debug!("Gcc is asked to print the commands only (with -### flag).");
+                    return Recognition::Ignored;
+                }
+                let passes = Argument::passes(
+                    &execution.executable,
+                    flags.as_slice(),
+                    self.keep_ambiguous_headers,
+                );
+                if passes.is_empty() && Argument::is_compiling(flags.as_slice()) {
+                    log::debug!("Gcc found no source to compile (with -c or -S flag).");
+                    return Recognition::Unknown;
+                }
+                if passes.is_empty() && Argument::is_linking_only(flags.as_slice()) {
+                    log::debug!("Gcc is asked to link only (without source to compile).");
+                    return Recognition::Ignored;
+                }
 
                 Recognition::Success(CompilerCall {
                     compiler: execution.executable.clone(),
                     working_dir: execution.working_dir.clone(),
                     passes,
+                    original_compiler: None,
+                    environment: None,
                 })
             }
             Err(error) => {
@@ -42,48 +68,57 @@ impl Interpreter for Gcc {
     }
 }
 
+impl Interpreter for Gcc {
+    fn name(&self) -> &'static str {
+        "gcc"
+    }
+
+    fn recognize(&self, execution: &Execution) -> Recognition<CompilerCall> {
+        if self.expand_environment {
+            let arguments = internal::expand_paths(&execution.arguments, &execution.environment);
+            self.recognize_arguments(execution, &arguments)
+        } else {
+            self.recognize_arguments(execution, &execution.arguments)
+        }
+    }
+}
+
 mod internal {
     use lazy_static::lazy_static;
+    use nom::branch::alt;
+    use nom::multi::many1;
+    use nom::sequence::preceded;
     use nom::{error::ErrorKind, IResult};
-    use regex::Regex;
-    use std::path::PathBuf;
+    use regex::{Captures, Regex};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
 
     use super::super::super::CompilerPass;
     use super::super::matchers::source::looks_like_a_source_file;
 
-    #[derive(Debug, PartialEq)]
-    enum Language {
-        C,
-        Cpp,
-        ObjectiveC,
-        ObjectiveCpp,
-        Ada,
-        Fortran,
-        Go,
-        D,
-        Assembler,
-        Other,
-    }
-
-    #[derive(Debug, PartialEq)]
+    /// The compiler passes in the order of execution.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
     enum Pass {
         Preprocessor,
         Compiler,
+        Assembler,
         Linker,
     }
 
-    #[derive(Debug, PartialEq)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
     enum Meaning {
         Compiler,
         ControlKindOfOutput { stop_before: Option<Pass> },
-        ControlLanguage(Language),
+        ControlLanguage,
         ControlPass(Pass),
         Diagnostic,
         Debug,
         Optimize,
         Instrumentation,
         DirectorySearch(Option<Pass>),
+        Dependency,
         Developer,
+        Other,
         Input(Pass),
         Output,
     }
@@ -95,115 +130,1923 @@ mod internal {
     }
 
     impl<'a> Argument<'a> {
-        pub(crate) fn passes(flags: &[Argument]) -> Vec<CompilerPass> {
-            let mut pass: Pass = Pass::Linker;
-            let mut inputs: Vec<String> = vec![];
+        pub(crate) fn passes(
+            compiler: &Path,
+            flags: &[Argument],
+            keep_ambiguous_headers: bool,
+        ) -> Vec<CompilerPass> {
+            let mut stop_before: Option<Pass> = None;
+            // The sources with the language override, which was active for them.
+            let mut inputs: Vec<(String, Option<String>)> = vec![];
+            let mut language: Option<String> = None;
             let mut output: Option<String> = None;
             let mut args: Vec<String> = vec![];
 
             for flag in flags {
                 match flag.meaning {
                     Meaning::ControlKindOfOutput {
-                        stop_before: Some(Pass::Compiler),
+                        stop_before: Some(pass),
                     } => {
-                        pass = Pass::Preprocessor;
-                        args.extend(flag.arguments.into_iter().map(String::to_owned));
+                        // The earliest pass to stop before wins.
+                        stop_before = Some(stop_before.map_or(pass, |current| current.min(pass)));
+                        args.extend(flag.arguments.iter().map(String::to_owned));
                     }
-                    Meaning::ControlKindOfOutput {
-                        stop_before: Some(Pass::Linker),
-                    } => {
-                        pass = Pass::Compiler;
-                        args.extend(flag.arguments.into_iter().map(String::to_owned));
-                    }
-                    Meaning::ControlKindOfOutput { .. }
-                    | Meaning::ControlLanguage(_)
-                    | Meaning::ControlPass(Pass::Preprocessor)
-                    | Meaning::ControlPass(Pass::Compiler)
-                    | Meaning::Diagnostic
-                    | Meaning::Debug
-                    | Meaning::Optimize
-                    | Meaning::Instrumentation
-                    | Meaning::DirectorySearch(None) => {
-                        args.extend(flag.arguments.into_iter().map(String::to_owned));
+                    Meaning::ControlLanguage => {
+                        // The `-x` flag applies to the following sources, until reset by `-x none`.
+                        language = match flag.arguments {
+                            [_, value] => Some(value.clone()),
+                            [joined] => joined.strip_prefix("-x").map(String::from),
+                            _ => None,
+                        }
+                        .filter(|value| value != "none");
                     }
-                    Meaning::Input(_) => {
+                    Meaning::Input(Pass::Preprocessor) => {
                         assert_eq!(flag.arguments.len(), 1);
-                        inputs.push(flag.arguments[0].clone())
+                        inputs.push((flag.arguments[0].clone(), language.clone()))
+                    }
+                    // The compiler takes the inputs as sources of the given language,
+                    // regardless of their extension. (Like generated files.)
+                    Meaning::Input(Pass::Linker) if language.is_some() => {
+                        inputs.push((flag.arguments[0].clone(), language.clone()))
                     }
                     Meaning::Output => {
-                        assert_eq!(flag.arguments.len(), 1);
-                        output = Some(flag.arguments[0].clone())
+                        let current = match flag.arguments {
+                            [_, value] => Some(value.clone()),
+                            [joined] => joined.strip_prefix("-o").map(String::from),
+                            _ => None,
+                        };
+                        // The compiler uses the last output flag, like we do.
+                        if let (Some(previous), Some(current)) = (&output, &current) {
+                            log::warn!(
+                                "Multiple output flags, {:?} is overridden by {:?}",
+                                previous,
+                                current
+                            );
+                        }
+                        output = current;
+                    }
+                    // These are not relevant for the compilation of the sources.
+                    // (The dependency flags are kept, the output decides about those.)
+                    Meaning::Compiler
+                    | Meaning::Input(_)
+                    | Meaning::ControlPass(Pass::Linker)
+                    | Meaning::DirectorySearch(Some(Pass::Linker)) => {}
+                    _ => {
+                        args.extend(flag.arguments.iter().map(String::to_owned));
                     }
-                    _ => {}
                 }
             }
 
-            match pass {
-                Pass::Preprocessor if inputs.is_empty() => {
+            match stop_before {
+                // Queries to the compiler (like `--version`) are not compiling anything.
+                Some(Pass::Preprocessor) => {
+                    vec![]
+                }
+                Some(Pass::Compiler) if inputs.is_empty() => {
                     vec![]
                 }
-                Pass::Preprocessor => {
+                Some(Pass::Compiler) => {
                     vec![CompilerPass::Preprocess]
                 }
-                Pass::Compiler | Pass::Linker => inputs
-                    .into_iter()
-                    .map(|source| CompilerPass::Compile {
-                        source: PathBuf::from(source),
-                        output: output.as_ref().map(PathBuf::from),
-                        flags: args.clone(),
-                    })
-                    .collect(),
+                _ => {
+                    // Each source is a separate translation unit. The output flag is
+                    // not specific to any of them, when there are multiple sources.
+                    // (The compiler rejects it, or it names the linker output.)
+                    let output = match inputs.len() {
+                        1 => output,
+                        _ => None,
+                    };
+                    inputs
+                        .into_iter()
+                        .filter(|(source, language)| {
+                            // The header with `-c` could be a precompiled header, or a
+                            // translation unit. Without the language given, it's not known.
+                            let ambiguous = stop_before.is_some()
+                                && language.is_none()
+                                && is_header(Path::new(source), None);
+                            if ambiguous && !keep_ambiguous_headers {
+                                log::debug!("Gcc dropped the header without language: {source}");
+                            }
+                            !ambiguous || keep_ambiguous_headers
+                        })
+                        .map(|(source, language)| {
+                            let source = PathBuf::from(source);
+                            let implied = match is_header(&source, language.as_deref()) {
+                                true => implied_header_output(&source, compiler, stop_before),
+                                false => implied_output(&source, stop_before),
+                            };
+                            let mut flags = args.clone();
+                            if let Some(language) = language {
+                                flags.extend([String::from("-x"), language]);
+                            }
+                            CompilerPass::Compile {
+                                output_implied: output.is_none() && implied.is_some(),
+                                output: output.as_ref().map(PathBuf::from).or(implied),
+                                source,
+                                flags,
+                            }
+                        })
+                        .collect()
+                }
             }
         }
+
+        /// Returns true, if the driver is asked to print the commands without running them.
+        pub(crate) fn is_dry_run(flags: &[Argument]) -> bool {
+            flags.iter().any(|flag| flag.arguments == ["-###"])
+        }
+
+        /// Returns true, if the compiler is asked to compile (and not to run a query).
+        ///
+        /// The earliest pass to stop before wins, like at the compiler pass detection.
+        pub(crate) fn is_compiling(flags: &[Argument]) -> bool {
+            let stop_before = flags
+                .iter()
+                .filter_map(|flag| match flag.meaning {
+                    Meaning::ControlKindOfOutput {
+                        stop_before: Some(pass),
+                    } => Some(pass),
+                    _ => None,
+                })
+                .min();
+            matches!(stop_before, Some(Pass::Linker | Pass::Assembler))
+        }
+
+        /// Returns true, if the compiler is asked to link the given objects and libraries only.
+        ///
+        /// None of the passes is stopped before, and there is no source to compile.
+        /// (The queries, like `--version`, are stopping before the preprocessor.)
+        pub(crate) fn is_linking_only(flags: &[Argument]) -> bool {
+            flags.iter().all(|flag| {
+                !matches!(
+                    flag.meaning,
+                    Meaning::ControlKindOfOutput {
+                        stop_before: Some(_)
+                    } | Meaning::Input(Pass::Preprocessor)
+                )
+            })
+        }
     }
 
-    pub(crate) fn compiler(i: &[String]) -> IResult<&[String], Argument> {
-        let candidate = &i[0];
-        if COMPILER_REGEX.is_match(candidate) {
-            const MEANING: Meaning = Meaning::Compiler;
-            Ok((
-                &i[1..],
-                Argument {
-                    arguments: &i[..0],
-                    meaning: MEANING,
-                },
-            ))
-        } else {
+    /// Returns the output file the compiler creates when the `-o` flag is not given.
+    ///
+    /// The compiler replaces the final extension of the source file name, and
+    /// creates the file in the working directory. (Like `src/foo.v2.c` compiled
+    /// with `-c` creates `foo.v2.o`.) The linker output is not implied, because
+    /// that one is not specific to a source file.
+    fn implied_output(source: &Path, stop_before: Option<Pass>) -> Option<PathBuf> {
+        let extension = match stop_before {
+            Some(Pass::Linker) => "o",
+            Some(Pass::Assembler) => "s",
+            _ => return None,
+        };
+        source
+            .file_name()
+            .map(|name| Path::new(name).with_extension(extension))
+    }
+
+    /// Returns true, if the source is a header, which is compiled into a precompiled header.
+    ///
+    /// The language is given by the `-x` flag (like `c++-header`), or by the file extension.
+    fn is_header(source: &Path, language: Option<&str>) -> bool {
+        match language {
+            Some(language) => language.ends_with("-header"),
+            None => matches!(
+                source.extension().and_then(|extension| extension.to_str()),
+                Some("h" | "hh" | "hpp" | "hxx" | "h++" | "H")
+            ),
+        }
+    }
+
+    /// Returns the precompiled header the compiler creates when the `-o` flag is not given.
+    ///
+    /// The output is created next to the header, with an extra extension. It's `.pch`
+    /// for clang, and `.gch` for GCC. (Like `include/foo.h` creates `include/foo.h.gch`.)
+    fn implied_header_output(
+        source: &Path,
+        compiler: &Path,
+        stop_before: Option<Pass>,
+    ) -> Option<PathBuf> {
+        if !matches!(stop_before, None | Some(Pass::Linker)) {
+            return None;
+        }
+        let is_clang = compiler
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains("clang"));
+        let extension = if is_clang { "pch" } else { "gch" };
+        let mut output = source.as_os_str().to_owned();
+        output.push(".");
+        output.push(extension);
+        Some(PathBuf::from(output))
+    }
+
+    /// Parse the arguments of the compiler call. (The compiler is not returned.)
+    pub(crate) fn parse(i: &[String]) -> IResult<&[String], Vec<Argument<'_>>> {
+        preceded(compiler, many1(alt((flag, source, argument))))(i)
+    }
+
+    /// Expand the environment variable references (like `$VAR` or `${VAR}`) in the
+    /// arguments, which are naming paths. (Like the sources, the output, the include
+    /// directories and the included files.)
+    ///
+    /// The references to undefined variables are left as those were.
+    pub(crate) fn expand_paths(
+        arguments: &[String],
+        environment: &HashMap<String, String>,
+    ) -> Vec<String> {
+        let flags = match parse(arguments) {
+            Ok((_, flags)) => flags,
+            Err(_) => return arguments.to_vec(),
+        };
+        let mut result = arguments[..1].to_vec();
+        for flag in flags {
+            let is_path = match flag.meaning {
+                Meaning::DirectorySearch(_) | Meaning::Input(Pass::Preprocessor) => true,
+                Meaning::Output => true,
+                Meaning::ControlPass(Pass::Preprocessor) => flag.arguments[0]
+                    .strip_prefix('-')
+                    .is_some_and(|name| name.starts_with("include") || name.starts_with("imacros")),
+                _ => false,
+            };
+            for argument in flag.arguments {
+                match is_path {
+                    true => result.push(expand_variables(argument, environment)),
+                    false => result.push(argument.clone()),
+                }
+            }
+        }
+        result
+    }
+
+    fn expand_variables(value: &str, environment: &HashMap<String, String>) -> String {
+        VARIABLE_REGEX
+            .replace_all(value, |captures: &Captures| {
+                let name = captures
+                    .get(1)
+                    .or_else(|| captures.get(2))
+                    .map_or("", |name| name.as_str());
+                match environment.get(name) {
+                    Some(value) => value.clone(),
+                    None => {
+                        log::warn!("Environment variable is not defined: {name} (in {value})");
+                        captures[0].to_string()
+                    }
+                }
+            })
+            .into_owned()
+    }
+
+    pub(crate) fn compiler(i: &[String]) -> IResult<&[String], Argument<'_>> {
+        let candidate = i
+            .first()
+            .and_then(|program| Path::new(program).file_name())
+            .and_then(|name| name.to_str())
+            // Windows executables might be called with the extension.
+            .map(|name| name.strip_suffix(".exe").unwrap_or(name));
+        match candidate {
+            Some(name) if COMPILER_REGEX.is_match(name) => {
+                const MEANING: Meaning = Meaning::Compiler;
+                Ok((
+                    &i[1..],
+                    Argument {
+                        arguments: &i[..1],
+                        meaning: MEANING,
+                    },
+                ))
+            }
             // Declare it as a non-recoverable error, so argument processing will stop after this.
-            Err(nom::Err::Failure(nom::error::Error::new(i, ErrorKind::Tag)))
+            _ => Err(nom::Err::Failure(nom::error::Error::new(i, ErrorKind::Tag))),
         }
     }
 
-    pub(crate) fn source(i: &[String]) -> IResult<&[String], Argument> {
-        let candidate = &i[0];
-        if looks_like_a_source_file(candidate.as_str()) {
-            const MEANING: Meaning = Meaning::Input(Pass::Preprocessor);
-            Ok((
-                &i[1..],
-                Argument {
-                    arguments: &i[..0],
-                    meaning: MEANING,
-                },
-            ))
-        } else {
-            Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Tag)))
+    pub(crate) fn source(i: &[String]) -> IResult<&[String], Argument<'_>> {
+        match i.first() {
+            Some(candidate) if looks_like_a_source_file(candidate.as_str()) => {
+                const MEANING: Meaning = Meaning::Input(Pass::Preprocessor);
+                Ok((
+                    &i[1..],
+                    Argument {
+                        arguments: &i[..1],
+                        meaning: MEANING,
+                    },
+                ))
+            }
+            _ => Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Tag))),
         }
     }
 
-    pub(crate) fn flag(i: &[String]) -> IResult<&[String], Argument> {
-        todo!()
+    pub(crate) fn flag(i: &[String]) -> IResult<&[String], Argument<'_>> {
+        FLAG_DEFINITIONS
+            .iter()
+            .find_map(|definition| definition.count(i).map(|count| (count, definition.meaning)))
+            .map(|(count, meaning)| {
+                (
+                    &i[count..],
+                    Argument {
+                        arguments: &i[..count],
+                        meaning,
+                    },
+                )
+            })
+            .ok_or_else(|| nom::Err::Error(nom::error::Error::new(i, ErrorKind::Tag)))
     }
 
+    /// Matches the arguments which were not recognized by the other parsers.
+    ///
+    /// Unknown flags are kept for the compilation, while the rest of the arguments
+    /// are considered as linker inputs. (Like object files or libraries.)
+    pub(crate) fn argument(i: &[String]) -> IResult<&[String], Argument<'_>> {
+        match i.first() {
+            Some(candidate) => {
+                let meaning = if candidate.starts_with('-') {
+                    Meaning::Other
+                } else {
+                    Meaning::Input(Pass::Linker)
+                };
+                Ok((
+                    &i[1..],
+                    Argument {
+                        arguments: &i[..1],
+                        meaning,
+                    },
+                ))
+            }
+            None => Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Eof))),
+        }
+    }
+
+    /// Describes how the flag and its value are spelled on the command line.
+    #[derive(Clone, Copy, Debug)]
+    enum Match {
+        /// The argument is the flag itself. (Like `-c`.)
+        Exactly,
+        /// The value is the next argument. (Like `-MF file`.)
+        Separate,
+        /// The value is glued to the flag with an equal sign. (Like `-std=c99`.)
+        GluedWithEq,
+        /// The value is glued to the flag or the next argument. (Like `-Idir` or `-I dir`.)
+        GluedOrSeparate,
+        /// The value is glued to the flag with an equal sign or the next argument.
+        /// (Like `--param=name=value` or `--param name=value`.)
+        GluedWithEqOrSeparate,
+        /// The argument starts with the flag. (Like `-Wall` for `-W`.)
+        Prefix,
+        /// The argument starts with the flag, and the value is the next argument.
+        /// (Like `-Xarch_arm64 -DFOO`.)
+        PrefixAndSeparate,
+    }
+
+    struct FlagDefinition {
+        flag: &'static str,
+        matching: Match,
+        meaning: Meaning,
+    }
+
+    impl FlagDefinition {
+        const fn new(flag: &'static str, matching: Match, meaning: Meaning) -> Self {
+            FlagDefinition {
+                flag,
+                matching,
+                meaning,
+            }
+        }
+
+        /// Returns the number of arguments this flag takes from the input, if it's matching.
+        fn count(&self, i: &[String]) -> Option<usize> {
+            let candidate = i.first()?.as_str();
+            let exact = candidate == self.flag;
+            let has_value = i.len() > 1;
+            let glued_with_eq = candidate
+                .strip_prefix(self.flag)
+                .is_some_and(|rest| rest.starts_with('='));
+
+            match self.matching {
+                Match::Exactly => exact.then_some(1),
+                Match::Separate => (exact && has_value).then_some(2),
+                Match::GluedWithEq => glued_with_eq.then_some(1),
+                Match::GluedOrSeparate if exact => has_value.then_some(2),
+                Match::GluedOrSeparate => candidate.starts_with(self.flag).then_some(1),
+                Match::GluedWithEqOrSeparate if exact => has_value.then_some(2),
+                Match::GluedWithEqOrSeparate => glued_with_eq.then_some(1),
+                Match::Prefix => candidate.starts_with(self.flag).then_some(1),
+                Match::PrefixAndSeparate => (candidate.len() > self.flag.len()
+                    && candidate.starts_with(self.flag)
+                    && has_value)
+                    .then_some(2),
+            }
+        }
+    }
+
+    const QUERY: Meaning = Meaning::ControlKindOfOutput {
+        stop_before: Some(Pass::Preprocessor),
+    };
+    const KIND_OF_OUTPUT: Meaning = Meaning::ControlKindOfOutput { stop_before: None };
+    const PREPROCESSOR: Meaning = Meaning::ControlPass(Pass::Preprocessor);
+    const ASSEMBLER: Meaning = Meaning::ControlPass(Pass::Assembler);
+    const LINKER: Meaning = Meaning::ControlPass(Pass::Linker);
+    const INCLUDE: Meaning = Meaning::DirectorySearch(Some(Pass::Preprocessor));
+
     lazy_static! {
+        // The flags are matched in the order of their length, to try the
+        // more specific flags first. (Like `-include` before `-I`.)
+        static ref FLAG_DEFINITIONS: Vec<FlagDefinition> = {
+            let mut definitions = vec![
+                FlagDefinition::new("-x", Match::GluedOrSeparate, Meaning::ControlLanguage),
+                FlagDefinition::new("-c", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Linker) }),
+                FlagDefinition::new("-S", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Assembler) }),
+                FlagDefinition::new("-E", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Compiler) }),
+                FlagDefinition::new("-o", Match::GluedOrSeparate, Meaning::Output),
+                FlagDefinition::new("-dumpbase", Match::Separate, KIND_OF_OUTPUT),
+                FlagDefinition::new("-dumpbase-ext", Match::Separate, KIND_OF_OUTPUT),
+                FlagDefinition::new("-dumpdir", Match::Separate, KIND_OF_OUTPUT),
+                FlagDefinition::new("-v", Match::Exactly, KIND_OF_OUTPUT),
+                FlagDefinition::new("-###", Match::Exactly, KIND_OF_OUTPUT),
+                FlagDefinition::new("--help", Match::Prefix, QUERY),
+                FlagDefinition::new("--target-help", Match::Exactly, QUERY),
+                FlagDefinition::new("--version", Match::Exactly, QUERY),
+                FlagDefinition::new("-pass-exit-codes", Match::Exactly, KIND_OF_OUTPUT),
+                FlagDefinition::new("-pipe", Match::Exactly, KIND_OF_OUTPUT),
+                FlagDefinition::new("-specs", Match::GluedWithEq, KIND_OF_OUTPUT),
+                FlagDefinition::new("-wrapper", Match::Separate, KIND_OF_OUTPUT),
+                FlagDefinition::new("@", Match::Prefix, KIND_OF_OUTPUT),
+                FlagDefinition::new("-A", Match::GluedOrSeparate, PREPROCESSOR),
+                FlagDefinition::new("-D", Match::GluedOrSeparate, PREPROCESSOR),
+                FlagDefinition::new("-U", Match::GluedOrSeparate, PREPROCESSOR),
+                FlagDefinition::new("-include", Match::GluedOrSeparate, PREPROCESSOR),
+                FlagDefinition::new("-imacros", Match::GluedOrSeparate, PREPROCESSOR),
+                FlagDefinition::new("-undef", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-pthread", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-M", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Compiler) }),
+                FlagDefinition::new("-MM", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Compiler) }),
+                FlagDefinition::new("-MG", Match::Exactly, Meaning::Dependency),
+                FlagDefinition::new("-MP", Match::Exactly, Meaning::Dependency),
+                FlagDefinition::new("-MD", Match::Exactly, Meaning::Dependency),
+                FlagDefinition::new("-MMD", Match::Exactly, Meaning::Dependency),
+                FlagDefinition::new("-MF", Match::GluedOrSeparate, Meaning::Dependency),
+                FlagDefinition::new("-MT", Match::GluedOrSeparate, Meaning::Dependency),
+                FlagDefinition::new("-MQ", Match::GluedOrSeparate, Meaning::Dependency),
+                // The compilation database fragment of clang, which is kept in the flags.
+                FlagDefinition::new("-MJ", Match::GluedOrSeparate, Meaning::Other),
+                FlagDefinition::new("-C", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-CC", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-P", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-traditional", Match::Prefix, PREPROCESSOR),
+                FlagDefinition::new("-trigraphs", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-remap", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-H", Match::Exactly, PREPROCESSOR),
+                FlagDefinition::new("-Xpreprocessor", Match::Separate, PREPROCESSOR),
+                FlagDefinition::new("-Wp,", Match::Prefix, PREPROCESSOR),
+                // The legacy include path divider, which is not taking a value.
+                FlagDefinition::new("-I-", Match::Exactly, INCLUDE),
+                FlagDefinition::new("-I", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-iplugindir", Match::GluedWithEq, Meaning::DirectorySearch(None)),
+                FlagDefinition::new("-iquote", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-isystem", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-idirafter", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-iprefix", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-iwithprefix", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-iwithprefixbefore", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-isysroot", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-imultilib", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-iwithsysroot", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-iframework", Match::GluedOrSeparate, INCLUDE),
+                FlagDefinition::new("-L", Match::GluedOrSeparate, Meaning::DirectorySearch(Some(Pass::Linker))),
+                FlagDefinition::new("-B", Match::GluedOrSeparate, Meaning::DirectorySearch(None)),
+                FlagDefinition::new("--sysroot", Match::GluedWithEqOrSeparate, Meaning::DirectorySearch(None)),
+                FlagDefinition::new("-flinker-output", Match::GluedWithEq, LINKER),
+                FlagDefinition::new("-fuse-ld", Match::GluedWithEq, LINKER),
+                FlagDefinition::new("-l", Match::GluedOrSeparate, LINKER),
+                FlagDefinition::new("-nostartfiles", Match::Exactly, LINKER),
+                FlagDefinition::new("-nodefaultlibs", Match::Exactly, LINKER),
+                FlagDefinition::new("-nolibc", Match::Exactly, LINKER),
+                FlagDefinition::new("-nostdlib", Match::Exactly, LINKER),
+                FlagDefinition::new("-e", Match::Separate, LINKER),
+                FlagDefinition::new("-entry", Match::GluedWithEq, LINKER),
+                FlagDefinition::new("-pie", Match::Exactly, LINKER),
+                FlagDefinition::new("-no-pie", Match::Exactly, LINKER),
+                FlagDefinition::new("-static-pie", Match::Exactly, LINKER),
+                FlagDefinition::new("-r", Match::Exactly, LINKER),
+                FlagDefinition::new("-rdynamic", Match::Exactly, LINKER),
+                FlagDefinition::new("-s", Match::Exactly, LINKER),
+                FlagDefinition::new("-symbolic", Match::Exactly, LINKER),
+                FlagDefinition::new("-static", Match::Prefix, LINKER),
+                FlagDefinition::new("-shared", Match::Prefix, LINKER),
+                FlagDefinition::new("-T", Match::Separate, LINKER),
+                FlagDefinition::new("-Xlinker", Match::Separate, LINKER),
+                FlagDefinition::new("-Wl,", Match::Prefix, LINKER),
+                FlagDefinition::new("-u", Match::Separate, LINKER),
+                FlagDefinition::new("-z", Match::Separate, LINKER),
+                FlagDefinition::new("-Xassembler", Match::Separate, ASSEMBLER),
+                FlagDefinition::new("-Wa,", Match::Prefix, ASSEMBLER),
+                FlagDefinition::new("-ansi", Match::Exactly, Meaning::Other),
+                FlagDefinition::new("-aux-info", Match::Separate, Meaning::Other),
+                FlagDefinition::new("-std", Match::GluedWithEq, Meaning::Other),
+                FlagDefinition::new("--param", Match::GluedWithEqOrSeparate, Meaning::Other),
+                FlagDefinition::new("-target", Match::Separate, Meaning::Other),
+                FlagDefinition::new("--target", Match::GluedWithEq, Meaning::Other),
+                // The Apple compilers are building for multiple architectures at once, and
+                // the `-Xarch_<arch>` flag passes the next flag only to the given architecture.
+                FlagDefinition::new("-arch", Match::Separate, Meaning::Other),
+                FlagDefinition::new("-Xarch_", Match::PrefixAndSeparate, Meaning::Other),
+                // The next argument is passed to the frontend as it is, that can be a path too.
+                FlagDefinition::new("-Xclang", Match::Separate, Meaning::Other),
+                // The flags of the frontend invocations (`clang -cc1`), where the action
+                // flags are defining the kind of output instead of the `-c` flag.
+                FlagDefinition::new("-cc1", Match::Exactly, Meaning::Other),
+                FlagDefinition::new("-emit-obj", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Linker) }),
+                FlagDefinition::new("-emit-llvm-bc", Match::Exactly, Meaning::ControlKindOfOutput { stop_before: Some(Pass::Linker) }),
+                FlagDefinition::new("-main-file-name", Match::Separate, Meaning::Other),
+                FlagDefinition::new("-triple", Match::Separate, Meaning::Other),
+                FlagDefinition::new("-resource-dir", Match::Separate, Meaning::Other),
+                FlagDefinition::new("-internal-isystem", Match::Separate, INCLUDE),
+                FlagDefinition::new("-internal-externc-isystem", Match::Separate, INCLUDE),
+                FlagDefinition::new("-dependency-file", Match::Separate, Meaning::Dependency),
+                // The preprocessor output modes of clang (for distributed builds), without value.
+                FlagDefinition::new("-frewrite-includes", Match::Exactly, Meaning::Other),
+                FlagDefinition::new("-frewrite-imports", Match::Exactly, Meaning::Other),
+                FlagDefinition::new("-O", Match::Prefix, Meaning::Optimize),
+                FlagDefinition::new("-g", Match::Prefix, Meaning::Debug),
+                FlagDefinition::new("-f", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("-m", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("-p", Match::Prefix, Meaning::Instrumentation),
+                FlagDefinition::new("-pedantic", Match::Prefix, Meaning::Diagnostic),
+                FlagDefinition::new("-w", Match::Exactly, Meaning::Diagnostic),
+                FlagDefinition::new("-W", Match::Prefix, Meaning::Diagnostic),
+                FlagDefinition::new("-no", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("-tno", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("-save", Match::Prefix, Meaning::Developer),
+                FlagDefinition::new("-d", Match::Prefix, Meaning::Developer),
+                FlagDefinition::new("-E", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("-Q", Match::Prefix, Meaning::Developer),
+                FlagDefinition::new("-X", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("-Y", Match::Prefix, Meaning::Other),
+                FlagDefinition::new("--", Match::Prefix, Meaning::Other),
+            ];
+            definitions.sort_by_key(|definition| std::cmp::Reverse(definition.flag.len()));
+            definitions
+        };
+
+        // The environment variable references, like `$SYSROOT` or `${SYSROOT}`.
+        static ref VARIABLE_REGEX: Regex =
+            Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}|\$([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+
         // - cc
         // - c++
         // - cxx
         // - CC
-        // - mcc, gcc, m++, g++, gfortran, fortran
+        // - mcc, gcc, m++, g++, gfortran, fortran, clang, clang++
         //   - with prefixes like: arm-none-eabi-
         //   - with postfixes like: -7.0 or 6.4.0
+        // - the AFL fuzzing wrappers (like afl-gcc, afl-clang-fast, afl-clang-lto++)
+        // - the MPI wrappers (mpicc, mpicxx, mpic++, mpiCC)
         static ref COMPILER_REGEX: Regex = Regex::new(
-            r"(^(cc|c\+\+|cxx|CC|(([^-]*-)*([mg](cc|\+\+)|[g]?fortran)(-?\d+(\.\d+){0,2})?))$)"
+            r"(^(cc|c\+\+|cxx|CC|(([^-]*-)*([mg](cc|\+\+)|[g]?fortran|clang(\+\+)?)(-?\d+(\.\d+){0,2})?)|afl-(cc|c\+\+|clang(-fast|-lto)?(\+\+)?|g(cc|\+\+)(-fast)?)|mpi(cc|cxx|c\+\+|CC))$)"
         ).unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::super::super::{CompilerPass, Language};
+    use super::*;
+    use crate::fixtures::fixtures::capture_warnings;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_compilation_with_param_flags() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc",
+                "--param",
+                "max-inline-insns-single=500",
+                "--param=inline-unit-growth=100",
+                "-c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings![
+                    "--param",
+                    "max-inline-insns-single=500",
+                    "--param=inline-unit-growth=100",
+                    "-c"
+                ],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_dump_flags() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc",
+                "-dumpbase",
+                "foo",
+                "-dumpdir",
+                "aux/",
+                "-c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-dumpbase", "foo", "-dumpdir", "aux/", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_implied_output_replaces_final_extension() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "src/foo.generated.cpp"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerPass::Compile {
+            source: PathBuf::from("src/foo.generated.cpp"),
+            output: Some(PathBuf::from("foo.generated.o")),
+            flags: vec_of_strings!["-c"],
+            output_implied: true,
+        };
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                assert_eq!(vec![expected], passes);
+                assert_eq!(Some(Language::Cpp), passes[0].language());
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_mixed_language_sources() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "a.c", "b.cpp", "-c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                let languages: Vec<_> = passes.iter().map(CompilerPass::language).collect();
+                assert_eq!(vec![Some(Language::C), Some(Language::Cpp)], languages);
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_language_override_applies_until_reset() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "-x", "c++", "a.c", "b.h", "-x", "none", "c.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = vec![
+            CompilerPass::Compile {
+                source: PathBuf::from("a.c"),
+                output: Some(PathBuf::from("a.o")),
+                flags: vec_of_strings!["-c", "-x", "c++"],
+                output_implied: true,
+            },
+            CompilerPass::Compile {
+                source: PathBuf::from("b.h"),
+                output: Some(PathBuf::from("b.o")),
+                flags: vec_of_strings!["-c", "-x", "c++"],
+                output_implied: true,
+            },
+            CompilerPass::Compile {
+                source: PathBuf::from("c.c"),
+                output: Some(PathBuf::from("c.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: true,
+            },
+        ];
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                assert_eq!(expected, passes);
+                let languages: Vec<_> = passes.iter().map(CompilerPass::language).collect();
+                assert_eq!(
+                    vec![Some(Language::Cpp), Some(Language::Cpp), Some(Language::C)],
+                    languages
+                );
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_flags_after_the_source_are_kept() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc",
+                "foo.c",
+                "-c",
+                "-std=c++20",
+                "-DFOO=1",
+                "-fno-rtti",
+                "-o",
+                "foo.o"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = vec![CompilerPass::Compile {
+            source: PathBuf::from("foo.c"),
+            output: Some(PathBuf::from("foo.o")),
+            flags: vec_of_strings!["-c", "-std=c++20", "-DFOO=1", "-fno-rtti"],
+            output_implied: false,
+        }];
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => assert_eq!(expected, passes),
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+
+        // The repeated flags are kept, the later ones are overriding the earlier ones.
+        let input = Execution {
+            arguments: vec_of_strings![
+                "gcc",
+                "-DFOO=1",
+                "-fsanitize=address",
+                "foo.c",
+                "-march=native",
+                "-UFOO",
+                "-DFOO=2",
+                "-Wall",
+                "-fno-exceptions",
+                "-fexceptions",
+                "-c"
+            ],
+            ..input
+        };
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => assert_eq!(
+                vec![CompilerPass::Compile {
+                    source: PathBuf::from("foo.c"),
+                    output: Some(PathBuf::from("foo.o")),
+                    flags: vec_of_strings![
+                        "-DFOO=1",
+                        "-fsanitize=address",
+                        "-march=native",
+                        "-UFOO",
+                        "-DFOO=2",
+                        "-Wall",
+                        "-fno-exceptions",
+                        "-fexceptions",
+                        "-c"
+                    ],
+                    output_implied: true,
+                }],
+                passes
+            ),
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_language_override_is_switched_between_sources() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc", "-c", "-x", "c", "a.c", "-xc++", "b.cpp", "-x", "none", "c.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                let languages: Vec<_> = passes.iter().map(CompilerPass::language).collect();
+                assert_eq!(
+                    vec![Some(Language::C), Some(Language::Cpp), Some(Language::C)],
+                    languages
+                );
+                let flags: Vec<_> = passes
+                    .iter()
+                    .map(|pass| match pass {
+                        CompilerPass::Compile { flags, .. } => flags.clone(),
+                        _ => vec![],
+                    })
+                    .collect();
+                assert_eq!(
+                    vec![
+                        vec_of_strings!["-c", "-x", "c"],
+                        vec_of_strings!["-c", "-x", "c++"],
+                        vec_of_strings!["-c"],
+                    ],
+                    flags
+                );
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_language_override_is_kept_for_any_extension() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "-x", "c", "a.cpp", "generated.inc"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = vec![
+            CompilerPass::Compile {
+                source: PathBuf::from("a.cpp"),
+                output: Some(PathBuf::from("a.o")),
+                flags: vec_of_strings!["-c", "-x", "c"],
+                output_implied: true,
+            },
+            CompilerPass::Compile {
+                source: PathBuf::from("generated.inc"),
+                output: Some(PathBuf::from("generated.o")),
+                flags: vec_of_strings!["-c", "-x", "c"],
+                output_implied: true,
+            },
+        ];
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                assert_eq!(expected, passes);
+                let languages: Vec<_> = passes.iter().map(CompilerPass::language).collect();
+                assert_eq!(vec![Some(Language::C), Some(Language::C)], languages);
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_compilation_with_target_triple() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments: vec_of_strings![
+                "clang",
+                "-target",
+                "riscv64-unknown-elf",
+                "--target=riscv64-unknown-elf",
+                "-c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings![
+                    "-target",
+                    "riscv64-unknown-elf",
+                    "--target=riscv64-unknown-elf",
+                    "-c"
+                ],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_compilation_with_drive_absolute_paths() {
+        let input = Execution {
+            executable: PathBuf::from(r"C:\mingw\bin\gcc.exe"),
+            arguments: vec_of_strings![
+                r"C:\mingw\bin\gcc.exe",
+                r"-IC:\include",
+                "-I",
+                r"D:\other\include",
+                "-c",
+                r"C:\src\foo.cpp",
+                "-o",
+                r"C:\build\foo.o"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from(r"C:\build"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from(r"C:\mingw\bin\gcc.exe"),
+            working_dir: PathBuf::from(r"C:\build"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from(r"C:\src\foo.cpp"),
+                output: Some(PathBuf::from(r"C:\build\foo.o")),
+                flags: vec_of_strings![r"-IC:\include", "-I", r"D:\other\include", "-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_frontend_compilation() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments: vec_of_strings![
+                "/usr/bin/clang",
+                "-cc1",
+                "-triple",
+                "x86_64-pc-linux-gnu",
+                "-emit-obj",
+                "-main-file-name",
+                "foo.c",
+                "-internal-isystem",
+                "/usr/lib/clang/17/include",
+                "-o",
+                "/tmp/foo-1a2b3c.o",
+                "-x",
+                "c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("/tmp/foo-1a2b3c.o")),
+                flags: vec_of_strings![
+                    "-cc1",
+                    "-triple",
+                    "x86_64-pc-linux-gnu",
+                    "-emit-obj",
+                    "-main-file-name",
+                    "foo.c",
+                    "-internal-isystem",
+                    "/usr/lib/clang/17/include",
+                    "-x",
+                    "c"
+                ],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_include_path_divider() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-I", "foo", "-I-", "-I", "bar", "-c", "main.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("main.c"),
+                output: Some(PathBuf::from("main.o")),
+                flags: vec_of_strings!["-I", "foo", "-I-", "-I", "bar", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_source_also_force_included() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-include", "foo.c", "-c", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-include", "foo.c", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_flag_values_are_not_taken_as_sources() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/g++"),
+            arguments: vec_of_strings![
+                "g++",
+                "-isystem",
+                "/opt/qt/include",
+                "-I/opt/qt/include/QtCore",
+                "-iquote",
+                "src/private.h",
+                "-Xclang",
+                "generated.cpp",
+                "-Xpreprocessor",
+                "extra.c",
+                "-include",
+                "prefix.h",
+                "-MT",
+                "stale.c",
+                "-c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/g++"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings![
+                    "-isystem",
+                    "/opt/qt/include",
+                    "-I/opt/qt/include/QtCore",
+                    "-iquote",
+                    "src/private.h",
+                    "-Xclang",
+                    "generated.cpp",
+                    "-Xpreprocessor",
+                    "extra.c",
+                    "-include",
+                    "prefix.h",
+                    "-MT",
+                    "stale.c",
+                    "-c"
+                ],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_forced_includes_keep_their_order() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc",
+                "-include",
+                "b.h",
+                "-Iinclude",
+                "-include",
+                "a.h",
+                "-c",
+                "main.c",
+                "-DNDEBUG"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let call = match SUT.recognize(&input) {
+            Recognition::Success(call) => call,
+            result => panic!("unexpected recognition: {:?}", result),
+        };
+        let entries = crate::output::into_entries(call).unwrap();
+
+        // The flags are emitted in the order of the original command, before the source.
+        assert_eq!(1, entries.len());
+        assert_eq!(
+            vec_of_strings![
+                "/usr/bin/gcc",
+                "-include",
+                "b.h",
+                "-Iinclude",
+                "-include",
+                "a.h",
+                "-c",
+                "-DNDEBUG",
+                "main.c"
+            ],
+            entries[0].arguments
+        );
+    }
+
+    #[test]
+    fn test_compilation_with_multiple_sources() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+        let expected = |outputs: [Option<&str>; 2]| {
+            Recognition::Success(CompilerCall {
+                compiler: PathBuf::from("/usr/bin/gcc"),
+                working_dir: PathBuf::from("/home/user"),
+                passes: ["src/a.c", "src/b.c"]
+                    .iter()
+                    .zip(outputs)
+                    .map(|(source, output)| CompilerPass::Compile {
+                        source: PathBuf::from(source),
+                        output: output.map(PathBuf::from),
+                        flags: vec_of_strings!["-Wall", "-c", "-O2"],
+                        output_implied: output.is_some(),
+                    })
+                    .collect(),
+                original_compiler: None,
+                environment: None,
+            })
+        };
+
+        assert_eq!(
+            expected([Some("a.o"), Some("b.o")]),
+            SUT.recognize(&execution(vec_of_strings![
+                "gcc", "-Wall", "-c", "src/a.c", "-O2", "src/b.c"
+            ]))
+        );
+        // The output can't be the same for all sources.
+        assert_eq!(
+            expected([Some("a.o"), Some("b.o")]),
+            SUT.recognize(&execution(vec_of_strings![
+                "gcc", "-Wall", "-c", "-o", "out.o", "src/a.c", "-O2", "src/b.c"
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_compilation_with_plugin_directory() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-iplugindir=/opt/plugins", "-c", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-iplugindir=/opt/plugins", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_clang_modules() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/clang++"),
+            arguments: vec_of_strings![
+                "clang++",
+                "-fmodules",
+                "-fcxx-modules",
+                "-fmodules-cache-path=/tmp/mc",
+                "-fimplicit-module-maps",
+                "-c",
+                "foo.cpp"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        // The module cache path is a single flag, and the module flags are kept.
+        let expected = CompilerPass::Compile {
+            source: PathBuf::from("foo.cpp"),
+            output: Some(PathBuf::from("foo.o")),
+            flags: vec_of_strings![
+                "-fmodules",
+                "-fcxx-modules",
+                "-fmodules-cache-path=/tmp/mc",
+                "-fimplicit-module-maps",
+                "-c"
+            ],
+            output_implied: true,
+        };
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                assert_eq!(vec![expected], passes)
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_compilation_with_per_architecture_flags() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments: vec_of_strings![
+                "clang",
+                "-Xarch_arm64",
+                "-DARM",
+                "-arch",
+                "arm64",
+                "-arch",
+                "x86_64",
+                "-c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings![
+                    "-Xarch_arm64",
+                    "-DARM",
+                    "-arch",
+                    "arm64",
+                    "-arch",
+                    "x86_64",
+                    "-c"
+                ],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_assembly_generation_implied_output() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-S", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.s")),
+                flags: vec_of_strings!["-S"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_dry_run_is_ignored() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        assert_eq!(
+            Recognition::Ignored,
+            SUT.recognize(&execution(vec_of_strings!["clang", "-###", "-c", "foo.c"]))
+        );
+        assert_eq!(
+            Recognition::Ignored,
+            SUT.recognize(&execution(vec_of_strings!["clang", "-c", "foo.c", "-###"]))
+        );
+    }
+
+    #[test]
+    fn test_kind_of_output_decides_the_pass() {
+        let recognize = |flag: &str| {
+            SUT.recognize(&Execution {
+                executable: PathBuf::from("/usr/bin/gcc"),
+                arguments: vec_of_strings!["gcc", flag, "foo.c"],
+                environment: HashMap::new(),
+                working_dir: PathBuf::from("/home/user"),
+            })
+        };
+        let expected = |passes: Vec<CompilerPass>| {
+            Recognition::Success(CompilerCall {
+                compiler: PathBuf::from("/usr/bin/gcc"),
+                working_dir: PathBuf::from("/home/user"),
+                passes,
+                original_compiler: None,
+                environment: None,
+            })
+        };
+        let compile = |flag: &str, output: &str| CompilerPass::Compile {
+            source: PathBuf::from("foo.c"),
+            output: Some(PathBuf::from(output)),
+            flags: vec_of_strings![flag],
+            output_implied: true,
+        };
+
+        assert_eq!(expected(vec![compile("-c", "foo.o")]), recognize("-c"));
+        assert_eq!(expected(vec![compile("-S", "foo.s")]), recognize("-S"));
+        assert_eq!(expected(vec![CompilerPass::Preprocess]), recognize("-E"));
+    }
+
+    #[test]
+    fn test_debug_flags_are_not_taking_value() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-g3", "-gdwarf-5", "-g", "foo.c", "-c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-g3", "-gdwarf-5", "-g", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_rewrite_includes_preprocessing() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+        let expected = |passes: Vec<CompilerPass>| {
+            Recognition::Success(CompilerCall {
+                compiler: PathBuf::from("/usr/bin/clang"),
+                working_dir: PathBuf::from("/home/user"),
+                passes,
+                original_compiler: None,
+                environment: None,
+            })
+        };
+
+        assert_eq!(
+            expected(vec![CompilerPass::Preprocess]),
+            SUT.recognize(&execution(vec_of_strings![
+                "clang",
+                "-E",
+                "-frewrite-includes",
+                "foo.c"
+            ]))
+        );
+        assert_eq!(
+            expected(vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-frewrite-imports", "-c"],
+                output_implied: true,
+            }]),
+            SUT.recognize(&execution(vec_of_strings![
+                "clang",
+                "-frewrite-imports",
+                "-c",
+                "foo.c"
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_precompiled_header_implied_output() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from(&arguments[0]),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+        let expected = |compiler: &str, source: &str, output: &str, flags: Vec<String>| {
+            Recognition::Success(CompilerCall {
+                compiler: PathBuf::from(compiler),
+                working_dir: PathBuf::from("/home/user"),
+                passes: vec![CompilerPass::Compile {
+                    source: PathBuf::from(source),
+                    output: Some(PathBuf::from(output)),
+                    flags,
+                    output_implied: true,
+                }],
+                original_compiler: None,
+                environment: None,
+            })
+        };
+
+        assert_eq!(
+            expected(
+                "clang",
+                "foo.h",
+                "foo.h.pch",
+                vec_of_strings!["-x", "c++-header"]
+            ),
+            SUT.recognize(&execution(vec_of_strings![
+                "clang",
+                "-x",
+                "c++-header",
+                "foo.h"
+            ]))
+        );
+        assert_eq!(
+            expected(
+                "g++",
+                "include/foo.hpp",
+                "include/foo.hpp.gch",
+                vec_of_strings![]
+            ),
+            SUT.recognize(&execution(vec_of_strings!["g++", "include/foo.hpp"]))
+        );
+    }
+
+    #[test]
+    fn test_header_compiled_as_translation_unit() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let input = execution(vec_of_strings![
+            "gcc", "-x", "c", "-c", "util.h", "-o", "util.o"
+        ]);
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                assert_eq!(
+                    vec![CompilerPass::Compile {
+                        source: PathBuf::from("util.h"),
+                        output: Some(PathBuf::from("util.o")),
+                        flags: vec_of_strings!["-c", "-x", "c"],
+                        output_implied: false,
+                    }],
+                    passes
+                );
+                assert_eq!(Some(Language::C), passes[0].language());
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+
+        // Without the language, the header is dropped (unless it's configured to keep).
+        let input = execution(vec_of_strings!["gcc", "-c", "util.h", "-o", "util.o"]);
+        assert_eq!(Recognition::Unknown, SUT.recognize(&input));
+        let expected = Recognition::Success(CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("util.h"),
+                output: Some(PathBuf::from("util.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        });
+        assert_eq!(
+            expected,
+            Gcc {
+                keep_ambiguous_headers: true,
+                expand_environment: false,
+            }
+            .recognize(&input)
+        );
+    }
+
+    #[test]
+    fn test_environment_references_are_expanded_in_paths() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc",
+                "-I$SYSROOT/include",
+                "-isystem",
+                "${SYSROOT}/usr/include",
+                "-I$UNDEFINED/include",
+                "-DPRICE=$5",
+                "-c",
+                "$SRC/main.c",
+                "-o",
+                "${OBJ}main.o"
+            ],
+            environment: HashMap::from([
+                ("SYSROOT".to_string(), "/opt/sysroot".to_string()),
+                ("SRC".to_string(), "src".to_string()),
+                ("OBJ".to_string(), "build/".to_string()),
+            ]),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("src/main.c"),
+                output: Some(PathBuf::from("build/main.o")),
+                flags: vec_of_strings![
+                    "-I/opt/sysroot/include",
+                    "-isystem",
+                    "/opt/sysroot/usr/include",
+                    "-I$UNDEFINED/include",
+                    "-DPRICE=$5",
+                    "-c"
+                ],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let sut = Gcc {
+            keep_ambiguous_headers: false,
+            expand_environment: true,
+        };
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+
+        // It's disabled by default.
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                assert!(matches!(
+                    &passes[..],
+                    [CompilerPass::Compile { source, .. }] if source == &PathBuf::from("$SRC/main.c")
+                ));
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_linking_only_is_ignored() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-o", "app", "a.o", "b.o", "-L/opt/lib", "-lm"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        assert_eq!(Recognition::Ignored, SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_and_linking_is_recognized() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-O2", "-o", "app", "a.c", "b.o", "-lm"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("a.c"),
+                output: Some(PathBuf::from("app")),
+                flags: vec_of_strings!["-O2"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_without_source_is_not_recognized() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "-o", "foo.o"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        assert_eq!(Recognition::Unknown, SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_compilation_database_fragment() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments: vec_of_strings!["clang", "-MJ", "cmd.json", "-c", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-MJ", "cmd.json", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_repeated_output_flags() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/cc"),
+            arguments: vec_of_strings!["cc", "-c", "-o", "a.o", "foo.c", "-ob.o"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("b.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let messages = capture_warnings(|| {
+            assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+        });
+        assert!(messages
+            .iter()
+            .any(|message| message.contains("\"a.o\" is overridden by \"b.o\"")));
+    }
+
+    #[test]
+    fn test_compilation_with_output_and_linker_flags() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/cc"),
+            arguments: vec_of_strings![
+                "/usr/bin/cc",
+                "-I",
+                "include",
+                "-DNDEBUG",
+                "-MD",
+                "-MF",
+                "deps.d",
+                "foo.c",
+                "-o",
+                "foo",
+                "-L/opt/lib",
+                "-lm",
+                "bar.o"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/cc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo")),
+                flags: vec_of_strings!["-I", "include", "-DNDEBUG", "-MD", "-MF", "deps.d"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_dependency_file_is_not_the_output() {
+        let output_of = |arguments: Vec<String>| match SUT.recognize(&Execution {
+            executable: PathBuf::from("/usr/bin/cc"),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        }) {
+            Recognition::Success(CompilerCall { passes, .. }) => match &passes[..] {
+                [CompilerPass::Compile { output, .. }] => output.clone(),
+                passes => panic!("unexpected passes: {:?}", passes),
+            },
+            result => panic!("unexpected recognition: {:?}", result),
+        };
+
+        // The output is the object file, regardless of the order of the flags.
+        let expected = Some(PathBuf::from("foo.o"));
+        assert_eq!(
+            expected,
+            output_of(vec_of_strings![
+                "cc", "-c", "-MD", "-MF", "foo.d", "-o", "foo.o", "foo.c"
+            ])
+        );
+        assert_eq!(
+            expected,
+            output_of(vec_of_strings![
+                "cc", "-c", "-o", "foo.o", "-MMD", "-MFfoo.d", "foo.c"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compiler_names() {
+        let execution = |program: &str| Execution {
+            executable: PathBuf::from(program),
+            arguments: vec_of_strings![program, "-c", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        for program in [
+            "cc",
+            "/usr/bin/c++",
+            "gcc-13",
+            "arm-none-eabi-g++",
+            "clang",
+            "clang++-17",
+            "gcc.exe",
+        ] {
+            match SUT.recognize(&execution(program)) {
+                Recognition::Success(_) => {}
+                result => panic!("{} is not recognized: {:?}", program, result),
+            }
+        }
+        assert_eq!(Recognition::Unknown, SUT.recognize(&execution("cc1plus")));
+    }
+
+    #[test]
+    fn test_compilation_with_afl_wrapper() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/local/bin/afl-clang-fast"),
+            arguments: vec_of_strings![
+                "afl-clang-fast",
+                "-fsanitize=fuzzer-no-link",
+                "-c",
+                "foo.c",
+                "-o",
+                "foo.o"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/local/bin/afl-clang-fast"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-fsanitize=fuzzer-no-link", "-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+
+        for program in [
+            "afl-gcc",
+            "afl-g++-fast",
+            "afl-clang-lto",
+            "afl-clang-fast++",
+        ] {
+            let input = Execution {
+                executable: PathBuf::from(program),
+                arguments: vec_of_strings![program, "-c", "foo.c"],
+                ..input.clone()
+            };
+            match SUT.recognize(&input) {
+                Recognition::Success(_) => {}
+                result => panic!("{} is not recognized: {:?}", program, result),
+            }
+        }
+        assert_eq!(
+            Recognition::Unknown,
+            SUT.recognize(&Execution {
+                executable: PathBuf::from("afl-fuzz"),
+                arguments: vec_of_strings!["afl-fuzz", "-i", "in", "-o", "out"],
+                ..input
+            })
+        );
+    }
+
+    #[test]
+    fn test_compilation_with_mpi_wrapper() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/mpicc"),
+            arguments: vec_of_strings!["mpicc", "-c", "foo.c", "-o", "foo.o"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/mpicc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+
+        for program in ["mpicxx", "mpic++", "mpiCC"] {
+            let input = Execution {
+                executable: PathBuf::from(program),
+                arguments: vec_of_strings![program, "-c", "foo.cpp"],
+                ..input.clone()
+            };
+            match SUT.recognize(&input) {
+                Recognition::Success(_) => {}
+                result => panic!("{} is not recognized: {:?}", program, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_not_a_compiler() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/ld"),
+            arguments: vec_of_strings!["ld", "-o", "foo", "foo.o"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        assert_eq!(Recognition::Unknown, SUT.recognize(&input));
+    }
+
+    const SUT: Gcc = Gcc {
+        keep_ambiguous_headers: false,
+        expand_environment: false,
+    };
+}
@@ -1,37 +1,70 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use regex::Regex;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::vec;
 
 use super::super::{CompilerCall, CompilerPass, Execution, Interpreter, Recognition};
+use super::matchers::executable::matches_executable;
+use super::matchers::input::input_of;
 use super::matchers::source::looks_like_a_source_file;
 
 /// A tool to recognize a compiler by executable name.
 pub(super) struct Generic {
     executables: HashSet<PathBuf>,
+    patterns: Vec<Regex>,
+    inputs: Vec<String>,
 }
 
 impl Generic {
     pub(super) fn from(compilers: &[PathBuf]) -> Box<dyn Interpreter> {
+        Self::with_inputs(compilers, &[])
+    }
+
+    /// Creates a tool to recognize the compilers, which are taking (some of) the
+    /// sources by the given input flags. (See the `input_of` matcher for the format.)
+    pub(super) fn with_inputs(compilers: &[PathBuf], inputs: &[String]) -> Box<dyn Interpreter> {
         let executables = compilers.iter().map(|compiler| compiler.clone()).collect();
-        Box::new(Self { executables })
+        Box::new(Self {
+            executables,
+            patterns: vec![],
+            inputs: inputs.to_vec(),
+        })
+    }
+
+    /// Creates a tool to recognize the compilers, which are matching the patterns.
+    pub(super) fn matching(patterns: &[Regex]) -> Box<dyn Interpreter> {
+        Box::new(Self {
+            executables: HashSet::new(),
+            patterns: patterns.to_vec(),
+            inputs: vec![],
+        })
     }
 }
 
 impl Interpreter for Generic {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
     /// This tool is a naive implementation only considering:
     /// - the executable name,
-    /// - one of the arguments is a source file,
+    /// - one of the arguments is a source file (or the value of an input flag),
     /// - the rest of the arguments are flags.
     fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
-        if self.executables.contains(&x.executable) {
+        if self.executables.contains(&x.executable)
+            || matches_executable(&self.patterns, &x.executable)
+        {
             let mut flags = vec![];
             let mut sources = vec![];
 
             // find sources and filter out requested flags.
-            for argument in x.arguments.iter().skip(1) {
-                if looks_like_a_source_file(argument.as_str()) {
+            let mut arguments = x.arguments.iter().skip(1);
+            while let Some(argument) = arguments.next() {
+                if let Some(source) = input_of(&self.inputs, argument, &mut arguments) {
+                    sources.push(PathBuf::from(source));
+                } else if looks_like_a_source_file(argument.as_str()) {
                     sources.push(PathBuf::from(argument));
                 } else {
                     flags.push(argument.clone());
@@ -50,8 +83,11 @@ impl Interpreter for Generic {
                             source: source.clone(),
                             output: None,
                             flags: flags.clone(),
+                            output_implied: false,
                         })
                         .collect(),
+                    original_compiler: None,
+                    environment: None,
                 })
             }
         } else {
@@ -93,7 +129,10 @@ mod test {
                 flags: vec_of_strings!["-Dthis=that", "-I.", "-o", "source.c.o"],
                 source: PathBuf::from("source.c"),
                 output: None,
+                output_implied: false,
             }],
+            original_compiler: None,
+            environment: None,
         };
 
         assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
@@ -128,7 +167,9 @@ mod test {
 
     lazy_static! {
         static ref SUT: Generic = Generic {
-            executables: vec_of_pathbuf!["/usr/bin/something"].into_iter().collect()
+            executables: vec_of_pathbuf!["/usr/bin/something"].into_iter().collect(),
+            patterns: vec![],
+            inputs: vec![],
         };
     }
 }
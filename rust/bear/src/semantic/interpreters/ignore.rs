@@ -1,31 +1,128 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use regex::Regex;
 use std::collections::HashSet;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 use super::super::{CompilerCall, Execution, Interpreter, Recognition};
+use super::matchers::executable::matches_executable;
+use crate::intercept::KEY_DESTINATION;
 
 /// A tool to ignore a command execution by executable name.
 pub(super) struct IgnoreByPath {
     executables: HashSet<PathBuf>,
+    patterns: Vec<Regex>,
 }
 
 impl IgnoreByPath {
     pub(super) fn new() -> Box<dyn Interpreter> {
         let executables = COREUTILS_FILES.iter().map(PathBuf::from).collect();
-        Box::new(Self { executables })
+        Box::new(Self {
+            executables,
+            patterns: vec![],
+        })
     }
 
     pub(super) fn from(compilers: &[PathBuf]) -> Box<dyn Interpreter> {
         let executables = compilers.iter().map(|compiler| compiler.clone()).collect();
-        Box::new(Self { executables })
+        Box::new(Self {
+            executables,
+            patterns: vec![],
+        })
+    }
+
+    /// Creates a tool to ignore the executables, which are matching the patterns.
+    pub(super) fn matching(patterns: &[Regex]) -> Box<dyn Interpreter> {
+        Box::new(Self {
+            executables: HashSet::new(),
+            patterns: patterns.to_vec(),
+        })
     }
 }
 
 /// A tool to ignore a command execution by arguments.
 impl Interpreter for IgnoreByPath {
+    fn name(&self) -> &'static str {
+        "ignore-by-path"
+    }
+
+    fn recognize(&self, execution: &Execution) -> Recognition<CompilerCall> {
+        if self.executables.contains(&execution.executable)
+            || matches_executable(&self.patterns, &execution.executable)
+        {
+            Recognition::Ignored
+        } else {
+            Recognition::Unknown
+        }
+    }
+}
+
+/// A tool to ignore a command execution by the file name of the executable.
+///
+/// It's for the tools, which are installed into various locations. (Like the
+/// JavaScript package managers and build tools, which are orchestrating the
+/// builds of the native addons. Or the Rust build tools and the build scripts of
+/// the crates, which are compiling C code with the `cc` crate. The compiler calls
+/// made by them are recognized on their own.)
+pub(super) struct IgnoreByName {
+    names: HashSet<OsString>,
+}
+
+impl IgnoreByName {
+    pub(super) fn new() -> Box<dyn Interpreter> {
+        let names = NODE_FILES
+            .iter()
+            .chain(RUST_FILES.iter())
+            .map(OsString::from)
+            .collect();
+        Box::new(Self { names })
+    }
+}
+
+impl Interpreter for IgnoreByName {
+    fn name(&self) -> &'static str {
+        "ignore-by-name"
+    }
+
+    fn recognize(&self, execution: &Execution) -> Recognition<CompilerCall> {
+        match execution.executable.file_name() {
+            Some(name) if self.names.contains(name) => Recognition::Ignored,
+            _ => Recognition::Unknown,
+        }
+    }
+}
+
+/// A tool to ignore the executions of our own wrapper.
+///
+/// In nested builds (a build which runs another build), the wrapper might be
+/// called as if it were the compiler. These are not compiler calls, the real
+/// compiler execution is reported by the wrapper itself. The execution is from
+/// the wrapper when the reporter address is in the environment, and the executable
+/// is the wrapper or one of the links in the wrapper directory.
+pub(super) struct IgnoreOwnWrapper {
+    executable: PathBuf,
+    directory: PathBuf,
+}
+
+impl IgnoreOwnWrapper {
+    pub(super) fn new(executable: PathBuf, directory: PathBuf) -> Box<dyn Interpreter> {
+        Box::new(Self {
+            executable,
+            directory,
+        })
+    }
+}
+
+impl Interpreter for IgnoreOwnWrapper {
+    fn name(&self) -> &'static str {
+        "own-wrapper"
+    }
+
     fn recognize(&self, execution: &Execution) -> Recognition<CompilerCall> {
-        if self.executables.contains(&execution.executable) {
+        let is_wrapper = execution.executable == self.executable
+            || execution.executable.parent() == Some(self.directory.as_path());
+        if is_wrapper && execution.environment.contains_key(KEY_DESTINATION) {
             Recognition::Ignored
         } else {
             Recognition::Unknown
@@ -142,6 +239,10 @@ static COREUTILS_FILES: [&str; 106] = [
     "/usr/bin/gmake",
 ];
 
+static NODE_FILES: [&str; 6] = ["node", "npm", "npx", "pnpm", "yarn", "node-gyp"];
+
+static RUST_FILES: [&str; 4] = ["cargo", "rustc", "rustdoc", "build-script-build"];
+
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
@@ -176,4 +277,69 @@ mod test {
 
         assert_eq!(Recognition::Unknown, sut.recognize(&input))
     }
+
+    #[test]
+    fn test_node_gyp_is_ignored() {
+        let input = Execution {
+            executable: PathBuf::from("/home/user/.nvm/versions/node/v20.11.0/bin/node-gyp"),
+            arguments: vec_of_strings!["node-gyp", "rebuild"],
+            working_dir: PathBuf::from("/home/user/addon"),
+            environment: HashMap::new(),
+        };
+        let sut = IgnoreByName::new();
+
+        assert_eq!(Recognition::Ignored, sut.recognize(&input));
+        // The compiler calls are not ignored.
+        let compiler = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "addon.cc"],
+            ..input
+        };
+        assert_eq!(Recognition::Unknown, sut.recognize(&compiler));
+    }
+
+    #[test]
+    fn test_cargo_build_script_is_ignored() {
+        let input = Execution {
+            executable: PathBuf::from(
+                "/home/user/crate/target/debug/build/shim-2f7d6c1e9a4b8c03/build-script-build",
+            ),
+            arguments: vec_of_strings!["build-script-build"],
+            working_dir: PathBuf::from("/home/user/crate"),
+            environment: HashMap::new(),
+        };
+        let sut = IgnoreByName::new();
+
+        assert_eq!(Recognition::Ignored, sut.recognize(&input));
+        let cargo = Execution {
+            executable: PathBuf::from("/home/user/.cargo/bin/cargo"),
+            arguments: vec_of_strings!["cargo", "build"],
+            ..input
+        };
+        assert_eq!(Recognition::Ignored, sut.recognize(&cargo));
+    }
+
+    #[test]
+    fn test_own_wrapper_is_ignored_only_with_reporter_address() {
+        let reported = Execution {
+            executable: PathBuf::from("/tmp/bear/wrappers/cc"),
+            arguments: vec_of_strings!["cc", "-c", "main.c"],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::from([(
+                KEY_DESTINATION.to_string(),
+                "127.0.0.1:12345".to_string(),
+            )]),
+        };
+        let not_reported = Execution {
+            environment: HashMap::new(),
+            ..reported.clone()
+        };
+        let sut = IgnoreOwnWrapper::new(
+            PathBuf::from("/usr/libexec/bear/wrapper"),
+            PathBuf::from("/tmp/bear/wrappers"),
+        );
+
+        assert_eq!(Recognition::Ignored, sut.recognize(&reported));
+        assert_eq!(Recognition::Unknown, sut.recognize(&not_reported));
+    }
 }
@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use regex::Regex;
+use std::path::Path;
+
+/// Returns true, if any of the patterns matches the executable.
+///
+/// The patterns are matched against the full path and against the file name
+/// of the executable. (Like `.*-gcc-[0-9]+` matches the cross compilers from
+/// any directory, while `^/opt/.*` matches only the installed ones.)
+pub fn matches_executable(patterns: &[Regex], executable: &Path) -> bool {
+    let path = executable.to_string_lossy();
+    let name = executable
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    patterns
+        .iter()
+        .any(|pattern| pattern.is_match(&path) || pattern.is_match(&name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matching_by_path_and_name() {
+        let patterns = [
+            Regex::new(r"^gcc-[0-9]+$").unwrap(),
+            Regex::new(r"^/opt/clang/").unwrap(),
+        ];
+
+        assert!(matches_executable(&patterns, Path::new("/usr/bin/gcc-13")));
+        assert!(matches_executable(
+            &patterns,
+            Path::new("/opt/clang/bin/clang")
+        ));
+        assert!(!matches_executable(&patterns, Path::new("/usr/bin/gcc")));
+        assert!(!matches_executable(&patterns, Path::new("/usr/bin/clang")));
+    }
+}
@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+/// Returns the source file, when the argument is one of the input flags.
+///
+/// The input flags are declaring the sources, which are not given as positional
+/// arguments. The flags with `=` suffix are taking the value joined to them (like
+/// `--input=foo.c`), the others are taking the next argument (like `--input foo.c`).
+pub fn input_of<'a>(
+    flags: &[String],
+    argument: &str,
+    arguments: &mut impl Iterator<Item = &'a String>,
+) -> Option<String> {
+    flags.iter().find_map(|flag| {
+        if flag.ends_with('=') {
+            argument
+                .strip_prefix(flag.as_str())
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        } else if argument == flag {
+            arguments.next().cloned()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_joined_and_separate_inputs() {
+        let flags = vec_of_strings!["--input=", "-c=", "--source"];
+        let arguments = vec_of_strings!["foo.c", "-o", "foo.o"];
+
+        let mut rest = arguments.iter();
+        assert_eq!(
+            Some(String::from("foo.c")),
+            input_of(&flags, "--input=foo.c", &mut rest)
+        );
+        assert_eq!(
+            Some(String::from("bar.c")),
+            input_of(&flags, "-c=bar.c", &mut rest)
+        );
+        assert_eq!(
+            Some(String::from("foo.c")),
+            input_of(&flags, "--source", &mut rest)
+        );
+        assert_eq!(Some(&String::from("-o")), rest.next());
+        // The flags without value, or not declared ones are not inputs.
+        assert_eq!(None, input_of(&flags, "--input=", &mut rest));
+        assert_eq!(None, input_of(&flags, "-c", &mut rest));
+        assert_eq!(Some(&String::from("foo.o")), rest.next());
+    }
+}
@@ -1,3 +1,5 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub(super) mod executable;
+pub(super) mod input;
 pub(super) mod source;
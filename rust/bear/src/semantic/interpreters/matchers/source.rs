@@ -37,7 +37,7 @@ lazy_static! {
             // C++
             "cc", "CC", "c++", "C++", "cxx", "cpp", "cp",
             // CUDA
-            "cu",
+            "cu", "cuh",
             // ObjectiveC
             "m", "mi", "mm", "M", "mii",
             // Preprocessed
@@ -1,41 +1,93 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::path::PathBuf;
+use regex::Regex;
+use std::path::{Path, PathBuf};
 
 use super::interpreters::combinators::Any;
+use super::interpreters::fortran::Fortran;
+use super::interpreters::gcc::Gcc;
 use super::interpreters::generic::Generic;
-use super::interpreters::ignore::IgnoreByPath;
+use super::interpreters::ignore::{IgnoreByName, IgnoreByPath, IgnoreOwnWrapper};
+use super::interpreters::msvc::Msvc;
+use super::interpreters::nvcc::Nvcc;
+use super::interpreters::response::ResponseFiles;
+use super::interpreters::unwrap::{
+    AnalyzerWrapper, Launcher, LeadingFlags, Libtool, ScriptWrapper, Unwrap, Unwrapper,
+};
 use super::Interpreter;
 
 mod combinators;
+mod fortran;
 mod gcc;
 mod generic;
 mod ignore;
 mod matchers;
+mod msvc;
+mod nvcc;
+mod response;
+mod unwrap;
+
+/// The names of the interpreters, which are recognizing compiler calls. (These can be
+/// the preferred interpreter.)
+pub const INTERPRETER_NAMES: [&str; 5] = ["fortran", "gcc", "msvc", "nvcc", "generic"];
 
 /// A builder for creating a tool which can recognize the semantic of a compiler,
 /// or ignore known non-compilers.
 pub struct Builder {
     interpreters: Vec<Box<dyn Interpreter>>,
+    unwrappers: Vec<Box<dyn Unwrapper>>,
+    response_file_depth: usize,
+    preferred_interpreter: Option<String>,
+    keep_ambiguous_headers: bool,
+    expand_environment: bool,
 }
 
 impl Builder {
     /// Creates a new builder with default settings.
     pub fn new() -> Self {
-        // FIXME: replace generic with gcc, when it's implemented
         Builder {
             interpreters: vec![
                 // ignore executables which are not compilers,
                 IgnoreByPath::new(),
+                // ignore the javascript build tools, which are calling the compilers,
+                IgnoreByName::new(),
+                // recognize the Fortran compilers (before the GCC, which matches `gfortran` too)
+                Fortran::new(),
                 // recognize default compiler
-                Generic::from(&[PathBuf::from("/usr/bin/g++")]),
+                Gcc::new(),
+                // recognize the MSVC compiler
+                Msvc::new(),
+                // recognize the CUDA compiler
+                Nvcc::new(),
+            ],
+            unwrappers: vec![
+                // remove the static analyzer shims of `scan-build`,
+                AnalyzerWrapper::new(),
+                // remove the compiler launchers (like `ccache`),
+                Launcher::known(),
+                // remove the `libtool` script of the autotools,
+                Libtool::new(),
             ],
+            response_file_depth: 0,
+            preferred_interpreter: None,
+            keep_ambiguous_headers: false,
+            expand_environment: false,
         }
     }
 
     /// Factory method to create a new tool from the builder.
-    pub fn build(self) -> impl Interpreter {
-        Any::new(self.interpreters)
+    pub fn build(mut self) -> impl Interpreter {
+        if self.keep_ambiguous_headers || self.expand_environment {
+            for interpreter in self.interpreters.iter_mut() {
+                if interpreter.name() == "gcc" {
+                    *interpreter =
+                        Gcc::with_options(self.keep_ambiguous_headers, self.expand_environment);
+                }
+            }
+        }
+        let any = Any::new(self.interpreters, self.preferred_interpreter);
+        let tool = Unwrap::new(self.unwrappers, Box::new(any));
+        ResponseFiles::new(self.response_file_depth, Box::new(tool))
     }
 
     /// Adds new interpreters to recognize as compilers by executable name.
@@ -48,6 +100,22 @@ impl Builder {
         self
     }
 
+    /// Adds new interpreters to recognize as compilers by executable name, which
+    /// are taking the sources by the given input flags. (Not only as positional
+    /// arguments.) The flags with `=` suffix are taking the value joined to them.
+    pub fn compilers_to_recognize_with_inputs(
+        mut self,
+        compilers: &[PathBuf],
+        inputs: &[String],
+    ) -> Self {
+        if !compilers.is_empty() {
+            // Add the new compilers at the end of the interpreters.
+            let tool = Generic::with_inputs(compilers, inputs);
+            self.interpreters.push(tool);
+        }
+        self
+    }
+
     /// Adds new interpreters to recognize as non-compilers by executable names.
     pub fn compilers_to_exclude(mut self, compilers: &[PathBuf]) -> Self {
         if !compilers.is_empty() {
@@ -57,6 +125,94 @@ impl Builder {
         }
         self
     }
+
+    /// Adds new interpreters to recognize as compilers by executable patterns.
+    ///
+    /// The patterns are matched against the full path and the file name of the executable.
+    pub fn compilers_to_recognize_matching(mut self, patterns: &[Regex]) -> Self {
+        if !patterns.is_empty() {
+            // Add the new compilers at the end of the interpreters.
+            let tool = Generic::matching(patterns);
+            self.interpreters.push(tool);
+        }
+        self
+    }
+
+    /// Adds new interpreters to recognize as non-compilers by executable patterns.
+    ///
+    /// The patterns are matched against the full path and the file name of the executable.
+    /// These are winning over the compilers to recognize, and the known compilers too.
+    pub fn compilers_to_exclude_matching(mut self, patterns: &[Regex]) -> Self {
+        if !patterns.is_empty() {
+            // Add these new compilers at the front of the interpreters.
+            let tool = IgnoreByPath::matching(patterns);
+            self.interpreters.insert(0, tool);
+        }
+        self
+    }
+
+    /// Adds the wrapper of the interception, to not recognize it as a compiler.
+    ///
+    /// The wrapper is the executable itself, and the directory with the links to it.
+    pub fn own_wrapper(mut self, executable: &Path, directory: &Path) -> Self {
+        let tool = IgnoreOwnWrapper::new(executable.to_path_buf(), directory.to_path_buf());
+        self.interpreters.insert(0, tool);
+        self
+    }
+
+    /// Adds wrapper programs, which are calling the compiler.
+    ///
+    /// The compiler is the first argument of the wrapper, which matches the pattern.
+    pub fn compiler_wrappers(mut self, wrappers: &[(PathBuf, Regex)]) -> Self {
+        for (executable, compiler) in wrappers {
+            let tool = ScriptWrapper::new(executable.clone(), compiler.clone());
+            self.unwrappers.push(tool);
+        }
+        self
+    }
+
+    /// Adds compiler launchers, which are calling the compiler given as first argument.
+    pub fn compiler_launchers(mut self, launchers: &[PathBuf]) -> Self {
+        if !launchers.is_empty() {
+            self.unwrappers.push(Launcher::new(launchers.to_vec()));
+        }
+        self
+    }
+
+    /// Enables to expand the response files (the `@file` arguments) up to the given depth.
+    ///
+    /// Zero depth disables the expansion.
+    pub fn response_file_depth(mut self, depth: usize) -> Self {
+        self.response_file_depth = depth;
+        self
+    }
+
+    /// Sets the interpreter, which wins when multiple interpreters are recognizing
+    /// the execution as compiler call.
+    pub fn preferred_interpreter(mut self, name: Option<&str>) -> Self {
+        self.preferred_interpreter = name.map(String::from);
+        self
+    }
+
+    /// Enables to recognize the compilation of headers without the language given.
+    pub fn keep_ambiguous_headers(mut self, enabled: bool) -> Self {
+        self.keep_ambiguous_headers = enabled;
+        self
+    }
+
+    /// Enables to expand the environment variable references in the paths of the arguments.
+    pub fn expand_environment(mut self, enabled: bool) -> Self {
+        self.expand_environment = enabled;
+        self
+    }
+
+    /// Enables to recognize compiler calls, where flags are preceding the compiler.
+    pub fn hoist_leading_flags(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.unwrappers.push(LeadingFlags::new());
+        }
+        self
+    }
 }
 
 #[cfg(test)]
@@ -64,10 +220,24 @@ mod test {
     use std::collections::HashMap;
     use std::path::PathBuf;
 
-    use super::super::{CompilerCall, Execution, Recognition};
+    use super::super::{CompilerCall, CompilerPass, Execution, Recognition};
     use super::*;
     use crate::{vec_of_pathbuf, vec_of_strings};
 
+    #[test]
+    fn test_interpreter_names_are_registered() {
+        let sut = Builder::new().compilers_to_recognize(vec_of_pathbuf!["/usr/bin/cc"].as_slice());
+        let names: Vec<&str> = sut
+            .interpreters
+            .iter()
+            .map(|interpreter| interpreter.name())
+            .collect();
+
+        for name in INTERPRETER_NAMES {
+            assert!(names.contains(&name), "{}", name);
+        }
+    }
+
     #[test]
     fn test_builder() {
         let sut = Builder::new().build();
@@ -91,6 +261,209 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_builder_with_compiler_patterns() {
+        let execution = |executable: &str| Execution {
+            executable: PathBuf::from(executable),
+            arguments: vec_of_strings![executable, "-c", "main.c"],
+            ..any_execution()
+        };
+        let patterns = [crate::output::compile_globs(&[String::from("*-gcc-*")])
+            .unwrap()
+            .remove(0)];
+
+        let sut = Builder::new()
+            .compilers_to_recognize_matching(&patterns)
+            .build();
+        let cross = execution("/opt/cross/bin/aarch64-linux-gnu-gcc-13");
+        assert!(matches!(sut.recognize(&cross), Recognition::Success(_)));
+
+        // The exclude patterns are winning over the include patterns,
+        let sut = Builder::new()
+            .compilers_to_recognize_matching(&patterns)
+            .compilers_to_exclude_matching(&[Regex::new(r"^aarch64-").unwrap()])
+            .build();
+        assert_eq!(Recognition::Ignored, sut.recognize(&cross));
+        // and over the known compilers too.
+        let sut = Builder::new()
+            .compilers_to_exclude_matching(&[Regex::new(r"^/usr/bin/g\+\+$").unwrap()])
+            .build();
+        assert_eq!(Recognition::Ignored, sut.recognize(&any_execution()));
+        assert!(matches!(
+            sut.recognize(&execution("/usr/bin/gcc")),
+            Recognition::Success(_)
+        ));
+    }
+
+    #[test]
+    fn test_builder_with_input_flags() {
+        let execution = Execution {
+            executable: PathBuf::from("/opt/vendor/bin/vcc"),
+            arguments: vec_of_strings!["vcc", "-O2", "--input=foo.c", "-o", "foo.o"],
+            ..any_execution()
+        };
+
+        let sut = Builder::new()
+            .compilers_to_recognize_with_inputs(
+                &[PathBuf::from("/opt/vendor/bin/vcc")],
+                &[String::from("--input=")],
+            )
+            .build();
+
+        match sut.recognize(&execution) {
+            Recognition::Success(CompilerCall { passes, .. }) => assert_eq!(
+                vec![CompilerPass::Compile {
+                    source: PathBuf::from("foo.c"),
+                    output: None,
+                    flags: vec_of_strings!["-O2", "-o", "foo.o"],
+                    output_implied: false,
+                }],
+                passes
+            ),
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_builder_with_own_wrapper() {
+        let sut = Builder::new()
+            .own_wrapper(
+                Path::new("/usr/libexec/bear/wrapper"),
+                Path::new("/tmp/bear/wrappers"),
+            )
+            .build();
+
+        let input = Execution {
+            executable: PathBuf::from("/tmp/bear/wrappers/g++"),
+            environment: HashMap::from([(
+                crate::intercept::KEY_DESTINATION.to_string(),
+                "127.0.0.1:12345".to_string(),
+            )]),
+            ..any_execution()
+        };
+        assert_eq!(Recognition::Ignored, sut.recognize(&input));
+        // The real compiler is still recognized.
+        match sut.recognize(&any_execution()) {
+            Recognition::Success(CompilerCall { .. }) => assert!(true),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_builder_attributes_the_recognition() {
+        let compilers = vec_of_pathbuf!["/usr/bin/g++"];
+        let sut = Builder::new().build();
+
+        let (result, name, rationale) = sut.recognize_with_attribution(&any_execution());
+        assert!(matches!(result, Recognition::Success(_)));
+        assert_eq!("gcc", name);
+        assert_eq!("recognized as compiler call", rationale);
+
+        let sut = Builder::new().compilers_to_exclude(&compilers).build();
+
+        let (result, name, _) = sut.recognize_with_attribution(&any_execution());
+        assert_eq!(Recognition::Ignored, result);
+        assert_eq!("ignore-by-path", name);
+    }
+
+    #[test]
+    fn test_builder_with_nvcc_to_exclude() {
+        let compilers = vec_of_pathbuf!["/usr/local/cuda/bin/nvcc"];
+        let input = Execution {
+            executable: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            arguments: vec_of_strings!["nvcc", "-c", "kernel.cu"],
+            ..any_execution()
+        };
+
+        let (result, name, _) = Builder::new().build().recognize_with_attribution(&input);
+        assert!(matches!(result, Recognition::Success(_)));
+        assert_eq!("nvcc", name);
+
+        let sut = Builder::new().compilers_to_exclude(&compilers).build();
+        assert_eq!(Recognition::Ignored, sut.recognize(&input));
+    }
+
+    #[test]
+    fn test_builder_with_mixed_languages() {
+        let compilers = vec_of_pathbuf!["/usr/bin/ifort"];
+        let sut = Builder::new().compilers_to_exclude(&compilers).build();
+        let execution = |executable: &str, source: &str| Execution {
+            executable: PathBuf::from(executable),
+            arguments: vec_of_strings![executable, "-c", source],
+            ..any_execution()
+        };
+
+        let (result, name, _) =
+            sut.recognize_with_attribution(&execution("/usr/bin/gfortran", "solver.f90"));
+        assert!(matches!(result, Recognition::Success(_)));
+        assert_eq!("fortran", name);
+        let (result, name, _) = sut.recognize_with_attribution(&execution("/usr/bin/gcc", "io.c"));
+        assert!(matches!(result, Recognition::Success(_)));
+        assert_eq!("gcc", name);
+
+        let excluded = execution("/usr/bin/ifort", "solver.f90");
+        assert_eq!(Recognition::Ignored, sut.recognize(&excluded));
+    }
+
+    #[test]
+    fn test_builder_with_msvc_compiler() {
+        let execution = |executable: &str| Execution {
+            executable: PathBuf::from(executable),
+            arguments: vec_of_strings!["cl", "/c", "/Fofoo.obj", "foo.cpp"],
+            ..any_execution()
+        };
+
+        let sut = Builder::new().build();
+        let (result, name, _) = sut.recognize_with_attribution(&execution("/opt/msvc/bin/cl"));
+        assert!(matches!(result, Recognition::Success(_)));
+        assert_eq!("msvc", name);
+
+        let compilers = vec_of_pathbuf!["/opt/msvc/bin/cl"];
+        let sut = Builder::new().compilers_to_exclude(&compilers).build();
+        assert_eq!(
+            Recognition::Ignored,
+            sut.recognize(&execution("/opt/msvc/bin/cl"))
+        );
+    }
+
+    #[test]
+    fn test_builder_with_cc_crate_compilation() {
+        let out_dir = "/home/user/shim/target/debug/build/shim-2f7d6c1e9a4b8c03/out";
+        let sut = Builder::new().build();
+
+        // The `cc` crate is running the compiler in the directory of the crate.
+        let compilation = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings![
+                "gcc",
+                "-O0",
+                "-fPIC",
+                "-o",
+                format!("{}/shim.o", out_dir),
+                "-c",
+                "shim.c"
+            ],
+            environment: HashMap::from([("OUT_DIR".to_string(), out_dir.to_string())]),
+            working_dir: PathBuf::from("/home/user/shim"),
+        };
+        match sut.recognize(&compilation) {
+            Recognition::Success(call) => {
+                assert_eq!(PathBuf::from("/home/user/shim"), call.working_dir);
+                assert_eq!(1, call.passes.len());
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+
+        let build_script = Execution {
+            executable: PathBuf::from(
+                "/home/user/shim/target/debug/build/shim-2f7d6c1e9a4b8c03/build-script-build",
+            ),
+            arguments: vec_of_strings!["build-script-build"],
+            ..compilation
+        };
+        assert_eq!(Recognition::Ignored, sut.recognize(&build_script));
+    }
+
     fn any_execution() -> Execution {
         Execution {
             executable: PathBuf::from("/usr/bin/g++"),
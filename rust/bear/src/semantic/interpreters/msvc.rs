@@ -0,0 +1,388 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use super::super::{CompilerCall, CompilerPass, Execution, Interpreter, Recognition};
+use super::matchers::source::looks_like_a_source_file;
+
+/// The options which are taking the next argument as value, when given without one.
+///
+/// (The options are given without the `/` or `-` prefix, the compiler accepts both.)
+const OPTIONS_WITH_VALUE: [&str; 4] = ["I", "D", "U", "FI"];
+
+/// The options which are making the compiler to stop after the preprocessing.
+const PREPROCESS_OPTIONS: [&str; 3] = ["E", "EP", "P"];
+
+/// The options which are queries to the compiler, these are not compiling anything.
+const QUERY_OPTIONS: [&str; 3] = ["?", "help", "Bv"];
+
+/// A tool to recognize the MSVC compiler (`cl.exe`) and the compatible `clang-cl`.
+///
+/// The options of the compiler are starting with `/` (or `-`), and the values of
+/// the options are usually joined to the option name. (Like `/Fobuild\obj\`.)
+pub(super) struct Msvc {}
+
+impl Msvc {
+    pub(super) fn new() -> Box<dyn Interpreter> {
+        Box::new(Msvc {})
+    }
+}
+
+impl Interpreter for Msvc {
+    fn name(&self) -> &'static str {
+        "msvc"
+    }
+
+    /// The recognition considers:
+    /// - the executable name (`cl` or `clang-cl`, with or without extension),
+    /// - the sources (by extension, or forced with the `/Tc` and `/Tp` options),
+    /// - the output (`/Fo`) and the options controlling the compiler passes.
+    ///
+    /// The arguments after the `/link` option are for the linker.
+    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+        if !is_msvc_compiler(&x.executable) {
+            return Recognition::Unknown;
+        }
+
+        let mut flags: Vec<String> = vec![];
+        // The sources with the language option, which was forced for them.
+        let mut sources: Vec<(PathBuf, Option<&str>)> = vec![];
+        let mut output: Option<String> = None;
+        let mut compile_only = false;
+        let mut preprocessing = false;
+        let mut querying = false;
+
+        let mut arguments = x.arguments.iter().skip(1);
+        while let Some(argument) = arguments.next() {
+            match option(argument) {
+                Some("c") => {
+                    compile_only = true;
+                    flags.push(argument.clone());
+                }
+                Some("link") => break,
+                Some(name) if name.starts_with("Fo") => {
+                    output = value_of(&name[2..], &mut arguments);
+                }
+                Some(name) if name.starts_with("Tc") || name.starts_with("Tp") => {
+                    let language = if name.starts_with("Tc") { "/TC" } else { "/TP" };
+                    if let Some(source) = value_of(&name[2..], &mut arguments) {
+                        sources.push((PathBuf::from(source), Some(language)));
+                    }
+                }
+                Some(name) if OPTIONS_WITH_VALUE.contains(&name) => {
+                    flags.push(argument.clone());
+                    flags.extend(arguments.next().cloned());
+                }
+                Some(name) if PREPROCESS_OPTIONS.contains(&name) => {
+                    preprocessing = true;
+                    flags.push(argument.clone());
+                }
+                Some(name) if QUERY_OPTIONS.contains(&name) => {
+                    querying = true;
+                    flags.push(argument.clone());
+                }
+                _ if looks_like_a_source_file(argument) => {
+                    sources.push((PathBuf::from(argument), None));
+                }
+                Some(_) => {
+                    flags.push(argument.clone());
+                }
+                // The other inputs (like object files and libraries) are for the linker.
+                None => {}
+            }
+        }
+
+        let passes = if querying {
+            vec![]
+        } else if preprocessing {
+            match sources.is_empty() {
+                true => vec![],
+                false => vec![CompilerPass::Preprocess],
+            }
+        } else {
+            if compile_only && sources.is_empty() {
+                log::debug!("Msvc found no source to compile (with /c option).");
+                return Recognition::Unknown;
+            }
+            // The output file is not specific to any source, when there are multiple.
+            // (The output directory is, the object file is named after the source.)
+            let output = match (output, sources.len()) {
+                (Some(output), _) if is_directory(&output) => Some(output),
+                (output, 1) => output,
+                _ => None,
+            };
+            sources
+                .into_iter()
+                .map(|(source, language)| {
+                    let mut flags = flags.clone();
+                    flags.extend(language.map(String::from));
+                    let implied = implied_output(&source, output.as_deref());
+                    CompilerPass::Compile {
+                        output_implied: output.is_none() && implied.is_some(),
+                        output: implied,
+                        source,
+                        flags,
+                    }
+                })
+                .collect()
+        };
+
+        Recognition::Success(CompilerCall {
+            compiler: x.executable.clone(),
+            working_dir: x.working_dir.clone(),
+            passes,
+            original_compiler: None,
+            environment: None,
+        })
+    }
+}
+
+/// Returns true, if the executable is the MSVC compiler (or a compatible one).
+fn is_msvc_compiler(executable: &Path) -> bool {
+    executable
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_ascii_lowercase())
+        .is_some_and(|name| {
+            let name = name.strip_suffix(".exe").unwrap_or(&name);
+            name == "cl" || name == "clang-cl"
+        })
+}
+
+/// Returns the option name (without the prefix), if the argument is an option.
+fn option(argument: &str) -> Option<&str> {
+    argument
+        .strip_prefix('/')
+        .or_else(|| argument.strip_prefix('-'))
+}
+
+/// Returns the value of an option, which is joined to the option name (optionally
+/// with a `:` separator), or given as the next argument.
+fn value_of<'a>(joined: &str, arguments: &mut impl Iterator<Item = &'a String>) -> Option<String> {
+    let joined = joined.strip_prefix(':').unwrap_or(joined);
+    match joined {
+        "" => arguments.next().cloned(),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Returns true, if the output names a directory. (It ends with a path separator.)
+fn is_directory(output: &str) -> bool {
+    output.ends_with('\\') || output.ends_with('/')
+}
+
+/// Returns the object file the compiler creates.
+///
+/// The compiler creates the object file even when it's also linking, named after
+/// the source file with the `.obj` extension. It's created in the output directory,
+/// if that was given, otherwise in the working directory. (The Windows path separator
+/// is considered on other platforms too, to recognize the cross-checked builds.)
+fn implied_output(source: &Path, output: Option<&str>) -> Option<PathBuf> {
+    let object = source
+        .to_str()
+        .and_then(|source| source.rsplit(['/', '\\']).next())
+        .map(|name| Path::new(name).with_extension("obj"))?;
+    match output {
+        Some(directory) if is_directory(directory) => Some(PathBuf::from(format!(
+            "{}{}",
+            directory,
+            object.to_string_lossy()
+        ))),
+        Some(file) => Some(PathBuf::from(file)),
+        None => Some(object),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::semantic::Language;
+    use crate::vec_of_strings;
+
+    use super::*;
+
+    fn execution(arguments: Vec<String>) -> Execution {
+        Execution {
+            executable: PathBuf::from("/opt/msvc/bin/cl.exe"),
+            arguments,
+            working_dir: PathBuf::from("/home/user/project"),
+            environment: HashMap::new(),
+        }
+    }
+
+    fn passes(arguments: Vec<String>) -> Vec<CompilerPass> {
+        match SUT.recognize(&execution(arguments)) {
+            Recognition::Success(CompilerCall { passes, .. }) => passes,
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_compilation_with_output() {
+        let input = execution(vec_of_strings!["cl", "/c", "/Fofoo.obj", "foo.cpp"]);
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/opt/msvc/bin/cl.exe"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.cpp"),
+                output: Some(PathBuf::from("foo.obj")),
+                flags: vec_of_strings!["/c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_includes_and_defines() {
+        let result = passes(vec_of_strings![
+            "cl.exe",
+            "/nologo",
+            "/I",
+            r"include\common",
+            r"/Iinclude\win32",
+            "/DNDEBUG",
+            "-D",
+            "VERSION=2",
+            "/EHsc",
+            "/c",
+            r"/Fobuild\obj\",
+            r"src\foo.cpp",
+            r"src\bar.c"
+        ]);
+
+        let flags = vec_of_strings![
+            "/nologo",
+            "/I",
+            r"include\common",
+            r"/Iinclude\win32",
+            "/DNDEBUG",
+            "-D",
+            "VERSION=2",
+            "/EHsc",
+            "/c"
+        ];
+        let expected = vec![
+            CompilerPass::Compile {
+                source: PathBuf::from(r"src\foo.cpp"),
+                output: Some(PathBuf::from(r"build\obj\foo.obj")),
+                flags: flags.clone(),
+                output_implied: false,
+            },
+            CompilerPass::Compile {
+                source: PathBuf::from(r"src\bar.c"),
+                output: Some(PathBuf::from(r"build\obj\bar.obj")),
+                flags,
+                output_implied: false,
+            },
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_forced_languages() {
+        let result = passes(vec_of_strings![
+            "cl",
+            "/c",
+            "/Tpgenerated.inc",
+            "/Tc",
+            "legacy.txt",
+            "main.c"
+        ]);
+
+        let languages: Vec<_> = result.iter().map(CompilerPass::language).collect();
+        assert_eq!(
+            vec![Some(Language::Cpp), Some(Language::C), Some(Language::C)],
+            languages
+        );
+        assert_eq!(
+            CompilerPass::Compile {
+                source: PathBuf::from("generated.inc"),
+                output: Some(PathBuf::from("generated.obj")),
+                flags: vec_of_strings!["/c", "/TP"],
+                output_implied: true,
+            },
+            result[0]
+        );
+    }
+
+    #[test]
+    fn test_linking_and_queries() {
+        // The object files are created, even when the sources are linked too.
+        let result = passes(vec_of_strings![
+            "cl",
+            "main.cpp",
+            "util.obj",
+            "/link",
+            "/OUT:app.exe",
+            "/DEBUG"
+        ]);
+        assert_eq!(
+            vec![CompilerPass::Compile {
+                source: PathBuf::from("main.cpp"),
+                output: Some(PathBuf::from("main.obj")),
+                flags: vec![],
+                output_implied: true,
+            }],
+            result
+        );
+
+        assert_eq!(
+            Vec::<CompilerPass>::new(),
+            passes(vec_of_strings!["cl", "/?"])
+        );
+        assert_eq!(
+            vec![CompilerPass::Preprocess],
+            passes(vec_of_strings!["cl", "/EP", "main.c"])
+        );
+        assert_eq!(
+            Recognition::Unknown,
+            SUT.recognize(&execution(vec_of_strings!["cl", "/c", "/Fofoo.obj"]))
+        );
+    }
+
+    #[test]
+    fn test_compiler_with_spaced_path() {
+        let input = Execution {
+            executable: PathBuf::from("/opt/Microsoft Visual Studio/VC/bin/cl.exe"),
+            ..execution(vec_of_strings!["cl.exe", "/c", "my file.cpp"])
+        };
+
+        match SUT.recognize(&input) {
+            Recognition::Success(CompilerCall { passes, .. }) => assert_eq!(
+                vec![CompilerPass::Compile {
+                    source: PathBuf::from("my file.cpp"),
+                    output: Some(PathBuf::from("my file.obj")),
+                    flags: vec_of_strings!["/c"],
+                    output_implied: true,
+                }],
+                passes
+            ),
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_compiler_with_spaced_drive_path() {
+        let executable = r"C:\Program Files\Microsoft Visual Studio\2022\VC\Tools\MSVC\bin\cl.exe";
+        assert!(is_msvc_compiler(Path::new(executable)));
+    }
+
+    #[test]
+    fn test_compiler_names() {
+        for name in ["cl", "cl.exe", "CL.EXE", "clang-cl", "clang-cl.exe"] {
+            assert!(is_msvc_compiler(Path::new(name)), "{}", name);
+        }
+        for name in ["clang", "cl-lint", "gcc"] {
+            assert!(!is_msvc_compiler(Path::new(name)), "{}", name);
+        }
+    }
+
+    const SUT: Msvc = Msvc {};
+}
@@ -0,0 +1,453 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use super::super::{CompilerCall, CompilerPass, Execution, Interpreter, Recognition};
+use super::matchers::source::looks_like_a_source_file;
+
+/// The flags which are taking the next argument as value.
+///
+/// The CUDA specific ones (like `-gencode` or `-arch`) are kept in the flags
+/// with their values, for the clang tooling to know the target architectures.
+const FLAGS_WITH_VALUE: [&str; 86] = [
+    "-I",
+    "--include-path",
+    "-isystem",
+    "--system-include",
+    "-include",
+    "--pre-include",
+    "-D",
+    "--define-macro",
+    "-U",
+    "--undefine-macro",
+    "-L",
+    "--library-path",
+    "-l",
+    "--library",
+    "-std",
+    "--std",
+    "-ccbin",
+    "--compiler-bindir",
+    "-arch",
+    "--gpu-architecture",
+    "-code",
+    "--gpu-code",
+    "-gencode",
+    "--generate-code",
+    "-Xlinker",
+    "--linker-options",
+    "-Xptxas",
+    "--ptxas-options",
+    "-Xnvlink",
+    "--nvlink-options",
+    "-odir",
+    "--output-directory",
+    "-MF",
+    "--dependency-output",
+    "-MT",
+    "--dependency-target-name",
+    "-maxrregcount",
+    "--maxrregcount",
+    "-Xarchive",
+    "--archive-options",
+    "-Xfatbin",
+    "--fatbin-options",
+    "-optf",
+    "--options-file",
+    "-keep-dir",
+    "--keep-dir",
+    "-t",
+    "--threads",
+    "-split-compile",
+    "--split-compile",
+    "-time",
+    "--time",
+    "-rdc",
+    "--relocatable-device-code",
+    "-ftz",
+    "--ftz",
+    "-prec-div",
+    "--prec-div",
+    "-prec-sqrt",
+    "--prec-sqrt",
+    "-fmad",
+    "--fmad",
+    "-default-stream",
+    "--default-stream",
+    "-cudart",
+    "--cudart",
+    "-cudadevrt",
+    "--cudadevrt",
+    "-ldir",
+    "--libdevice-directory",
+    "-target-dir",
+    "--target-directory",
+    "-m",
+    "--machine",
+    "-Werror",
+    "--Werror",
+    "-diag-suppress",
+    "--diag-suppress",
+    "-diag-error",
+    "--diag-error",
+    "-diag-warn",
+    "--diag-warn",
+    "-run-args",
+    "--run-args",
+    "-dependency-drive-prefix",
+    "--dependency-drive-prefix",
+];
+
+/// The flags which are making the compiler to stop after the compilation.
+const COMPILE_FLAGS: [&str; 6] = ["-c", "--compile", "-dc", "--device-c", "-dw", "--device-w"];
+
+/// The flags which are making the compiler to stop after the preprocessing.
+const PREPROCESS_FLAGS: [&str; 2] = ["-E", "--preprocess"];
+
+/// The flags which are queries to the compiler, these are not compiling anything.
+const QUERY_FLAGS: [&str; 8] = [
+    "--version",
+    "-V",
+    "--help",
+    "-h",
+    "-M",
+    "--generate-dependencies",
+    "-MM",
+    "--generate-nonsystem-dependencies",
+];
+
+/// A tool to recognize the CUDA compiler driver (`nvcc`).
+pub(super) struct Nvcc {}
+
+impl Nvcc {
+    pub(super) fn new() -> Box<dyn Interpreter> {
+        Box::new(Nvcc {})
+    }
+}
+
+impl Interpreter for Nvcc {
+    fn name(&self) -> &'static str {
+        "nvcc"
+    }
+
+    /// The recognition considers:
+    /// - the executable name,
+    /// - the sources (CUDA or host language sources),
+    /// - the output and the flags controlling the compiler passes,
+    /// - the flags forwarded to the host compiler (`-Xcompiler`), which are
+    ///   split into individual flags.
+    /// - the unknown flags, which are kept verbatim (with their value, when the
+    ///   next argument is not a flag or a source).
+    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+        if !is_nvcc(&x.executable) {
+            return Recognition::Unknown;
+        }
+
+        let mut flags: Vec<String> = vec![];
+        let mut sources: Vec<PathBuf> = vec![];
+        let mut output: Option<String> = None;
+        let mut compiling = false;
+        let mut preprocessing = false;
+        let mut querying = false;
+
+        let mut arguments = x.arguments.iter().skip(1).peekable();
+        while let Some(argument) = arguments.next() {
+            let (name, joined) = match argument.split_once('=') {
+                Some((name, value)) if name.starts_with('-') => (name, Some(value.to_string())),
+                _ => (argument.as_str(), None),
+            };
+            match name {
+                "-o" | "--output-file" => {
+                    output = joined.or_else(|| arguments.next().cloned());
+                }
+                "-Xcompiler" | "--compiler-options" => {
+                    if let Some(value) = joined.or_else(|| arguments.next().cloned()) {
+                        flags.extend(
+                            value
+                                .split(',')
+                                .filter(|flag| !flag.is_empty())
+                                .map(String::from),
+                        );
+                    }
+                }
+                _ if FLAGS_WITH_VALUE.contains(&name) => {
+                    flags.push(argument.clone());
+                    if joined.is_none() {
+                        flags.extend(arguments.next().cloned());
+                    }
+                }
+                _ if COMPILE_FLAGS.contains(&name) => {
+                    compiling = true;
+                    flags.push(argument.clone());
+                }
+                _ if PREPROCESS_FLAGS.contains(&name) => {
+                    preprocessing = true;
+                    flags.push(argument.clone());
+                }
+                _ if QUERY_FLAGS.contains(&name) => {
+                    querying = true;
+                    flags.push(argument.clone());
+                }
+                "-x" | "--x" => {
+                    flags.push(argument.clone());
+                    if joined.is_none() {
+                        flags.extend(arguments.next().cloned());
+                    }
+                }
+                _ if looks_like_a_source_file(argument) => {
+                    sources.push(PathBuf::from(argument));
+                }
+                // The other inputs (like object files) are for the linker.
+                _ if !argument.starts_with('-') => {}
+                // The unknown flags are kept verbatim. The next argument is taken as
+                // its value, when it's not a flag or a source. (It might take one.)
+                _ => {
+                    flags.push(argument.clone());
+                    if joined.is_none() {
+                        flags.extend(
+                            arguments
+                                .next_if(|next| {
+                                    !next.starts_with('-') && !looks_like_a_source_file(next)
+                                })
+                                .cloned(),
+                        );
+                    }
+                }
+            }
+        }
+
+        let passes = if querying {
+            vec![]
+        } else if preprocessing {
+            match sources.is_empty() {
+                true => vec![],
+                false => vec![CompilerPass::Preprocess],
+            }
+        } else {
+            if compiling && sources.is_empty() {
+                log::debug!("Nvcc found no source to compile (with -c or -dc flag).");
+                return Recognition::Unknown;
+            }
+            sources
+                .into_iter()
+                .map(|source| {
+                    let implied = match compiling {
+                        true => implied_output(&source),
+                        false => None,
+                    };
+                    CompilerPass::Compile {
+                        output_implied: output.is_none() && implied.is_some(),
+                        output: output.as_ref().map(PathBuf::from).or(implied),
+                        source,
+                        flags: flags.clone(),
+                    }
+                })
+                .collect()
+        };
+
+        Recognition::Success(CompilerCall {
+            compiler: x.executable.clone(),
+            working_dir: x.working_dir.clone(),
+            passes,
+            original_compiler: None,
+            environment: None,
+        })
+    }
+}
+
+/// Returns true, if the executable is the CUDA compiler driver.
+fn is_nvcc(executable: &Path) -> bool {
+    matches!(
+        executable.file_name().and_then(|name| name.to_str()),
+        Some("nvcc" | "nvcc.exe")
+    )
+}
+
+/// Returns the object file the compiler creates when the `-o` flag is not given.
+///
+/// Like the GCC, it replaces the extension of the source file name, and creates
+/// the file in the working directory.
+fn implied_output(source: &Path) -> Option<PathBuf> {
+    source
+        .file_name()
+        .map(|name| Path::new(name).with_extension("o"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::vec_of_strings;
+
+    use super::*;
+
+    #[test]
+    fn test_compilation_with_gpu_architectures() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            arguments: vec_of_strings![
+                "nvcc",
+                "-x",
+                "cu",
+                "-dc",
+                "-gencode",
+                "arch=compute_70,code=sm_70",
+                "-arch=sm_80",
+                "-I",
+                "include",
+                "-Xcompiler",
+                "-O2,-Wall",
+                "src/kernel.cu",
+                "-o",
+                "kernel.o"
+            ],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("src/kernel.cu"),
+                output: Some(PathBuf::from("kernel.o")),
+                flags: vec_of_strings![
+                    "-x",
+                    "cu",
+                    "-dc",
+                    "-gencode",
+                    "arch=compute_70,code=sm_70",
+                    "-arch=sm_80",
+                    "-I",
+                    "include",
+                    "-O2",
+                    "-Wall"
+                ],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_compilation_with_host_sources() {
+        let input = Execution {
+            executable: PathBuf::from("C:/CUDA/bin/nvcc.exe"),
+            arguments: vec_of_strings![
+                "nvcc.exe",
+                "--compiler-options=-fPIC",
+                "-c",
+                "main.cpp",
+                "header.cuh"
+            ],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("C:/CUDA/bin/nvcc.exe"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![
+                CompilerPass::Compile {
+                    source: PathBuf::from("main.cpp"),
+                    output: Some(PathBuf::from("main.o")),
+                    flags: vec_of_strings!["-fPIC", "-c"],
+                    output_implied: true,
+                },
+                CompilerPass::Compile {
+                    source: PathBuf::from("header.cuh"),
+                    output: Some(PathBuf::from("header.o")),
+                    flags: vec_of_strings!["-fPIC", "-c"],
+                    output_implied: true,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_unknown_flags_are_kept_with_their_values() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            arguments: vec_of_strings![
+                "nvcc",
+                "-c",
+                "-rdc",
+                "true",
+                "--fancy-option",
+                "fast",
+                "-lineinfo",
+                "kernel.cu"
+            ],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("kernel.cu"),
+                output: Some(PathBuf::from("kernel.o")),
+                flags: vec_of_strings!["-c", "-rdc", "true", "--fancy-option", "fast", "-lineinfo"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), SUT.recognize(&input));
+    }
+
+    #[test]
+    fn test_queries_and_linking() {
+        let execution = |arguments: Vec<String>| Execution {
+            executable: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            arguments,
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        let expected = Recognition::Success(CompilerCall {
+            compiler: PathBuf::from("/usr/local/cuda/bin/nvcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![],
+            original_compiler: None,
+            environment: None,
+        });
+        assert_eq!(
+            expected,
+            SUT.recognize(&execution(vec_of_strings!["nvcc", "--version"]))
+        );
+        assert_eq!(
+            expected,
+            SUT.recognize(&execution(vec_of_strings![
+                "nvcc", "kernel.o", "main.o", "-o", "app"
+            ]))
+        );
+        assert_eq!(
+            Recognition::Unknown,
+            SUT.recognize(&execution(vec_of_strings!["nvcc", "-c"]))
+        );
+    }
+
+    #[test]
+    fn test_not_matching() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/cc"),
+            arguments: vec_of_strings!["cc", "-c", "kernel.cu"],
+            working_dir: PathBuf::from("/home/user"),
+            environment: HashMap::new(),
+        };
+
+        assert_eq!(Recognition::Unknown, SUT.recognize(&input));
+    }
+
+    const SUT: Nvcc = Nvcc {};
+}
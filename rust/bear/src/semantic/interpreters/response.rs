@@ -0,0 +1,285 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::super::{CompilerCall, Execution, Interpreter, Recognition};
+
+/// Expands the response files of the execution before the recognition.
+///
+/// The response file is an argument starting with `@`, which content is read
+/// as (whitespace separated and shell quoted) arguments. Response files can
+/// refer to other response files. The expansion stops at the maximum depth,
+/// and at the response files referring to themselves (directly or indirectly).
+/// These references are kept as they are. (Zero maximum depth disables the
+/// expansion.)
+pub(super) struct ResponseFiles {
+    max_depth: usize,
+    interpreter: Box<dyn Interpreter>,
+}
+
+impl ResponseFiles {
+    pub(super) fn new(max_depth: usize, interpreter: Box<dyn Interpreter>) -> impl Interpreter {
+        ResponseFiles {
+            max_depth,
+            interpreter,
+        }
+    }
+
+    /// Returns the execution with the expanded arguments, if there was any response file.
+    fn expand(&self, x: &Execution) -> Option<Execution> {
+        let (program, arguments) = x.arguments.split_first()?;
+        if self.max_depth == 0 || !arguments.iter().any(|argument| argument.starts_with('@')) {
+            return None;
+        }
+        let mut visited = HashSet::new();
+        let mut expanded = vec![program.clone()];
+        expanded.extend(self.expand_arguments(arguments, &x.working_dir, 0, &mut visited));
+        Some(Execution {
+            executable: x.executable.clone(),
+            arguments: expanded,
+            environment: x.environment.clone(),
+            working_dir: x.working_dir.clone(),
+        })
+    }
+
+    fn expand_arguments(
+        &self,
+        arguments: &[String],
+        working_dir: &Path,
+        depth: usize,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Vec<String> {
+        let mut result = vec![];
+        for argument in arguments {
+            let file = match argument.strip_prefix('@') {
+                Some(file) if !file.is_empty() => working_dir.join(file),
+                _ => {
+                    result.push(argument.clone());
+                    continue;
+                }
+            };
+            if visited.contains(&file) {
+                log::warn!("Response file {:?} is referring to itself.", file);
+                result.push(argument.clone());
+                continue;
+            }
+            if depth >= self.max_depth {
+                log::warn!(
+                    "Response file {:?} is over the maximum depth of {}.",
+                    file,
+                    self.max_depth
+                );
+                result.push(argument.clone());
+                continue;
+            }
+            // The compiler treats the argument as a file name, when the file can't be read.
+            let content = match std::fs::read_to_string(&file) {
+                Ok(content) => content,
+                Err(error) => {
+                    log::debug!("Response file {:?} can't be read: {}", file, error);
+                    result.push(argument.clone());
+                    continue;
+                }
+            };
+            match shell_words::split(&content) {
+                Ok(words) => {
+                    visited.insert(file.clone());
+                    result.extend(self.expand_arguments(&words, working_dir, depth + 1, visited));
+                    visited.remove(&file);
+                }
+                Err(error) => {
+                    log::warn!("Response file {:?} can't be parsed: {}", file, error);
+                    result.push(argument.clone());
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Interpreter for ResponseFiles {
+    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+        match self.expand(x) {
+            Some(expanded) => self.interpreter.recognize(&expanded),
+            None => self.interpreter.recognize(x),
+        }
+    }
+
+    fn recognize_with_attribution(
+        &self,
+        x: &Execution,
+    ) -> (Recognition<CompilerCall>, &'static str, String) {
+        match self.expand(x) {
+            Some(expanded) => self.interpreter.recognize_with_attribution(&expanded),
+            None => self.interpreter.recognize_with_attribution(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::super::super::{CompilerPass, Language};
+    use super::super::gcc::Gcc;
+    use super::*;
+    use crate::fixtures::fixtures::capture_warnings;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_nested_response_files() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-response-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(directory.join("build")).unwrap();
+        std::fs::write(
+            directory.join("build/flags.rsp"),
+            "-I 'include dir'\n-DNAME=\"quoted value\"\n@build/sources.rsp\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("build/sources.rsp"), "-c\nmain.c\n").unwrap();
+
+        let sut = ResponseFiles::new(16, Gcc::new());
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "@build/flags.rsp", "@not-existing.rsp"],
+            environment: HashMap::new(),
+            working_dir: directory.clone(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: directory.clone(),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("main.c"),
+                output: Some(PathBuf::from("main.o")),
+                flags: vec_of_strings![
+                    "-I",
+                    "include dir",
+                    "-DNAME=quoted value",
+                    "-c",
+                    "@not-existing.rsp"
+                ],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_language_of_response_file_applies_to_following_sources() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-response-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("sources.rsp"), "-x c++ a.cpp\n").unwrap();
+
+        let sut = ResponseFiles::new(16, Gcc::new());
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "@sources.rsp", "b.c"],
+            environment: HashMap::new(),
+            working_dir: directory.clone(),
+        };
+        let result = sut.recognize(&input);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // The language is still active for the source after the response file.
+        match result {
+            Recognition::Success(CompilerCall { passes, .. }) => {
+                let languages: Vec<_> = passes.iter().map(CompilerPass::language).collect();
+                assert_eq!(vec![Some(Language::Cpp), Some(Language::Cpp)], languages);
+            }
+            result => panic!("unexpected recognition: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_output_of_response_file_is_normalized() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-response-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("output.rsp"), "-o build/foo.o\n").unwrap();
+
+        let sut = ResponseFiles::new(16, Gcc::new());
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "-c", "foo.c", "@output.rsp"],
+            environment: HashMap::new(),
+            working_dir: directory.clone(),
+        };
+        let result = sut.recognize(&input);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // The output is taken from the expanded arguments,
+        let call = match result {
+            Recognition::Success(call) => call,
+            result => panic!("unexpected recognition: {:?}", result),
+        };
+        match &call.passes[..] {
+            [CompilerPass::Compile { output, .. }] => {
+                assert_eq!(&Some(PathBuf::from("build/foo.o")), output)
+            }
+            passes => panic!("unexpected passes: {:?}", passes),
+        }
+        // and it's made absolute in the entry, like any other output.
+        let entries = crate::output::into_entries(call).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(Some(directory.join("build/foo.o")), entries[0].output);
+        assert_eq!(
+            vec_of_strings!["/usr/bin/gcc", "-c", "-o", "build/foo.o", "foo.c"],
+            entries[0].arguments
+        );
+    }
+
+    #[test]
+    fn test_self_referential_response_file() {
+        let directory =
+            std::env::temp_dir().join(format!("bear-response-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("flags.rsp"),
+            "-DNAME=\"with space\" @other.rsp",
+        )
+        .unwrap();
+        std::fs::write(directory.join("other.rsp"), "-c @flags.rsp").unwrap();
+
+        let sut = ResponseFiles::new(16, Gcc::new());
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/gcc"),
+            arguments: vec_of_strings!["gcc", "@flags.rsp", "main.c"],
+            environment: HashMap::new(),
+            working_dir: directory.clone(),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: directory.clone(),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("main.c"),
+                output: Some(PathBuf::from("main.o")),
+                flags: vec_of_strings!["-DNAME=with space", "-c", "@flags.rsp"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let messages = capture_warnings(|| {
+            assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+        });
+        assert!(messages
+            .iter()
+            .any(|message| message.contains("flags.rsp\" is referring to itself")));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
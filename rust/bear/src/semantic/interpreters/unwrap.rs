@@ -0,0 +1,642 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use super::super::{CompilerCall, Execution, Interpreter, Recognition};
+
+/// The maximum number of wrappers to remove from a single execution.
+///
+/// It protects against wrappers which are calling themselves.
+const MAX_DEPTH: usize = 8;
+
+/// Responsible to remove a wrapper program from the execution.
+///
+/// Returns the execution of the wrapped program, if the execution was a
+/// call to a known wrapper.
+pub(super) trait Unwrapper: Send {
+    fn unwrap(&self, _: &Execution) -> Option<Execution>;
+}
+
+/// Recognizes the semantic of the wrapped program.
+///
+/// The wrappers are removed from the execution before the recognition.
+/// This is repeated until none of the unwrappers are matching. (Which
+/// allows wrappers to be called via other wrappers.)
+pub(super) struct Unwrap {
+    unwrappers: Vec<Box<dyn Unwrapper>>,
+    interpreter: Box<dyn Interpreter>,
+}
+
+impl Unwrap {
+    pub(super) fn new(
+        unwrappers: Vec<Box<dyn Unwrapper>>,
+        interpreter: Box<dyn Interpreter>,
+    ) -> impl Interpreter {
+        Unwrap {
+            unwrappers,
+            interpreter,
+        }
+    }
+}
+
+impl Unwrap {
+    /// Returns the execution without the wrappers, if there was any.
+    fn unwrap_all(&self, x: &Execution) -> Option<Execution> {
+        let mut current = None;
+        for _ in 0..MAX_DEPTH {
+            let candidate = current.as_ref().unwrap_or(x);
+            match self.unwrappers.iter().find_map(|u| u.unwrap(candidate)) {
+                Some(unwrapped) => {
+                    log::debug!("execution unwrapped: {:?}", unwrapped);
+                    current = Some(unwrapped)
+                }
+                None => break,
+            }
+        }
+        current
+    }
+}
+
+impl Interpreter for Unwrap {
+    fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+        let current = self.unwrap_all(x);
+        self.interpreter.recognize(current.as_ref().unwrap_or(x))
+    }
+
+    fn recognize_with_attribution(
+        &self,
+        x: &Execution,
+    ) -> (Recognition<CompilerCall>, &'static str, String) {
+        match self.unwrap_all(x) {
+            Some(current) => {
+                let (result, name, rationale) =
+                    self.interpreter.recognize_with_attribution(&current);
+                let rationale =
+                    format!("{}, after unwrapped to {:?}", rationale, current.executable);
+                (result, name, rationale)
+            }
+            None => self.interpreter.recognize_with_attribution(x),
+        }
+    }
+}
+
+/// Unwraps the executions of scripts, which are calling the compiler.
+///
+/// The compiler is the first argument which matches the pattern. (Like
+/// `python3 cc_wrapper.py gcc -c foo.c`, where the compiler is `gcc`.)
+pub(super) struct ScriptWrapper {
+    executable: PathBuf,
+    compiler: Regex,
+}
+
+impl ScriptWrapper {
+    pub(super) fn new(executable: PathBuf, compiler: Regex) -> Box<dyn Unwrapper> {
+        Box::new(Self {
+            executable,
+            compiler,
+        })
+    }
+}
+
+impl Unwrapper for ScriptWrapper {
+    fn unwrap(&self, x: &Execution) -> Option<Execution> {
+        if !is_same_program(&self.executable, &x.executable) {
+            return None;
+        }
+        let position = x
+            .arguments
+            .iter()
+            .skip(1)
+            .position(|argument| self.compiler.is_match(argument))?
+            + 1;
+        Some(Execution {
+            executable: PathBuf::from(&x.arguments[position]),
+            arguments: x.arguments[position..].to_vec(),
+            environment: x.environment.clone(),
+            working_dir: x.working_dir.clone(),
+        })
+    }
+}
+
+/// Unwraps the analyzer shims of the `scan-build` tool.
+///
+/// The `ccc-analyzer` and `c++-analyzer` shims are running the static analyzer and
+/// then the real compiler, which is taken from the `CCC_CC` and `CCC_CXX` environment
+/// variables. (When those are not set, the shims are using the platform default.)
+pub(super) struct AnalyzerWrapper;
+
+impl AnalyzerWrapper {
+    pub(super) fn new() -> Box<dyn Unwrapper> {
+        Box::new(Self)
+    }
+}
+
+#[cfg(target_os = "macos")]
+const ANALYZER_DEFAULT_COMPILERS: (&str, &str) = ("clang", "clang++");
+#[cfg(not(target_os = "macos"))]
+const ANALYZER_DEFAULT_COMPILERS: (&str, &str) = ("gcc", "g++");
+
+impl Unwrapper for AnalyzerWrapper {
+    fn unwrap(&self, x: &Execution) -> Option<Execution> {
+        let (key, default) = match x.executable.file_name()?.to_str()? {
+            "ccc-analyzer" => ("CCC_CC", ANALYZER_DEFAULT_COMPILERS.0),
+            "c++-analyzer" => ("CCC_CXX", ANALYZER_DEFAULT_COMPILERS.1),
+            _ => return None,
+        };
+        let compiler = x
+            .environment
+            .get(key)
+            .filter(|value| !value.is_empty())
+            .map_or(default, String::as_str);
+        let mut arguments = vec![compiler.to_string()];
+        arguments.extend(x.arguments.iter().skip(1).cloned());
+        Some(Execution {
+            executable: PathBuf::from(compiler),
+            arguments,
+            environment: x.environment.clone(),
+            working_dir: x.working_dir.clone(),
+        })
+    }
+}
+
+/// The compiler launchers, which are known without configuration.
+const KNOWN_LAUNCHERS: [&str; 4] = ["ccache", "sccache", "distcc", "icecc"];
+
+/// Unwraps the compiler launchers, which are calling the compiler given as first argument.
+///
+/// The launchers (like `ccache gcc -c foo.c`) are caching or distributing the compilation.
+/// Launcher calls without a compiler (like `ccache --show-stats`) are not unwrapped.
+pub(super) struct Launcher {
+    programs: Vec<PathBuf>,
+}
+
+impl Launcher {
+    pub(super) fn new(programs: Vec<PathBuf>) -> Box<dyn Unwrapper> {
+        Box::new(Self { programs })
+    }
+
+    pub(super) fn known() -> Box<dyn Unwrapper> {
+        Self::new(KNOWN_LAUNCHERS.iter().map(PathBuf::from).collect())
+    }
+}
+
+impl Unwrapper for Launcher {
+    fn unwrap(&self, x: &Execution) -> Option<Execution> {
+        if !self
+            .programs
+            .iter()
+            .any(|program| is_same_program(program, &x.executable))
+        {
+            return None;
+        }
+        let compiler = x
+            .arguments
+            .get(1)
+            .filter(|argument| !argument.starts_with('-'))?;
+        Some(Execution {
+            executable: PathBuf::from(compiler),
+            arguments: x.arguments[1..].to_vec(),
+            environment: x.environment.clone(),
+            working_dir: x.working_dir.clone(),
+        })
+    }
+}
+
+/// The shells, which are running the `libtool` script in the generated makefiles.
+const SHELLS: [&str; 3] = ["sh", "bash", "dash"];
+
+/// Unwraps the compilations of the `libtool` script (generated by the autotools).
+///
+/// The compilations are like `libtool --tag=CXX --mode=compile g++ -c foo.cpp`,
+/// and often the script is executed by a shell (`/bin/bash ../libtool ...`).
+/// The options of `libtool` are preceding the real compiler command. The tag
+/// is selecting the configuration of the script, but the compiler is given
+/// explicitly, so the tag is dropped with the other options.
+///
+/// Only the compile mode is unwrapped. (The link mode is running the linker.)
+pub(super) struct Libtool;
+
+impl Libtool {
+    pub(super) fn new() -> Box<dyn Unwrapper> {
+        Box::new(Self)
+    }
+}
+
+impl Unwrapper for Libtool {
+    fn unwrap(&self, x: &Execution) -> Option<Execution> {
+        let script = match x.executable.file_name()?.to_str()? {
+            "libtool" => 1,
+            name if SHELLS.contains(&name) => {
+                let script = Path::new(x.arguments.get(1)?);
+                if script.file_name()? != "libtool" {
+                    return None;
+                }
+                2
+            }
+            _ => return None,
+        };
+
+        let mut mode = None;
+        let mut position = script;
+        while let Some(argument) = x.arguments.get(position) {
+            match argument.as_str() {
+                "--mode" => {
+                    mode = x.arguments.get(position + 1).map(String::as_str);
+                    position += 2;
+                }
+                "--tag" => position += 2,
+                _ if argument.starts_with("--mode=") => {
+                    mode = Some(&argument[7..]);
+                    position += 1;
+                }
+                _ if argument.starts_with("--") => position += 1,
+                // The mode can be given as the first non-option argument too.
+                mode_name if mode.is_none() => {
+                    mode = Some(mode_name);
+                    position += 1;
+                }
+                _ => break,
+            }
+        }
+        if mode != Some("compile") {
+            return None;
+        }
+        let compiler = x
+            .arguments
+            .get(position)
+            .filter(|argument| !argument.starts_with('-'))?;
+        Some(Execution {
+            executable: PathBuf::from(compiler),
+            arguments: x.arguments[position..].to_vec(),
+            environment: x.environment.clone(),
+            working_dir: x.working_dir.clone(),
+        })
+    }
+}
+
+/// Moves the flags which are preceding the compiler after the compiler.
+///
+/// Some flag prepending shims are producing executions like `-I/common clang -c foo.c`.
+/// The compiler is the first argument which is not a flag (or the value of a flag). The
+/// known flags with separate value (like `-include prefix.h`) are consumed with their
+/// value, other flags shall have glued values before the compiler.
+pub(super) struct LeadingFlags;
+
+/// The flags which are taking their value as a separate argument.
+const FLAGS_WITH_VALUE: [&str; 13] = [
+    "-include",
+    "-imacros",
+    "-I",
+    "-D",
+    "-U",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-isysroot",
+    "--sysroot",
+    "-x",
+    "-Xclang",
+    "-target",
+];
+
+impl LeadingFlags {
+    pub(super) fn new() -> Box<dyn Unwrapper> {
+        Box::new(Self)
+    }
+}
+
+impl Unwrapper for LeadingFlags {
+    fn unwrap(&self, x: &Execution) -> Option<Execution> {
+        let mut position = 0;
+        while let Some(argument) = x.arguments.get(position) {
+            if !argument.starts_with('-') {
+                break;
+            }
+            position += match FLAGS_WITH_VALUE.contains(&argument.as_str()) {
+                true => 2,
+                false => 1,
+            };
+        }
+        if position == 0 || position >= x.arguments.len() {
+            return None;
+        }
+        let compiler = &x.arguments[position];
+        let mut arguments = vec![compiler.clone()];
+        arguments.extend(x.arguments[..position].iter().cloned());
+        arguments.extend(x.arguments[position + 1..].iter().cloned());
+        Some(Execution {
+            executable: PathBuf::from(compiler),
+            arguments,
+            environment: x.environment.clone(),
+            working_dir: x.working_dir.clone(),
+        })
+    }
+}
+
+/// Compare the program with the executable.
+///
+/// When the program is given without a directory, only the file names are compared.
+fn is_same_program(program: &Path, executable: &Path) -> bool {
+    if program.components().count() == 1 {
+        executable.file_name() == Some(program.as_os_str())
+    } else {
+        executable == program
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::super::super::CompilerPass;
+    use super::super::gcc::Gcc;
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_python_wrapper_is_unwrapped() {
+        let sut = Unwrap::new(
+            vec![ScriptWrapper::new(
+                PathBuf::from("python3"),
+                Regex::new(r"(gcc|clang)$").unwrap(),
+            )],
+            Gcc::new(),
+        );
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/python3"),
+            arguments: vec_of_strings![
+                "python3",
+                "/opt/tools/cc_wrapper.py",
+                "--verbose",
+                "gcc",
+                "-c",
+                "foo.c"
+            ],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+    }
+
+    #[test]
+    fn test_analyzer_wrapper_is_unwrapped() {
+        let sut = Unwrap::new(vec![AnalyzerWrapper::new()], Gcc::new());
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/libexec/scan-build/ccc-analyzer"),
+            arguments: vec_of_strings!["ccc-analyzer", "-c", "foo.c"],
+            environment: HashMap::from([("CCC_CC".to_string(), "gcc".to_string())]),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("gcc"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+    }
+
+    #[test]
+    fn test_leading_flags_are_moved_after_the_compiler() {
+        let sut = Unwrap::new(vec![LeadingFlags::new()], Gcc::new());
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/clang"),
+            arguments: vec_of_strings!["-I/common", "-DSHIM", "clang", "-c", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("clang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-I/common", "-DSHIM", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+
+        // The values of the leading flags are not taken as the compiler.
+        let input = Execution {
+            arguments: vec_of_strings!["-include", "prefix.h", "clang", "-c", "foo.c"],
+            ..input
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("clang"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-include", "prefix.h", "-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+    }
+
+    #[test]
+    fn test_chained_launchers_are_unwrapped() {
+        let sut = Unwrap::new(
+            vec![
+                Launcher::known(),
+                Launcher::new(vec![PathBuf::from("/opt/bin/buildcache")]),
+            ],
+            Gcc::new(),
+        );
+
+        let input = Execution {
+            executable: PathBuf::from("/opt/bin/buildcache"),
+            arguments: vec_of_strings!["buildcache", "ccache", "distcc", "clang++", "-c", "foo.cc"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("clang++"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.cc"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+    }
+
+    #[test]
+    fn test_launcher_with_spaced_compiler_path() {
+        let sut = Unwrap::new(vec![Launcher::known()], Gcc::new());
+        let compiler = "/Applications/Xcode.app/Contents/Developer/Toolchains/Xcode Default.xctoolchain/usr/bin/clang";
+
+        let input = Execution {
+            executable: PathBuf::from("/opt/homebrew/bin/ccache"),
+            arguments: vec_of_strings!["ccache", compiler, "-c", "main c.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/Users/user/My Project"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from(compiler),
+            working_dir: PathBuf::from("/Users/user/My Project"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("main c.c"),
+                output: Some(PathBuf::from("main c.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+    }
+
+    #[test]
+    fn test_launcher_without_compiler_is_not_unwrapped() {
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/ccache"),
+            arguments: vec_of_strings!["ccache", "--show-stats"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        assert_eq!(None, Launcher::known().unwrap(&input));
+    }
+
+    #[test]
+    fn test_libtool_compilation_is_unwrapped() {
+        let sut = Unwrap::new(vec![Libtool::new()], Gcc::new());
+        let execution = |executable: &str, arguments: Vec<String>| Execution {
+            executable: PathBuf::from(executable),
+            arguments,
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        let expected = CompilerCall {
+            compiler: PathBuf::from("g++"),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![CompilerPass::Compile {
+                source: PathBuf::from("foo.cpp"),
+                output: Some(PathBuf::from("foo.o")),
+                flags: vec_of_strings!["-c"],
+                output_implied: true,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let input = execution(
+            "/usr/bin/libtool",
+            vec_of_strings![
+                "libtool",
+                "--tag=CXX",
+                "--mode=compile",
+                "g++",
+                "-c",
+                "foo.cpp"
+            ],
+        );
+        assert_eq!(
+            Recognition::Success(expected.clone()),
+            sut.recognize(&input)
+        );
+
+        let input = execution(
+            "/bin/bash",
+            vec_of_strings![
+                "/bin/bash",
+                "../libtool",
+                "--tag",
+                "CXX",
+                "--silent",
+                "compile",
+                "g++",
+                "-c",
+                "foo.cpp"
+            ],
+        );
+        assert_eq!(Recognition::Success(expected), sut.recognize(&input));
+
+        // The link mode and the other scripts are not unwrapped.
+        let input = execution(
+            "/usr/bin/libtool",
+            vec_of_strings![
+                "libtool",
+                "--tag=CC",
+                "--mode=link",
+                "gcc",
+                "-o",
+                "libfoo.la"
+            ],
+        );
+        assert_eq!(None, Libtool::new().unwrap(&input));
+        let input = execution(
+            "/bin/sh",
+            vec_of_strings![
+                "/bin/sh",
+                "configure",
+                "--mode=compile",
+                "gcc",
+                "-c",
+                "foo.c"
+            ],
+        );
+        assert_eq!(None, Libtool::new().unwrap(&input));
+    }
+
+    #[test]
+    fn test_other_executables_are_not_unwrapped() {
+        let sut = ScriptWrapper::new(
+            PathBuf::from("/usr/bin/python3"),
+            Regex::new(r"(gcc|clang)$").unwrap(),
+        );
+
+        let input = Execution {
+            executable: PathBuf::from("/usr/bin/perl"),
+            arguments: vec_of_strings!["perl", "wrapper.pl", "gcc", "-c", "foo.c"],
+            environment: HashMap::new(),
+            working_dir: PathBuf::from("/home/user"),
+        };
+
+        assert_eq!(None, sut.unwrap(&input));
+    }
+}
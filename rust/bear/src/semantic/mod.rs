@@ -11,29 +11,154 @@
 //! ones that are relevant to build a JSON compilation database.
 
 pub mod interpreters;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
 
 use super::intercept::Execution;
-use std::path::PathBuf;
+use path_absolutize::Absolutize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Represents an executed command semantic.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CompilerCall {
     pub compiler: PathBuf,
     pub working_dir: PathBuf,
     pub passes: Vec<CompilerPass>,
+    /// The first argument of the execution, as it was captured. (The interpreters
+    /// are leaving it empty, the recognition fills it.)
+    pub original_compiler: Option<String>,
+    /// The environment of the execution. (The interpreters are leaving it empty,
+    /// the recognition fills it.)
+    pub environment: Option<HashMap<String, String>>,
+}
+
+impl CompilerCall {
+    /// Check the compiler of the call against a configured compiler.
+    ///
+    /// The configured compiler without directory matches by the file name. The others
+    /// are compared as absolute paths, the relative ones are resolved against the
+    /// working directory of the call. (The way the recognition resolves the compiler.)
+    pub fn is_compiler(&self, configured: &Path) -> bool {
+        if configured.components().count() == 1 {
+            return self.compiler.file_name() == Some(configured.as_os_str());
+        }
+        let absolute = |path: &Path| {
+            path.absolutize_from(&self.working_dir)
+                .map(|absolute| absolute.to_path_buf())
+                .unwrap_or_else(|_| path.to_path_buf())
+        };
+        absolute(&self.compiler) == absolute(configured)
+    }
 }
 
 /// Represents a compiler call pass.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum CompilerPass {
     Preprocess,
     Compile {
         source: PathBuf,
         output: Option<PathBuf>,
         flags: Vec<String>,
+        /// The output is not given by the `-o` flag, but it's the one the compiler
+        /// implies. (Like `foo.o` for the `-c foo.c` arguments.)
+        output_implied: bool,
     },
 }
 
+impl CompilerPass {
+    /// Returns the language of the source file, if it's a compile pass.
+    ///
+    /// The language is taken from the last `-x` flag (unless it's `-x none`), or the
+    /// MSVC `/TC` and `/TP` flags, otherwise it's derived from the final extension
+    /// of the source file.
+    pub fn language(&self) -> Option<Language> {
+        match self {
+            CompilerPass::Preprocess => None,
+            CompilerPass::Compile { source, flags, .. } => {
+                let mut explicit: Option<&str> = None;
+                let mut arguments = flags.iter();
+                while let Some(flag) = arguments.next() {
+                    let value = match flag.as_str() {
+                        "/TC" | "-TC" => Some("c"),
+                        "/TP" | "-TP" => Some("c++"),
+                        _ => match flag.strip_prefix("-x") {
+                            Some("") => arguments.next().map(String::as_str),
+                            Some(value) => Some(value),
+                            None => continue,
+                        },
+                    };
+                    explicit = value.filter(|value| *value != "none");
+                }
+                match explicit {
+                    Some(value) => Language::from_name(value),
+                    None => Language::from_path(source),
+                }
+            }
+        }
+    }
+}
+
+/// Represents the programming language of a source file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    C,
+    Cpp,
+    ObjectiveC,
+    ObjectiveCpp,
+    Cuda,
+    Fortran,
+    Assembler,
+}
+
+impl Language {
+    /// Returns the language from the final extension of the file name.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        match extension {
+            "c" | "i" | "h" => Some(Language::C),
+            "cc" | "cp" | "cxx" | "cpp" | "CPP" | "c++" | "C" | "ii" | "hh" | "H" | "hp"
+            | "hxx" | "hpp" | "HPP" | "h++" | "tcc" => Some(Language::Cpp),
+            "m" | "mi" => Some(Language::ObjectiveC),
+            "mm" | "M" | "mii" => Some(Language::ObjectiveCpp),
+            "cu" => Some(Language::Cuda),
+            "f" | "for" | "ftn" | "F" | "FOR" | "fpp" | "FPP" | "FTN" | "f90" | "f95" | "f03"
+            | "f08" | "F90" | "F95" | "F03" | "F08" => Some(Language::Fortran),
+            "s" | "S" | "sx" | "asm" => Some(Language::Assembler),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the language, as the `-x` compiler flag takes it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::C => "c",
+            Language::Cpp => "c++",
+            Language::ObjectiveC => "objective-c",
+            Language::ObjectiveCpp => "objective-c++",
+            Language::Cuda => "cuda",
+            Language::Fortran => "fortran",
+            Language::Assembler => "assembler",
+        }
+    }
+
+    /// Returns the language from the value of the `-x` compiler flag.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c" | "c-header" | "cpp-output" => Some(Language::C),
+            "c++" | "c++-header" | "c++-cpp-output" => Some(Language::Cpp),
+            "objective-c" | "objective-c-header" | "objc-cpp-output" => Some(Language::ObjectiveC),
+            "objective-c++" | "objective-c++-header" | "objc++-cpp-output" => {
+                Some(Language::ObjectiveCpp)
+            }
+            "cuda" => Some(Language::Cuda),
+            "f77" | "f77-cpp-input" | "f95" | "f95-cpp-input" => Some(Language::Fortran),
+            "assembler" | "assembler-with-cpp" => Some(Language::Assembler),
+            _ => None,
+        }
+    }
+}
+
 /// Responsible to recognize the semantic of an executed command.
 ///
 /// The implementation can be responsible for a single compiler,
@@ -45,6 +170,24 @@ pub enum CompilerPass {
 /// later on.
 pub trait Interpreter: Send {
     fn recognize(&self, _: &Execution) -> Recognition<CompilerCall>;
+
+    /// The name of the interpreter, which is reported at the attribution.
+    fn name(&self) -> &'static str {
+        "unnamed"
+    }
+
+    /// Recognize the semantic, and report which interpreter produced the result.
+    ///
+    /// Returns the recognition result, the name of the interpreter and a short
+    /// rationale. Composite interpreters are reporting the one which decided.
+    fn recognize_with_attribution(
+        &self,
+        execution: &Execution,
+    ) -> (Recognition<CompilerCall>, &'static str, String) {
+        let result = self.recognize(execution);
+        let rationale = result.rationale();
+        (result, self.name(), rationale)
+    }
 }
 
 /// Represents a semantic recognition result.
@@ -63,3 +206,59 @@ pub enum Recognition<T> {
     /// The command was not recognized.
     Unknown,
 }
+
+impl<T> Recognition<T> {
+    /// Returns a short description of the result, for debugging purposes.
+    fn rationale(&self) -> String {
+        match self {
+            Recognition::Success(_) => String::from("recognized as compiler call"),
+            Recognition::Ignored => String::from("recognized as non-compiler"),
+            Recognition::Error(reason) => format!("recognized with failure: {}", reason),
+            Recognition::Unknown => String::from("not recognized"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[test]
+    fn test_language_from_final_extension() {
+        assert_eq!(
+            Some(Language::Cpp),
+            Language::from_path(Path::new("foo.generated.cpp"))
+        );
+        assert_eq!(
+            Some(Language::C),
+            Language::from_path(Path::new("libfoo.v2.c"))
+        );
+        assert_eq!(None, Language::from_path(Path::new("foo.o")));
+    }
+
+    #[test]
+    fn test_language_from_flags() {
+        let pass = |flags: Vec<String>| CompilerPass::Compile {
+            source: PathBuf::from("foo.c"),
+            output: None,
+            flags,
+            output_implied: false,
+        };
+
+        assert_eq!(Some(Language::C), pass(vec_of_strings!["-c"]).language());
+        assert_eq!(
+            Some(Language::Cpp),
+            pass(vec_of_strings!["-x", "c++", "-c"]).language()
+        );
+        assert_eq!(
+            Some(Language::Cpp),
+            pass(vec_of_strings!["-xc++", "-c"]).language()
+        );
+        assert_eq!(
+            Some(Language::C),
+            pass(vec_of_strings!["-x", "c++", "-x", "none"]).language()
+        );
+        assert_eq!(None, CompilerPass::Preprocess.language());
+    }
+}
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Support to test the interpreters in isolation.
+//!
+//! This module is enabled by the `test-support` feature, and it's meant to be
+//! used by the tests of the custom interpreters. (Those can be outside of this
+//! crate too.) It provides a builder to create executions, and assertions over
+//! the recognition results.
+//!
+//! ```rust,ignore
+//! use bear::semantic::testing::{assert_recognized, compile_pass, ExecutionBuilder};
+//!
+//! let execution = ExecutionBuilder::new("/usr/bin/cc")
+//!     .arguments(&["-c", "main.c"])
+//!     .build();
+//! assert_recognized(
+//!     &interpreter,
+//!     &execution,
+//!     vec![compile_pass("main.c", Some("main.o"), &["-c"])],
+//! );
+//! ```
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::{CompilerCall, CompilerPass, Execution, Interpreter, Recognition};
+
+/// Builder to create an execution.
+///
+/// The first argument is the executable itself, the builder is taking the
+/// rest of the arguments. The working directory is `/home/user` and the
+/// environment is empty, unless those are given.
+pub struct ExecutionBuilder {
+    execution: Execution,
+}
+
+impl ExecutionBuilder {
+    pub fn new(executable: impl Into<PathBuf>) -> Self {
+        let executable = executable.into();
+        let program = executable.to_string_lossy().to_string();
+        ExecutionBuilder {
+            execution: Execution {
+                executable,
+                arguments: vec![program],
+                environment: HashMap::new(),
+                working_dir: PathBuf::from("/home/user"),
+            },
+        }
+    }
+
+    /// Appends the arguments, after the executable.
+    pub fn arguments(mut self, arguments: &[&str]) -> Self {
+        self.execution
+            .arguments
+            .extend(arguments.iter().map(|argument| argument.to_string()));
+        self
+    }
+
+    pub fn working_dir(mut self, working_dir: impl Into<PathBuf>) -> Self {
+        self.execution.working_dir = working_dir.into();
+        self
+    }
+
+    pub fn environment(mut self, key: &str, value: &str) -> Self {
+        self.execution
+            .environment
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn build(self) -> Execution {
+        self.execution
+    }
+}
+
+/// Creates a compile pass from the given source, output and flags.
+pub fn compile_pass(source: &str, output: Option<&str>, flags: &[&str]) -> CompilerPass {
+    CompilerPass::Compile {
+        source: PathBuf::from(source),
+        output: output.map(PathBuf::from),
+        flags: flags.iter().map(|flag| flag.to_string()).collect(),
+        output_implied: false,
+    }
+}
+
+/// Asserts the execution is recognized as a compiler call with the given passes.
+///
+/// The compiler and the working directory of the call must be the ones of the execution.
+pub fn assert_recognized(
+    interpreter: &dyn Interpreter,
+    execution: &Execution,
+    passes: Vec<CompilerPass>,
+) {
+    let expected = Recognition::Success(CompilerCall {
+        compiler: execution.executable.clone(),
+        working_dir: execution.working_dir.clone(),
+        passes,
+        original_compiler: None,
+        environment: None,
+    });
+    assert_recognition(interpreter, execution, expected);
+}
+
+/// Asserts the execution is recognized as a non-compiler.
+pub fn assert_ignored(interpreter: &dyn Interpreter, execution: &Execution) {
+    assert_recognition(interpreter, execution, Recognition::Ignored);
+}
+
+/// Asserts the execution is not recognized by the interpreter.
+pub fn assert_not_recognized(interpreter: &dyn Interpreter, execution: &Execution) {
+    assert_recognition(interpreter, execution, Recognition::Unknown);
+}
+
+/// Asserts the recognition result, and report the interpreter and the arguments at failure.
+pub fn assert_recognition(
+    interpreter: &dyn Interpreter,
+    execution: &Execution,
+    expected: Recognition<CompilerCall>,
+) {
+    let result = interpreter.recognize(execution);
+    assert_eq!(
+        expected,
+        result,
+        "interpreter {:?} recognized {:?} differently",
+        interpreter.name(),
+        execution.arguments
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Recognizes the `toycc` compiler, which takes every argument as source,
+    /// except the flags. And it ignores the `make` calls.
+    struct Toy {}
+
+    impl Interpreter for Toy {
+        fn name(&self) -> &'static str {
+            "toy"
+        }
+
+        fn recognize(&self, x: &Execution) -> Recognition<CompilerCall> {
+            match x.executable.file_name().and_then(|name| name.to_str()) {
+                Some("toycc") => {
+                    let (flags, sources): (Vec<_>, Vec<_>) = x
+                        .arguments
+                        .iter()
+                        .skip(1)
+                        .partition(|argument| argument.starts_with('-'));
+                    Recognition::Success(CompilerCall {
+                        compiler: x.executable.clone(),
+                        working_dir: x.working_dir.clone(),
+                        passes: sources
+                            .into_iter()
+                            .map(|source| CompilerPass::Compile {
+                                source: PathBuf::from(source),
+                                output: None,
+                                flags: flags.iter().map(|flag| flag.to_string()).collect(),
+                                output_implied: false,
+                            })
+                            .collect(),
+                        original_compiler: None,
+                        environment: None,
+                    })
+                }
+                Some("make") => Recognition::Ignored,
+                _ => Recognition::Unknown,
+            }
+        }
+    }
+
+    #[test]
+    fn test_harness_with_trivial_interpreter() {
+        let compilation = ExecutionBuilder::new("/opt/toy/bin/toycc")
+            .arguments(&["-O2", "main.toy", "util.toy"])
+            .working_dir("/home/user/project")
+            .environment("TOY_HOME", "/opt/toy")
+            .build();
+        assert_recognized(
+            &Toy {},
+            &compilation,
+            vec![
+                compile_pass("main.toy", None, &["-O2"]),
+                compile_pass("util.toy", None, &["-O2"]),
+            ],
+        );
+
+        assert_ignored(&Toy {}, &ExecutionBuilder::new("/usr/bin/make").build());
+        assert_not_recognized(
+            &Toy {},
+            &ExecutionBuilder::new("/usr/bin/cc")
+                .arguments(&["-c", "main.c"])
+                .build(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "interpreter \"toy\" recognized")]
+    fn test_harness_reports_the_difference() {
+        let compilation = ExecutionBuilder::new("/opt/toy/bin/toycc")
+            .arguments(&["main.toy"])
+            .build();
+        assert_recognized(&Toy {}, &compilation, vec![]);
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Responsible for the graceful shutdown of the application.
+//!
+//! When the user interrupts the application (with SIGINT or SIGTERM), the
+//! process is not terminated immediately. Instead, the processing of new
+//! events stops, and the results which were produced so far are written
+//! into the output. This way the progress is not lost.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Install the signal handlers, which are requesting the shutdown.
+#[cfg(unix)]
+pub fn register() -> anyhow::Result<()> {
+    extern "C" fn handler(_: libc::c_int) {
+        REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: the handler is only storing into an atomic, which is async-signal-safe.
+        let previous = unsafe {
+            libc::signal(
+                signal,
+                handler as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            )
+        };
+        if previous == libc::SIG_ERR {
+            anyhow::bail!(
+                "Failed to install signal handler: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Install the signal handlers, which are requesting the shutdown.
+#[cfg(not(unix))]
+pub fn register() -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Returns true, if the shutdown was requested.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Stops the iteration when the shutdown was requested.
+///
+/// The elements produced before the request are still passed on, so those
+/// can be flushed into the output.
+pub fn until_requested<T>(iterator: impl Iterator<Item = T>) -> impl Iterator<Item = T> {
+    until_flagged(iterator, &REQUESTED)
+}
+
+/// Stops the iteration when the flag is set.
+fn until_flagged<T>(
+    iterator: impl Iterator<Item = T>,
+    flag: &'static AtomicBool,
+) -> impl Iterator<Item = T> {
+    iterator.take_while(move |_| {
+        let stop = flag.load(Ordering::SeqCst);
+        if stop {
+            log::warn!("Shutdown requested, stop processing new events.");
+        }
+        !stop
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::output::OutputWriter;
+    use crate::{args, config, semantic, vec_of_strings};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_recognized_calls_are_flushed_on_interrupt() {
+        static FLAG: AtomicBool = AtomicBool::new(false);
+
+        let file_name =
+            std::env::temp_dir().join(format!("bear-shutdown-test-{}.json", rand::random::<u64>()));
+        let writer = OutputWriter::configure(
+            &args::BuildSemantic {
+                file_name: file_name.to_string_lossy().to_string(),
+                append: false,
+                dry_run: false,
+            },
+            &config::Output::default(),
+        )
+        .unwrap();
+
+        let calls = ["first.c", "second.c", "third.c"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, source)| {
+                // The user interrupts while the second call is being processed.
+                if index == 1 {
+                    FLAG.store(true, Ordering::SeqCst);
+                }
+                semantic::CompilerCall {
+                    compiler: PathBuf::from("/usr/bin/cc"),
+                    working_dir: PathBuf::from("/home/user"),
+                    passes: vec![semantic::CompilerPass::Compile {
+                        source: PathBuf::from(source),
+                        output: None,
+                        flags: vec_of_strings!["-c"],
+                        output_implied: false,
+                    }],
+                    original_compiler: None,
+                    environment: None,
+                }
+            });
+
+        writer.run(until_flagged(calls, &FLAG)).unwrap();
+
+        let content = std::fs::read_to_string(&file_name).unwrap();
+        std::fs::remove_file(&file_name).unwrap();
+        assert!(content.contains("first.c"));
+        assert!(!content.contains("second.c"));
+        assert!(!content.contains("third.c"));
+    }
+}
@@ -7,36 +7,103 @@
 //! are defined in the configuration this module is given.
 
 use super::{config, semantic};
+use path_absolutize::Absolutize;
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
 
-pub enum Transformation {
-    None,
-    Config(Vec<config::Compiler>),
+/// The time limit of the resource directory query. (The compiler is killed after it.)
+const RESOURCE_DIR_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Transformation {
+    compilers: Vec<config::Compiler>,
+    source_mappings: Vec<SourceMapping>,
+    flags_to_remove: Vec<String>,
+    output_directories: Vec<config::OutputDirectory>,
+    output_suffixes_to_strip: Vec<Regex>,
+    collapse_include_paths: bool,
+    pin_resource_dir: bool,
+    // The resource directories of the compilers, which were queried already.
+    resource_dirs: RefCell<HashMap<PathBuf, Option<String>>>,
+}
+
+/// The compiled form of the `config::SourceMapping`.
+struct SourceMapping {
+    pattern: Regex,
+    replacement: String,
 }
 
-impl From<&config::Output> for Transformation {
-    fn from(config: &config::Output) -> Self {
+impl TryFrom<&config::Output> for Transformation {
+    type Error = anyhow::Error;
+
+    fn try_from(config: &config::Output) -> Result<Self, Self::Error> {
         match config {
-            config::Output::Clang { compilers, .. } => {
-                if compilers.is_empty() {
-                    Transformation::None
-                } else {
-                    let compilers = compilers.clone();
-                    Transformation::Config(compilers)
-                }
+            config::Output::Clang(clang) => {
+                let config::ClangOutput {
+                    compilers,
+                    source_mappings,
+                    flags_to_remove,
+                    output_directories,
+                    output_suffixes_to_strip,
+                    collapse_include_paths,
+                    pin_resource_dir,
+                    ..
+                } = clang.as_ref();
+                let source_mappings = source_mappings
+                    .iter()
+                    .map(|mapping| {
+                        Regex::new(&mapping.from).map(|pattern| SourceMapping {
+                            pattern,
+                            replacement: mapping.to.clone(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let output_suffixes_to_strip = output_suffixes_to_strip
+                    .iter()
+                    .map(|suffix| Regex::new(&format!("(?:{})$", suffix)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Transformation {
+                    compilers: compilers.clone(),
+                    source_mappings,
+                    flags_to_remove: flags_to_remove.clone(),
+                    output_directories: output_directories.clone(),
+                    output_suffixes_to_strip,
+                    collapse_include_paths: *collapse_include_paths,
+                    pin_resource_dir: *pin_resource_dir,
+                    resource_dirs: RefCell::new(HashMap::new()),
+                })
             }
-            config::Output::Semantic { .. } => Transformation::None,
+            config::Output::Semantic { .. } => Ok(Transformation {
+                compilers: vec![],
+                source_mappings: vec![],
+                flags_to_remove: vec![],
+                output_directories: vec![],
+                output_suffixes_to_strip: vec![],
+                collapse_include_paths: false,
+                pin_resource_dir: false,
+                resource_dirs: RefCell::new(HashMap::new()),
+            }),
         }
     }
 }
 
 impl Transformation {
     pub fn apply(&self, input: semantic::CompilerCall) -> Option<semantic::CompilerCall> {
+        let input = self.remap_sources(input);
+        let input = self.collapse_include_paths(self.remove_flags(input));
+        let input = self.infer_outputs(self.strip_output_suffixes(input));
+        let input = self.pin_resource_dir(input);
         let semantic::CompilerCall {
             compiler,
             passes,
             working_dir,
+            original_compiler,
+            environment,
         } = &input;
-        match self.lookup(&compiler) {
+        match self.lookup(&input) {
             Some(config::Compiler {
                 ignore: config::Ignore::Always,
                 ..
@@ -62,20 +129,293 @@ impl Transformation {
                     compiler: compiler.clone(),
                     working_dir: working_dir.clone(),
                     passes: new_passes,
+                    original_compiler: original_compiler.clone(),
+                    environment: environment.clone(),
                 })
             }
             None => Some(input),
         }
     }
 
-    // TODO: allow multiple matches for the same compiler
-    fn lookup(&self, compiler: &std::path::Path) -> Option<&config::Compiler> {
-        match self {
-            Transformation::Config(compilers) => compilers.iter().find(|c| c.path == compiler),
-            _ => None,
+    /// Rewrite the source files of the compiler passes with the first matching mapping.
+    fn remap_sources(&self, input: semantic::CompilerCall) -> semantic::CompilerCall {
+        if self.source_mappings.is_empty() {
+            return input;
+        }
+        let passes = input
+            .passes
+            .into_iter()
+            .map(|pass| match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    flags,
+                    output_implied,
+                } => semantic::CompilerPass::Compile {
+                    source: self.remap_source(source),
+                    output,
+                    flags,
+                    output_implied,
+                },
+                semantic::CompilerPass::Preprocess => semantic::CompilerPass::Preprocess,
+            })
+            .collect();
+        semantic::CompilerCall {
+            compiler: input.compiler,
+            working_dir: input.working_dir,
+            passes,
+            original_compiler: input.original_compiler,
+            environment: input.environment,
         }
     }
 
+    fn remap_source(&self, source: PathBuf) -> PathBuf {
+        let candidate = match source.to_str() {
+            Some(candidate) => candidate,
+            None => return source,
+        };
+        match self
+            .source_mappings
+            .iter()
+            .find(|mapping| mapping.pattern.is_match(candidate))
+        {
+            Some(mapping) => {
+                let result = mapping
+                    .pattern
+                    .replace(candidate, mapping.replacement.as_str())
+                    .to_string();
+                log::debug!("source file {:?} is mapped to {:?}", candidate, result);
+                PathBuf::from(result)
+            }
+            None => source,
+        }
+    }
+
+    /// Remove the configured flags from all compile passes.
+    ///
+    /// A flag ending with `*` removes all flags starting with the given prefix.
+    fn remove_flags(&self, input: semantic::CompilerCall) -> semantic::CompilerCall {
+        if self.flags_to_remove.is_empty() {
+            return input;
+        }
+        let to_remove = |flag: &String| {
+            self.flags_to_remove
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => flag.starts_with(prefix),
+                    None => flag == pattern,
+                })
+        };
+        let passes = input
+            .passes
+            .into_iter()
+            .map(|pass| match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    mut flags,
+                    output_implied,
+                } => {
+                    flags.retain(|flag| !to_remove(flag));
+                    semantic::CompilerPass::Compile {
+                        source,
+                        output,
+                        flags,
+                        output_implied,
+                    }
+                }
+                semantic::CompilerPass::Preprocess => semantic::CompilerPass::Preprocess,
+            })
+            .collect();
+        semantic::CompilerCall {
+            compiler: input.compiler,
+            working_dir: input.working_dir,
+            passes,
+            original_compiler: input.original_compiler,
+            environment: input.environment,
+        }
+    }
+
+    /// Remove the `-I` flags of the directories, which are given with `-isystem` too.
+    ///
+    /// The compiler ignores the `-I` of a system directory, and searches it at the
+    /// place of the `-isystem` flag. Removing the `-I` keeps the search order.
+    fn collapse_include_paths(&self, input: semantic::CompilerCall) -> semantic::CompilerCall {
+        if !self.collapse_include_paths {
+            return input;
+        }
+        let passes = input
+            .passes
+            .into_iter()
+            .map(|pass| match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    flags,
+                    output_implied,
+                } => semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    flags: collapse_include_flags(flags),
+                    output_implied,
+                },
+                semantic::CompilerPass::Preprocess => semantic::CompilerPass::Preprocess,
+            })
+            .collect();
+        semantic::CompilerCall { passes, ..input }
+    }
+
+    /// Add the `-resource-dir` flag of the clang compilers to the compile passes.
+    ///
+    /// The passes which are already having the flag are not changed.
+    fn pin_resource_dir(&self, input: semantic::CompilerCall) -> semantic::CompilerCall {
+        if !self.pin_resource_dir || !is_clang_compiler(&input.compiler) {
+            return input;
+        }
+        let resource_dir = match self.resource_dir_of(&input.compiler, input.environment.as_ref()) {
+            Some(resource_dir) => resource_dir,
+            None => return input,
+        };
+        let passes = input
+            .passes
+            .into_iter()
+            .map(|pass| match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    mut flags,
+                    output_implied,
+                } => {
+                    if !flags.iter().any(|flag| flag.starts_with("-resource-dir")) {
+                        flags.push(format!("-resource-dir={}", resource_dir));
+                    }
+                    semantic::CompilerPass::Compile {
+                        source,
+                        output,
+                        flags,
+                        output_implied,
+                    }
+                }
+                semantic::CompilerPass::Preprocess => semantic::CompilerPass::Preprocess,
+            })
+            .collect();
+        semantic::CompilerCall { passes, ..input }
+    }
+
+    /// Returns the resource directory of the compiler. (It's queried once for each compiler,
+    /// with the environment of its first call.)
+    fn resource_dir_of(
+        &self,
+        compiler: &Path,
+        environment: Option<&HashMap<String, String>>,
+    ) -> Option<String> {
+        self.resource_dirs
+            .borrow_mut()
+            .entry(compiler.to_path_buf())
+            .or_insert_with(|| query_resource_dir(compiler, environment))
+            .clone()
+    }
+
+    /// Remove the first matching suffix from the output of the compile passes.
+    ///
+    /// It's done before the output inference, to compare the final output name.
+    fn strip_output_suffixes(&self, input: semantic::CompilerCall) -> semantic::CompilerCall {
+        if self.output_suffixes_to_strip.is_empty() {
+            return input;
+        }
+        let passes = input
+            .passes
+            .into_iter()
+            .map(|pass| match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    flags,
+                    output_implied,
+                } => semantic::CompilerPass::Compile {
+                    source,
+                    output: output.map(|output| self.strip_output_suffix(output)),
+                    flags,
+                    output_implied,
+                },
+                semantic::CompilerPass::Preprocess => semantic::CompilerPass::Preprocess,
+            })
+            .collect();
+        semantic::CompilerCall { passes, ..input }
+    }
+
+    fn strip_output_suffix(&self, output: PathBuf) -> PathBuf {
+        let candidate = match output.to_str() {
+            Some(candidate) => candidate,
+            None => return output,
+        };
+        self.output_suffixes_to_strip
+            .iter()
+            .find_map(|suffix| suffix.find(candidate))
+            // Keep the output, when the whole file name would be removed.
+            .filter(|found| {
+                let stripped = &candidate[..found.start()];
+                !stripped.is_empty() && !stripped.ends_with(['/', '\\'])
+            })
+            .map(|found| PathBuf::from(&candidate[..found.start()]))
+            .unwrap_or(output)
+    }
+
+    /// Infer the output of the compile passes, which are not naming it explicitly.
+    ///
+    /// The output was not named, when it's missing or it's only implied by the
+    /// compiler. (The output of the `-o` flag is kept, even if it's the same as the
+    /// implied one.) The inferred output is not given by the `-o` flag either.
+    fn infer_outputs(&self, input: semantic::CompilerCall) -> semantic::CompilerCall {
+        if self.output_directories.is_empty() {
+            return input;
+        }
+        let working_dir = input.working_dir.as_path();
+        let passes = input
+            .passes
+            .into_iter()
+            .map(|pass| match pass {
+                semantic::CompilerPass::Compile {
+                    source,
+                    output,
+                    flags,
+                    output_implied,
+                } if output.is_none() || output_implied => {
+                    let output = self.infer_output(&source, working_dir).or(output);
+                    semantic::CompilerPass::Compile {
+                        source,
+                        output_implied: output.is_some(),
+                        output,
+                        flags,
+                    }
+                }
+                pass => pass,
+            })
+            .collect();
+        semantic::CompilerCall {
+            compiler: input.compiler,
+            working_dir: input.working_dir,
+            passes,
+            original_compiler: input.original_compiler,
+            environment: input.environment,
+        }
+    }
+
+    fn infer_output(&self, source: &Path, working_dir: &Path) -> Option<PathBuf> {
+        let source = source.absolutize_from(working_dir).ok()?;
+        self.output_directories.iter().find_map(|mapping| {
+            let source_root = mapping.source_root.absolutize_from(working_dir).ok()?;
+            let object_root = mapping.object_root.absolutize_from(working_dir).ok()?;
+            let relative = source.strip_prefix(&source_root).ok()?;
+            Some(object_root.join(relative).with_extension("o"))
+        })
+    }
+
+    // TODO: allow multiple matches for the same compiler
+    fn lookup(&self, call: &semantic::CompilerCall) -> Option<&config::Compiler> {
+        self.compilers.iter().find(|c| call.is_compiler(&c.path))
+    }
+
     fn filter(arguments: &config::Arguments, passes: &[semantic::CompilerPass]) -> bool {
         let match_flags = arguments.match_.as_slice();
         passes.iter().any(|pass| match pass {
@@ -100,6 +440,7 @@ impl Transformation {
                     source,
                     output,
                     flags,
+                    output_implied,
                 } => {
                     let mut new_flags = flags.clone();
                     new_flags.retain(|flag| !arguments_to_remove.contains(flag));
@@ -108,6 +449,7 @@ impl Transformation {
                         source: source.clone(),
                         output: output.clone(),
                         flags: new_flags,
+                        output_implied: *output_implied,
                     });
                 }
                 semantic::CompilerPass::Preprocess => {
@@ -118,3 +460,752 @@ impl Transformation {
         new_passes
     }
 }
+
+/// Returns true, if the compiler is clang. (With or without target prefix and version suffix.)
+fn is_clang_compiler(compiler: &Path) -> bool {
+    lazy_static::lazy_static! {
+        static ref CLANG_REGEX: Regex =
+            Regex::new(r"^([^-]+-)*clang(\+\+)?(-[0-9.]+)?(\.exe)?$").unwrap();
+    }
+    compiler
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| CLANG_REGEX.is_match(name))
+}
+
+/// Runs the compiler with the `-print-resource-dir` flag, and returns the printed directory.
+///
+/// The compiler runs with the environment of the execution (when it's known), instead of
+/// the environment of this process. It's killed, when it's not finished in time.
+fn query_resource_dir(
+    compiler: &Path,
+    environment: Option<&HashMap<String, String>>,
+) -> Option<String> {
+    let mut command = Command::new(compiler);
+    command
+        .arg("-print-resource-dir")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(environment) = environment {
+        command.env_clear().envs(environment);
+    }
+    let output = command
+        .spawn()
+        .and_then(|child| wait_with_timeout(child, RESOURCE_DIR_QUERY_TIMEOUT));
+    match output {
+        Ok(output) if output.status.success() => {
+            let resource_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            log::debug!("resource directory of {:?} is {:?}", compiler, resource_dir);
+            Some(resource_dir).filter(|resource_dir| !resource_dir.is_empty())
+        }
+        Ok(output) => {
+            log::warn!(
+                "failed to query the resource directory of {:?}: {}",
+                compiler,
+                output.status
+            );
+            None
+        }
+        Err(error) => {
+            log::warn!(
+                "failed to query the resource directory of {:?}: {}",
+                compiler,
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Waits for the child process to finish, and kills it at the timeout.
+fn wait_with_timeout(mut child: Child, timeout: Duration) -> std::io::Result<Output> {
+    let deadline = Instant::now() + timeout;
+    while child.try_wait()?.is_none() {
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("not finished in {} seconds", timeout.as_secs_f32()),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    child.wait_with_output()
+}
+
+/// Returns the flags without the `-I` flags of the system include directories.
+///
+/// The flags are grouped with their values first. (Like `-I dir` or `-Idir`.)
+fn collapse_include_flags(flags: Vec<String>) -> Vec<String> {
+    let mut groups: Vec<Vec<String>> = vec![];
+    let mut flags = flags.into_iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "-I" | "-isystem" => {
+                let mut group = vec![flag];
+                group.extend(flags.next());
+                groups.push(group);
+            }
+            _ => groups.push(vec![flag]),
+        }
+    }
+    let value_of = |group: &[String], prefix: &str| match group {
+        [flag, value] if flag == prefix => Some(value.clone()),
+        [flag] if flag != "-I-" => flag.strip_prefix(prefix).map(String::from),
+        _ => None,
+    };
+    let system: std::collections::HashSet<String> = groups
+        .iter()
+        .filter_map(|group| value_of(group, "-isystem"))
+        .filter(|directory| !directory.is_empty())
+        .collect();
+    groups
+        .into_iter()
+        .filter(|group| match value_of(group, "-I") {
+            Some(directory) if system.contains(&directory) => {
+                log::debug!("include directory {:?} is a system directory", directory);
+                false
+            }
+            _ => true,
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec_of_strings;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resource_dir_is_pinned() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory =
+            std::env::temp_dir().join(format!("bear-resource-dir-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&directory)?;
+        let compiler = directory.join("clang");
+        let queries = directory.join("queries");
+        std::fs::write(
+            &compiler,
+            format!(
+                "#!/bin/sh\necho \"$1\" >> {}\necho $TOOLCHAIN/lib/clang/17\n",
+                queries.display()
+            ),
+        )?;
+        std::fs::set_permissions(&compiler, std::fs::Permissions::from_mode(0o755))?;
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.pin_resource_dir = true;
+        }
+        let sut = Transformation::try_from(&config)?;
+        let call = |flags: Vec<String>| semantic::CompilerCall {
+            compiler: compiler.clone(),
+            working_dir: PathBuf::from("/home/user"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("main.c"),
+                output: None,
+                flags,
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: Some(HashMap::from([(
+                String::from("TOOLCHAIN"),
+                String::from("/opt/toolchain"),
+            )])),
+        };
+        let flags_of = |call: Option<semantic::CompilerCall>| match call {
+            Some(semantic::CompilerCall { passes, .. }) => match &passes[..] {
+                [semantic::CompilerPass::Compile { flags, .. }] => flags.clone(),
+                passes => panic!("unexpected passes: {:?}", passes),
+            },
+            None => panic!("compiler call is dropped"),
+        };
+
+        let pinned = flags_of(sut.apply(call(vec_of_strings!["-c"])));
+        let given = flags_of(sut.apply(call(vec_of_strings!["-c", "-resource-dir=/usr/lib"])));
+        let queried = std::fs::read_to_string(&queries)?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            vec_of_strings!["-c", "-resource-dir=/opt/toolchain/lib/clang/17"],
+            pinned
+        );
+        assert_eq!(vec_of_strings!["-c", "-resource-dir=/usr/lib"], given);
+        // The compiler is queried only once.
+        assert_eq!("-print-resource-dir\n", queried);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_slow_query_is_killed() -> anyhow::Result<()> {
+        let child = Command::new("sh")
+            .args(["-c", "sleep 10"])
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let started = Instant::now();
+
+        let result = wait_with_timeout(child, Duration::from_millis(100));
+
+        assert_eq!(
+            Some(std::io::ErrorKind::TimedOut),
+            result.err().map(|error| error.kind())
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_clang_compiler_names() {
+        for name in [
+            "clang",
+            "/usr/bin/clang++",
+            "clang-17",
+            "aarch64-linux-gnu-clang",
+        ] {
+            assert!(is_clang_compiler(Path::new(name)), "{}", name);
+        }
+        for name in ["gcc", "clang-cl", "clang-tidy", "/usr/bin/clangd"] {
+            assert!(!is_clang_compiler(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_distcc_pump_source_is_mapped_back() {
+        let config = config::Output::Clang(Box::new(config::ClangOutput {
+            compilers: vec![],
+            filter: config::Filter::default(),
+            format: config::Format::default(),
+            source_mappings: vec![config::SourceMapping {
+                from: String::from(r"^/tmp/distcc_pump_[^/]+(?P<path>/.*)\.i$"),
+                to: String::from("${path}.c"),
+            }],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec![],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: 16,
+            json_lines_output: None,
+            compiler_databases: config::CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: config::AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: config::CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+        let sut = Transformation::try_from(&config).unwrap();
+
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("/tmp/distcc_pump_x1y2z3/home/user/project/src/foo.i"),
+                    output: Some(PathBuf::from("src/foo.o")),
+                    flags: vec_of_strings!["-c", "-O2"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/bar.c"),
+                    output: Some(PathBuf::from("src/bar.o")),
+                    flags: vec_of_strings!["-c", "-O2"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("/home/user/project/src/foo.c"),
+                    output: Some(PathBuf::from("src/foo.o")),
+                    flags: vec_of_strings!["-c", "-O2"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/bar.c"),
+                    output: Some(PathBuf::from("src/bar.o")),
+                    flags: vec_of_strings!["-c", "-O2"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Some(expected), sut.apply(input));
+    }
+
+    #[test]
+    fn test_output_is_inferred_from_output_directories() {
+        let config = config::Output::Clang(Box::new(config::ClangOutput {
+            compilers: vec![],
+            filter: config::Filter::default(),
+            format: config::Format::default(),
+            source_mappings: vec![],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec![],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![config::OutputDirectory {
+                source_root: PathBuf::from("/home/user/project"),
+                object_root: PathBuf::from("/home/user/project/build"),
+            }],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: 16,
+            json_lines_output: None,
+            compiler_databases: config::CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: config::AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: config::CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+        let sut = Transformation::try_from(&config).unwrap();
+
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/foo.c"),
+                    output: Some(PathBuf::from("foo.o")),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: true,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/qux.c"),
+                    output: Some(PathBuf::from("qux.o")),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/bar.c"),
+                    output: Some(PathBuf::from("objects/bar.o")),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("/opt/other/baz.c"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/foo.c"),
+                    output: Some(PathBuf::from("/home/user/project/build/src/foo.o")),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: true,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/qux.c"),
+                    output: Some(PathBuf::from("qux.o")),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/bar.c"),
+                    output: Some(PathBuf::from("objects/bar.o")),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+                semantic::CompilerPass::Compile {
+                    source: PathBuf::from("/opt/other/baz.c"),
+                    output: None,
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                },
+            ],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Some(expected), sut.apply(input));
+    }
+
+    #[test]
+    fn test_temporary_output_suffix_is_stripped() {
+        let config = config::Output::Clang(Box::new(config::ClangOutput {
+            compilers: vec![],
+            filter: config::Filter::default(),
+            format: config::Format::default(),
+            source_mappings: vec![],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec![],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: 16,
+            json_lines_output: None,
+            compiler_databases: config::CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec_of_strings![r"\.tmp", r"\.TMP-[0-9A-Za-z]+"],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: config::AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: config::CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+        let sut = Transformation::try_from(&config).unwrap();
+
+        let call = |outputs: &[Option<&str>]| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: outputs
+                .iter()
+                .map(|output| semantic::CompilerPass::Compile {
+                    source: PathBuf::from("src/foo.c"),
+                    output: output.map(PathBuf::from),
+                    flags: vec_of_strings!["-c"],
+                    output_implied: false,
+                })
+                .collect(),
+            original_compiler: None,
+            environment: None,
+        };
+
+        let input = call(&[
+            Some("build/foo.o.tmp"),
+            Some("build/foo.o.TMP-a1b2"),
+            Some("build/foo.o"),
+            Some("build/.tmp"),
+            None,
+        ]);
+        let expected = call(&[
+            Some("build/foo.o"),
+            Some("build/foo.o"),
+            Some("build/foo.o"),
+            Some("build/.tmp"),
+            None,
+        ]);
+        assert_eq!(Some(expected), sut.apply(input));
+    }
+
+    #[test]
+    fn test_invalid_source_mapping_is_rejected() {
+        let config = config::Output::Clang(Box::new(config::ClangOutput {
+            compilers: vec![],
+            filter: config::Filter::default(),
+            format: config::Format::default(),
+            source_mappings: vec![config::SourceMapping {
+                from: String::from("(unclosed"),
+                to: String::from("$1"),
+            }],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec![],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: 16,
+            json_lines_output: None,
+            compiler_databases: config::CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: config::AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: config::CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+
+        assert!(Transformation::try_from(&config).is_err());
+    }
+
+    #[test]
+    fn test_debug_flags_are_removed_on_request() {
+        let config = config::Output::Clang(Box::new(config::ClangOutput {
+            compilers: vec![],
+            filter: config::Filter::default(),
+            format: config::Format::default(),
+            source_mappings: vec![],
+            compiler_wrappers: vec![],
+            flags_to_remove: vec_of_strings!["-g*"],
+            dependency_manifest: None,
+            allowed_compilers: vec![],
+            hoist_leading_flags: false,
+            output_directories: vec![],
+            compile_flags_files: false,
+            overwrite_compile_flags_files: false,
+            response_file_depth: 16,
+            json_lines_output: None,
+            compiler_databases: config::CompilerDatabases::default(),
+            append: false,
+            max_entries_in_memory: None,
+            include: vec![],
+            exclude: vec![],
+            keep_ambiguous_headers: false,
+            output_suffixes_to_strip: vec![],
+            deduplicate: true,
+            expand_environment: false,
+            append_limits: config::AppendLimits::default(),
+            synthetic_outputs: false,
+            collapse_include_paths: false,
+            compiler_patterns: config::CompilerPatterns::default(),
+            post_write_hook: None,
+            streaming: false,
+            sort: true,
+            keep_existing_order: false,
+            pin_resource_dir: false,
+        }));
+        let sut = Transformation::try_from(&config).unwrap();
+
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings!["-g3", "-gdwarf-5", "-ggdb", "-c", "-Wall"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings!["-c", "-Wall"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Some(expected), sut.apply(input));
+    }
+
+    #[test]
+    fn test_compilers_are_matched_with_the_resolved_compiler() {
+        let ignored = |path: &str| config::Compiler {
+            path: PathBuf::from(path),
+            ignore: config::Ignore::Always,
+            arguments: config::Arguments::default(),
+            input_flags: vec![],
+        };
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.compilers = vec![ignored("cc"), ignored("./bin/gcc")];
+        }
+        let sut = Transformation::try_from(&config).unwrap();
+
+        let call = |compiler: &str| semantic::CompilerCall {
+            compiler: PathBuf::from(compiler),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings!["-c"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        // The bare name matches the compiler, which was resolved from the `PATH`.
+        assert_eq!(None, sut.apply(call("/usr/bin/cc")));
+        // The relative path is resolved against the working directory.
+        assert_eq!(None, sut.apply(call("/home/user/project/bin/gcc")));
+        assert!(sut.apply(call("/usr/bin/gcc")).is_some());
+    }
+
+    #[test]
+    fn test_include_paths_of_system_directories_are_collapsed() {
+        let mut config = config::Output::default();
+        if let config::Output::Clang(clang) = &mut config {
+            clang.collapse_include_paths = true;
+        }
+        let sut = Transformation::try_from(&config).unwrap();
+
+        let call = |flags: Vec<String>| semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags,
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+        let input = call(vec_of_strings![
+            "-c",
+            "-I",
+            "/opt/qt/include",
+            "-Iinclude",
+            "-I/opt/boost",
+            "-isystem",
+            "/opt/qt/include",
+            "-isystem/opt/boost",
+            "-I-"
+        ]);
+
+        let expected = call(vec_of_strings![
+            "-c",
+            "-Iinclude",
+            "-isystem",
+            "/opt/qt/include",
+            "-isystem/opt/boost",
+            "-I-"
+        ]);
+        assert_eq!(Some(expected), sut.apply(input.clone()));
+
+        // It's not done by default.
+        let sut = Transformation::try_from(&config::Output::default()).unwrap();
+        assert_eq!(Some(input.clone()), sut.apply(input));
+    }
+
+    #[test]
+    fn test_color_diagnostic_flags_are_removed() {
+        let sut = Transformation::try_from(&config::Output::default()).unwrap();
+
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings![
+                    "-c",
+                    "-fdiagnostics-color=always",
+                    "-fcolor-diagnostics",
+                    "-Wall"
+                ],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/clang"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings!["-c", "-Wall"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Some(expected), sut.apply(input));
+    }
+
+    #[test]
+    fn test_gcc_specific_flags_are_removed() {
+        let sut = Transformation::try_from(&config::Output::default()).unwrap();
+
+        let input = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings![
+                    "-c",
+                    "-I",
+                    "include",
+                    "-fno-canonical-system-headers",
+                    "-Wall"
+                ],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        let expected = semantic::CompilerCall {
+            compiler: PathBuf::from("/usr/bin/gcc"),
+            working_dir: PathBuf::from("/home/user/project"),
+            passes: vec![semantic::CompilerPass::Compile {
+                source: PathBuf::from("foo.c"),
+                output: None,
+                flags: vec_of_strings!["-c", "-I", "include", "-Wall"],
+                output_implied: false,
+            }],
+            original_compiler: None,
+            environment: None,
+        };
+
+        assert_eq!(Some(expected), sut.apply(input));
+    }
+}
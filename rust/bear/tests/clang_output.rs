@@ -97,12 +97,16 @@ mod success {
                     file: std::path::PathBuf::from("./file_a.c"),
                     arguments: vec_of_strings!("cc", "-c", "./file_a.c", "-o", "./file_a.o"),
                     output: None,
+                    original_compiler: None,
+                    language: None,
                 },
                 Entry {
                     directory: std::path::PathBuf::from("/home/user"),
                     file: std::path::PathBuf::from("./file_b.c"),
                     arguments: vec_of_strings!("cc", "-c", "./file_b.c", "-o", "./file_b.o"),
                     output: Some(std::path::PathBuf::from("./file_b.o")),
+                    original_compiler: None,
+                    language: None,
                 },
             ]
         }
@@ -176,6 +180,24 @@ mod success {
 
             Ok(())
         }
+
+        #[test]
+        fn save_with_string_command_syntax() -> Result<(), Error> {
+            let input = expected_values();
+
+            // Create fake "file"
+            let mut buffer = Cursor::new(Vec::new());
+            let result = write_with_command(&mut buffer, input.into_iter());
+            assert!(result.is_ok());
+
+            // Use the fake "file" as input
+            buffer.seek(SeekFrom::Start(0)).unwrap();
+            let content: Value = serde_json::from_reader(&mut buffer)?;
+
+            assert_eq!(expected_with_string_syntax(), content);
+
+            Ok(())
+        }
     }
 
     mod quoted {
@@ -196,6 +218,8 @@ mod success {
                         "./file_a.o"
                     ),
                     output: None,
+                    original_compiler: None,
+                    language: None,
                 },
                 Entry {
                     directory: std::path::PathBuf::from("/home/user"),
@@ -210,6 +234,8 @@ mod success {
                         "./file_b.o"
                     ),
                     output: None,
+                    original_compiler: None,
+                    language: None,
                 },
             ]
         }
@@ -256,6 +282,397 @@ mod success {
 
             Ok(())
         }
+
+        #[test]
+        fn save_with_spaced_compiler_path() -> Result<(), Error> {
+            let input = vec![Entry {
+                directory: std::path::PathBuf::from("/Users/user/project"),
+                file: std::path::PathBuf::from("/Users/user/project/main.c"),
+                arguments: vec_of_strings!(
+                    "/Applications/Xcode.app/Contents/Developer/Toolchains/Xcode Default.xctoolchain/usr/bin/clang",
+                    "-c",
+                    "main.c"
+                ),
+                output: None,
+                original_compiler: None,
+                language: None,
+            }];
+
+            let mut buffer = Vec::new();
+            write_with_command(&mut buffer, input.clone().into_iter())?;
+            let content: Value = serde_json::from_slice(&buffer)?;
+
+            assert_eq!(
+                json!("'/Applications/Xcode.app/Contents/Developer/Toolchains/Xcode Default.xctoolchain/usr/bin/clang' -c main.c"),
+                content[0]["command"]
+            );
+            let entries: Vec<Entry> = read(buffer.as_slice()).map(|e| e.unwrap()).collect();
+            assert_eq!(input, entries);
+
+            Ok(())
+        }
+
+        fn spaced_entry() -> Entry {
+            Entry {
+                directory: std::path::PathBuf::from("/home/user"),
+                file: std::path::PathBuf::from("/home/user/my file.c"),
+                arguments: vec_of_strings!("cc", "-DNAME=\"it's\"", "-c", "my file.c"),
+                output: None,
+                original_compiler: None,
+                language: None,
+            }
+        }
+
+        #[test]
+        fn save_with_single_quotes_when_needed() -> Result<(), Error> {
+            let input = vec![spaced_entry()];
+
+            let mut buffer = Vec::new();
+            let quoting = Quoting {
+                style: QuoteStyle::Single,
+                always: false,
+            };
+            write_with_quoted_command(&mut buffer, input.clone().into_iter(), quoting)?;
+            let content: Value = serde_json::from_slice(&buffer)?;
+
+            assert_eq!(
+                json!(r#"cc '-DNAME="it'\''s"' -c 'my file.c'"#),
+                content[0]["command"]
+            );
+            let entries: Vec<Entry> = read(buffer.as_slice()).map(|e| e.unwrap()).collect();
+            assert_eq!(input, entries);
+
+            Ok(())
+        }
+
+        #[test]
+        fn save_with_double_quotes_always() -> Result<(), Error> {
+            let input = vec![spaced_entry()];
+
+            let mut buffer = Vec::new();
+            let quoting = Quoting {
+                style: QuoteStyle::Double,
+                always: true,
+            };
+            write_with_quoted_command(&mut buffer, input.clone().into_iter(), quoting)?;
+            let content: Value = serde_json::from_slice(&buffer)?;
+
+            assert_eq!(
+                json!(r#""cc" "-DNAME=\"it's\"" "-c" "my file.c""#),
+                content[0]["command"]
+            );
+            let entries: Vec<Entry> = read(buffer.as_slice()).map(|e| e.unwrap()).collect();
+            assert_eq!(input, entries);
+
+            Ok(())
+        }
+
+        #[test]
+        fn save_with_double_quotes_by_the_windows_rules() -> Result<(), Error> {
+            let input = vec![Entry {
+                directory: std::path::PathBuf::from(r"C:\Users\user"),
+                file: std::path::PathBuf::from(r"C:\Users\user\main.c"),
+                arguments: vec_of_strings!(
+                    r"C:\Program Files\LLVM\bin\clang.exe",
+                    r"-IC:\Program Files\include\",
+                    r#"-DPATH=\"a b\""#,
+                    "-c",
+                    r"src\main.c"
+                ),
+                output: None,
+                original_compiler: None,
+                language: None,
+            }];
+
+            let mut buffer = Vec::new();
+            let quoting = Quoting {
+                style: QuoteStyle::Double,
+                always: false,
+            };
+            write_with_quoted_command(&mut buffer, input.into_iter(), quoting)?;
+            let content: Value = serde_json::from_slice(&buffer)?;
+
+            // The backslashes are doubled only before a double quote.
+            assert_eq!(
+                json!(
+                    r#""C:\Program Files\LLVM\bin\clang.exe" "-IC:\Program Files\include\\" "-DPATH=\\\"a b\\\"" -c "src\main.c""#
+                ),
+                content[0]["command"]
+            );
+
+            Ok(())
+        }
+
+        #[test]
+        fn save_and_load_with_both_syntax() {
+            let mut input = expected_values();
+            input.push(Entry {
+                directory: std::path::PathBuf::from("/home/user/my project"),
+                file: std::path::PathBuf::from("/home/user/my project/file c.c"),
+                arguments: vec_of_strings!(
+                    "/opt/my tools/cc",
+                    "-I",
+                    "include dir",
+                    "-c",
+                    "file c.c",
+                    "-o",
+                    "file c.o"
+                ),
+                output: Some(std::path::PathBuf::from("/home/user/my project/file c.o")),
+                original_compiler: None,
+                language: None,
+            });
+
+            let mut with_array = Vec::new();
+            assert!(write(&mut with_array, input.clone().into_iter()).is_ok());
+            let mut with_string = Vec::new();
+            assert!(write_with_command(&mut with_string, input.clone().into_iter()).is_ok());
+
+            for content in [with_array, with_string] {
+                let entries: Vec<Entry> = read(content.as_slice()).map(|e| e.unwrap()).collect();
+
+                assert_eq!(input, entries);
+            }
+        }
+    }
+}
+
+mod keyed {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn save_keyed_by_file() -> Result<(), Error> {
+        let input = vec![
+            Entry {
+                directory: PathBuf::from("/home/user"),
+                file: PathBuf::from("./file_a.c"),
+                arguments: vec_of_strings!["cc", "-c", "./file_a.c", "-o", "./file_a.o"],
+                output: Some(PathBuf::from("./file_a.o")),
+                original_compiler: None,
+                language: None,
+            },
+            Entry {
+                directory: PathBuf::from("/home/user"),
+                file: PathBuf::from("./file_b.c"),
+                arguments: vec_of_strings!["cc", "-c", "./file_b.c"],
+                output: None,
+                original_compiler: None,
+                language: None,
+            },
+            Entry {
+                directory: PathBuf::from("/home/user"),
+                file: PathBuf::from("./file_b.c"),
+                arguments: vec_of_strings!["cc", "-c", "-O2", "./file_b.c"],
+                output: None,
+                original_compiler: None,
+                language: None,
+            },
+        ];
+
+        let mut buffer = Cursor::new(Vec::new());
+        let result = write_keyed_by_file(&mut buffer, input.into_iter());
+        assert!(result.is_ok());
+
+        buffer.seek(SeekFrom::Start(0)).unwrap();
+        let content: Value = serde_json::from_reader(&mut buffer)?;
+
+        let expected = json!({
+            "./file_a.c": {
+                "directory": "/home/user",
+                "arguments": ["cc", "-c", "./file_a.c", "-o", "./file_a.o"],
+                "output": "./file_a.o"
+            },
+            "./file_b.c": [
+                {
+                    "directory": "/home/user",
+                    "arguments": ["cc", "-c", "./file_b.c"]
+                },
+                {
+                    "directory": "/home/user",
+                    "arguments": ["cc", "-c", "-O2", "./file_b.c"]
+                }
+            ]
+        });
+        assert_eq!(expected, content);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_keyed_by_file_with_command() -> Result<(), Error> {
+        let input = vec![Entry {
+            directory: PathBuf::from("/home/user"),
+            file: PathBuf::from("./file a.c"),
+            arguments: vec_of_strings!["cc", "-c", "./file a.c"],
+            output: None,
+            original_compiler: None,
+            language: None,
+        }];
+        let quoting = Quoting {
+            style: QuoteStyle::Double,
+            always: false,
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+        let result =
+            write_keyed_by_file_with_quoted_command(&mut buffer, input.into_iter(), quoting);
+        assert!(result.is_ok());
+
+        buffer.seek(SeekFrom::Start(0)).unwrap();
+        let content: Value = serde_json::from_reader(&mut buffer)?;
+
+        let expected = json!({
+            "./file a.c": {
+                "directory": "/home/user",
+                "command": r#"cc -c "./file a.c""#
+            }
+        });
+        assert_eq!(expected, content);
+
+        Ok(())
+    }
+}
+
+mod lines {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn save_json_lines() -> Result<(), Error> {
+        let input = vec![
+            Entry {
+                directory: PathBuf::from("/home/user"),
+                file: PathBuf::from("./file_a.c"),
+                arguments: vec_of_strings!["cc", "-c", "./file_a.c", "-o", "./file_a.o"],
+                output: Some(PathBuf::from("./file_a.o")),
+                original_compiler: None,
+                language: None,
+            },
+            Entry {
+                directory: PathBuf::from("/home/user"),
+                file: PathBuf::from("./file_b.c"),
+                arguments: vec_of_strings!["cc", "-c", "./file_b.c"],
+                output: None,
+                original_compiler: None,
+                language: None,
+            },
+        ];
+
+        let mut buffer = Cursor::new(Vec::new());
+        let result = write_lines(&mut buffer, input.into_iter());
+        assert!(result.is_ok());
+
+        let content = String::from_utf8(buffer.into_inner()).unwrap();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+
+        let expected = vec![
+            json!({
+                "directory": "/home/user",
+                "file": "./file_a.c",
+                "arguments": ["cc", "-c", "./file_a.c", "-o", "./file_a.o"],
+                "output": "./file_a.o"
+            }),
+            json!({
+                "directory": "/home/user",
+                "file": "./file_b.c",
+                "arguments": ["cc", "-c", "./file_b.c"]
+            }),
+        ];
+        assert_eq!(expected, lines);
+        assert!(content.ends_with('\n'));
+
+        Ok(())
+    }
+}
+
+mod original_compiler {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn save_and_load_with_original_compiler() -> Result<(), Error> {
+        let input = vec![Entry {
+            directory: PathBuf::from("/home/user"),
+            file: PathBuf::from("./file_a.c"),
+            arguments: vec_of_strings!["/usr/bin/gcc-12", "-c", "./file_a.c"],
+            output: None,
+            original_compiler: Some(String::from("cc")),
+            language: None,
+        }];
+
+        let mut buffer = Cursor::new(Vec::new());
+        write(&mut buffer, input.clone().into_iter())?;
+
+        buffer.seek(SeekFrom::Start(0)).unwrap();
+        let content: Value = serde_json::from_reader(&mut buffer)?;
+        let expected = json!([
+            {
+                "directory": "/home/user",
+                "file": "./file_a.c",
+                "arguments": ["/usr/bin/gcc-12", "-c", "./file_a.c"],
+                "original_compiler": "cc"
+            }
+        ]);
+        assert_eq!(expected, content);
+
+        buffer.seek(SeekFrom::Start(0)).unwrap();
+        let loaded: Vec<Entry> = read(&mut buffer).collect::<Result<_, _>>()?;
+        assert_eq!(input, loaded);
+
+        Ok(())
+    }
+}
+
+mod incremental {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn save_entries_incrementally() -> Result<(), Error> {
+        let entry = |file: &str| Entry {
+            directory: PathBuf::from("/home/user"),
+            file: PathBuf::from(file),
+            arguments: vec_of_strings!["cc", "-c", file],
+            output: None,
+            original_compiler: None,
+            language: None,
+        };
+        let input = vec![
+            entry("./file_a.c"),
+            entry("./file_b.c"),
+            entry("./file_c.c"),
+        ];
+
+        let mut sut = IncrementalWriter::new(Cursor::new(Vec::new()));
+        for entry in &input {
+            sut.append_entry(entry)?;
+        }
+        let mut buffer = sut.finish()?;
+
+        buffer.seek(SeekFrom::Start(0)).unwrap();
+        let content: Value = serde_json::from_reader(&mut buffer)?;
+        assert_eq!(3, content.as_array().map(Vec::len).unwrap_or_default());
+
+        buffer.seek(SeekFrom::Start(0)).unwrap();
+        let loaded: Vec<Entry> = read(&mut buffer).collect::<Result<_, _>>()?;
+        assert_eq!(input, loaded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_no_entries_incrementally() -> Result<(), Error> {
+        let sut = IncrementalWriter::with_command(Cursor::new(Vec::new()));
+        let buffer = sut.finish()?;
+
+        let content: Value = serde_json::from_slice(buffer.get_ref())?;
+        assert_eq!(json!([]), content);
+
+        Ok(())
     }
 }
 